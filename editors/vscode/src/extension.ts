@@ -54,9 +54,55 @@ async function startClient(context: ExtensionContext): Promise<void> {
         commands.registerCommand("typst-lsp.unpinMain", () => commandPinMain(false))
     );
     context.subscriptions.push(commands.registerCommand("typst-lsp.showPdf", commandShowPdf));
+    context.subscriptions.push(
+        commands.registerCommand("typst-lsp.showPreview", commandShowPreview)
+    );
+    context.subscriptions.push(commands.registerCommand("typst-lsp.revealPdf", commandRevealPdf));
     context.subscriptions.push(commands.registerCommand("typst-lsp.clearCache", commandClearCache));
+    context.subscriptions.push(window.registerUriHandler({ handleUri: handleDeepLink }));
+
+    await client.start();
+    client.onNotification("typst-lsp/readOnlyFile", onReadOnlyFile);
 
-    return client.start();
+    // Tell the server which file is shown, so the preview can follow only the main file
+    context.subscriptions.push(window.onDidChangeActiveTextEditor(notifyActiveEditor));
+    notifyActiveEditor();
+}
+
+function notifyActiveEditor(): void {
+    const uri = window.activeTextEditor?.document.uri.toString() ?? null;
+    void client?.sendNotification("typst-lsp/activeEditor", { uri });
+}
+
+// Opens deep links copied from the preview. VS Code hands them to the extension as
+// `vscode://nvarner.typst-lsp/open?...`, the same query as the server's `typst-lsp://open?...`.
+async function handleDeepLink(uri: Uri): Promise<void> {
+    if (uri.path !== "/open") {
+        return;
+    }
+    const link = `typst-lsp://open?${uri.query}`;
+    const project = window.activeTextEditor?.document.uri.toString();
+    const args = project !== undefined ? [link, project] : [link];
+    try {
+        await commands.executeCommand("typst-lsp.openDeepLink", ...args);
+    } catch (e) {
+        void window.showErrorMessage(`Could not open link: ${e}`);
+    }
+}
+
+// Marks files the server says shouldn't be edited, like those in the package cache, as read-only.
+// The command only exists from VS Code 1.79 on, so older versions just keep the files editable.
+async function onReadOnlyFile({ uri }: { uri: string }): Promise<void> {
+    if (window.activeTextEditor?.document.uri.toString() !== Uri.parse(uri).toString()) {
+        return;
+    }
+    try {
+        await commands.executeCommand("workbench.action.files.setActiveEditorReadonlyInSession");
+    } catch {
+        void window.showInformationMessage(
+            "This file is part of a downloaded package. Edits to it are not kept."
+        );
+    }
 }
 
 export function deactivate(): Promise<void> | undefined {
@@ -133,6 +179,36 @@ async function commandExportCurrentPdf(): Promise<void> {
     });
 }
 
+async function commandRevealPdf(): Promise<void> {
+    const activeEditor = window.activeTextEditor;
+    if (activeEditor === undefined) {
+        return;
+    }
+
+    const uri = activeEditor.document.uri.toString();
+
+    await client?.sendRequest("workspace/executeCommand", {
+        command: "typst-lsp.revealPdf",
+        arguments: [uri],
+    });
+}
+
+// Opens the preview where the cursor is
+async function commandShowPreview(): Promise<void> {
+    const activeEditor = window.activeTextEditor;
+    if (activeEditor === undefined) {
+        return;
+    }
+
+    const uri = activeEditor.document.uri.toString();
+    const { line, character } = activeEditor.selection.active;
+
+    await client?.sendRequest("workspace/executeCommand", {
+        command: "typst-lsp.openPreview",
+        arguments: [uri, { line, character }],
+    });
+}
+
 async function commandPinMain(isPin: boolean): Promise<void> {
     if (!isPin) {
         await client?.sendRequest("workspace/executeCommand", {
@@ -1,16 +1,24 @@
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
 use std::{fmt, path::PathBuf};
 
 use anyhow::bail;
 use futures::future::BoxFuture;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use itertools::Itertools;
-use serde::Deserialize;
-use serde_json::{Map, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use tower_lsp::lsp_types::{
     self, ConfigurationItem, InitializeParams, PositionEncodingKind, Registration, Url,
 };
 use tracing::warn;
 
 use crate::ext::InitializeParamsExt;
+use crate::server::export::ExportFormat;
+use crate::server::page_size::PageSize;
+use crate::workspace::font_manager::FontManager;
+use crate::workspace::SUPPORTED_FEATURES;
+use crate::TYPST_VERSION;
 
 const CONFIG_REGISTRATION_ID: &str = "config";
 const CONFIG_METHOD_ID: &str = "workspace/didChangeConfiguration";
@@ -23,7 +31,7 @@ pub fn get_config_registration() -> Registration {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum ExperimentalFormatterMode {
     #[default]
@@ -31,7 +39,7 @@ pub enum ExperimentalFormatterMode {
     On,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum ExportPdfMode {
     Never,
@@ -42,7 +50,7 @@ pub enum ExportPdfMode {
     OnPinnedMainType,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum SemanticTokensMode {
     Disable,
@@ -50,6 +58,198 @@ pub enum SemanticTokensMode {
     Enable,
 }
 
+/// Whether to flag labels and imports which are never used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LintUnusedMode {
+    Disable,
+    #[default]
+    Enable,
+}
+
+/// When to compile documents for their diagnostics and report them to the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticsMode {
+    #[default]
+    On,
+    OnSave,
+    /// Only compile to export or preview, as `exportPdf` says
+    Off,
+}
+
+/// Whether to warn about uses of Typst functions which are deprecated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LintDeprecatedMode {
+    Disable,
+    #[default]
+    Enable,
+}
+
+/// How sharp preview pages are rendered, trading speed for quality
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PreviewQuality {
+    Draft,
+    #[default]
+    Normal,
+    High,
+}
+
+impl PreviewQuality {
+    /// Rendered pixels per point at 100% zoom
+    pub fn render_scale(self) -> f32 {
+        match self {
+            Self::Draft => 1.5,
+            Self::Normal => 3.0,
+            Self::High => 4.5,
+        }
+    }
+}
+
+/// How pages are arranged in the preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PreviewLayout {
+    /// One page per row
+    #[default]
+    Single,
+    /// Two pages per row, like facing pages
+    Spread,
+    /// Two pages per row, after the first page on its own like a book's cover
+    SpreadWithCover,
+}
+
+impl PreviewLayout {
+    /// The layout to switch to when toggling through them
+    pub fn next(self) -> Self {
+        match self {
+            Self::Single => Self::Spread,
+            Self::Spread => Self::SpreadWithCover,
+            Self::SpreadWithCover => Self::Single,
+        }
+    }
+}
+
+/// How pages look in the preview, like in a PDF viewer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PreviewPageStyle {
+    /// Logical pixels between pages, with half of it above the first row
+    pub gap: f32,
+    /// The background between pages as `#rrggbb`, or `None` for the window's background
+    pub gap_color: Option<String>,
+    pub shadow: bool,
+    /// In logical pixels
+    pub corner_radius: f32,
+}
+
+impl Default for PreviewPageStyle {
+    fn default() -> Self {
+        Self {
+            gap: 10.0,
+            gap_color: None,
+            shadow: false,
+            corner_radius: 0.0,
+        }
+    }
+}
+
+impl PreviewPageStyle {
+    /// The gap color as RGB, or `None` if it isn't set or not a valid `#rrggbb` color
+    pub fn gap_rgb(&self) -> Option<[u8; 3]> {
+        let hex = self.gap_color.as_deref()?.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some([channel(0)?, channel(2)?, channel(4)?])
+    }
+}
+
+/// Which edits the preview scrolls to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PreviewFollow {
+    /// Edits to any file compiled into the previewed document
+    #[default]
+    Always,
+    /// Only edits and jumps while the editor shows the previewed document's main file, so editing
+    /// included files doesn't move the preview
+    MainOnly,
+}
+
+/// How far below the top of the viewport the preview puts a position it jumps to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JumpScrollMargin {
+    /// A fraction of the viewport's height, from 0 for the top to 1 for the bottom
+    Fraction(f32),
+    /// Logical pixels
+    Pixels { pixels: f32 },
+}
+
+impl Default for JumpScrollMargin {
+    fn default() -> Self {
+        Self::Fraction(0.3)
+    }
+}
+
+impl JumpScrollMargin {
+    /// The margin in logical pixels in a viewport of the height, at most the whole height
+    pub fn pixels(self, visible_height: f32) -> f32 {
+        let margin = match self {
+            Self::Fraction(fraction) => fraction * visible_height,
+            Self::Pixels { pixels } => pixels,
+        };
+        margin.min(visible_height).max(0.0)
+    }
+}
+
+/// The PDF standard exported PDFs should conform to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PdfStandard {
+    /// PDF 1.7
+    #[default]
+    #[serde(rename = "1.7")]
+    V1_7,
+    /// PDF/A-2b, for archival
+    #[serde(rename = "a-2b")]
+    A2b,
+    /// PDF/A-3b, for archival with attachments
+    #[serde(rename = "a-3b")]
+    A3b,
+}
+
+impl fmt::Display for PdfStandard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::V1_7 => write!(f, "PDF 1.7"),
+            Self::A2b => write!(f, "PDF/A-2b"),
+            Self::A3b => write!(f, "PDF/A-3b"),
+        }
+    }
+}
+
+/// Settings which replace the others while the profile is in use, like a fast draft version and
+/// a final version for print. Settings it leaves out keep their values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Profile {
+    /// Merged over `inputs`
+    pub inputs: BTreeMap<String, String>,
+    /// The format of exports which don't ask for one, over the file's `export` directive
+    pub format: Option<ExportFormat>,
+    /// The page size the profile's exports are laid out at, instead of the document's own
+    pub page_size: Option<PageSize>,
+    /// Pixels per point of exported PNGs, over the file's `dpi` directive
+    pub pixel_per_pt: Option<f32>,
+    pub preview_quality: Option<PreviewQuality>,
+    pub preview_show_bleed: Option<bool>,
+    pub pdf_standard: Option<PdfStandard>,
+}
+
 pub type Listener<T> = Box<dyn FnMut(&T) -> BoxFuture<anyhow::Result<()>> + Send + Sync>;
 
 const CONFIG_ITEMS: &[&str] = &[
@@ -57,19 +257,172 @@ const CONFIG_ITEMS: &[&str] = &[
     "rootPath",
     "semanticTokens",
     "experimentalFormatterMode",
+    "previewCachedPages",
+    "previewCacheMb",
+    "pdfStandard",
+    "embedSource",
+    "preloadPackages",
+    "lintUnused",
+    "snippetsFile",
+    "previewInvert",
+    "previewQuality",
+    "openPreviewOnStart",
+    "mainFiles",
+    "previewLayout",
+    "lintDeprecated",
+    "compileInterval",
+    "debounceMin",
+    "debounceMax",
+    "previewShowBleed",
+    "previewBleedMm",
+    "previewOverview",
+    "previewKeybindings",
+    "previewRefineDelay",
+    "previewPageStyle",
+    "inputs",
+    "jumpScrollMargin",
+    "previewFollow",
+    "profiles",
+    "diagnostics",
+    "previewPrerenderPages",
 ];
 
-#[derive(Default)]
+/// The items which `null` sets to no limit, rather than resetting them to their defaults
+const NULLABLE_LIMIT_ITEMS: &[&str] = &["previewCachedPages", "previewCacheMb"];
+
+const DEFAULT_DEBOUNCE_MIN: u64 = 10;
+const DEFAULT_DEBOUNCE_MAX: u64 = 500;
+const DEFAULT_PREVIEW_BLEED_MM: f64 = 3.0;
+const DEFAULT_PREVIEW_REFINE_DELAY: u64 = 400;
+pub const DEFAULT_PREVIEW_CACHED_PAGES: usize = 64;
+pub const DEFAULT_PREVIEW_CACHE_MB: usize = 512;
+
+/// Where the effective value of a setting came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConfigSource {
+    Default,
+    /// A command line flag
+    Cli,
+    /// The editor's settings or initialization options
+    Editor,
+    /// A command, like pinning the main file
+    Command,
+}
+
 pub struct Config {
     pub main_file: Option<Url>,
     pub export_pdf: ExportPdfMode,
     pub root_path: Option<PathBuf>,
     pub semantic_tokens: SemanticTokensMode,
     pub formatter: ExperimentalFormatterMode,
+    /// How many rendered pages the preview keeps around, 64 unless set, or `None` to keep all of
+    /// them
+    pub preview_cached_pages: Option<usize>,
+    /// How many megabytes of rendered pages the preview keeps around, 512 unless set, or `None`
+    /// for no limit
+    pub preview_cache_mb: Option<usize>,
+    pub pdf_standard: PdfStandard,
+    /// Attach the document's sources and a package lockfile to exported PDFs
+    pub embed_source: bool,
+    /// Packages to download when the server starts, like `@preview/example:0.1.0`
+    pub preload_packages: Vec<String>,
+    pub lint_unused: LintUnusedMode,
+    /// JSON file with snippets to offer in addition to the built-in ones
+    pub snippets_file: Option<PathBuf>,
+    /// Render the preview with inverted lightness, for reading in the dark
+    pub preview_invert: bool,
+    pub preview_quality: PreviewQuality,
+    /// Compile and show the preview as soon as a main file is known, without waiting for an edit
+    pub open_preview_on_start: bool,
+    /// Globs of the files compiled as documents of their own, relative to the project root, for
+    /// projects with several documents
+    pub main_files: Vec<String>,
+    pub preview_layout: PreviewLayout,
+    pub lint_deprecated: LintDeprecatedMode,
+    /// Milliseconds between the starts of compilations after edits, or `None` to wait as long as
+    /// the last compilation took
+    pub compile_interval: Option<u64>,
+    /// Bounds in milliseconds of how long to wait for more edits before compiling, which adapts to
+    /// how long compilations take
+    pub debounce_min: Option<u64>,
+    pub debounce_max: Option<u64>,
+    /// Outline the trim box of pages in the preview and shade the bleed around it. Typst pages
+    /// have no bleed of their own, so documents for print are laid out on pages enlarged by it.
+    pub preview_show_bleed: bool,
+    /// How far the bleed extends past the trim box on each side, in millimeters
+    pub preview_bleed_mm: Option<f64>,
+    /// Show a strip of tiny pages along the edge of the preview, to drag along to seek
+    pub preview_overview: bool,
+    /// Key combos like `Ctrl+Shift+C` for actions in the preview, by the action's name, replacing
+    /// the default keys of those actions
+    pub preview_keybindings: BTreeMap<String, Vec<String>>,
+    /// Milliseconds without scrolling or edits after which pages shown as drafts in the meantime
+    /// are rendered at full quality
+    pub preview_refine_delay: Option<u64>,
+    pub preview_page_style: PreviewPageStyle,
+    /// Strings documents read from `sys.inputs`, merged over those given with `--input`
+    pub inputs: BTreeMap<String, String>,
+    pub jump_scroll_margin: JumpScrollMargin,
+    pub preview_follow: PreviewFollow,
+    /// Named sets of settings to switch between, see [`Profile`]
+    pub profiles: BTreeMap<String, Profile>,
+    /// The name of the profile in use, chosen with the `useProfile` command
+    pub active_profile: Option<String>,
+    pub diagnostics: DiagnosticsMode,
+    /// How many pages from the top of a document the preview renders in the background after
+    /// it's compiled, before they're scrolled to
+    pub preview_prerender_pages: usize,
+    /// The items the editor set, as opposed to those left at their defaults
+    editor_items: HashSet<&'static str>,
     semantic_tokens_listeners: Vec<Listener<SemanticTokensMode>>,
     formatter_listeners: Vec<Listener<ExperimentalFormatterMode>>,
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            main_file: Default::default(),
+            export_pdf: Default::default(),
+            root_path: Default::default(),
+            semantic_tokens: Default::default(),
+            formatter: Default::default(),
+            preview_cached_pages: Some(DEFAULT_PREVIEW_CACHED_PAGES),
+            preview_cache_mb: Some(DEFAULT_PREVIEW_CACHE_MB),
+            pdf_standard: Default::default(),
+            embed_source: Default::default(),
+            preload_packages: Default::default(),
+            lint_unused: Default::default(),
+            snippets_file: Default::default(),
+            preview_invert: Default::default(),
+            preview_quality: Default::default(),
+            open_preview_on_start: Default::default(),
+            main_files: Default::default(),
+            preview_layout: Default::default(),
+            lint_deprecated: Default::default(),
+            compile_interval: Default::default(),
+            debounce_min: Default::default(),
+            debounce_max: Default::default(),
+            preview_show_bleed: Default::default(),
+            preview_bleed_mm: Default::default(),
+            preview_overview: Default::default(),
+            preview_keybindings: Default::default(),
+            preview_refine_delay: Default::default(),
+            preview_page_style: Default::default(),
+            inputs: Default::default(),
+            jump_scroll_margin: Default::default(),
+            preview_follow: Default::default(),
+            profiles: Default::default(),
+            active_profile: Default::default(),
+            diagnostics: Default::default(),
+            preview_prerender_pages: Default::default(),
+            editor_items: Default::default(),
+            semantic_tokens_listeners: Default::default(),
+            formatter_listeners: Default::default(),
+        }
+    }
+}
+
 impl Config {
     pub fn get_items() -> Vec<ConfigurationItem> {
         let sections = CONFIG_ITEMS
@@ -114,6 +467,18 @@ impl Config {
     }
 
     pub async fn update_by_map(&mut self, update: &Map<String, Value>) -> anyhow::Result<()> {
+        for item in CONFIG_ITEMS {
+            match update.get(*item) {
+                Some(Value::Null) if !NULLABLE_LIMIT_ITEMS.contains(item) => {
+                    self.editor_items.remove(item);
+                }
+                Some(_) => {
+                    self.editor_items.insert(*item);
+                }
+                None => {}
+            }
+        }
+
         let export_pdf = update
             .get("exportPdf")
             .map(ExportPdfMode::deserialize)
@@ -154,6 +519,275 @@ impl Config {
             self.formatter = formatter;
         }
 
+        let preview_cached_pages = update.get("previewCachedPages");
+        if let Some(preview_cached_pages) = preview_cached_pages {
+            if preview_cached_pages.is_null() {
+                self.preview_cached_pages = None;
+            }
+            if let Some(preview_cached_pages) = preview_cached_pages.as_u64() {
+                self.preview_cached_pages = Some(preview_cached_pages as usize);
+            }
+        }
+
+        let preview_cache_mb = update.get("previewCacheMb");
+        if let Some(preview_cache_mb) = preview_cache_mb {
+            if preview_cache_mb.is_null() {
+                self.preview_cache_mb = None;
+            }
+            if let Some(preview_cache_mb) = preview_cache_mb.as_u64() {
+                self.preview_cache_mb = Some(preview_cache_mb as usize);
+            }
+        }
+
+        let pdf_standard = update
+            .get("pdfStandard")
+            .map(PdfStandard::deserialize)
+            .and_then(Result::ok);
+        if let Some(pdf_standard) = pdf_standard {
+            self.pdf_standard = pdf_standard;
+        }
+
+        let embed_source = update.get("embedSource").and_then(Value::as_bool);
+        if let Some(embed_source) = embed_source {
+            self.embed_source = embed_source;
+        }
+
+        let preload_packages = update
+            .get("preloadPackages")
+            .map(Vec::<String>::deserialize)
+            .and_then(Result::ok);
+        if let Some(preload_packages) = preload_packages {
+            self.preload_packages = preload_packages;
+        }
+
+        let lint_unused = update
+            .get("lintUnused")
+            .map(LintUnusedMode::deserialize)
+            .and_then(Result::ok);
+        if let Some(lint_unused) = lint_unused {
+            self.lint_unused = lint_unused;
+        }
+
+        let snippets_file = update.get("snippetsFile");
+        if let Some(snippets_file) = snippets_file {
+            if snippets_file.is_null() {
+                self.snippets_file = None;
+            }
+            if let Some(snippets_file) = snippets_file.as_str().map(PathBuf::from) {
+                self.snippets_file = Some(snippets_file);
+            }
+        }
+
+        let preview_invert = update.get("previewInvert").and_then(Value::as_bool);
+        if let Some(preview_invert) = preview_invert {
+            self.preview_invert = preview_invert;
+        }
+
+        let preview_quality = update
+            .get("previewQuality")
+            .map(PreviewQuality::deserialize)
+            .and_then(Result::ok);
+        if let Some(preview_quality) = preview_quality {
+            self.preview_quality = preview_quality;
+        }
+
+        let open_preview_on_start = update.get("openPreviewOnStart").and_then(Value::as_bool);
+        if let Some(open_preview_on_start) = open_preview_on_start {
+            self.open_preview_on_start = open_preview_on_start;
+        }
+
+        let main_files = update
+            .get("mainFiles")
+            .map(Vec::<String>::deserialize)
+            .and_then(Result::ok);
+        if let Some(main_files) = main_files {
+            self.main_files = main_files;
+        }
+
+        let preview_layout = update
+            .get("previewLayout")
+            .map(PreviewLayout::deserialize)
+            .and_then(Result::ok);
+        if let Some(preview_layout) = preview_layout {
+            self.preview_layout = preview_layout;
+        }
+
+        let lint_deprecated = update
+            .get("lintDeprecated")
+            .map(LintDeprecatedMode::deserialize)
+            .and_then(Result::ok);
+        if let Some(lint_deprecated) = lint_deprecated {
+            self.lint_deprecated = lint_deprecated;
+        }
+
+        let compile_interval = update.get("compileInterval");
+        if let Some(compile_interval) = compile_interval {
+            if compile_interval.is_null() {
+                self.compile_interval = None;
+            }
+            if let Some(compile_interval) = compile_interval.as_u64() {
+                self.compile_interval = Some(compile_interval);
+            }
+        }
+
+        let debounce_min = update.get("debounceMin");
+        if let Some(debounce_min) = debounce_min {
+            if debounce_min.is_null() {
+                self.debounce_min = None;
+            }
+            if let Some(debounce_min) = debounce_min.as_u64() {
+                self.debounce_min = Some(debounce_min);
+            }
+        }
+
+        let debounce_max = update.get("debounceMax");
+        if let Some(debounce_max) = debounce_max {
+            if debounce_max.is_null() {
+                self.debounce_max = None;
+            }
+            if let Some(debounce_max) = debounce_max.as_u64() {
+                self.debounce_max = Some(debounce_max);
+            }
+        }
+
+        let preview_show_bleed = update.get("previewShowBleed").and_then(Value::as_bool);
+        if let Some(preview_show_bleed) = preview_show_bleed {
+            self.preview_show_bleed = preview_show_bleed;
+        }
+
+        let preview_bleed_mm = update.get("previewBleedMm");
+        if let Some(preview_bleed_mm) = preview_bleed_mm {
+            if preview_bleed_mm.is_null() {
+                self.preview_bleed_mm = None;
+            }
+            let preview_bleed_mm = preview_bleed_mm.as_f64();
+            if let Some(preview_bleed_mm) = preview_bleed_mm.filter(|mm| *mm >= 0.0) {
+                self.preview_bleed_mm = Some(preview_bleed_mm);
+            }
+        }
+
+        let preview_overview = update.get("previewOverview").and_then(Value::as_bool);
+        if let Some(preview_overview) = preview_overview {
+            self.preview_overview = preview_overview;
+        }
+
+        let preview_keybindings = update.get("previewKeybindings").and_then(Value::as_object);
+        if let Some(preview_keybindings) = preview_keybindings {
+            self.preview_keybindings = preview_keybindings
+                .iter()
+                .filter_map(|(action, combos)| {
+                    let combos = match combos {
+                        Value::String(combo) => vec![combo.clone()],
+                        combos => Vec::<String>::deserialize(combos)
+                            .inspect_err(|err| warn!(%err, action, "skipping invalid keybinding"))
+                            .ok()?,
+                    };
+                    Some((action.clone(), combos))
+                })
+                .collect();
+        }
+
+        let preview_refine_delay = update.get("previewRefineDelay");
+        if let Some(preview_refine_delay) = preview_refine_delay {
+            if preview_refine_delay.is_null() {
+                self.preview_refine_delay = None;
+            }
+            if let Some(preview_refine_delay) = preview_refine_delay.as_u64() {
+                self.preview_refine_delay = Some(preview_refine_delay);
+            }
+        }
+
+        let preview_page_style = update
+            .get("previewPageStyle")
+            .map(PreviewPageStyle::deserialize)
+            .and_then(Result::ok)
+            .filter(|style| style.gap >= 0.0 && style.corner_radius >= 0.0);
+        if let Some(preview_page_style) = preview_page_style {
+            self.preview_page_style = preview_page_style;
+        }
+
+        let inputs = update.get("inputs");
+        if let Some(inputs) = inputs {
+            if inputs.is_null() {
+                self.inputs = BTreeMap::new();
+            }
+            if let Some(inputs) = inputs.as_object() {
+                self.inputs = inputs
+                    .iter()
+                    .filter_map(|(key, value)| match value {
+                        Value::String(value) => Some((key.clone(), value.clone())),
+                        _ => {
+                            warn!(key, "skipping input which is not a string");
+                            None
+                        }
+                    })
+                    .collect();
+            }
+        }
+
+        let jump_scroll_margin = update
+            .get("jumpScrollMargin")
+            .map(JumpScrollMargin::deserialize)
+            .and_then(Result::ok)
+            .and_then(|margin| match margin {
+                JumpScrollMargin::Fraction(fraction) if fraction.is_finite() => {
+                    Some(JumpScrollMargin::Fraction(fraction.clamp(0.0, 1.0)))
+                }
+                JumpScrollMargin::Pixels { pixels } if pixels.is_finite() => {
+                    Some(JumpScrollMargin::Pixels {
+                        pixels: pixels.max(0.0),
+                    })
+                }
+                _ => None,
+            });
+        if let Some(jump_scroll_margin) = jump_scroll_margin {
+            self.jump_scroll_margin = jump_scroll_margin;
+        }
+
+        let preview_follow = update
+            .get("previewFollow")
+            .map(PreviewFollow::deserialize)
+            .and_then(Result::ok);
+        if let Some(preview_follow) = preview_follow {
+            self.preview_follow = preview_follow;
+        }
+
+        let profiles = update.get("profiles");
+        if let Some(profiles) = profiles {
+            if profiles.is_null() {
+                self.profiles = BTreeMap::new();
+            }
+            if let Some(profiles) = profiles.as_object() {
+                self.profiles = profiles
+                    .iter()
+                    .filter_map(|(name, profile)| {
+                        let profile = Profile::deserialize(profile)
+                            .inspect_err(|err| warn!(%err, name, "skipping invalid profile"))
+                            .ok()?;
+                        Some((name.clone(), profile))
+                    })
+                    .collect();
+            }
+        }
+
+        let diagnostics = update
+            .get("diagnostics")
+            .map(DiagnosticsMode::deserialize)
+            .and_then(Result::ok);
+        if let Some(diagnostics) = diagnostics {
+            self.diagnostics = diagnostics;
+        }
+
+        let preview_prerender_pages = update.get("previewPrerenderPages");
+        if let Some(preview_prerender_pages) = preview_prerender_pages {
+            if preview_prerender_pages.is_null() {
+                self.preview_prerender_pages = 0;
+            }
+            if let Some(preview_prerender_pages) = preview_prerender_pages.as_u64() {
+                self.preview_prerender_pages = preview_prerender_pages as usize;
+            }
+        }
+
         self.validate_main_file();
         Ok(())
     }
@@ -165,6 +799,193 @@ impl Config {
         Ok(())
     }
 
+    /// Switches to the profile with the name, or back to no profile, failing if there is no such
+    /// profile
+    pub fn use_profile(&mut self, name: Option<String>) -> anyhow::Result<()> {
+        if let Some(name) = &name {
+            if !self.profiles.contains_key(name) {
+                bail!("there is no profile named {name:?}");
+            }
+        }
+        self.active_profile = name;
+        Ok(())
+    }
+
+    /// The profile in use, if it's still configured
+    pub fn profile(&self) -> Option<&Profile> {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+    }
+
+    /// The `inputs`, with those of the profile merged over them
+    pub fn profile_inputs(&self) -> BTreeMap<String, String> {
+        let mut inputs = self.inputs.clone();
+        if let Some(profile) = self.profile() {
+            inputs.extend(profile.inputs.clone());
+        }
+        inputs
+    }
+
+    pub fn pdf_standard(&self) -> PdfStandard {
+        self.profile()
+            .and_then(|profile| profile.pdf_standard)
+            .unwrap_or(self.pdf_standard)
+    }
+
+    pub fn preview_quality(&self) -> PreviewQuality {
+        self.profile()
+            .and_then(|profile| profile.preview_quality)
+            .unwrap_or(self.preview_quality)
+    }
+
+    /// The format of exports which don't ask for one, if the profile sets it
+    pub fn export_format(&self) -> Option<ExportFormat> {
+        self.profile().and_then(|profile| profile.format)
+    }
+
+    /// Pixels per point of exported PNGs, if the profile sets it
+    pub fn export_pixel_per_pt(&self) -> Option<f32> {
+        self.profile().and_then(|profile| profile.pixel_per_pt)
+    }
+
+    /// The value of every setting the server uses, along with where it came from
+    pub fn effective(&self) -> Map<String, Value> {
+        let cli_inputs = crate::workspace::cli_inputs();
+        let mut inputs = cli_inputs.clone();
+        inputs.extend(self.profile_inputs());
+
+        let values = [
+            ("exportPdf", json!(self.export_pdf)),
+            ("rootPath", json!(self.root_path)),
+            ("semanticTokens", json!(self.semantic_tokens)),
+            ("experimentalFormatterMode", json!(self.formatter)),
+            ("previewCachedPages", json!(self.preview_cached_pages)),
+            ("previewCacheMb", json!(self.preview_cache_mb)),
+            ("pdfStandard", json!(self.pdf_standard)),
+            ("embedSource", json!(self.embed_source)),
+            ("preloadPackages", json!(self.preload_packages)),
+            ("lintUnused", json!(self.lint_unused)),
+            ("snippetsFile", json!(self.snippets_file)),
+            ("previewInvert", json!(self.preview_invert)),
+            ("previewQuality", json!(self.preview_quality)),
+            ("openPreviewOnStart", json!(self.open_preview_on_start)),
+            ("mainFiles", json!(self.main_files)),
+            ("previewLayout", json!(self.preview_layout)),
+            ("lintDeprecated", json!(self.lint_deprecated)),
+            ("compileInterval", json!(self.compile_interval)),
+            ("debounceMin", json!(self.debounce_min)),
+            ("debounceMax", json!(self.debounce_max)),
+            ("previewShowBleed", json!(self.preview_show_bleed)),
+            ("previewBleedMm", json!(self.preview_bleed_mm)),
+            ("previewOverview", json!(self.preview_overview)),
+            ("previewKeybindings", json!(self.preview_keybindings)),
+            ("previewRefineDelay", json!(self.preview_refine_delay)),
+            ("previewPageStyle", json!(self.preview_page_style)),
+            ("inputs", json!(inputs)),
+            ("jumpScrollMargin", json!(self.jump_scroll_margin)),
+            ("previewFollow", json!(self.preview_follow)),
+            ("profiles", json!(self.profiles)),
+            ("diagnostics", json!(self.diagnostics)),
+            ("previewPrerenderPages", json!(self.preview_prerender_pages)),
+        ];
+
+        let mut effective: Map<String, Value> = values
+            .into_iter()
+            .map(|(item, value)| {
+                let source = if self.editor_items.contains(item) {
+                    ConfigSource::Editor
+                } else if item == "inputs" && !cli_inputs.is_empty() {
+                    ConfigSource::Cli
+                } else {
+                    ConfigSource::Default
+                };
+                (item.to_owned(), json!({ "value": value, "source": source }))
+            })
+            .collect();
+
+        let main_file_source = match self.main_file {
+            Some(_) => ConfigSource::Command,
+            None => ConfigSource::Default,
+        };
+        effective.insert(
+            "mainFile".to_owned(),
+            json!({ "value": self.main_file, "source": main_file_source }),
+        );
+
+        let active_profile_source = match self.active_profile {
+            Some(_) => ConfigSource::Command,
+            None => ConfigSource::Default,
+        };
+        effective.insert(
+            "activeProfile".to_owned(),
+            json!({ "value": self.active_profile, "source": active_profile_source }),
+        );
+
+        let font_paths = FontManager::font_paths();
+        let font_paths_source = match font_paths {
+            [] => ConfigSource::Default,
+            _ => ConfigSource::Cli,
+        };
+        effective.insert(
+            "fontPaths".to_owned(),
+            json!({ "value": font_paths, "source": font_paths_source }),
+        );
+
+        effective
+    }
+
+    /// The least and most time to wait for more edits before compiling
+    pub fn debounce_bounds(&self) -> (Duration, Duration) {
+        (
+            Duration::from_millis(self.debounce_min.unwrap_or(DEFAULT_DEBOUNCE_MIN)),
+            Duration::from_millis(self.debounce_max.unwrap_or(DEFAULT_DEBOUNCE_MAX)),
+        )
+    }
+
+    /// How far the bleed extends past the trim box in the preview, in points, or `None` if it isn't
+    /// shown
+    pub fn preview_bleed_pt(&self) -> Option<f64> {
+        let show_bleed = self
+            .profile()
+            .and_then(|profile| profile.preview_show_bleed)
+            .unwrap_or(self.preview_show_bleed);
+        show_bleed.then(|| {
+            typst::layout::Abs::mm(self.preview_bleed_mm.unwrap_or(DEFAULT_PREVIEW_BLEED_MM))
+                .to_pt()
+        })
+    }
+
+    /// How long the preview waits for scrolling and edits to stop before refining drafts
+    pub fn preview_refine_delay(&self) -> Duration {
+        Duration::from_millis(
+            self.preview_refine_delay
+                .unwrap_or(DEFAULT_PREVIEW_REFINE_DELAY),
+        )
+    }
+
+    /// The `mainFiles` globs, or `None` if there are none. Invalid globs are skipped.
+    pub fn main_files_globs(&self) -> Option<GlobSet> {
+        if self.main_files.is_empty() {
+            return None;
+        }
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.main_files {
+            match Glob::new(pattern) {
+                Ok(glob) => {
+                    builder.add(glob);
+                }
+                Err(err) => warn!(%err, pattern, "skipping invalid main files glob"),
+            }
+        }
+
+        builder
+            .build()
+            .inspect_err(|err| warn!(%err, "could not build main files globs"))
+            .ok()
+    }
+
     fn validate_main_file(&mut self) {
         if let Some(main_file) = &self.main_file {
             if let Some(root_path) = &self.root_path {
@@ -189,6 +1010,36 @@ impl fmt::Debug for Config {
             .field("export_pdf", &self.export_pdf)
             .field("formatter", &self.formatter)
             .field("semantic_tokens", &self.semantic_tokens)
+            .field("preview_cached_pages", &self.preview_cached_pages)
+            .field("preview_cache_mb", &self.preview_cache_mb)
+            .field("pdf_standard", &self.pdf_standard)
+            .field("embed_source", &self.embed_source)
+            .field("preload_packages", &self.preload_packages)
+            .field("lint_unused", &self.lint_unused)
+            .field("snippets_file", &self.snippets_file)
+            .field("preview_invert", &self.preview_invert)
+            .field("preview_quality", &self.preview_quality)
+            .field("open_preview_on_start", &self.open_preview_on_start)
+            .field("main_files", &self.main_files)
+            .field("preview_layout", &self.preview_layout)
+            .field("lint_deprecated", &self.lint_deprecated)
+            .field("compile_interval", &self.compile_interval)
+            .field("debounce_min", &self.debounce_min)
+            .field("debounce_max", &self.debounce_max)
+            .field("preview_show_bleed", &self.preview_show_bleed)
+            .field("preview_bleed_mm", &self.preview_bleed_mm)
+            .field("preview_overview", &self.preview_overview)
+            .field("preview_keybindings", &self.preview_keybindings)
+            .field("preview_refine_delay", &self.preview_refine_delay)
+            .field("preview_page_style", &self.preview_page_style)
+            .field("inputs", &self.inputs)
+            .field("jump_scroll_margin", &self.jump_scroll_margin)
+            .field("preview_follow", &self.preview_follow)
+            .field("profiles", &self.profiles)
+            .field("active_profile", &self.active_profile)
+            .field("diagnostics", &self.diagnostics)
+            .field("preview_prerender_pages", &self.preview_prerender_pages)
+            .field("editor_items", &self.editor_items)
             .field(
                 "semantic_tokens_listeners",
                 &format_args!("Vec[len = {}]", self.semantic_tokens_listeners.len()),
@@ -234,6 +1085,12 @@ pub struct ConstConfig {
     pub supports_semantic_tokens_dynamic_registration: bool,
     pub supports_document_formatting_dynamic_registration: bool,
     pub supports_config_change_registration: bool,
+    /// The client requests diagnostics itself, so they shouldn't be pushed as well
+    pub supports_pull_diagnostics: bool,
+    /// The client can be asked to pull diagnostics again after a compilation
+    pub supports_diagnostic_refresh: bool,
+    /// Experimental Typst features from the `features` initialization option
+    pub features: Vec<String>,
 }
 
 impl ConstConfig {
@@ -245,6 +1102,36 @@ impl ConstConfig {
             PositionEncoding::Utf16
         }
     }
+
+    fn requested_features(params: &InitializeParams) -> Vec<String> {
+        params
+            .initialization_options
+            .as_ref()
+            .and_then(|init| init.get("features"))
+            .map(Vec::<String>::deserialize)
+            .and_then(Result::ok)
+            .unwrap_or_default()
+    }
+
+    /// Fails if a requested feature isn't supported by the bundled Typst version
+    pub fn validate_features(&self) -> anyhow::Result<()> {
+        let unknown: Vec<_> = self
+            .features
+            .iter()
+            .filter(|feature| !SUPPORTED_FEATURES.contains(&feature.as_str()))
+            .collect();
+        if !unknown.is_empty() {
+            bail!(
+                "unknown Typst features {} requested, Typst {TYPST_VERSION} supports {}",
+                unknown.iter().join(", "),
+                match SUPPORTED_FEATURES {
+                    [] => "none".to_owned(),
+                    supported => supported.join(", "),
+                }
+            );
+        }
+        Ok(())
+    }
 }
 
 impl From<&InitializeParams> for ConstConfig {
@@ -256,6 +1143,9 @@ impl From<&InitializeParams> for ConstConfig {
             supports_document_formatting_dynamic_registration: params
                 .supports_document_formatting_dynamic_registration(),
             supports_config_change_registration: params.supports_config_change_registration(),
+            supports_pull_diagnostics: params.supports_pull_diagnostics(),
+            supports_diagnostic_refresh: params.supports_diagnostic_refresh(),
+            features: Self::requested_features(params),
         }
     }
 }
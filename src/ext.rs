@@ -22,6 +22,8 @@ pub trait InitializeParamsExt {
     fn document_formatting_capabilities(&self) -> Option<&DocumentFormattingClientCapabilities>;
     fn supports_semantic_tokens_dynamic_registration(&self) -> bool;
     fn supports_document_formatting_dynamic_registration(&self) -> bool;
+    fn supports_pull_diagnostics(&self) -> bool;
+    fn supports_diagnostic_refresh(&self) -> bool;
     fn root_uris(&self) -> Vec<Url>;
 }
 
@@ -73,6 +75,22 @@ impl InitializeParamsExt for InitializeParams {
             .unwrap_or(false)
     }
 
+    fn supports_pull_diagnostics(&self) -> bool {
+        self.capabilities
+            .text_document
+            .as_ref()
+            .is_some_and(|text_document| text_document.diagnostic.is_some())
+    }
+
+    fn supports_diagnostic_refresh(&self) -> bool {
+        self.capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.diagnostic.as_ref())
+            .and_then(|diagnostic| diagnostic.refresh_support)
+            .unwrap_or(false)
+    }
+
     #[allow(deprecated)] // `self.root_path` is marked as deprecated
     fn root_uris(&self) -> Vec<Url> {
         match self.workspace_folders.as_ref() {
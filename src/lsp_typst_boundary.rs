@@ -129,7 +129,7 @@ pub mod typst_to_lsp {
         CompletionTextEdit, DiagnosticRelatedInformation, Documentation, InsertTextFormat,
         LanguageString, Location, MarkedString, MarkupContent, MarkupKind, TextEdit,
     };
-    use tracing::error;
+    use tracing::{error, warn};
     use typst::diag::{EcoString, Tracepoint};
     use typst::foundations::{CastInfo, Repr};
     use typst::syntax::{FileId, Source, Spanned};
@@ -290,11 +290,14 @@ pub mod typst_to_lsp {
         Ok(tracepoints)
     }
 
+    /// Converts a Typst diagnostic to the LSP diagnostic at its span. If the diagnostic is in a
+    /// file that was included or imported from another file, each `#include` or `#import` that led
+    /// to it gets a diagnostic as well, pointing back at the original location.
     async fn diagnostic(
         project: &Project,
         typst_diagnostic: &TypstDiagnostic,
         const_config: &ConstConfig,
-    ) -> anyhow::Result<(LspUri, LspDiagnostic)> {
+    ) -> anyhow::Result<Vec<(LspUri, LspDiagnostic)>> {
         let Some((id, span)) = diagnostic_span_id(typst_diagnostic) else {
             bail!("could not find any id")
         };
@@ -307,12 +310,56 @@ pub mod typst_to_lsp {
         let lsp_severity = diagnostic_severity(typst_diagnostic.severity);
 
         let typst_message = &typst_diagnostic.message;
-        let typst_hints = &typst_diagnostic.hints;
-        let lsp_message = format!("{typst_message}{}", diagnostic_hints(typst_hints));
+        let lsp_message = diagnostic_message(typst_diagnostic);
 
         let tracepoints =
             diagnostic_related_information(project, typst_diagnostic, const_config).await?;
 
+        let origin = Location {
+            uri: uri.clone(),
+            range: lsp_range.raw_range,
+        };
+
+        let mut diagnostics = Vec::with_capacity(1);
+        for tracepoint in &typst_diagnostic.trace {
+            let Some(import_id) = tracepoint.span.id() else {
+                continue;
+            };
+            if !matches!(tracepoint.v, Tracepoint::Import) || import_id == id {
+                continue;
+            }
+
+            // The diagnostic itself is still worth reporting without the include site
+            let import_uri = match project.full_id_to_uri(project.fill_id(import_id)).await {
+                Ok(import_uri) => import_uri,
+                Err(err) => {
+                    warn!(%err, "could not find the file including a diagnostic");
+                    continue;
+                }
+            };
+            let import_source = match project.read_source_by_uri(&import_uri) {
+                Ok(import_source) => import_source,
+                Err(err) => {
+                    warn!(%err, %import_uri, "could not read the file including a diagnostic");
+                    continue;
+                }
+            };
+            let import_range = diagnostic_range(&import_source, tracepoint.span, const_config);
+
+            let import_diagnostic = LspDiagnostic {
+                range: import_range.raw_range,
+                severity: Some(lsp_severity),
+                message: lsp_message.clone(),
+                source: Some("typst".to_owned()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: origin.clone(),
+                    message: typst_message.to_string(),
+                }]),
+                ..Default::default()
+            };
+            diagnostics.push((import_uri, import_diagnostic));
+        }
+
         let diagnostic = LspDiagnostic {
             range: lsp_range.raw_range,
             severity: Some(lsp_severity),
@@ -321,8 +368,9 @@ pub mod typst_to_lsp {
             related_information: Some(tracepoints),
             ..Default::default()
         };
+        diagnostics.push((uri, diagnostic));
 
-        Ok((uri, diagnostic))
+        Ok(diagnostics)
     }
 
     fn diagnostic_span_id(typst_diagnostic: &TypstDiagnostic) -> Option<(FileId, TypstSpan)> {
@@ -360,6 +408,14 @@ pub mod typst_to_lsp {
         }
     }
 
+    /// The message of the diagnostic, followed by its hints, so they aren't lost in clients which
+    /// don't show related information.
+    pub fn diagnostic_message(typst_diagnostic: &TypstDiagnostic) -> String {
+        let typst_message = &typst_diagnostic.message;
+        let typst_hints = &typst_diagnostic.hints;
+        format!("{typst_message}{}", diagnostic_hints(typst_hints))
+    }
+
     fn diagnostic_hints(typst_hints: &[EcoString]) -> Format<impl Iterator<Item = EcoString> + '_> {
         iter::repeat(EcoString::from("\n\nHint: "))
             .take(typst_hints.len())
@@ -382,6 +438,7 @@ pub mod typst_to_lsp {
             .collect::<Vec<_>>()
             .await
             .into_iter()
+            .flatten()
             .into_group_map()
     }
 
@@ -526,4 +583,18 @@ mod test {
         assert_eq!(post_emoji_position, post_emoji_actual);
         assert_eq!(end_position, end_actual);
     }
+
+    #[test]
+    fn diagnostic_message_includes_hints() {
+        let diagnostic = TypstDiagnostic::error(TypstSpan::detached(), "unknown variable: a-b")
+            .with_hint("if you meant to use subtraction, try adding spaces around the minus sign");
+
+        let message = typst_to_lsp::diagnostic_message(&diagnostic);
+
+        assert_eq!(
+            message,
+            "unknown variable: a-b\n\nHint: if you meant to use subtraction, try adding spaces \
+             around the minus sign"
+        );
+    }
 }
@@ -1,11 +1,14 @@
 #![recursion_limit = "256"]
 
-use bpaf::{construct, OptionParser, Parser};
+use std::path::PathBuf;
+
+use bpaf::{construct, long, OptionParser, Parser};
 use logging::{tracing_init, tracing_shutdown};
-use server::TypstServer;
 use server::{log::LspLayer, ui::Ui};
+use server::{SharedServer, TypstServer};
 use tower_lsp::{LspService, Server};
 use tracing_subscriber::{reload, Registry};
+use workspace::font_manager::FontManager;
 
 mod command;
 mod config;
@@ -26,7 +29,13 @@ async fn main() {
 
 #[tracing::instrument(skip_all)]
 async fn run(lsp_tracing_layer_handle: reload::Handle<Option<LspLayer>, Registry>) {
-    let _args = arg_parser().run();
+    let args = arg_parser().run();
+
+    // Search for fonts in the background while the server starts up, so they are ready by the
+    // first compilation.
+    FontManager::set_font_paths(args.font_paths);
+    tokio::task::spawn_blocking(FontManager::shared);
+    workspace::set_cli_inputs(args.inputs.into_iter().collect());
 
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
@@ -37,35 +46,87 @@ async fn run(lsp_tracing_layer_handle: reload::Handle<Option<LspLayer>, Registry
         once_cell::sync::OnceCell<std::sync::Arc<tokio::sync::RwLock<workspace::Workspace>>>,
     > = Default::default();
 
+    // The preview reads its settings from the same config the server updates
+    let config: std::sync::Arc<tokio::sync::RwLock<config::Config>> = Default::default();
+
+    // Number of running compilations, so the preview can show when the document is being compiled
+    let (compiling_tx, compiling_rx) = tokio::sync::watch::channel(0usize);
+    let compiling_tx = std::sync::Arc::new(compiling_tx);
+
+    // Whether the preview window is shown, toggled by a command and closing the window
+    let (preview_visible, _) = tokio::sync::watch::channel(true);
+    let preview_visible = std::sync::Arc::new(preview_visible);
+    let preview_visible_for_server = std::sync::Arc::clone(&preview_visible);
+
+    // The document shown next to the preview to compare it with, set by a command
+    let (compare_tx, compare_rx) = tokio::sync::watch::channel(None);
+
     let (tx, rx) = tokio::sync::oneshot::channel();
 
     let workspace_for_server = std::sync::Arc::clone(&workspace);
-    let (service, socket) = LspService::new(move |client| {
+    let config_for_server = std::sync::Arc::clone(&config);
+    let (service, socket) = LspService::build(move |client| {
         tx.send(client.clone()).unwrap();
-        TypstServer::new(
+        SharedServer::new(TypstServer::new(
             client,
             lsp_tracing_layer_handle,
             to_ui_tx,
             workspace_for_server,
-        )
-    });
+            config_for_server,
+            compiling_tx,
+            preview_visible_for_server,
+            compare_tx,
+        ))
+    })
+    .custom_method(
+        "typst-lsp/activeEditor",
+        SharedServer::active_editor_changed,
+    )
+    .finish();
 
     let server_fut = Server::new(stdin, stdout, socket).serve(service);
-    let ui_fut = Ui::run(workspace, rx.await.unwrap(), to_ui_rx);
+    let ui_fut = Ui::run(
+        workspace,
+        config,
+        rx.await.unwrap(),
+        to_ui_rx,
+        compiling_rx,
+        preview_visible,
+        compare_rx,
+    );
 
     futures::join!(server_fut, ui_fut);
 }
 
 #[derive(Debug, Clone)]
-struct Args {}
+struct Args {
+    font_paths: Vec<PathBuf>,
+    inputs: Vec<(String, String)>,
+}
 
 fn arg_parser() -> OptionParser<Args> {
-    construct!(Args {}).to_options().version(
-        format!(
-            "{}, commit {} (Typst version {TYPST_VERSION})",
-            env!("CARGO_PKG_VERSION"),
-            env!("GIT_COMMIT")
+    let font_paths = long("font-path")
+        .help("Add an additional directory to search for fonts")
+        .argument::<PathBuf>("DIR")
+        .many();
+
+    let inputs = long("input")
+        .help("Add a string key-value pair visible to documents through `sys.inputs`")
+        .argument::<String>("KEY=VALUE")
+        .parse(|input| match input.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+            _ => Err("expected an input like `key=value`"),
+        })
+        .many();
+
+    construct!(Args { font_paths, inputs })
+        .to_options()
+        .version(
+            format!(
+                "{}, commit {} (Typst version {TYPST_VERSION})",
+                env!("CARGO_PKG_VERSION"),
+                env!("GIT_COMMIT")
+            )
+            .as_str(),
         )
-        .as_str(),
-    )
 }
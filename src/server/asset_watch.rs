@@ -0,0 +1,122 @@
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use tower_lsp::lsp_types::Url;
+use tracing::{info, warn};
+
+use crate::config::DiagnosticsMode;
+
+use super::TypstServer;
+
+/// Files like images and data which compile and export the documents reading them again when
+/// they change, see [`TypstServer::watch_assets`]
+#[derive(Debug, Default)]
+pub struct AssetWatches {
+    /// Relative to the project root, `None` when not watching
+    globs: Option<GlobSet>,
+}
+
+impl AssetWatches {
+    pub fn is_watching(&self) -> bool {
+        self.globs.is_some()
+    }
+}
+
+impl TypstServer {
+    /// Compiles and exports the documents again whenever files they read change which match the
+    /// globs, replacing the globs watched so far, or stops watching with no globs. Which files a
+    /// document reads is only known once it was compiled, so the open documents are compiled
+    /// right away. Returns those which read matching files.
+    pub async fn watch_assets(&self, globs: &[String]) -> anyhow::Result<Vec<Url>> {
+        let globs = if globs.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in globs {
+                builder
+                    .add(Glob::new(pattern).with_context(|| format!("invalid glob `{pattern}`"))?);
+            }
+            Some(builder.build()?)
+        };
+        let watching = globs.is_some();
+        self.asset_watches.lock().globs = globs;
+        if !watching {
+            return Ok(Vec::new());
+        }
+
+        let open_uris = self.workspace().read().await.open_uris();
+        let mut roots = Vec::new();
+        for uri in open_uris {
+            let root = self.compilation_root(&uri).await;
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        roots.sort();
+
+        let mut documents = Vec::new();
+        for root in roots {
+            if let Err(err) = self.compile_source(&root).await {
+                warn!(%err, %root, "could not compile to find the assets read");
+                continue;
+            }
+            let read = self.included_files.lock().read_by(&root);
+            if self.watched_assets(&read).await.is_empty() {
+                continue;
+            }
+            documents.push(root);
+        }
+
+        Ok(documents)
+    }
+
+    /// Compiles and exports the documents which read any of the changed files again, if those
+    /// match the watched globs, so documents follow data files regenerated outside the editor
+    pub async fn assets_changed(&self, uris: &[Url]) {
+        if !self.asset_watches.lock().is_watching() {
+            return;
+        }
+
+        let mut roots = Vec::new();
+        for uri in self.watched_assets(uris).await {
+            for root in self.included_files.lock().mains_reading(&uri) {
+                if !roots.contains(&root) {
+                    roots.push(root);
+                }
+            }
+        }
+        if roots.is_empty() {
+            return;
+        }
+
+        let publish_diagnostics = self.config.read().await.diagnostics == DiagnosticsMode::On;
+        for root in roots {
+            info!(%root, "compiling again after assets changed");
+            if let Err(err) = self
+                .run_diagnostics_and_export(&root, None, publish_diagnostics)
+                .await
+            {
+                warn!(%err, %root, "could not compile after assets changed");
+            }
+        }
+        self.run_watched_exports().await;
+    }
+
+    /// The files among the given ones which match the watched globs
+    async fn watched_assets(&self, uris: &[Url]) -> Vec<Url> {
+        let workspace = self.read_workspace().await;
+        let watches = self.asset_watches.lock();
+        let Some(globs) = &watches.globs else {
+            return Vec::new();
+        };
+
+        uris.iter()
+            .filter(|uri| {
+                workspace.full_id(uri).is_ok_and(|full_id| {
+                    full_id.package().spec().is_none()
+                        && globs.is_match(full_id.vpath().as_rootless_path())
+                })
+            })
+            .cloned()
+            .collect()
+    }
+}
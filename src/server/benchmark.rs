@@ -0,0 +1,182 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    NumberOrString, Url, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressCreateParams,
+    WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+use tracing::{info, warn};
+
+use super::typst_compiler::compile;
+use super::TypstServer;
+
+pub const DEFAULT_BENCHMARK_RUNS: usize = 10;
+
+/// Distinguishes the progress tokens of concurrent benchmarks
+static BENCHMARK_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Benchmark {
+    pub file: Url,
+    pub runs: usize,
+    pub pages: usize,
+    /// With Typst's caches cleared before each run
+    pub cold: Timings,
+    /// With Typst's caches kept from the previous run
+    pub warm: Timings,
+}
+
+/// Compile durations in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timings {
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+}
+
+impl Timings {
+    fn new(mut durations: Vec<Duration>) -> Self {
+        durations.sort();
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let middle = durations.len() / 2;
+        let median = if durations.len().is_multiple_of(2) {
+            (millis(durations[middle - 1]) + millis(durations[middle])) / 2.0
+        } else {
+            millis(durations[middle])
+        };
+        Self {
+            min: millis(durations[0]),
+            median,
+            max: millis(durations[durations.len() - 1]),
+        }
+    }
+}
+
+impl TypstServer {
+    /// Compiles the file's document `runs` times cold and then `runs` times warm, reporting
+    /// progress to the client. Only compiles, so nothing is published or exported.
+    #[tracing::instrument(skip(self))]
+    pub async fn benchmark(&self, uri: &Url, runs: usize) -> anyhow::Result<Benchmark> {
+        if runs == 0 {
+            bail!("need at least one run");
+        }
+        let root = self.compilation_root(uri).await;
+
+        let count = BENCHMARK_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let token = NumberOrString::String(format!("typst-lsp/benchmark/{count}"));
+        let progress = self
+            .client
+            .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .map_err(|err| warn!(%err, "could not create progress for benchmark"))
+            .is_ok();
+        let progress_token = progress.then_some(&token);
+
+        self.report_progress(
+            progress_token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: "Benchmarking Typst compilation".to_owned(),
+                percentage: Some(0),
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        let result = self.benchmark_runs(&root, runs, progress_token).await;
+
+        self.report_progress(
+            progress_token,
+            WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+        )
+        .await;
+
+        let (pages, cold, warm) = result?;
+        let benchmark = Benchmark {
+            file: root,
+            runs,
+            pages,
+            cold: Timings::new(cold),
+            warm: Timings::new(warm),
+        };
+        info!(?benchmark, "benchmarked compilation");
+        Ok(benchmark)
+    }
+
+    async fn benchmark_runs(
+        &self,
+        root: &Url,
+        runs: usize,
+        progress_token: Option<&NumberOrString>,
+    ) -> anyhow::Result<(usize, Vec<Duration>, Vec<Duration>)> {
+        let mut pages = 0;
+        let mut cold = Vec::with_capacity(runs);
+        let mut warm = Vec::with_capacity(runs);
+
+        // The last cold run leaves the caches warm for the first warm run
+        for run in 0..2 * runs {
+            let is_cold = run < runs;
+            self.report_progress(
+                progress_token,
+                WorkDoneProgress::Report(WorkDoneProgressReport {
+                    message: Some(format!(
+                        "{} run {} of {runs}",
+                        if is_cold { "Cold" } else { "Warm" },
+                        run % runs + 1
+                    )),
+                    percentage: Some((run * 100 / (2 * runs)) as u32),
+                    ..Default::default()
+                }),
+            )
+            .await;
+
+            let (document, duration) = self
+                .thread_with_world(root)
+                .await?
+                .run(move |world| {
+                    if is_cold {
+                        comemo::evict(0);
+                    }
+                    let start = Instant::now();
+                    let (document, _) = compile(&world);
+                    (document, start.elapsed())
+                })
+                .await?;
+
+            pages = document
+                .context("the document failed to compile")?
+                .pages
+                .len();
+            if is_cold {
+                cold.push(duration);
+            } else {
+                warm.push(duration);
+            }
+        }
+
+        Ok((pages, cold, warm))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn takes_middle_of_sorted_durations() {
+        let millis = |millis: &[u64]| millis.iter().copied().map(Duration::from_millis).collect();
+
+        let timings = Timings::new(millis(&[30, 10, 20]));
+        assert_eq!(
+            (timings.min, timings.median, timings.max),
+            (10.0, 20.0, 30.0)
+        );
+        assert_eq!(Timings::new(millis(&[40, 10, 30, 20])).median, 25.0);
+    }
+}
@@ -0,0 +1,507 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{Location, Url};
+use typst::syntax::ast::{self, AstNode};
+use typst::syntax::{FileId, LinkedNode, Source, SyntaxKind};
+use typst::World;
+
+use crate::lsp_typst_boundary::typst_to_lsp;
+
+use super::lint::{self, walk};
+use super::typst_compiler::compile;
+use super::TypstServer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BibliographyIssueKind {
+    /// An entry with the same key as an earlier one, in the same or another file
+    DuplicateKey,
+    /// An entry which can't be parsed, or a bibliography file which can't be read
+    MalformedEntry,
+    /// A citation of a key which no bibliography has
+    MissingEntry,
+    /// An entry which is never cited
+    UnusedEntry,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BibliographyIssue {
+    pub kind: BibliographyIssueKind,
+    pub key: Option<String>,
+    pub message: String,
+    /// In the bibliography file for entries, in the Typst file for citations
+    pub location: Location,
+}
+
+/// What the `checkBibliography` command found
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BibliographyCheck {
+    /// The bibliography files the document uses
+    pub files: Vec<Url>,
+    pub issues: Vec<BibliographyIssue>,
+}
+
+/// A file passed to `bibliography(..)`, with the location of the path in the call
+struct BibliographyFile {
+    id: FileId,
+    call: (FileId, Range<usize>),
+    text: Result<String, String>,
+}
+
+/// An issue located by byte range in a Typst or bibliography file
+type Issue = (
+    BibliographyIssueKind,
+    Option<String>,
+    String,
+    FileId,
+    Range<usize>,
+);
+
+/// A cited key with the range of the citation
+type Citation = (String, FileId, Range<usize>);
+
+/// The key of a bibliography entry with the entry's range
+type Entry = (String, Range<usize>);
+
+/// The range of an entry which can't be read, with the reason
+type MalformedEntry = (Range<usize>, String);
+
+impl TypstServer {
+    /// Compiles the file's document and checks the bibliography files it uses against its
+    /// citations: duplicate keys, entries which can't be parsed, citations of keys which aren't in
+    /// a bibliography and entries which are never cited. BibTeX files are checked entry by entry,
+    /// Hayagriva YAML files only for their keys.
+    pub async fn check_bibliography(&self, uri: &Url) -> anyhow::Result<BibliographyCheck> {
+        let root = self.compilation_root(uri).await;
+        let (project, _) = self.project_and_full_id(&root).await?;
+
+        let (sources, files) = self
+            .thread_with_world(&root)
+            .await?
+            .run(|world| {
+                let _ = compile(&world);
+                let sources = lint::project_sources(&world);
+                let files: Vec<_> = sources
+                    .iter()
+                    .flat_map(bibliography_paths)
+                    .map(|(id, call)| BibliographyFile {
+                        id,
+                        call,
+                        text: world
+                            .file(id)
+                            .map_err(|err| err.to_string())
+                            .and_then(|bytes| {
+                                String::from_utf8(bytes.to_vec())
+                                    .map_err(|_| "the file is not UTF-8".to_owned())
+                            }),
+                    })
+                    .collect();
+                (sources, files)
+            })
+            .await?;
+
+        let issues = bibliography_issues(&sources, &files);
+
+        // Bibliography files are only read as bytes by the world, so they're made sources here
+        // just to convert their ranges
+        let mut texts: HashMap<FileId, Source> = sources
+            .into_iter()
+            .map(|source| (source.id(), source))
+            .collect();
+        let mut bibliographies = Vec::new();
+        for file in files {
+            if !bibliographies.contains(&file.id) {
+                bibliographies.push(file.id);
+            }
+            if let Ok(text) = file.text {
+                texts
+                    .entry(file.id)
+                    .or_insert_with(|| Source::new(file.id, text));
+            }
+        }
+
+        let position_encoding = self.const_config().position_encoding;
+        let mut located = Vec::new();
+        for (kind, key, message, id, range) in issues {
+            let Some(source) = texts.get(&id) else {
+                continue;
+            };
+            let uri = project.full_id_to_uri(project.fill_id(id)).await?;
+            located.push(BibliographyIssue {
+                kind,
+                key,
+                message,
+                location: Location {
+                    uri,
+                    range: typst_to_lsp::range(range, source, position_encoding).raw_range,
+                },
+            });
+        }
+
+        let mut file_uris = Vec::new();
+        for id in bibliographies {
+            file_uris.push(project.full_id_to_uri(project.fill_id(id)).await?);
+        }
+
+        Ok(BibliographyCheck {
+            files: file_uris,
+            issues: located,
+        })
+    }
+}
+
+/// Compares the entries of the bibliography files with the citations in the sources
+fn bibliography_issues(sources: &[Source], files: &[BibliographyFile]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let mut keys = HashSet::new();
+    let mut entries = Vec::new();
+    let mut read = HashSet::new();
+    for file in files {
+        // Several calls may use the same file
+        if !read.insert(file.id) {
+            continue;
+        }
+        let text = match &file.text {
+            Ok(text) => text,
+            Err(err) => {
+                let (id, range) = file.call.clone();
+                let message = format!("could not read the bibliography: {err}");
+                issues.push((
+                    BibliographyIssueKind::MalformedEntry,
+                    None,
+                    message,
+                    id,
+                    range,
+                ));
+                continue;
+            }
+        };
+
+        let yaml = matches!(
+            file.id.vpath().as_rootless_path().extension(),
+            Some(extension) if extension == "yml" || extension == "yaml"
+        );
+        let (file_entries, malformed) = if yaml {
+            (yaml_keys(text), Vec::new())
+        } else {
+            bibtex_entries(text)
+        };
+
+        for (range, message) in malformed {
+            issues.push((
+                BibliographyIssueKind::MalformedEntry,
+                None,
+                message,
+                file.id,
+                range,
+            ));
+        }
+        for (key, range) in file_entries {
+            if keys.contains(&key) {
+                let message = format!("the key `{key}` is used by an earlier entry");
+                issues.push((
+                    BibliographyIssueKind::DuplicateKey,
+                    Some(key),
+                    message,
+                    file.id,
+                    range,
+                ));
+                continue;
+            }
+            keys.insert(key.clone());
+            entries.push((key, file.id, range));
+        }
+    }
+
+    let (citations, labels, full) = citations(sources);
+    for (key, id, range) in &citations {
+        // `@name` refers to labels in the document as well
+        if !keys.contains(key) && !labels.contains(key) {
+            let message = format!("`{key}` is cited but no bibliography has it");
+            issues.push((
+                BibliographyIssueKind::MissingEntry,
+                Some(key.clone()),
+                message,
+                *id,
+                range.clone(),
+            ));
+        }
+    }
+
+    // With `full: true` all entries are listed, cited or not
+    if !full {
+        let cited: HashSet<_> = citations.iter().map(|(key, ..)| key).collect();
+        for (key, id, range) in entries {
+            if !cited.contains(&key) {
+                let message = format!("`{key}` is never cited");
+                issues.push((
+                    BibliographyIssueKind::UnusedEntry,
+                    Some(key),
+                    message,
+                    id,
+                    range,
+                ));
+            }
+        }
+    }
+
+    issues
+}
+
+/// The files passed to `bibliography(..)` as a path or an array of paths, with the range of each
+/// path in the source
+fn bibliography_paths(source: &Source) -> Vec<(FileId, (FileId, Range<usize>))> {
+    let mut paths = Vec::new();
+    walk(&LinkedNode::new(source.root()), &mut |node| {
+        if bibliography_call(node).is_none() {
+            return;
+        }
+        let Some(arg) = node.cast::<ast::FuncCall>().and_then(|call| {
+            call.args().items().find_map(|arg| match arg {
+                ast::Arg::Pos(expr) => Some(expr),
+                _ => None,
+            })
+        }) else {
+            return;
+        };
+
+        let strings: Vec<ast::Str> = match arg {
+            ast::Expr::Str(path) => vec![path],
+            ast::Expr::Array(array) => array
+                .items()
+                .filter_map(|item| match item {
+                    ast::ArrayItem::Pos(ast::Expr::Str(path)) => Some(path),
+                    _ => None,
+                })
+                .collect(),
+            _ => return,
+        };
+        for path in strings {
+            if let Some(path_node) = node.find(path.span()) {
+                let id = source.id().join(&path.get());
+                paths.push((id, (source.id(), path_node.range())));
+            }
+        }
+    });
+    paths
+}
+
+fn bibliography_call<'a>(node: &LinkedNode<'a>) -> Option<ast::FuncCall<'a>> {
+    let call = node.get().cast::<ast::FuncCall>()?;
+    let ast::Expr::Ident(callee) = call.callee() else {
+        return None;
+    };
+    (callee.as_str() == "bibliography").then_some(call)
+}
+
+/// The keys cited with `@key`, `cite(<key>)` or `cite(label("key"))` in the sources, the labels
+/// defined in markup, and whether a bibliography lists all its entries with `full: true`
+fn citations(sources: &[Source]) -> (Vec<Citation>, HashSet<String>, bool) {
+    let mut cited = Vec::new();
+    let mut labels = HashSet::new();
+    let mut full = false;
+
+    for source in sources {
+        walk(&LinkedNode::new(source.root()), &mut |node| {
+            if let Some(reference) = node.cast::<ast::Ref>() {
+                cited.push((reference.target().to_owned(), source.id(), node.range()));
+            } else if let Some(label) = node.cast::<ast::Label>() {
+                if node.parent_kind() == Some(SyntaxKind::Markup) {
+                    labels.insert(label.get().to_owned());
+                }
+            } else if let Some(call) = bibliography_call(node) {
+                full |= call.args().items().any(|arg| {
+                    matches!(arg, ast::Arg::Named(named)
+                        if named.name().as_str() == "full"
+                            && matches!(named.expr(), ast::Expr::Bool(full) if full.get()))
+                });
+            } else if let Some(call) = node.cast::<ast::FuncCall>() {
+                let ast::Expr::Ident(callee) = call.callee() else {
+                    return;
+                };
+                if callee.as_str() != "cite" {
+                    return;
+                }
+                let key = call.args().items().find_map(|arg| match arg {
+                    ast::Arg::Pos(ast::Expr::Label(label)) => Some(label.get().to_owned()),
+                    _ => None,
+                });
+                let key = key.or_else(|| {
+                    node.children()
+                        .find(|child| child.kind() == SyntaxKind::Args)
+                        .and_then(|args| args.children().find_map(|arg| lint::label_call(&arg)))
+                });
+                if let Some(key) = key {
+                    cited.push((key, source.id(), node.range()));
+                }
+            }
+        });
+    }
+
+    (cited, labels, full)
+}
+
+/// The keys of the entries in a BibTeX file with their ranges, and the entries which can't be
+/// read with the reason. Only the structure is checked, not the fields of entries.
+fn bibtex_entries(text: &str) -> (Vec<Entry>, Vec<MalformedEntry>) {
+    let mut entries = Vec::new();
+    let mut malformed = Vec::new();
+
+    let mut pos = 0;
+    while let Some(found) = text[pos..].find('@') {
+        let start = pos + found;
+        let rest = &text[start + 1..];
+        let kind_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        // Text between entries is a comment, which may contain `@`, like in email addresses
+        if kind_len == 0 {
+            pos = start + 1;
+            continue;
+        }
+        let kind = rest[..kind_len].to_ascii_lowercase();
+        let head = start..start + 1 + kind_len;
+
+        let after_kind = head.end + (text[head.end..].len() - text[head.end..].trim_start().len());
+        let close = match text[after_kind..].chars().next() {
+            Some('{') => '}',
+            Some('(') => ')',
+            // Only taken for an entry when it starts a line
+            _ if !text[..start]
+                .rsplit('\n')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .is_empty() =>
+            {
+                pos = head.end;
+                continue;
+            }
+            _ => {
+                malformed.push((head.clone(), format!("expected `{{` after `@{kind}`")));
+                pos = head.end;
+                continue;
+            }
+        };
+        let body_start = after_kind + 1;
+        let Some(body_len) = matching_close(&text[body_start..], close) else {
+            malformed.push((head, format!("the `@{kind}` entry is never closed")));
+            break;
+        };
+        let body = &text[body_start..body_start + body_len];
+        pos = body_start + body_len + 1;
+
+        if matches!(kind.as_str(), "comment" | "string" | "preamble") {
+            continue;
+        }
+
+        let key_len = body.find(',').unwrap_or(body.len());
+        let key = body[..key_len].trim();
+        if key.is_empty() || key.contains(|c: char| c.is_whitespace() || "={}\"".contains(c)) {
+            malformed.push((head, format!("the `@{kind}` entry has no key")));
+            continue;
+        }
+        let key_start = body_start + body[..key_len].find(key).unwrap_or_default();
+        entries.push((key.to_owned(), key_start..key_start + key.len()));
+    }
+
+    (entries, malformed)
+}
+
+/// Where the entry ends, at the closing character outside of braces
+fn matching_close(body: &str, close: char) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, c) in body.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            c if c == close && depth == 0 => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The keys of the entries in a Hayagriva YAML file, which are its top-level keys
+fn yaml_keys(text: &str) -> Vec<(String, Range<usize>)> {
+    let mut keys = Vec::new();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let starts_entry = line.starts_with(|c: char| !c.is_whitespace() && !"#-".contains(c));
+        if let Some(colon) = line.find(':').filter(|_| starts_entry) {
+            let key = line[..colon].trim_end();
+            let unquoted = key.trim_matches(|c| c == '"' || c == '\'');
+            let offset = line_start + key.find(unquoted).unwrap_or_default();
+            keys.push((unquoted.to_owned(), offset..offset + unquoted.len()));
+        }
+        line_start += line.len();
+    }
+    keys
+}
+
+#[cfg(test)]
+mod test {
+    use typst::syntax::VirtualPath;
+
+    use super::*;
+
+    #[test]
+    fn reports_duplicate_missing_and_unused_entries() {
+        let main = FileId::new(None, VirtualPath::new("main.typ"));
+        let text =
+            "@intro and @knuth, #cite(<lamport>)\n= Intro <intro>\n#bibliography(\"refs.bib\")";
+        let source = Source::new(main, text.to_owned());
+        let bib = "% Reach us at mail@example.com\n\
+                   @book{knuth, title = {The {TeX}book}}\n\
+                   @article{turing, title = {On Computable Numbers}}\n\
+                   @misc{knuth, title = {Again}}\n\
+                   @book{, title = {No key}}\n";
+        let files = bibliography_paths(&source)
+            .into_iter()
+            .map(|(id, call)| BibliographyFile {
+                id,
+                call,
+                text: Ok(bib.to_owned()),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+
+        let issues: Vec<_> = bibliography_issues(&[source], &files)
+            .into_iter()
+            .map(|(kind, key, _, id, range)| {
+                let text = if id == main { text } else { bib };
+                (kind, key, text[range].to_owned())
+            })
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (
+                    BibliographyIssueKind::MalformedEntry,
+                    None,
+                    "@book".to_owned()
+                ),
+                (
+                    BibliographyIssueKind::DuplicateKey,
+                    Some("knuth".to_owned()),
+                    "knuth".to_owned()
+                ),
+                (
+                    BibliographyIssueKind::MissingEntry,
+                    Some("lamport".to_owned()),
+                    "cite(<lamport>)".to_owned()
+                ),
+                (
+                    BibliographyIssueKind::UnusedEntry,
+                    Some("turing".to_owned()),
+                    "turing".to_owned()
+                ),
+            ]
+        );
+    }
+}
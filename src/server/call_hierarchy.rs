@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Position, SymbolKind,
+    Url,
+};
+use typst::syntax::ast::{self, AstNode};
+use typst::syntax::{FileId, LinkedNode, Source};
+
+use crate::config::PositionEncoding;
+use crate::lsp_typst_boundary::{lsp_to_typst, typst_to_lsp, LspRange};
+
+use super::TypstServer;
+
+/// How many imports deep calls are followed through re-exports, in case files import each other
+const MAX_IMPORT_DEPTH: usize = 8;
+
+/// A function bound with `let`, by the file it's defined in and its name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FunctionId {
+    file: FileId,
+    name: String,
+}
+
+/// Where calls are made from: a function, or the top level of a file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Caller {
+    Function(FunctionId),
+    File(FileId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Definition {
+    name: String,
+    /// Of the whole binding
+    range: Range<usize>,
+    name_range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Call {
+    /// The module the function is accessed on, as in `utils.f(..)`
+    module: Option<String>,
+    name: String,
+    /// Of the called name
+    range: Range<usize>,
+    /// The function the call is made in, or `None` at the top level
+    caller: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Import {
+    path: String,
+    /// The name the module itself is bound to, if any
+    module: Option<String>,
+    items: ImportItems,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ImportItems {
+    None,
+    All,
+    /// The original names of the imported items with the names they are bound to
+    Names(Vec<(String, String)>),
+}
+
+/// The function definitions, calls and imports of a source, from its syntax tree alone
+#[derive(Debug, Default)]
+struct Functions {
+    definitions: Vec<Definition>,
+    calls: Vec<Call>,
+    imports: Vec<Import>,
+}
+
+impl Functions {
+    fn new(source: &Source) -> Self {
+        let mut functions = Self::default();
+        functions.collect(&LinkedNode::new(source.root()), None);
+        functions
+    }
+
+    fn collect(&mut self, node: &LinkedNode, caller: Option<&str>) {
+        let mut caller = caller.map(str::to_owned);
+
+        if let Some((name, name_range)) = function_name(node) {
+            self.definitions.push(Definition {
+                name: name.clone(),
+                range: node.range(),
+                name_range,
+            });
+            caller = Some(name);
+        } else if let Some(call) = node.cast::<ast::FuncCall>() {
+            let callee = match call.callee() {
+                ast::Expr::Ident(ident) => Some((None, ident)),
+                ast::Expr::FieldAccess(access) => match access.target() {
+                    ast::Expr::Ident(module) => {
+                        Some((Some(module.get().to_string()), access.field()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some((module, ident)) = callee {
+                if let Some(range) = node.find(ident.span()).map(|ident| ident.range()) {
+                    self.calls.push(Call {
+                        module,
+                        name: ident.get().to_string(),
+                        range,
+                        caller: caller.clone(),
+                    });
+                }
+            }
+        } else if let Some(import) = node.cast::<ast::ModuleImport>() {
+            self.imports.extend(import_of(import));
+        }
+
+        for child in node.children() {
+            self.collect(&child, caller.as_deref());
+        }
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.definitions
+            .iter()
+            .any(|definition| definition.name == name)
+    }
+}
+
+/// The name of the function bound by a `let f(..) = ..` or `let f = (..) => ..`, with its range
+fn function_name(node: &LinkedNode) -> Option<(String, Range<usize>)> {
+    let binding = node.cast::<ast::LetBinding>()?;
+    let ident = match binding.kind() {
+        ast::LetBindingKind::Closure(ident) => ident,
+        ast::LetBindingKind::Normal(ast::Pattern::Normal(ast::Expr::Ident(ident)))
+            if matches!(binding.init(), Some(ast::Expr::Closure(_))) =>
+        {
+            ident
+        }
+        _ => return None,
+    };
+    let range = node.find(ident.span())?.range();
+    Some((ident.get().to_string(), range))
+}
+
+fn import_of(import: ast::ModuleImport) -> Option<Import> {
+    let ast::Expr::Str(path) = import.source() else {
+        return None;
+    };
+    let path = path.get().to_string();
+    if path.starts_with('@') {
+        return None;
+    }
+
+    let items = match import.imports() {
+        None => ImportItems::None,
+        Some(ast::Imports::Wildcard) => ImportItems::All,
+        Some(ast::Imports::Items(items)) => ImportItems::Names(
+            items
+                .iter()
+                .map(|item| match item {
+                    ast::ImportItem::Simple(ident) => {
+                        (ident.get().to_string(), ident.get().to_string())
+                    }
+                    ast::ImportItem::Renamed(renamed) => (
+                        renamed.original_name().get().to_string(),
+                        renamed.new_name().get().to_string(),
+                    ),
+                })
+                .collect(),
+        ),
+    };
+    // Without items, the module is bound to the name of the file
+    let module = match import.new_name() {
+        Some(name) => Some(name.get().to_string()),
+        None if items == ImportItems::None => std::path::Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned()),
+        None => None,
+    };
+
+    Some(Import {
+        path,
+        module,
+        items,
+    })
+}
+
+/// The functions of all Typst sources in a project
+struct Index {
+    files: HashMap<FileId, (Url, Source, Functions)>,
+    position_encoding: PositionEncoding,
+}
+
+impl Index {
+    /// The definition the name refers to in the file, possibly imported from another one
+    fn resolve(&self, file: FileId, module: Option<&str>, name: &str) -> Option<FunctionId> {
+        self.resolve_in(file, module, name, 0)
+    }
+
+    fn resolve_in(
+        &self,
+        file: FileId,
+        module: Option<&str>,
+        name: &str,
+        depth: usize,
+    ) -> Option<FunctionId> {
+        if depth > MAX_IMPORT_DEPTH {
+            return None;
+        }
+        let (_, _, functions) = self.files.get(&file)?;
+
+        if module.is_none() && functions.defines(name) {
+            return Some(FunctionId {
+                file,
+                name: name.to_owned(),
+            });
+        }
+
+        functions.imports.iter().rev().find_map(|import| {
+            let imported = file.join(&import.path);
+            match (module, &import.items) {
+                (Some(module), _) if import.module.as_deref() == Some(module) => {
+                    self.resolve_in(imported, None, name, depth + 1)
+                }
+                (Some(_), _) => None,
+                (None, ImportItems::All) => self.resolve_in(imported, None, name, depth + 1),
+                (None, ImportItems::Names(names)) => names
+                    .iter()
+                    .find(|(_, bound)| bound == name)
+                    .and_then(|(original, _)| self.resolve_in(imported, None, original, depth + 1)),
+                (None, ImportItems::None) => None,
+            }
+        })
+    }
+
+    /// The function defined or called at the offset of the file
+    fn function_at(&self, file: FileId, offset: usize) -> Option<FunctionId> {
+        let (_, _, functions) = self.files.get(&file)?;
+        let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+
+        if let Some(definition) = functions
+            .definitions
+            .iter()
+            .find(|definition| contains(&definition.name_range))
+        {
+            return Some(FunctionId {
+                file,
+                name: definition.name.clone(),
+            });
+        }
+        let call = functions.calls.iter().find(|call| contains(&call.range))?;
+        self.resolve(file, call.module.as_deref(), &call.name)
+    }
+
+    fn file_of(&self, uri: &Url) -> Option<FileId> {
+        self.files
+            .iter()
+            .find(|(_, (file_uri, _, _))| file_uri == uri)
+            .map(|(&id, _)| id)
+    }
+
+    fn caller_of(&self, item: &CallHierarchyItem) -> Option<Caller> {
+        let file = self.file_of(&item.uri)?;
+        Some(match item.kind {
+            SymbolKind::FILE => Caller::File(file),
+            _ => Caller::Function(FunctionId {
+                file,
+                name: item.name.clone(),
+            }),
+        })
+    }
+
+    fn range(&self, file: FileId, range: Range<usize>) -> Option<LspRange> {
+        let (_, source, _) = self.files.get(&file)?;
+        Some(typst_to_lsp::range(range, source, self.position_encoding))
+    }
+
+    fn item(&self, caller: &Caller) -> Option<CallHierarchyItem> {
+        match caller {
+            Caller::Function(function) => {
+                let (uri, _, functions) = self.files.get(&function.file)?;
+                let definition = functions
+                    .definitions
+                    .iter()
+                    .find(|definition| definition.name == function.name)?;
+                Some(CallHierarchyItem {
+                    name: function.name.clone(),
+                    kind: SymbolKind::FUNCTION,
+                    tags: None,
+                    detail: Some(
+                        function
+                            .file
+                            .vpath()
+                            .as_rootless_path()
+                            .display()
+                            .to_string(),
+                    ),
+                    uri: uri.clone(),
+                    range: self
+                        .range(function.file, definition.range.clone())?
+                        .raw_range,
+                    selection_range: self
+                        .range(function.file, definition.name_range.clone())?
+                        .raw_range,
+                    data: None,
+                })
+            }
+            Caller::File(file) => {
+                let (uri, source, _) = self.files.get(file)?;
+                let range = self.range(*file, 0..source.len_bytes())?.raw_range;
+                Some(CallHierarchyItem {
+                    name: file.vpath().as_rootless_path().display().to_string(),
+                    kind: SymbolKind::FILE,
+                    tags: None,
+                    detail: None,
+                    uri: uri.clone(),
+                    range,
+                    selection_range: range,
+                    data: None,
+                })
+            }
+        }
+    }
+
+    /// The calls of the function, grouped by where they are made from
+    fn incoming(&self, target: &FunctionId) -> Vec<CallHierarchyIncomingCall> {
+        let mut calls: HashMap<Caller, Vec<Range<usize>>> = HashMap::new();
+        for (&file, (_, _, functions)) in &self.files {
+            for call in &functions.calls {
+                if self
+                    .resolve(file, call.module.as_deref(), &call.name)
+                    .as_ref()
+                    != Some(target)
+                {
+                    continue;
+                }
+                let caller = match &call.caller {
+                    Some(name) => Caller::Function(FunctionId {
+                        file,
+                        name: name.clone(),
+                    }),
+                    None => Caller::File(file),
+                };
+                calls.entry(caller).or_default().push(call.range.clone());
+            }
+        }
+
+        calls
+            .into_iter()
+            .filter_map(|(caller, ranges)| {
+                let file = match &caller {
+                    Caller::Function(function) => function.file,
+                    Caller::File(file) => *file,
+                };
+                Some(CallHierarchyIncomingCall {
+                    from: self.item(&caller)?,
+                    from_ranges: self.ranges(file, ranges)?,
+                })
+            })
+            .collect()
+    }
+
+    /// The functions called from the caller, with the ranges of the calls
+    fn outgoing(&self, caller: &Caller) -> Vec<CallHierarchyOutgoingCall> {
+        let (file, name) = match caller {
+            Caller::Function(function) => (function.file, Some(function.name.as_str())),
+            Caller::File(file) => (*file, None),
+        };
+        let Some((_, _, functions)) = self.files.get(&file) else {
+            return Vec::new();
+        };
+
+        let mut calls: HashMap<FunctionId, Vec<Range<usize>>> = HashMap::new();
+        for call in &functions.calls {
+            if call.caller.as_deref() != name {
+                continue;
+            }
+            if let Some(target) = self.resolve(file, call.module.as_deref(), &call.name) {
+                calls.entry(target).or_default().push(call.range.clone());
+            }
+        }
+
+        calls
+            .into_iter()
+            .filter_map(|(target, ranges)| {
+                Some(CallHierarchyOutgoingCall {
+                    to: self.item(&Caller::Function(target))?,
+                    from_ranges: self.ranges(file, ranges)?,
+                })
+            })
+            .collect()
+    }
+
+    fn ranges(
+        &self,
+        file: FileId,
+        ranges: Vec<Range<usize>>,
+    ) -> Option<Vec<tower_lsp::lsp_types::Range>> {
+        ranges
+            .into_iter()
+            .map(|range| Some(self.range(file, range)?.raw_range))
+            .collect()
+    }
+}
+
+impl TypstServer {
+    /// The function defined or called at the position, for `textDocument/prepareCallHierarchy`.
+    /// Calls are resolved by name, to definitions in the same file or imported from other files
+    /// of the project.
+    pub async fn prepare_call_hierarchy(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> anyhow::Result<Option<Vec<CallHierarchyItem>>> {
+        let index = self.call_index(uri).await?;
+        let Some(file) = index.file_of(uri) else {
+            return Ok(None);
+        };
+        let (_, source, _) = &index.files[&file];
+        let offset = lsp_to_typst::position_to_offset(position, index.position_encoding, source);
+
+        let item = index
+            .function_at(file, offset)
+            .and_then(|function| index.item(&Caller::Function(function)));
+        Ok(item.map(|item| vec![item]))
+    }
+
+    pub async fn incoming_calls(
+        &self,
+        item: &CallHierarchyItem,
+    ) -> anyhow::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let index = self.call_index(&item.uri).await?;
+        Ok(match index.caller_of(item) {
+            Some(Caller::Function(function)) => Some(index.incoming(&function)),
+            _ => None,
+        })
+    }
+
+    pub async fn outgoing_calls(
+        &self,
+        item: &CallHierarchyItem,
+    ) -> anyhow::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let index = self.call_index(&item.uri).await?;
+        Ok(index.caller_of(item).map(|caller| index.outgoing(&caller)))
+    }
+
+    /// Collects the functions of the Typst files in the project of the file
+    async fn call_index(&self, uri: &Url) -> anyhow::Result<Index> {
+        let (project, _) = self.project_and_full_id(uri).await?;
+
+        let mut files = HashMap::new();
+        for id in project.file_ids() {
+            let is_typst = id
+                .vpath()
+                .as_rootless_path()
+                .extension()
+                .is_some_and(|extension| extension == "typ");
+            if !is_typst {
+                continue;
+            }
+            let uri = project.full_id_to_uri(project.fill_id(id)).await?;
+            let Ok(source) = project.read_source_by_uri(&uri) else {
+                continue;
+            };
+            let functions = Functions::new(&source);
+            files.insert(id, (uri, source, functions));
+        }
+
+        Ok(Index {
+            files,
+            position_encoding: self.const_config().position_encoding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use typst::syntax::VirtualPath;
+
+    use super::*;
+
+    fn index(files: &[(&str, &str)]) -> Index {
+        let files = files
+            .iter()
+            .map(|(path, text)| {
+                let id = FileId::new(None, VirtualPath::new(path));
+                let uri = Url::parse(&format!("file:///project/{path}")).unwrap();
+                let source = Source::new(id, (*text).to_owned());
+                let functions = Functions::new(&source);
+                (id, (uri, source, functions))
+            })
+            .collect();
+        Index {
+            files,
+            position_encoding: PositionEncoding::Utf16,
+        }
+    }
+
+    #[test]
+    fn resolves_calls_through_imports() {
+        let lib = FileId::new(None, VirtualPath::new("lib.typ"));
+        let main = FileId::new(None, VirtualPath::new("main.typ"));
+        let index = index(&[
+            (
+                "lib.typ",
+                "#let helper(x) = x\n#let note(body) = helper(body)",
+            ),
+            (
+                "main.typ",
+                "#import \"lib.typ\": note as n\n#import \"lib.typ\"\n#n[A] #lib.helper(1)",
+            ),
+        ]);
+        let helper = FunctionId {
+            file: lib,
+            name: "helper".to_owned(),
+        };
+
+        assert_eq!(
+            index.resolve(main, None, "n"),
+            Some(FunctionId {
+                file: lib,
+                name: "note".to_owned()
+            })
+        );
+        assert_eq!(
+            index.resolve(main, Some("lib"), "helper"),
+            Some(helper.clone())
+        );
+
+        let mut callers: Vec<_> = index
+            .incoming(&helper)
+            .into_iter()
+            .map(|call| (call.from.name, call.from_ranges.len()))
+            .collect();
+        callers.sort();
+        assert_eq!(
+            callers,
+            [("main.typ".to_owned(), 1), ("note".to_owned(), 1)]
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Command, Range as LspRawRange, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{ast, LinkedNode, Source, SyntaxKind};
+
+use crate::config::{LintDeprecatedMode, PositionEncoding};
+use crate::lsp_typst_boundary::{lsp_to_typst, typst_to_lsp, LspRange, TypstRange};
+use crate::workspace::project::Project;
+
+use super::command::LspCommand;
+use super::deprecations::deprecation_actions;
+use super::TypstServer;
+
+impl TypstServer {
+    /// Collects the code actions available in the given range of a source.
+    pub async fn code_actions(
+        &self,
+        uri: &Url,
+        range: LspRawRange,
+    ) -> anyhow::Result<Vec<CodeActionOrCommand>> {
+        let position_encoding = self.const_config().position_encoding;
+        let lint_deprecated =
+            self.config.read().await.lint_deprecated == LintDeprecatedMode::Enable;
+
+        let actions = self
+            .scope_with_source(uri)
+            .await?
+            .run2(|source, project| async move {
+                let typst_range =
+                    lsp_to_typst::range(&LspRange::new(range, position_encoding), &source);
+
+                let mut actions = Vec::new();
+                actions.extend(
+                    Self::create_file_actions(uri, &source, &project, typst_range.clone()).await,
+                );
+                actions.extend(Self::download_package_actions(
+                    uri,
+                    &source,
+                    &project,
+                    typst_range.clone(),
+                ));
+                if lint_deprecated {
+                    actions.extend(deprecation_actions(
+                        uri,
+                        &source,
+                        typst_range.clone(),
+                        position_encoding,
+                    ));
+                }
+                actions.extend(wrap_actions(uri, &source, typst_range, position_encoding));
+                actions
+            })
+            .await;
+
+        Ok(actions)
+    }
+
+    /// Offers to create the files of `#include`s in the range which don't exist yet.
+    async fn create_file_actions(
+        uri: &Url,
+        source: &Source,
+        project: &Project,
+        range: TypstRange,
+    ) -> Vec<CodeActionOrCommand> {
+        // Files in packages are never written to
+        if source.id().package().is_some() {
+            return Vec::new();
+        }
+
+        let mut paths = Vec::new();
+        module_paths_in_range(
+            &LinkedNode::new(source.root()),
+            &range,
+            ModuleKind::Include,
+            &mut paths,
+        );
+
+        let mut actions = Vec::new();
+        for path in paths {
+            let id = source.id().join(&path);
+            let Ok(target) = project.full_id_to_uri(project.fill_id(id)).await else {
+                continue;
+            };
+
+            if !project.in_workspace(&target) || project.read_bytes_by_uri(&target).is_ok() {
+                continue;
+            }
+
+            let title = format!("Create {path}");
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: title.clone(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                command: Some(Command {
+                    title,
+                    command: LspCommand::CreateFile.into(),
+                    arguments: Some(vec![target.to_string().into(), uri.to_string().into()]),
+                }),
+                ..Default::default()
+            }));
+        }
+
+        actions
+    }
+
+    /// Offers to download the packages of `#import`s in the range which aren't available locally.
+    fn download_package_actions(
+        uri: &Url,
+        source: &Source,
+        project: &Project,
+        range: TypstRange,
+    ) -> Vec<CodeActionOrCommand> {
+        let mut paths = Vec::new();
+        module_paths_in_range(
+            &LinkedNode::new(source.root()),
+            &range,
+            ModuleKind::Import,
+            &mut paths,
+        );
+
+        paths
+            .iter()
+            .filter_map(|path| PackageSpec::from_str(path).ok())
+            // Only packages from the package repository can be downloaded
+            .filter(|spec| spec.namespace == "preview" && !project.package_available(spec))
+            .map(|spec| {
+                let title = format!("Download package {spec}");
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.clone(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    command: Some(Command {
+                        title,
+                        command: LspCommand::DownloadPackage.into(),
+                        arguments: Some(vec![spec.to_string().into(), uri.to_string().into()]),
+                    }),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Offers to wrap the selection in a content block or a code block, if it consists of whole
+/// syntax nodes in markup or code.
+fn wrap_actions(
+    uri: &Url,
+    source: &Source,
+    range: TypstRange,
+    position_encoding: PositionEncoding,
+) -> Vec<CodeActionOrCommand> {
+    let Some((mode, range)) = selected_nodes(source, range) else {
+        return Vec::new();
+    };
+    let selected = &source.text()[range.clone()];
+
+    // Markup and code can't simply be nested in each other, so switch modes inside the new block
+    let (content, code) = match mode {
+        SelectionMode::Markup => (format!("#[{selected}]"), format!("#{{ [{selected}] }}")),
+        SelectionMode::Embedded => (
+            format!("#[{selected}]"),
+            format!("#{{ {} }}", &selected[1..]),
+        ),
+        SelectionMode::Code => (format!("[#({selected})]"), format!("{{ {selected} }}")),
+    };
+
+    let lsp_range = typst_to_lsp::range(range, source, position_encoding).raw_range;
+    let action = |title: &str, kind: &'static str, new_text: String| {
+        let edit = TextEdit {
+            range: lsp_range,
+            new_text,
+        };
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.to_owned(),
+            kind: Some(CodeActionKind::new(kind)),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    };
+
+    vec![
+        action(
+            "Wrap in content block",
+            "refactor.rewrite.wrapInContent",
+            content,
+        ),
+        action("Wrap in code block", "refactor.rewrite.wrapInCode", code),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SelectionMode {
+    Markup,
+    /// A single expression embedded in markup, including its `#`
+    Embedded,
+    Code,
+}
+
+/// Checks that the range, without surrounding whitespace, covers a run of whole sibling nodes in
+/// markup or code, and returns the mode and the trimmed range.
+fn selected_nodes(source: &Source, range: TypstRange) -> Option<(SelectionMode, TypstRange)> {
+    let selected = source.text().get(range.clone())?;
+    let start = range.start + (selected.len() - selected.trim_start().len());
+    let end = range.end - (selected.len() - selected.trim_end().len());
+    if start >= end {
+        return None;
+    }
+    let range = start..end;
+
+    let mut parent = LinkedNode::new(source.root());
+    loop {
+        let enclosing = parent.children().find(|child| {
+            let child_range = child.range();
+            child_range.start <= range.start
+                && range.end <= child_range.end
+                && child_range != range
+                && child.get().children().len() > 0
+        });
+        match enclosing {
+            Some(child) => parent = child,
+            None => break,
+        }
+    }
+
+    let selected: Vec<_> = parent
+        .children()
+        .filter(|child| range.start <= child.range().start && child.range().end <= range.end)
+        .collect();
+    let first = selected.first()?;
+    let last = selected.last()?;
+    if first.range().start != range.start || last.range().end != range.end {
+        return None;
+    }
+
+    let mode = match parent.kind() {
+        // Expressions embedded in markup without their `#` can't be wrapped on their own
+        SyntaxKind::Markup
+            if first
+                .prev_leaf()
+                .is_some_and(|prev| prev.kind() == SyntaxKind::Hash) =>
+        {
+            return None
+        }
+        SyntaxKind::Markup if selected.len() == 2 && first.kind() == SyntaxKind::Hash => {
+            SelectionMode::Embedded
+        }
+        SyntaxKind::Markup => SelectionMode::Markup,
+        SyntaxKind::Math | SyntaxKind::Raw => return None,
+        _ => SelectionMode::Code,
+    };
+    Some((mode, range))
+}
+
+/// Which kind of module path to collect with [`module_paths_in_range`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModuleKind {
+    Include,
+    Import,
+}
+
+/// Finds the string paths of `#include`s or `#import`s overlapping the range.
+fn module_paths_in_range(
+    node: &LinkedNode,
+    range: &TypstRange,
+    kind: ModuleKind,
+    paths: &mut Vec<String>,
+) {
+    let node_range = node.range();
+    if node_range.end < range.start || node_range.start > range.end {
+        return;
+    }
+
+    let source = match kind {
+        ModuleKind::Include => node
+            .cast::<ast::ModuleInclude>()
+            .map(|include| include.source()),
+        ModuleKind::Import => node
+            .cast::<ast::ModuleImport>()
+            .map(|import| import.source()),
+    };
+    if let Some(ast::Expr::Str(path)) = source {
+        paths.push(path.get().to_string());
+    }
+
+    for child in node.children() {
+        module_paths_in_range(&child, range, kind, paths);
+    }
+}
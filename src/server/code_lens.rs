@@ -0,0 +1,98 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower_lsp::lsp_types::{CodeLens, Command, Range, Url};
+
+use super::command::LspCommand;
+use super::TypstServer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum LensKind {
+    Preview,
+    ExportPdf,
+    PinMain,
+}
+
+/// What a lens is for, kept in the lens until the client asks to resolve its command
+#[derive(Debug, Serialize, Deserialize)]
+struct LensData {
+    kind: LensKind,
+    uri: Url,
+}
+
+/// Lenses at the top of the file to preview it, export it and pin it as the main file. Their
+/// commands are left for [`TypstServer::resolve_code_lens`], since finding the main file to preview
+/// and export may mean compiling.
+pub fn code_lenses(uri: &Url) -> Vec<CodeLens> {
+    [LensKind::Preview, LensKind::ExportPdf, LensKind::PinMain]
+        .into_iter()
+        .map(|kind| CodeLens {
+            range: Range::default(),
+            command: None,
+            data: serde_json::to_value(LensData {
+                kind,
+                uri: uri.clone(),
+            })
+            .ok(),
+        })
+        .collect()
+}
+
+impl TypstServer {
+    /// Fills in the command of a lens from [`code_lenses`]. Previewing and exporting apply to the
+    /// main file the document belongs to, pinning to the document itself.
+    pub async fn resolve_code_lens(&self, mut lens: CodeLens) -> anyhow::Result<CodeLens> {
+        let data = lens.data.take().context("code lens has no data")?;
+        let LensData { kind, uri } = serde_json::from_value(data)?;
+
+        let target = match kind {
+            LensKind::Preview | LensKind::ExportPdf => self.compilation_root(&uri).await,
+            LensKind::PinMain => uri.clone(),
+        };
+        let pinned = self.main_url().await.as_ref() == Some(&uri);
+        lens.command = Some(lens_command(kind, &target, pinned));
+
+        Ok(lens)
+    }
+}
+
+/// The command of a lens, run on the main file for previewing and exporting, or on the document
+/// for pinning
+fn lens_command(kind: LensKind, target: &Url, pinned: bool) -> Command {
+    match kind {
+        LensKind::Preview => command("▶ Preview", LspCommand::OpenPreview, target.as_str()),
+        LensKind::ExportPdf => command("⬇ Export PDF", LspCommand::ExportPdf, target.as_str()),
+        LensKind::PinMain if pinned => command("📌 Unpin main", LspCommand::PinMain, "detached"),
+        LensKind::PinMain => command("📌 Pin as main", LspCommand::PinMain, target.as_str()),
+    }
+}
+
+fn command(title: &str, command: LspCommand, argument: &str) -> Command {
+    Command {
+        title: title.to_owned(),
+        command: command.into(),
+        arguments: Some(vec![Value::String(argument.to_owned())]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lenses_run_their_commands() {
+        let uri = Url::parse("file:///project/main.typ").unwrap();
+        let run = |kind, pinned| lens_command(kind, &uri, pinned).command;
+
+        assert_eq!(run(LensKind::Preview, false), "typst-lsp.openPreview");
+        assert_eq!(run(LensKind::ExportPdf, false), "typst-lsp.doPdfExport");
+        assert_eq!(run(LensKind::PinMain, false), "typst-lsp.doPinMain");
+
+        let unpin = lens_command(LensKind::PinMain, &uri, true);
+        assert_eq!(
+            unpin.arguments,
+            Some(vec![Value::String("detached".to_owned())])
+        );
+    }
+}
@@ -1,11 +1,46 @@
-use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use itertools::Itertools;
+use serde::Deserialize;
+use serde_json::{json, Value};
 use tower_lsp::jsonrpc;
 use tower_lsp::{
     jsonrpc::{Error, Result},
-    lsp_types::Url,
+    lsp_types::{Position as LspPosition, Range as LspRawRange, ShowDocumentParams, Url},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use typst::diag::Severity;
+use typst::layout::{Abs, Point};
+use typst::model::Document;
+use typst::syntax::package::PackageSpec;
+use typst::syntax::Source;
+
+use crate::config::ConfigSource;
+use crate::lsp_typst_boundary::{lsp_to_typst, LspRange};
+use crate::workspace::SUPPORTED_FEATURES;
 
+use super::benchmark::DEFAULT_BENCHMARK_RUNS;
+use super::deep_link::DeepLink;
+use super::doctor::DoctorOptions;
+use super::export::{
+    export_document, export_slides, file_name_suffix, page_png, page_svg, pdf_path, pixel_png,
+    pixel_png_path, region_png, region_png_path, write_pdf, ExportFormat, InputsOptions,
+    PdfOptions, PixelOptions, RegionOptions, SlidesOptions,
+};
+use super::outline::{headings, table_of_contents, OutlineOptions};
+use super::page_size::{with_page_size, PageSize};
+use super::query::{query, QueryOptions};
+use super::reveal::reveal_in_file_manager;
+use super::selection_export::snippet_text;
+use super::typst_compiler::compile;
+use super::ui::{CompareDocument, CompareOptions};
+use super::visual_diff::{diff_dir, export_visual_diff, text_at_revision, DiffOptions};
 use super::TypstServer;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,6 +48,44 @@ pub enum LspCommand {
     ExportPdf,
     ClearCache,
     PinMain,
+    ExportPageSvg,
+    UsedFonts,
+    SourceTree,
+    ExportSlides,
+    OpenPdf,
+    LockPackages,
+    CreateFile,
+    DownloadPackage,
+    DebugWorld,
+    CheckReferences,
+    WatchExport,
+    UnwatchExport,
+    ShowConfig,
+    ExportAll,
+    RenderPreviewImage,
+    ExportOutline,
+    ElementAt,
+    ExportSelection,
+    ExportPageSize,
+    MissingFiles,
+    ParseTree,
+    OpenDeepLink,
+    Benchmark,
+    OpenPreview,
+    ExportPixels,
+    Query,
+    DiffExport,
+    RevealPdf,
+    ExportWithInputs,
+    Doctor,
+    ExportDiagnostics,
+    RenderRegion,
+    UseProfile,
+    ListLabels,
+    TogglePreview,
+    Compare,
+    CheckBibliography,
+    WatchAssets,
 }
 
 impl From<LspCommand> for String {
@@ -21,6 +94,44 @@ impl From<LspCommand> for String {
             LspCommand::ExportPdf => "typst-lsp.doPdfExport".to_string(),
             LspCommand::ClearCache => "typst-lsp.doClearCache".to_string(),
             LspCommand::PinMain => "typst-lsp.doPinMain".to_string(),
+            LspCommand::ExportPageSvg => "typst-lsp.exportPageSvg".to_string(),
+            LspCommand::UsedFonts => "typst-lsp.usedFonts".to_string(),
+            LspCommand::SourceTree => "typst-lsp.sourceTree".to_string(),
+            LspCommand::ExportSlides => "typst-lsp.exportSlides".to_string(),
+            LspCommand::OpenPdf => "typst-lsp.openPdf".to_string(),
+            LspCommand::LockPackages => "typst-lsp.lockPackages".to_string(),
+            LspCommand::CreateFile => "typst-lsp.createFile".to_string(),
+            LspCommand::DownloadPackage => "typst-lsp.downloadPackage".to_string(),
+            LspCommand::DebugWorld => "typst-lsp.debugWorld".to_string(),
+            LspCommand::CheckReferences => "typst-lsp.checkReferences".to_string(),
+            LspCommand::WatchExport => "typst-lsp.watchExport".to_string(),
+            LspCommand::UnwatchExport => "typst-lsp.unwatchExport".to_string(),
+            LspCommand::ShowConfig => "typst-lsp.showConfig".to_string(),
+            LspCommand::ExportAll => "typst-lsp.exportAll".to_string(),
+            LspCommand::RenderPreviewImage => "typst-lsp.renderPreviewImage".to_string(),
+            LspCommand::ExportOutline => "typst-lsp.exportOutline".to_string(),
+            LspCommand::ElementAt => "typst-lsp.elementAt".to_string(),
+            LspCommand::ExportSelection => "typst-lsp.exportSelection".to_string(),
+            LspCommand::ExportPageSize => "typst-lsp.exportPageSize".to_string(),
+            LspCommand::MissingFiles => "typst-lsp.missingFiles".to_string(),
+            LspCommand::ParseTree => "typst-lsp.parseTree".to_string(),
+            LspCommand::OpenDeepLink => "typst-lsp.openDeepLink".to_string(),
+            LspCommand::Benchmark => "typst-lsp.benchmark".to_string(),
+            LspCommand::OpenPreview => "typst-lsp.openPreview".to_string(),
+            LspCommand::ExportPixels => "typst-lsp.exportPixels".to_string(),
+            LspCommand::Query => "typst-lsp.query".to_string(),
+            LspCommand::DiffExport => "typst-lsp.diffExport".to_string(),
+            LspCommand::RevealPdf => "typst-lsp.revealPdf".to_string(),
+            LspCommand::ExportWithInputs => "typst-lsp.exportWithInputs".to_string(),
+            LspCommand::Doctor => "typst-lsp.doctor".to_string(),
+            LspCommand::ExportDiagnostics => "typst-lsp.exportDiagnostics".to_string(),
+            LspCommand::RenderRegion => "typst-lsp.renderRegion".to_string(),
+            LspCommand::UseProfile => "typst-lsp.useProfile".to_string(),
+            LspCommand::ListLabels => "typst-lsp.listLabels".to_string(),
+            LspCommand::TogglePreview => "typst-lsp.togglePreview".to_string(),
+            LspCommand::Compare => "typst-lsp.compare".to_string(),
+            LspCommand::CheckBibliography => "typst-lsp.checkBibliography".to_string(),
+            LspCommand::WatchAssets => "typst-lsp.watchAssets".to_string(),
         }
     }
 }
@@ -31,16 +142,98 @@ impl LspCommand {
             "typst-lsp.doPdfExport" => Some(Self::ExportPdf),
             "typst-lsp.doClearCache" => Some(Self::ClearCache),
             "typst-lsp.doPinMain" => Some(Self::PinMain),
+            "typst-lsp.exportPageSvg" => Some(Self::ExportPageSvg),
+            "typst-lsp.usedFonts" => Some(Self::UsedFonts),
+            "typst-lsp.sourceTree" => Some(Self::SourceTree),
+            "typst-lsp.exportSlides" => Some(Self::ExportSlides),
+            "typst-lsp.openPdf" => Some(Self::OpenPdf),
+            "typst-lsp.lockPackages" => Some(Self::LockPackages),
+            "typst-lsp.createFile" => Some(Self::CreateFile),
+            "typst-lsp.downloadPackage" => Some(Self::DownloadPackage),
+            "typst-lsp.debugWorld" => Some(Self::DebugWorld),
+            "typst-lsp.checkReferences" => Some(Self::CheckReferences),
+            "typst-lsp.watchExport" => Some(Self::WatchExport),
+            "typst-lsp.unwatchExport" => Some(Self::UnwatchExport),
+            "typst-lsp.showConfig" => Some(Self::ShowConfig),
+            "typst-lsp.exportAll" => Some(Self::ExportAll),
+            "typst-lsp.renderPreviewImage" => Some(Self::RenderPreviewImage),
+            "typst-lsp.exportOutline" => Some(Self::ExportOutline),
+            "typst-lsp.elementAt" => Some(Self::ElementAt),
+            "typst-lsp.exportSelection" => Some(Self::ExportSelection),
+            "typst-lsp.exportPageSize" => Some(Self::ExportPageSize),
+            "typst-lsp.missingFiles" => Some(Self::MissingFiles),
+            "typst-lsp.parseTree" => Some(Self::ParseTree),
+            "typst-lsp.openDeepLink" => Some(Self::OpenDeepLink),
+            "typst-lsp.benchmark" => Some(Self::Benchmark),
+            "typst-lsp.openPreview" => Some(Self::OpenPreview),
+            "typst-lsp.exportPixels" => Some(Self::ExportPixels),
+            "typst-lsp.query" => Some(Self::Query),
+            "typst-lsp.diffExport" => Some(Self::DiffExport),
+            "typst-lsp.revealPdf" => Some(Self::RevealPdf),
+            "typst-lsp.exportWithInputs" => Some(Self::ExportWithInputs),
+            "typst-lsp.doctor" => Some(Self::Doctor),
+            "typst-lsp.exportDiagnostics" => Some(Self::ExportDiagnostics),
+            "typst-lsp.renderRegion" => Some(Self::RenderRegion),
+            "typst-lsp.useProfile" => Some(Self::UseProfile),
+            "typst-lsp.listLabels" => Some(Self::ListLabels),
+            "typst-lsp.togglePreview" => Some(Self::TogglePreview),
+            "typst-lsp.compare" => Some(Self::Compare),
+            "typst-lsp.checkBibliography" => Some(Self::CheckBibliography),
+            "typst-lsp.watchAssets" => Some(Self::WatchAssets),
             _ => None,
         }
     }
 
     pub fn all_as_string() -> Vec<String> {
-        vec![
+        let mut commands = vec![
             Self::ExportPdf.into(),
             Self::ClearCache.into(),
             Self::PinMain.into(),
-        ]
+            Self::ExportPageSvg.into(),
+            Self::UsedFonts.into(),
+            Self::SourceTree.into(),
+            Self::ExportSlides.into(),
+            Self::OpenPdf.into(),
+            Self::LockPackages.into(),
+            Self::CreateFile.into(),
+            Self::DownloadPackage.into(),
+            Self::CheckReferences.into(),
+            Self::WatchExport.into(),
+            Self::UnwatchExport.into(),
+            Self::ShowConfig.into(),
+            Self::ExportAll.into(),
+            Self::RenderPreviewImage.into(),
+            Self::ExportOutline.into(),
+            Self::ElementAt.into(),
+            Self::ExportSelection.into(),
+            Self::ExportPageSize.into(),
+            Self::MissingFiles.into(),
+            Self::OpenDeepLink.into(),
+            Self::Benchmark.into(),
+            Self::OpenPreview.into(),
+            Self::ExportPixels.into(),
+            Self::Query.into(),
+            Self::DiffExport.into(),
+            Self::RevealPdf.into(),
+            Self::ExportWithInputs.into(),
+            Self::Doctor.into(),
+            Self::ExportDiagnostics.into(),
+            Self::RenderRegion.into(),
+            Self::UseProfile.into(),
+            Self::ListLabels.into(),
+            Self::TogglePreview.into(),
+            Self::Compare.into(),
+            Self::CheckBibliography.into(),
+            Self::WatchAssets.into(),
+        ];
+
+        // Only meant for maintainers diagnosing issues, so not advertised in release builds
+        if cfg!(debug_assertions) {
+            commands.push(Self::DebugWorld.into());
+            commands.push(Self::ParseTree.into());
+        }
+
+        commands
     }
 }
 
@@ -74,7 +267,10 @@ impl TypstServer {
             jsonrpc::Error::internal_error()
         })?;
 
-        self.typst(|_| comemo::evict(0)).await;
+        self.typst(|_| comemo::evict(0)).await.map_err(|err| {
+            error!(%err, "could not clear cache");
+            jsonrpc::Error::internal_error()
+        })?;
 
         Ok(())
     }
@@ -109,6 +305,1348 @@ impl TypstServer {
             main_url = self.main_url().await
         );
 
+        if let Some(main_uri) = self.main_url().await {
+            self.open_preview_on_start(&main_uri).await;
+        }
+
         Ok(())
     }
+
+    /// Render a single page of the document as SVG and return the SVG string. The client passes
+    /// the file URI and the zero-based page index.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_page_svg(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let page_index = page_index_argument(&arguments, 1)?;
+
+        let document = self.compiled_document(&file_uri).await?;
+
+        let svg = page_svg(&document, page_index).ok_or_else(|| {
+            Error::invalid_params(format!(
+                "Page index {page_index} is out of range for a document with {} pages",
+                document.pages.len()
+            ))
+        })?;
+
+        Ok(Value::String(svg))
+    }
+
+    /// Compile the document and list the fonts used in its output, with whether each was a
+    /// fallback and how many glyphs were set in it.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_used_fonts(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let document = self.compiled_document(&file_uri).await?;
+
+        let used_fonts = self.used_fonts(&document).await;
+
+        serde_json::to_value(used_fonts).map_err(|err| {
+            error!(%err, "could not serialize used fonts");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Report the tree of `#include` and `#import` relationships of a document.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_source_tree(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+
+        let source_tree = self.source_tree(&file_uri).await.map_err(|err| {
+            error!(%err, "could not build source tree");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        serde_json::to_value(source_tree).map_err(|err| {
+            error!(%err, "could not serialize source tree");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Report the state of the world the main file would be compiled in. Meant for debugging.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_debug_world(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+
+        let debug_world = self.debug_world(&file_uri).await.map_err(|err| {
+            error!(%err, "could not inspect world");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        serde_json::to_value(debug_world).map_err(|err| {
+            error!(%err, "could not serialize world");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Export every page as a high-resolution PNG for presenting, optionally along with a PDF of
+    /// the deck. The client passes the file URI and optionally an object with the target `width`
+    /// and `height` in pixels and whether to write a `pdf`. Returns the URIs of the written files.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_slides(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let options = match arguments.get(1) {
+            Some(options) => SlidesOptions::deserialize(options)
+                .map_err(|err| Error::invalid_params(format!("Invalid slide options: {err}")))?,
+            None => SlidesOptions::default(),
+        };
+        let source_path = file_uri
+            .to_file_path()
+            .map_err(|()| Error::invalid_params("Slides can only be exported for local files"))?;
+
+        let pdf_options = self.pdf_options(&file_uri).await?;
+        let document = self.compiled_document(&file_uri).await?;
+
+        let written = tokio::task::spawn_blocking(move || {
+            export_slides(&document, &source_path, options, &pdf_options)
+        })
+        .await
+        .map_err(|err| {
+            error!(%err, "slide export panicked");
+            jsonrpc::Error::internal_error()
+        })?
+        .map_err(|err| {
+            error!(%err, "could not export slides");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let uris = written
+            .into_iter()
+            .filter_map(|path| Url::from_file_path(path).ok())
+            .map(|uri| Value::String(uri.into()))
+            .collect();
+        Ok(Value::Array(uris))
+    }
+
+    /// Export the document as a PDF next to its source and open it in the system's PDF viewer.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_open_pdf(&self, arguments: Vec<Value>) -> Result<()> {
+        let file_uri = uri_argument(&arguments)?;
+        let Some(pdf_path) = pdf_path(&file_uri) else {
+            return Err(Error::invalid_params(
+                "PDFs can only be exported for local files",
+            ));
+        };
+
+        let pdf_options = self.pdf_options(&file_uri).await?;
+        let document = self.compiled_document(&file_uri).await?;
+
+        let path = pdf_path.clone();
+        tokio::task::spawn_blocking(move || write_pdf(&document, &path, &pdf_options))
+            .await
+            .map_err(|err| {
+                error!(%err, "PDF export panicked");
+                jsonrpc::Error::internal_error()
+            })?
+            .map_err(|err| {
+                error!(%err, "could not export PDF");
+                jsonrpc::Error::internal_error()
+            })?;
+
+        if !pdf_path.is_file() {
+            return Err(Error::invalid_params(
+                "The export has not produced a PDF file yet",
+            ));
+        }
+        let pdf_uri = Url::from_file_path(&pdf_path).map_err(|()| {
+            error!(path = %pdf_path.display(), "could not convert PDF path to URI");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let params = ShowDocumentParams {
+            uri: pdf_uri,
+            external: Some(true),
+            take_focus: Some(true),
+            selection: None,
+        };
+        let shown = self.client.show_document(params).await?;
+        if !shown {
+            warn!(path = %pdf_path.display(), "client did not open the PDF");
+        }
+
+        Ok(())
+    }
+
+    /// Write the exact versions of the packages the document uses to `typst.lock` in the project
+    /// root. Returns the URI of the lockfile.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_lock_packages(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+
+        let lock_uri = self.lock_packages(&file_uri).await.map_err(|err| {
+            error!(%err, "could not lock packages");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        Ok(Value::String(lock_uri.into()))
+    }
+
+    /// Create an empty file and open it. The client passes the URI of the new file and the URI of
+    /// the source that refers to it, which is compiled again afterwards.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_create_file(&self, arguments: Vec<Value>) -> Result<()> {
+        let file_uri = uri_argument(&arguments)?;
+        let source_uri = arguments
+            .get(1)
+            .and_then(Value::as_str)
+            .and_then(|uri| Url::parse(uri).ok());
+
+        {
+            let mut workspace = self.workspace().write().await;
+            if workspace.read_bytes(&file_uri).is_ok() {
+                return Err(Error::invalid_params("File already exists"));
+            }
+            workspace
+                .write_local(file_uri.clone(), &[])
+                .map_err(|err| {
+                    error!(%err, "could not create file");
+                    jsonrpc::Error::internal_error()
+                })?;
+        }
+
+        let params = ShowDocumentParams {
+            uri: file_uri,
+            external: Some(false),
+            take_focus: Some(true),
+            selection: None,
+        };
+        self.client.show_document(params).await?;
+
+        if let Some(source_uri) = source_uri {
+            self.on_source_changed(&source_uri, None).await;
+        }
+
+        Ok(())
+    }
+
+    /// Download a package and compile the source that imports it again. The client passes the
+    /// package spec and the URI of the source.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_download_package(&self, arguments: Vec<Value>) -> Result<()> {
+        let Some(spec) = arguments.first().and_then(Value::as_str) else {
+            return Err(Error::invalid_params(
+                "Missing package spec as first argument",
+            ));
+        };
+        let spec = PackageSpec::from_str(spec)
+            .map_err(|err| Error::invalid_params(format!("Invalid package spec: {err}")))?;
+        let source_uri = arguments
+            .get(1)
+            .and_then(Value::as_str)
+            .and_then(|uri| Url::parse(uri).ok());
+
+        self.download_packages("Downloading Typst package", &[spec])
+            .await
+            .map_err(|err| {
+                error!(%err, "could not download package");
+                jsonrpc::Error::internal_error()
+            })?;
+
+        if let Some(source_uri) = source_uri {
+            self.on_source_changed(&source_uri, None).await;
+        }
+
+        Ok(())
+    }
+
+    /// Compile the document and list the references to labels or bibliography entries which don't
+    /// resolve, with where they are. An empty list means all references resolve.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_check_references(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+
+        let unresolved = self.unresolved_references(&file_uri).await.map_err(|err| {
+            error!(%err, "could not check references");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        serde_json::to_value(unresolved).map_err(|err| {
+            error!(%err, "could not serialize unresolved references");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Export the document now and again whenever its output changes, until `unwatchExport`. The
+    /// client passes the file URI and optionally the format, `"pdf"`, `"png"`, `"svg"` or
+    /// `"svgMerged"`, which defaults to the file's `export` directive, else PDF. Returns the URIs
+    /// of the files written now.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_watch_export(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let format = arguments
+            .get(1)
+            .map(ExportFormat::deserialize)
+            .transpose()
+            .map_err(|err| Error::invalid_params(format!("Invalid export format: {err}")))?;
+
+        let written = self.watch_export(&file_uri, format).await.map_err(|err| {
+            error!(%err, "could not export watched file");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let uris = written
+            .into_iter()
+            .filter_map(|path| Url::from_file_path(path).ok())
+            .map(|uri| Value::String(uri.into()))
+            .collect();
+        Ok(Value::Array(uris))
+    }
+
+    /// Stop exporting the document after changes, as started by `watchExport`.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_unwatch_export(&self, arguments: Vec<Value>) -> Result<()> {
+        let file_uri = uri_argument(&arguments)?;
+
+        if !self.unwatch_export(&file_uri) {
+            return Err(Error::invalid_params(
+                "The file is not being exported on changes",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the configuration the server actually uses, with the value of every setting and
+    /// whether it's the default, came from a command line flag, the editor, or a command. There are
+    /// no project configuration files, so no setting comes from one. Also lists the experimental
+    /// Typst features which were requested and those which are supported.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_show_config(&self) -> Result<Value> {
+        let mut effective = self.config.read().await.effective();
+
+        let features = &self.const_config().features;
+        let features_source = match features.as_slice() {
+            [] => ConfigSource::Default,
+            _ => ConfigSource::Editor,
+        };
+        effective.insert(
+            "features".to_owned(),
+            json!({ "value": features, "source": features_source }),
+        );
+        effective.insert("supportedFeatures".to_owned(), json!(SUPPORTED_FEATURES));
+
+        Ok(Value::Object(effective))
+    }
+
+    /// Export every main file matching `mainFiles` as a PDF, or the pinned main file if there is
+    /// one. Returns the URIs of the written files.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_all(&self) -> Result<Value> {
+        let written = self.export_all().await.map_err(|err| {
+            error!(%err, "could not export all main files");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let uris: Vec<_> = written
+            .iter()
+            .filter_map(|path| Url::from_file_path(path).ok())
+            .collect();
+        serde_json::to_value(uris).map_err(|err| {
+            error!(%err, "could not serialize exported files");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Render a single page of the document as a PNG and return it base64-encoded, for thumbnails
+    /// in the editor. The client passes the file URI, and optionally the zero-based page index,
+    /// which defaults to the first page, and the pixels per point, which defaults to the file's
+    /// `dpi` directive, else 2. Large pages are scaled down to at most 2048 pixels on each side.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_render_preview_image(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let page_index = match arguments.get(1) {
+            Some(_) => page_index_argument(&arguments, 1)?,
+            None => 0,
+        };
+        let pixel_per_pt = match arguments.get(2) {
+            Some(scale) => scale
+                .as_f64()
+                .filter(|scale| *scale > 0.0)
+                .ok_or_else(|| Error::invalid_params("Scale must be a positive number"))?
+                as f32,
+            None => self.export_directives(&file_uri).await.pixel_per_pt(),
+        };
+
+        let document = self.compiled_document(&file_uri).await?;
+        let page_count = document.pages.len();
+
+        let png =
+            tokio::task::spawn_blocking(move || page_png(&document, page_index, pixel_per_pt))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|png| png)
+                .map_err(|err| {
+                    error!(%err, "could not render page");
+                    jsonrpc::Error::internal_error()
+                })?
+                .ok_or_else(|| {
+                    Error::invalid_params(format!(
+                "Page index {page_index} is out of range for a document with {page_count} pages"
+            ))
+                })?;
+
+        Ok(Value::String(STANDARD.encode(png)))
+    }
+
+    /// Build a table of contents from the headings of the compiled document, with the page each
+    /// heading is on. The client passes the file URI and optionally an object with the `format`,
+    /// `markdown` (the default) or `text`, and an `output` file to also write it to. Returns the
+    /// table of contents.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_outline(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let options = match arguments.get(1) {
+            Some(options) => OutlineOptions::deserialize(options)
+                .map_err(|err| Error::invalid_params(format!("Invalid outline options: {err}")))?,
+            None => OutlineOptions::default(),
+        };
+
+        let document = self.compiled_document(&file_uri).await?;
+        let toc = table_of_contents(&headings(&document), options.format);
+
+        if let Some(output) = options.output {
+            let source_path = file_uri.to_file_path().map_err(|()| {
+                Error::invalid_params("The outline can only be written next to local files")
+            })?;
+            let output = match source_path.parent() {
+                Some(dir) => dir.join(output),
+                None => output,
+            };
+            tokio::fs::write(&output, &toc).await.map_err(|err| {
+                error!(%err, ?output, "could not write outline");
+                jsonrpc::Error::internal_error()
+            })?;
+            info!(?output, "wrote outline");
+        }
+
+        Ok(Value::String(toc))
+    }
+
+    /// Hit-test a page of the document, like clicking the preview does. The client passes the file
+    /// URI, the zero-based page index and the `x` and `y` coordinates of the point in points from
+    /// the page's top left. Returns the kind, bounding box and source location of the topmost
+    /// element at the point, or null if there is none.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_element_at(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let page_index = page_index_argument(&arguments, 1)?;
+        let coordinate = |position: usize| {
+            arguments
+                .get(position)
+                .and_then(Value::as_f64)
+                .map(Abs::pt)
+                .ok_or_else(|| Error::invalid_params("Missing point coordinates"))
+        };
+        let point = Point::new(coordinate(2)?, coordinate(3)?);
+
+        let document = self.compiled_document(&file_uri).await?;
+        let element = self
+            .element_at(&file_uri, &document, page_index, point)
+            .await
+            .map_err(|err| {
+                error!(%err, "could not find element at point");
+                jsonrpc::Error::internal_error()
+            })?;
+
+        serde_json::to_value(element).map_err(|err| {
+            error!(%err, "could not serialize element");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Export the selected part of a file as a document of its own, like a single figure or table.
+    /// The client passes the file URI, the selected range and optionally the format, `"pdf"`,
+    /// `"png"`, `"svg"` or `"svgMerged"`, which defaults to the file's `export` directive, else
+    /// PDF. The snippet keeps the file's imports, bindings and rules before it,
+    /// and is written next to the file with `-selection` appended to its name. Returns the URIs of
+    /// the written files.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_selection(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let range = arguments
+            .get(1)
+            .map(LspRawRange::deserialize)
+            .and_then(std::result::Result::ok)
+            .ok_or_else(|| Error::invalid_params("Missing selected range argument"))?;
+        let format = arguments
+            .get(2)
+            .map(ExportFormat::deserialize)
+            .transpose()
+            .map_err(|err| Error::invalid_params(format!("Invalid export format: {err}")))?;
+        let position_encoding = self.const_config().position_encoding;
+
+        self.export_variant(
+            &file_uri,
+            format,
+            &BTreeMap::new(),
+            "selection",
+            "The selection on its own",
+            |source| {
+                let range = lsp_to_typst::range(&LspRange::new(range, position_encoding), source);
+                snippet_text(source, range).map_err(Error::invalid_params)
+            },
+        )
+        .await
+    }
+
+    /// Compile a variant of the file, whose text `variant` derives from the file's source, with
+    /// the `inputs` set over the configured ones, and export it next to the file with `-{suffix}`
+    /// appended to its name. The format defaults to the file's `export` directive, else the
+    /// profile's, else PDF, and `what` names the variant in errors. Returns the URIs of the written
+    /// files.
+    async fn export_variant(
+        &self,
+        file_uri: &Url,
+        format: Option<ExportFormat>,
+        inputs: &BTreeMap<String, String>,
+        suffix: &str,
+        what: &str,
+        variant: impl FnOnce(&Source) -> Result<String>,
+    ) -> Result<Value> {
+        let source_path = file_uri
+            .to_file_path()
+            .map_err(|()| Error::invalid_params("Only local files can be exported"))?;
+        let directives = self.export_directives(file_uri).await;
+        let (profile_format, profile_pixel_per_pt, standard) = {
+            let config = self.config.read().await;
+            (
+                config.export_format(),
+                config.export_pixel_per_pt(),
+                config.pdf_standard(),
+            )
+        };
+        let format = directives.format_over_profile(format, profile_format);
+
+        let (source, project) = self
+            .scope_with_source(file_uri)
+            .await
+            .map_err(|err| {
+                error!(%err, "could not read file to export");
+                jsonrpc::Error::internal_error()
+            })?
+            .run2(|source, project| (source, project));
+        let text = variant(&source)?;
+        let project = project.with_inputs(inputs);
+
+        let (document, diagnostics) = self
+            .thread_with_world((Source::new(source.id(), text), project))
+            .await
+            .map_err(|err| {
+                error!(%err, "could not set up world for variant");
+                jsonrpc::Error::internal_error()
+            })?
+            .run(|world| compile(&world))
+            .await
+            .map_err(|err| {
+                error!(%err, "compiling the variant panicked");
+                jsonrpc::Error::internal_error()
+            })?;
+        let Some(document) = document else {
+            let errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .map(|diagnostic| diagnostic.message.as_str())
+                .join("; ");
+            return Err(Error::invalid_params(format!(
+                "{what} can't be compiled: {errors}"
+            )));
+        };
+
+        let pdf_options = PdfOptions {
+            standard,
+            ..Default::default()
+        };
+        let stem = source_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let variant_path = source_path.with_file_name(format!("{stem}-{suffix}.typ"));
+        let written = tokio::task::spawn_blocking(move || {
+            let pixel_per_pt = profile_pixel_per_pt.unwrap_or_else(|| directives.pixel_per_pt());
+            export_document(&document, &variant_path, format, pixel_per_pt, &pdf_options)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|written| written)
+        .map_err(|err| {
+            error!(%err, "could not export variant");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let uris: Vec<_> = written
+            .iter()
+            .filter_map(|path| Url::from_file_path(path).ok())
+            .collect();
+        serde_json::to_value(uris).map_err(|err| {
+            error!(%err, "could not serialize exported files");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Export the document at another page size than its own, for print variants, without editing
+    /// it. The client passes the file URI, the page size, either a paper name like `"a4"` or an
+    /// object with the `width` and `height` like `"8.5in"`, and optionally the format. The file is
+    /// written next to the document with the size appended to its name. Returns the URIs of the
+    /// written files.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_page_size(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let page_size = arguments
+            .get(1)
+            .ok_or_else(|| Error::invalid_params("Missing page size argument"))
+            .and_then(|page_size| {
+                PageSize::deserialize(page_size)
+                    .map_err(|err| Error::invalid_params(format!("Invalid page size: {err}")))
+            })?;
+        let format = arguments
+            .get(2)
+            .map(ExportFormat::deserialize)
+            .transpose()
+            .map_err(|err| Error::invalid_params(format!("Invalid export format: {err}")))?;
+        let set_rule = page_size.set_rule().map_err(Error::invalid_params)?;
+
+        self.export_variant(
+            &file_uri,
+            format,
+            &BTreeMap::new(),
+            &page_size.suffix(),
+            "The document at this page size",
+            |source| Ok(with_page_size(source, &set_rule)),
+        )
+        .await
+    }
+
+    /// List the paths of imports, includes and files read by functions like `image` which don't
+    /// resolve, for a health check of the project after moving files around. The client passes
+    /// the file URI. Returns the path, the error and the location of each.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_missing_files(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+
+        let missing = self.missing_files(&file_uri).await.map_err(|err| {
+            error!(%err, "could not collect missing files");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        serde_json::to_value(missing).map_err(|err| {
+            error!(%err, "could not serialize missing files");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Return the syntax tree of the file as JSON, with the kind, byte range and LSP range of each
+    /// node, for tooling and for reproducing issues with features built on the syntax tree
+    #[tracing::instrument(skip(self))]
+    pub async fn command_parse_tree(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+
+        let tree = self.parse_tree(&file_uri).await.map_err(|err| {
+            error!(%err, "could not parse file");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        serde_json::to_value(tree).map_err(|err| {
+            error!(%err, "could not serialize syntax tree");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Open the spot a deep link like the preview copies points to, see [`deep_link`]. The client
+    /// passes the link and optionally the URI of a file in the project to resolve it in, which
+    /// defaults to the main file.
+    ///
+    /// [`deep_link`]: super::deep_link
+    #[tracing::instrument(skip(self))]
+    pub async fn command_open_deep_link(&self, arguments: Vec<Value>) -> Result<Value> {
+        let link: DeepLink = arguments
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::invalid_params("Missing deep link argument"))?
+            .parse()
+            .map_err(|err| Error::invalid_params(format!("Invalid deep link: {err:#}")))?;
+        let project_uri = match arguments
+            .get(1)
+            .and_then(Value::as_str)
+            .and_then(|uri| Url::parse(uri).ok())
+        {
+            Some(uri) => Some(uri),
+            None => self.main_url().await,
+        };
+        let project_uri = project_uri.ok_or_else(|| {
+            Error::invalid_params("No project to open the link in, pass a file or pin a main file")
+        })?;
+
+        let (uri, position) = self
+            .resolve_deep_link(&link, &project_uri)
+            .await
+            .map_err(|err| Error::invalid_params(format!("Could not open {link}: {err:#}")))?;
+
+        let params = ShowDocumentParams {
+            uri,
+            external: Some(false),
+            take_focus: Some(true),
+            selection: Some(LspRawRange {
+                start: position,
+                end: position,
+            }),
+        };
+        let shown = self.client.show_document(params).await?;
+
+        Ok(Value::Bool(shown))
+    }
+
+    /// Benchmark compiling the document of the file, see [`TypstServer::benchmark`]. The client
+    /// passes the file URI and optionally the number of runs, which defaults to 10. Returns the
+    /// minimum, median and maximum timings of cold and warm compilations and the page count.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_benchmark(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let runs =
+            match arguments.get(1) {
+                Some(runs) => runs.as_u64().filter(|&runs| runs > 0).ok_or_else(|| {
+                    Error::invalid_params("Number of runs must be a positive integer")
+                })? as usize,
+                None => DEFAULT_BENCHMARK_RUNS,
+            };
+
+        let benchmark = self.benchmark(&file_uri, runs).await.map_err(|err| {
+            error!(%err, "could not benchmark compilation");
+            Error::invalid_params(format!("Could not benchmark compilation: {err:#}"))
+        })?;
+
+        serde_json::to_value(benchmark).map_err(|err| {
+            error!(%err, "could not serialize benchmark");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Show the document of the file in the preview. The client passes the file URI and optionally
+    /// the position of the cursor in it, which the preview scrolls to. Without one, it starts at the
+    /// top of the document.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_open_preview(&self, arguments: Vec<Value>) -> Result<()> {
+        let file_uri = uri_argument(&arguments)?;
+        let cursor = arguments
+            .get(1)
+            .filter(|cursor| !cursor.is_null())
+            .map(LspPosition::deserialize)
+            .transpose()
+            .map_err(|err| Error::invalid_params(format!("Invalid cursor position: {err}")))?;
+
+        self.open_preview(&file_uri, cursor).await.map_err(|err| {
+            error!(%err, "could not open preview");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Export a page as a PNG of exactly the given width and height in pixels, next to the
+    /// source. The second argument holds the [`PixelOptions`]; documents with several pages need
+    /// a page index. Returns the URI of the written image.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_pixels(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let options = arguments
+            .get(1)
+            .ok_or_else(|| Error::invalid_params("Missing pixel options argument"))
+            .and_then(|options| {
+                PixelOptions::deserialize(options)
+                    .map_err(|err| Error::invalid_params(format!("Invalid pixel options: {err}")))
+            })?;
+        if options.width == 0 || options.height == 0 {
+            return Err(Error::invalid_params("Image dimensions must not be zero"));
+        }
+        let source_path = file_uri
+            .to_file_path()
+            .map_err(|()| Error::invalid_params("Images can only be exported for local files"))?;
+
+        let document = self.compiled_document(&file_uri).await?;
+        let page_index = match options.page {
+            Some(index) => index,
+            None if document.pages.len() > 1 => {
+                return Err(Error::invalid_params(format!(
+                    "The document has {} pages, pass the index of the page to export",
+                    document.pages.len()
+                )))
+            }
+            None => 0,
+        };
+        if page_index >= document.pages.len() {
+            return Err(Error::invalid_params(format!(
+                "No page with index {page_index}"
+            )));
+        }
+        let path = pixel_png_path(&source_path, &options).map_err(|err| {
+            error!(%err, "could not determine image path");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let written = tokio::task::spawn_blocking(move || {
+            let frame = &document.pages[page_index].frame;
+            let png = pixel_png(frame, options.width, options.height, options.fit)?;
+            std::fs::write(&path, png)?;
+            anyhow::Ok(path)
+        })
+        .await
+        .map_err(|err| {
+            error!(%err, "pixel export panicked");
+            jsonrpc::Error::internal_error()
+        })?
+        .map_err(|err| {
+            error!(%err, "could not export image");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let uri = Url::from_file_path(written).map_err(|()| jsonrpc::Error::internal_error())?;
+        Ok(Value::String(uri.into()))
+    }
+
+    /// Query the compiled document for the elements matching a selector, like `typst query`.
+    /// The second argument holds the [`QueryOptions`]. Returns the elements as JSON.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_query(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let options = arguments
+            .get(1)
+            .ok_or_else(|| Error::invalid_params("Missing query options argument"))
+            .and_then(|options| {
+                QueryOptions::deserialize(options)
+                    .map_err(|err| Error::invalid_params(format!("Invalid query options: {err}")))
+            })?;
+
+        let document = self.compiled_document(&file_uri).await?;
+        let root = self.compilation_root(&file_uri).await;
+        let result = self
+            .thread_with_world(&root)
+            .await
+            .map_err(|err| {
+                error!(%err, "could not set up world for query");
+                jsonrpc::Error::internal_error()
+            })?
+            .run(move |world| query(&world, &document, &options))
+            .await
+            .map_err(|err| {
+                error!(%err, "query panicked");
+                jsonrpc::Error::internal_error()
+            })?;
+
+        result.map_err(Error::invalid_params)
+    }
+
+    /// Render the document and its version at a git revision, `HEAD` unless the second argument's
+    /// [`DiffOptions`] say otherwise, and write an image of each page that changed to a
+    /// `<name>-diff` directory next to the file, with the changed pixels highlighted. Only the
+    /// file itself is taken from the revision, the files it includes are used as they are now.
+    /// Returns the pages that changed, with the URIs of their images.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_diff_export(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let options = match arguments.get(1) {
+            Some(options) => DiffOptions::deserialize(options)
+                .map_err(|err| Error::invalid_params(format!("Invalid diff options: {err}")))?,
+            None => DiffOptions::default(),
+        };
+        if options.pixel_per_pt.is_nan() || options.pixel_per_pt <= 0.0 {
+            return Err(Error::invalid_params("Pixels per point must be positive"));
+        }
+        let source_path = file_uri
+            .to_file_path()
+            .map_err(|()| Error::invalid_params("Only local files can be diffed"))?;
+
+        let old_document = self
+            .document_at_revision(&file_uri, &options.revision)
+            .await?;
+        let new_document = self.compiled_document(&file_uri).await?;
+
+        let diffs = tokio::task::spawn_blocking(move || {
+            let dir = diff_dir(&source_path)?;
+            export_visual_diff(&old_document, &new_document, &dir, options.pixel_per_pt)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|diffs| diffs)
+        .map_err(|err| {
+            error!(%err, "could not export visual diff");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let diffs: Vec<_> = diffs
+            .into_iter()
+            .map(|diff| {
+                json!({
+                    "page": diff.page,
+                    "changedPixels": diff.changed_pixels,
+                    "uri": Url::from_file_path(&diff.path).ok(),
+                })
+            })
+            .collect();
+        Ok(Value::Array(diffs))
+    }
+
+    /// Open the system's file manager with the document's exported PDF selected, exporting it
+    /// first if it hasn't been yet. The PDF is where `exportPdf` writes it, next to the file.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_reveal_pdf(&self, arguments: Vec<Value>) -> Result<()> {
+        let file_uri = uri_argument(&arguments)?;
+        let Some(pdf_path) = pdf_path(&file_uri) else {
+            return Err(Error::invalid_params(
+                "PDFs can only be exported for local files",
+            ));
+        };
+
+        if !pdf_path.is_file() {
+            let pdf_options = self.pdf_options(&file_uri).await?;
+            let document = self.compiled_document(&file_uri).await?;
+            let path = pdf_path.clone();
+            tokio::task::spawn_blocking(move || write_pdf(&document, &path, &pdf_options))
+                .await
+                .map_err(|err| {
+                    error!(%err, "PDF export panicked");
+                    jsonrpc::Error::internal_error()
+                })?
+                .map_err(|err| {
+                    error!(%err, "could not export PDF");
+                    jsonrpc::Error::internal_error()
+                })?;
+        }
+
+        tokio::task::spawn_blocking(move || reveal_in_file_manager(&pdf_path))
+            .await
+            .map_err(|err| {
+                error!(%err, "revealing the PDF panicked");
+                jsonrpc::Error::internal_error()
+            })?
+            .map_err(|err| {
+                error!(%err, "could not open file manager");
+                Error::invalid_params(format!("Could not open the file manager: {err}"))
+            })
+    }
+
+    /// Export the document with other `sys.inputs`, e.g. a final version next to the draft the
+    /// configured inputs select. The second argument holds the [`InputsOptions`]. The file is
+    /// written next to the document with the suffix appended to its name. Returns the URIs of the
+    /// written files.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_with_inputs(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let options = arguments
+            .get(1)
+            .ok_or_else(|| Error::invalid_params("Missing inputs options argument"))
+            .and_then(|options| {
+                InputsOptions::deserialize(options)
+                    .map_err(|err| Error::invalid_params(format!("Invalid inputs options: {err}")))
+            })?;
+        let suffix = options.suffix();
+        if suffix.is_empty() {
+            return Err(Error::invalid_params(
+                "Give inputs or a suffix, so the export doesn't replace the document's",
+            ));
+        }
+
+        self.export_variant(
+            &file_uri,
+            options.format,
+            &options.inputs,
+            &suffix,
+            "The document with these inputs",
+            |source| Ok(source.text().to_owned()),
+        )
+        .await
+    }
+
+    /// Check the environment the server runs in: the Typst version, the fonts found, the package
+    /// cache, whether the package repository can be reached and the workspace root. The first
+    /// argument optionally holds the [`DoctorOptions`]. Returns the name, `pass`, `warn` or `fail`
+    /// and a message of each check.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_doctor(&self, arguments: Vec<Value>) -> Result<Value> {
+        let options = match arguments.first() {
+            Some(options) => DoctorOptions::deserialize(options)
+                .map_err(|err| Error::invalid_params(format!("Invalid doctor options: {err}")))?,
+            None => DoctorOptions::default(),
+        };
+
+        let checks = self.doctor(options).await;
+        serde_json::to_value(checks).map_err(|err| {
+            error!(%err, "could not serialize checks");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Compile every main file, or the pinned main file, and write their diagnostics to a SARIF
+    /// 2.1.0 report for code scanning in CI. The client optionally passes the path to write it to,
+    /// which defaults to `typst-diagnostics.sarif` in the workspace root. Returns the URI of the
+    /// report, the numbers of errors and warnings and an `exitCode` of 1 if there are errors.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_export_diagnostics(&self, arguments: Vec<Value>) -> Result<Value> {
+        let path = arguments.first().and_then(Value::as_str).map(PathBuf::from);
+        if path.as_ref().is_some_and(|path| !path.is_absolute()) {
+            return Err(Error::invalid_params("The report path must be absolute"));
+        }
+
+        let summary = self.export_diagnostics(path).await.map_err(|err| {
+            error!(%err, "could not export diagnostics");
+            Error::invalid_params(format!("Could not export diagnostics: {err}"))
+        })?;
+        serde_json::to_value(summary).map_err(|err| {
+            error!(%err, "could not serialize diagnostics summary");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Render a rectangle of a page to a PNG, to extract a figure or a table. The second argument
+    /// holds the [`RegionOptions`], with the rectangle in points from the page's top left corner.
+    /// Returns the image base64-encoded, or the URI of the file written next to the source with
+    /// `write`.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_render_region(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+        let options = arguments
+            .get(1)
+            .ok_or_else(|| Error::invalid_params("Missing region options argument"))
+            .and_then(|options| {
+                RegionOptions::deserialize(options)
+                    .map_err(|err| Error::invalid_params(format!("Invalid region options: {err}")))
+            })?;
+        let pixel_per_pt = match options.pixel_per_pt {
+            Some(scale) if scale.is_finite() && scale > 0.0 => scale,
+            Some(_) => return Err(Error::invalid_params("Scale must be a positive number")),
+            None => self.export_directives(&file_uri).await.pixel_per_pt(),
+        };
+
+        let document = self.compiled_document(&file_uri).await?;
+        let Some(page) = document.pages.get(options.page) else {
+            return Err(Error::invalid_params(format!(
+                "Page index {} is out of range for a document with {} pages",
+                options.page,
+                document.pages.len()
+            )));
+        };
+        options
+            .validate(page.frame.size())
+            .map_err(Error::invalid_params)?;
+
+        let png = tokio::task::spawn_blocking(move || {
+            region_png(&document.pages[options.page].frame, &options, pixel_per_pt)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|png| png)
+        .map_err(|err| {
+            error!(%err, "could not render region");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        if !options.write {
+            return Ok(Value::String(STANDARD.encode(png)));
+        }
+
+        let source_path = file_uri
+            .to_file_path()
+            .map_err(|()| Error::invalid_params("Images can only be written for local files"))?;
+        let path = region_png_path(&source_path, options.page).map_err(|err| {
+            error!(%err, "could not determine image path");
+            jsonrpc::Error::internal_error()
+        })?;
+        tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || std::fs::write(path, png)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|written| Ok(written?))
+        .map_err(|err| {
+            error!(%err, "could not write region image");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        let uri = Url::from_file_path(path).map_err(|()| jsonrpc::Error::internal_error())?;
+        Ok(Value::String(uri.into()))
+    }
+
+    /// Switch to the profile with the name given as the first argument, or back to no profile with
+    /// `null`, see [`Profile`](crate::config::Profile). Compiles the open files again with the
+    /// profile's inputs and updates the preview. With a profile, also exports the pinned main file
+    /// or every file matching `mainFiles`, else the file at the optional second argument, with the
+    /// profile's settings and its name appended to the file name. Returns the URIs of the written
+    /// files.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_use_profile(&self, arguments: Vec<Value>) -> Result<Value> {
+        let name = match arguments.first() {
+            Some(Value::String(name)) => Some(name.clone()),
+            Some(Value::Null) => None,
+            _ => {
+                return Err(Error::invalid_params(
+                    "Missing profile name as first argument",
+                ))
+            }
+        };
+        let file_uri = match arguments.get(1) {
+            Some(_) => Some(uri_argument(&arguments[1..])?),
+            None => None,
+        };
+
+        self.config
+            .write()
+            .await
+            .use_profile(name.clone())
+            .map_err(|err| Error::invalid_params(err.to_string()))?;
+        info!(profile = ?name, "switched profile");
+        self.update_inputs().await;
+
+        let targets = match self.main_url().await {
+            Some(main) => vec![main],
+            None => self.main_files().await,
+        };
+        let targets = match (targets.is_empty(), file_uri) {
+            (true, Some(file_uri)) => vec![file_uri],
+            _ => targets,
+        };
+
+        // The preview quality and bleed may have changed even if the inputs didn't
+        if self.preview_shown.load(Ordering::Relaxed) {
+            if let Some(uri) = targets.first() {
+                if let Err(err) = self.run_export(uri).await {
+                    warn!(%err, %uri, "could not update preview with profile");
+                }
+            }
+        }
+
+        let Some(name) = name else {
+            return Ok(json!([]));
+        };
+        let page_size = self
+            .config
+            .read()
+            .await
+            .profile()
+            .and_then(|profile| profile.page_size.clone());
+        let set_rule = page_size
+            .as_ref()
+            .map(PageSize::set_rule)
+            .transpose()
+            .map_err(Error::invalid_params)?;
+
+        let mut written = Vec::new();
+        for uri in &targets {
+            let uris = self
+                .export_variant(
+                    uri,
+                    None,
+                    &BTreeMap::new(),
+                    &file_name_suffix(&name),
+                    "The document with this profile",
+                    |source| {
+                        Ok(match &set_rule {
+                            Some(set_rule) => with_page_size(source, set_rule),
+                            None => source.text().to_owned(),
+                        })
+                    },
+                )
+                .await?;
+            if let Value::Array(uris) = uris {
+                written.extend(uris);
+            }
+        }
+
+        Ok(Value::Array(written))
+    }
+
+    /// List every label defined in the Typst files of the workspace, with its location, how often
+    /// it's referenced and whether another label has the same name, for managing labels in large
+    /// documents.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_list_labels(&self) -> Result<Value> {
+        let labels = self.list_labels().await;
+        serde_json::to_value(labels).map_err(|err| {
+            error!(%err, "could not serialize labels");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Hide the preview window if it's shown and show it otherwise, or show it with `true` and hide
+    /// it with `false` as the first argument. The preview keeps its pages and scroll position and
+    /// is updated while hidden. Returns whether the preview is shown now.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_toggle_preview(&self, arguments: Vec<Value>) -> Result<Value> {
+        let visible = match arguments.first() {
+            Some(Value::Bool(visible)) => *visible,
+            Some(_) => {
+                return Err(Error::invalid_params(
+                    "Expected a boolean as first argument",
+                ))
+            }
+            None => !*self.preview_visible.borrow(),
+        };
+
+        self.preview_visible.send_replace(visible);
+        info!(visible, "toggled preview");
+        Ok(Value::Bool(visible))
+    }
+
+    /// Show the file's document next to the preview, to compare them, or stop comparing with
+    /// `null` as the first argument. With a `revision` in the second argument's
+    /// [`CompareOptions`], the file is shown as it was at that git revision instead, to compare
+    /// versions. The two lists are side by side unless `sideBySide` is false, then the compared
+    /// document is below the preview, and scroll together unless `syncScroll` is false.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_compare(&self, arguments: Vec<Value>) -> Result<Value> {
+        if arguments.first().is_none_or(Value::is_null) {
+            self.compare.send_replace(None);
+            return Ok(Value::Null);
+        }
+
+        let file_uri = uri_argument(&arguments)?;
+        let options = match arguments.get(1) {
+            Some(options) => CompareOptions::deserialize(options)
+                .map_err(|err| Error::invalid_params(format!("Invalid compare options: {err}")))?,
+            None => CompareOptions::default(),
+        };
+        let document = match &options.revision {
+            Some(revision) => self.document_at_revision(&file_uri, revision).await?,
+            None => self.compiled_document(&file_uri).await?,
+        };
+
+        let pages = document.pages.len();
+        self.compare.send_replace(Some(CompareDocument {
+            document,
+            side_by_side: options.side_by_side,
+            sync_scroll: options.sync_scroll,
+        }));
+        Ok(json!({ "pages": pages }))
+    }
+
+    /// Check the bibliography files of the file's document before submitting it: duplicate keys,
+    /// entries which can't be parsed, citations of keys missing from the bibliography and entries
+    /// which are never cited. The client passes the file URI. Returns the bibliography files and
+    /// the issues with their locations.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_check_bibliography(&self, arguments: Vec<Value>) -> Result<Value> {
+        let file_uri = uri_argument(&arguments)?;
+
+        let check = self.check_bibliography(&file_uri).await.map_err(|err| {
+            error!(%err, "could not check bibliography");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        serde_json::to_value(check).map_err(|err| {
+            error!(%err, "could not serialize bibliography check");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    /// Compile and export documents again whenever files they read change which match the globs
+    /// in the first argument, like `data/*.csv` relative to the project root, even if no Typst
+    /// file changed. For workflows where a script regenerates images or data files. Replaces the
+    /// globs watched so far, an empty array or `null` stops watching. Returns the open documents
+    /// which read matching files.
+    #[tracing::instrument(skip(self))]
+    pub async fn command_watch_assets(&self, arguments: Vec<Value>) -> Result<Value> {
+        let globs = match arguments.first() {
+            None | Some(Value::Null) => Vec::new(),
+            Some(globs) => Vec::<String>::deserialize(globs)
+                .map_err(|err| Error::invalid_params(format!("Invalid asset globs: {err}")))?,
+        };
+
+        let documents = self
+            .watch_assets(&globs)
+            .await
+            .map_err(|err| Error::invalid_params(format!("{err:#}")))?;
+
+        Ok(json!(documents))
+    }
+
+    /// The configured PDF options for exporting the document at `file_uri`, failing if they can't
+    /// be exported with this version of Typst.
+    pub async fn pdf_options(&self, file_uri: &Url) -> Result<PdfOptions> {
+        let (standard, embed_source) = {
+            let config = self.config.read().await;
+            (config.pdf_standard(), config.embed_source)
+        };
+
+        let mut options = PdfOptions {
+            standard,
+            ..Default::default()
+        };
+        options
+            .validate()
+            .map_err(|err| Error::invalid_params(err.to_string()))?;
+
+        if embed_source {
+            options.attachments = self.source_attachments(file_uri).await.map_err(|err| {
+                error!(%err, "could not collect sources to embed");
+                jsonrpc::Error::internal_error()
+            })?;
+        }
+
+        Ok(options)
+    }
+
+    /// Compile the file as it is at the git revision, with the files it includes as they are now,
+    /// failing if it has errors.
+    async fn document_at_revision(&self, file_uri: &Url, revision: &str) -> Result<Arc<Document>> {
+        let path = file_uri
+            .to_file_path()
+            .map_err(|()| Error::invalid_params("Only local files are tracked by git"))?;
+        let git_revision = revision.to_owned();
+        let old_text = tokio::task::spawn_blocking(move || text_at_revision(&path, &git_revision))
+            .await
+            .map_err(|err| {
+                error!(%err, "reading the file from git panicked");
+                jsonrpc::Error::internal_error()
+            })?
+            .map_err(Error::invalid_params)?;
+
+        let (source, project) = self
+            .scope_with_source(file_uri)
+            .await
+            .map_err(|err| {
+                error!(%err, "could not read file at revision");
+                jsonrpc::Error::internal_error()
+            })?
+            .run2(|source, project| (source, project));
+        let (document, _) = self
+            .thread_with_world((Source::new(source.id(), old_text), project))
+            .await
+            .map_err(|err| {
+                error!(%err, "could not set up world for old version");
+                jsonrpc::Error::internal_error()
+            })?
+            .run(|world| compile(&world))
+            .await
+            .map_err(|err| {
+                error!(%err, "compiling the old version panicked");
+                jsonrpc::Error::internal_error()
+            })?;
+        document.ok_or_else(|| {
+            Error::invalid_params(format!(
+                "The file at {revision} has errors and could not be compiled"
+            ))
+        })
+    }
+
+    /// Compile the document for a command, failing if it has errors.
+    async fn compiled_document(&self, file_uri: &Url) -> Result<Arc<Document>> {
+        let (document, _) = self.compile_source(file_uri).await.map_err(|err| {
+            error!(%err, "could not compile document");
+            jsonrpc::Error::internal_error()
+        })?;
+        document
+            .ok_or_else(|| Error::invalid_params("Document has errors and could not be compiled"))
+    }
+}
+
+/// Parses the file URI passed as the first argument of a command.
+fn uri_argument(arguments: &[Value]) -> Result<Url> {
+    let Some(file_uri) = arguments.first().and_then(|v| v.as_str()) else {
+        return Err(Error::invalid_params("Missing file URI as first argument"));
+    };
+    Url::parse(file_uri).map_err(|_| Error::invalid_params("Parameter is not a valid URI"))
+}
+
+/// Parses a zero-based page index passed as the argument at `position`.
+fn page_index_argument(arguments: &[Value], position: usize) -> Result<usize> {
+    arguments
+        .get(position)
+        .and_then(Value::as_u64)
+        .map(|index| index as usize)
+        .ok_or_else(|| Error::invalid_params("Missing page index argument"))
 }
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tokio::task::AbortHandle;
+use tower_lsp::lsp_types::{Range, Url};
+use tracing::error;
+
+use super::TypstServer;
+
+/// How much each compilation counts towards the average compile duration
+const AVERAGE_WEIGHT: f64 = 0.3;
+
+/// Spaces out compilations after source changes, so that sustained editing of a slow document
+/// doesn't start compilations back to back. Changes which arrive while a compilation waits are
+/// compiled along with it, see [`TypstServer::schedule_compile`].
+#[derive(Debug, Default)]
+pub struct CompileThrottle {
+    last_start: Option<Instant>,
+    last_duration: Duration,
+    /// Exponential moving average of compile durations, to adapt the debounce to
+    average_duration: Option<Duration>,
+    /// The compilations waiting for changes to settle, by root
+    waiting: HashMap<Url, WaitingCompile>,
+    /// Counts the compilations scheduled, so a waiting one can tell whether it was replaced
+    scheduled: u64,
+}
+
+#[derive(Debug)]
+struct WaitingCompile {
+    id: u64,
+    task: AbortHandle,
+}
+
+impl CompileThrottle {
+    /// How long to wait before the next compilation may start
+    fn wait(&self, interval: Option<Duration>, now: Instant) -> Duration {
+        let interval = interval.unwrap_or(self.last_duration);
+        self.last_start.map_or(Duration::ZERO, |last_start| {
+            (last_start + interval).saturating_duration_since(now)
+        })
+    }
+
+    /// How long to wait for more changes before compiling: half the average compile duration, so
+    /// slow documents aren't constantly being compiled while typing, within the bounds
+    fn debounce(&self, (min, max): (Duration, Duration)) -> Duration {
+        let average = self.average_duration.unwrap_or_default();
+        (average / 2).clamp(min, max.max(min))
+    }
+
+    pub fn finished(&mut self, duration: Duration) {
+        self.last_duration = duration;
+        self.average_duration = Some(match self.average_duration {
+            Some(average) => {
+                average.mul_f64(1.0 - AVERAGE_WEIGHT) + duration.mul_f64(AVERAGE_WEIGHT)
+            }
+            None => duration,
+        });
+    }
+}
+
+impl TypstServer {
+    /// Compiles the root once changes to it settle: when no other change arrived for as long as
+    /// [`CompileThrottle::debounce`] says, and at least `compileInterval` after the last
+    /// compilation started, or as long as the last compilation took if it's unset. The
+    /// compilation waits on its own task, which the next change to the root cancels and replaces,
+    /// compiling this change along with it.
+    pub(super) async fn schedule_compile(&self, root: Url, change: Option<Range>) {
+        let (bounds, interval) = {
+            let config = self.config.read().await;
+            let interval = config.compile_interval.map(Duration::from_millis);
+            (config.debounce_bounds(), interval)
+        };
+
+        let mut throttle = self.compile_throttle.lock();
+        let debounce = throttle.debounce(bounds);
+        throttle.scheduled += 1;
+        let id = throttle.scheduled;
+
+        let task = self.spawn({
+            let root = root.clone();
+            move |server| async move {
+                tokio::time::sleep(debounce).await;
+                let wait = server
+                    .compile_throttle
+                    .lock()
+                    .wait(interval, Instant::now());
+                tokio::time::sleep(wait).await;
+
+                {
+                    let mut throttle = server.compile_throttle.lock();
+                    if throttle.waiting.get(&root).map(|waiting| waiting.id) != Some(id) {
+                        return;
+                    }
+                    throttle.waiting.remove(&root);
+                    throttle.last_start = Some(Instant::now());
+                }
+
+                if let Err(err) = server.compile_changes(&root, change).await {
+                    error!(%err, %root, "could not handle source change");
+                }
+            }
+        });
+        let waiting = WaitingCompile {
+            id,
+            task: task.abort_handle(),
+        };
+        if let Some(replaced) = throttle.waiting.insert(root, waiting) {
+            replaced.task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn waits_for_the_interval_since_the_last_start() {
+        let start = Instant::now();
+        let throttle = CompileThrottle {
+            last_start: Some(start),
+            last_duration: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let later = start + Duration::from_millis(200);
+
+        assert_eq!(throttle.wait(None, later), Duration::from_millis(300));
+        assert_eq!(
+            throttle.wait(Some(Duration::from_millis(100)), later),
+            Duration::ZERO
+        );
+        assert_eq!(CompileThrottle::default().wait(None, later), Duration::ZERO);
+    }
+
+    #[test]
+    fn adapts_debounce_to_compile_durations() {
+        let bounds = (Duration::from_millis(20), Duration::from_millis(600));
+        let mut throttle = CompileThrottle::default();
+        assert_eq!(throttle.debounce(bounds), Duration::from_millis(20));
+
+        throttle.finished(Duration::from_millis(400));
+        assert_eq!(throttle.debounce(bounds), Duration::from_millis(200));
+
+        for _ in 0..20 {
+            throttle.finished(Duration::from_secs(2));
+        }
+        assert_eq!(throttle.debounce(bounds), Duration::from_millis(600));
+    }
+}
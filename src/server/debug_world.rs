@@ -0,0 +1,87 @@
+use serde::Serialize;
+use tower_lsp::lsp_types::Url;
+use typst::syntax::FileId;
+use typst::World;
+
+use crate::workspace::SUPPORTED_FEATURES;
+
+use super::TypstServer;
+
+/// A snapshot of what a [`ProjectWorld`](crate::workspace::world::ProjectWorld) resolves to, for
+/// diagnosing problems like packages which don't resolve.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugWorld {
+    pub main: String,
+    /// Length of the main source in bytes
+    pub main_len: usize,
+    /// Files in the main source's package
+    pub files: Vec<String>,
+    pub font_count: usize,
+    pub packages: Vec<DebugPackage>,
+    /// The time the world reports to Typst, in RFC 3339 format
+    pub now: String,
+    /// The experimental features the bundled Typst version can enable
+    pub supported_features: &'static [&'static str],
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugPackage {
+    pub spec: String,
+    pub description: Option<String>,
+}
+
+impl TypstServer {
+    /// Builds the world for the main file, falling back to the given file, and reads its state
+    /// through the world's accessors. Nothing is compiled.
+    pub async fn debug_world(&self, uri: &Url) -> anyhow::Result<DebugWorld> {
+        let debug_world = self
+            .thread_with_world(self.main_url().await.as_ref().unwrap_or(uri))
+            .await?
+            .run(|world| {
+                let main = world.main();
+
+                let mut files: Vec<String> =
+                    world.file_ids().into_iter().map(describe_id).collect();
+                files.sort();
+
+                let book = world.book();
+                let font_count = (0..)
+                    .take_while(|&index| book.info(index).is_some())
+                    .count();
+
+                let packages = world
+                    .packages()
+                    .iter()
+                    .map(|(spec, description)| DebugPackage {
+                        spec: spec.to_string(),
+                        description: description
+                            .as_ref()
+                            .map(|description| description.to_string()),
+                    })
+                    .collect();
+
+                DebugWorld {
+                    main: describe_id(main.id()),
+                    main_len: main.len_bytes(),
+                    files,
+                    font_count,
+                    packages,
+                    now: world.now().captured().to_rfc3339(),
+                    supported_features: SUPPORTED_FEATURES,
+                }
+            })
+            .await?;
+
+        Ok(debug_world)
+    }
+}
+
+fn describe_id(id: FileId) -> String {
+    let path = id.vpath().as_rootless_path().display();
+    match id.package() {
+        Some(spec) => format!("{spec}/{path}"),
+        None => path.to_string(),
+    }
+}
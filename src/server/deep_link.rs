@@ -0,0 +1,157 @@
+//! Links to a spot in a project's sources, to share in reviews. They look like
+//! `typst-lsp://open?file=/chapter.typ&line=12&column=5`, with
+//!
+//! - `file`: the path of the file from the project root,
+//! - `package`: the package the file belongs to, like `@preview/example:0.1.0`, unless it's in the
+//!   project itself,
+//! - `line` and `column`: where in the file, both starting at 1 and counting characters.
+//!
+//! Paths are relative to the project, so links work in anyone's checkout of it.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use tower_lsp::lsp_types::{Position, Url};
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{FileId, Source, VirtualPath};
+
+use crate::lsp_typst_boundary::typst_to_lsp;
+
+use super::TypstServer;
+
+pub const DEEP_LINK_SCHEME: &str = "typst-lsp";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeepLink {
+    pub package: Option<PackageSpec>,
+    pub path: VirtualPath,
+    /// Starting at 1
+    pub line: usize,
+    /// Starting at 1, in characters
+    pub column: usize,
+}
+
+impl DeepLink {
+    /// The link to the offset in the file
+    pub fn new(id: FileId, source: &Source, offset: usize) -> Option<Self> {
+        Some(Self {
+            package: id.package().cloned(),
+            path: id.vpath().clone(),
+            line: source.byte_to_line(offset)? + 1,
+            column: source.byte_to_column(offset)? + 1,
+        })
+    }
+
+    pub fn id(&self) -> FileId {
+        FileId::new(self.package.clone(), self.path.clone())
+    }
+}
+
+impl fmt::Display for DeepLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut url = Url::parse(&format!("{DEEP_LINK_SCHEME}://open")).map_err(|_| fmt::Error)?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("file", &self.path.as_rooted_path().to_string_lossy());
+            if let Some(package) = &self.package {
+                query.append_pair("package", &package.to_string());
+            }
+            query.append_pair("line", &self.line.to_string());
+            query.append_pair("column", &self.column.to_string());
+        }
+        write!(f, "{url}")
+    }
+}
+
+impl FromStr for DeepLink {
+    type Err = anyhow::Error;
+
+    fn from_str(link: &str) -> anyhow::Result<Self> {
+        let url = Url::parse(link).context("invalid link")?;
+        if url.scheme() != DEEP_LINK_SCHEME || url.host_str() != Some("open") {
+            bail!("not a {DEEP_LINK_SCHEME}://open link");
+        }
+
+        let (mut path, mut package, mut line, mut column) = (None, None, None, None);
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "file" => path = Some(VirtualPath::new(value.as_ref())),
+                "package" => {
+                    let spec = PackageSpec::from_str(&value)
+                        .map_err(|err| anyhow::anyhow!("invalid package {value:?}: {err}"))?;
+                    package = Some(spec);
+                }
+                "line" => line = Some(position_part(&value).context("invalid line")?),
+                "column" => column = Some(position_part(&value).context("invalid column")?),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            package,
+            path: path.context("link has no file")?,
+            line: line.unwrap_or(1),
+            column: column.unwrap_or(1),
+        })
+    }
+}
+
+fn position_part(value: &str) -> Option<usize> {
+    value.parse().ok().filter(|&value| value >= 1)
+}
+
+impl TypstServer {
+    /// Resolves the link in the project of the file, returning the URI of the linked file and the
+    /// position in it. Positions past the end of a line or the file are moved back to the end.
+    pub async fn resolve_deep_link(
+        &self,
+        link: &DeepLink,
+        project_uri: &Url,
+    ) -> anyhow::Result<(Url, Position)> {
+        let (project, _) = self.project_and_full_id(project_uri).await?;
+        let uri = project.full_id_to_uri(project.fill_id(link.id())).await?;
+        let source = project.read_source_by_uri(&uri)?;
+
+        let line = (link.line - 1).min(source.len_lines().saturating_sub(1));
+        let offset = source
+            .line_column_to_byte(line, link.column - 1)
+            .or_else(|| {
+                let range = source.line_to_range(line)?;
+                let text = source.text()[range.clone()].trim_end_matches(['\r', '\n']);
+                Some(range.start + text.len())
+            })
+            .unwrap_or(source.len_bytes());
+        let position = typst_to_lsp::offset_to_position(
+            offset,
+            self.const_config().position_encoding,
+            &source,
+        );
+
+        Ok((uri, position))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let link = DeepLink {
+            package: Some(PackageSpec::from_str("@preview/example:0.1.0").unwrap()),
+            path: VirtualPath::new("src/lib file.typ"),
+            line: 12,
+            column: 5,
+        };
+        let text = link.to_string();
+
+        assert_eq!(
+            text,
+            "typst-lsp://open?file=%2Fsrc%2Flib+file.typ&package=%40preview%2Fexample%3A0.1.0&line=12&column=5"
+        );
+        assert_eq!(text.parse::<DeepLink>().unwrap(), link);
+        assert!("typst-lsp://open?line=3".parse::<DeepLink>().is_err());
+        assert!("https://open?file=main.typ".parse::<DeepLink>().is_err());
+    }
+}
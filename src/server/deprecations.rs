@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, DiagnosticSeverity, DiagnosticTag,
+    TextEdit, Url, WorkspaceEdit,
+};
+use typst::syntax::ast::{self, AstNode};
+use typst::syntax::{LinkedNode, Source};
+
+use crate::config::{ConstConfig, PositionEncoding};
+use crate::lsp_typst_boundary::{typst_to_lsp, TypstRange};
+use crate::workspace::project::Project;
+use crate::TYPST_VERSION;
+
+use super::diagnostics::DiagnosticsMap;
+use super::lint::walk;
+
+/// A use of a function which is deprecated as of the bundled Typst version, [`TYPST_VERSION`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecation {
+    pub range: Range<usize>,
+    pub message: String,
+    /// A replacement for part of the source which migrates away from the deprecated use, when
+    /// there is a mechanical one
+    pub fix: Option<(Range<usize>, String)>,
+}
+
+/// Finds calls of functions which `context` made obsolete: `locate` and `style` with callbacks,
+/// and the location and styles arguments of `query` and `measure`. Goes by name, so functions of
+/// the user's shadowing these are flagged as well.
+pub fn deprecations(source: &Source) -> Vec<Deprecation> {
+    let mut deprecations = Vec::new();
+    walk(&LinkedNode::new(source.root()), &mut |node| {
+        deprecations.extend(deprecation(source, node));
+    });
+    deprecations
+}
+
+fn deprecation(source: &Source, node: &LinkedNode) -> Option<Deprecation> {
+    let call = node.cast::<ast::FuncCall>()?;
+    let ast::Expr::Ident(callee) = call.callee() else {
+        return None;
+    };
+    let positional: Vec<_> = call
+        .args()
+        .items()
+        .filter_map(|arg| match arg {
+            ast::Arg::Pos(expr) => Some(expr),
+            _ => None,
+        })
+        .collect();
+    let text = |expr: ast::Expr| Some(node.find(expr.span())?.range());
+
+    match (callee.as_str(), positional.as_slice()) {
+        ("locate", [ast::Expr::Closure(closure)]) => {
+            let fix = closure_param(*closure).and_then(|param| {
+                let body = &source.text()[text(closure.body())?];
+                let replacement = format!("context {{ let {param} = here(); {body} }}");
+                Some((node.range(), replacement))
+            });
+            Some(Deprecation {
+                range: node.range(),
+                message: deprecated_message("`locate` with a callback", "`context` and `here()`"),
+                fix,
+            })
+        }
+        ("style", [ast::Expr::Closure(_)]) => Some(Deprecation {
+            range: node.range(),
+            message: deprecated_message("`style`", "`context`"),
+            fix: None,
+        }),
+        (name @ ("query" | "measure"), [first, second]) => {
+            let argument = if name == "query" {
+                "location"
+            } else {
+                "styles"
+            };
+            let second = text(*second)?;
+            Some(Deprecation {
+                range: second.clone(),
+                message: deprecated_message(
+                    &format!("The {argument} argument of `{name}`"),
+                    "`context`",
+                ),
+                fix: Some((text(*first)?.end..second.end, String::new())),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn deprecated_message(what: &str, instead: &str) -> String {
+    format!("{what} is deprecated in Typst {TYPST_VERSION}, use {instead} instead")
+}
+
+/// The name of the closure's only parameter, if that's all it has
+fn closure_param(closure: ast::Closure) -> Option<String> {
+    let mut params = closure.params().children();
+    let param = params.next()?;
+    if params.next().is_some() {
+        return None;
+    }
+    match param {
+        ast::Param::Pos(ast::Pattern::Normal(ast::Expr::Ident(ident))) => {
+            Some(ident.get().to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Converts the findings to warnings which editors show as struck through
+pub async fn deprecation_diagnostics(
+    project: &Project,
+    sources: &[Source],
+    const_config: &ConstConfig,
+) -> DiagnosticsMap {
+    let mut diagnostics = DiagnosticsMap::new();
+
+    for source in sources {
+        let deprecations = deprecations(source);
+        if deprecations.is_empty() {
+            continue;
+        }
+        let Ok(uri) = project.full_id_to_uri(project.fill_id(source.id())).await else {
+            continue;
+        };
+
+        let entry = diagnostics.entry(uri).or_default();
+        for deprecation in deprecations {
+            let range =
+                typst_to_lsp::range(deprecation.range, source, const_config.position_encoding);
+            entry.push(Diagnostic {
+                range: range.raw_range,
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some("typst-lsp".to_owned()),
+                message: deprecation.message,
+                tags: Some(vec![DiagnosticTag::DEPRECATED]),
+                ..Default::default()
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// Offers to migrate the deprecated uses in the range which have a mechanical fix
+pub fn deprecation_actions(
+    uri: &Url,
+    source: &Source,
+    range: TypstRange,
+    position_encoding: PositionEncoding,
+) -> Vec<CodeActionOrCommand> {
+    deprecations(source)
+        .into_iter()
+        .filter(|deprecation| {
+            deprecation.range.start <= range.end && range.start <= deprecation.range.end
+        })
+        .filter_map(|deprecation| {
+            let (fix_range, new_text) = deprecation.fix?;
+            let edit = TextEdit {
+                range: typst_to_lsp::range(fix_range, source, position_encoding).raw_range,
+                new_text,
+            };
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Migrate deprecated call".to_owned(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixed(text: &str) -> String {
+        let source = Source::detached(text);
+        let mut text = text.to_owned();
+        let mut fixes: Vec<_> = deprecations(&source)
+            .into_iter()
+            .filter_map(|deprecation| deprecation.fix)
+            .collect();
+        fixes.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+        for (range, replacement) in fixes {
+            text.replace_range(range, &replacement);
+        }
+        text
+    }
+
+    #[test]
+    fn migrates_callbacks_and_arguments() {
+        assert_eq!(
+            fixed("#locate(loc => [Page #loc.page()])"),
+            "#context { let loc = here(); [Page #loc.page()] }"
+        );
+        assert_eq!(
+            fixed("#{ measure([Hi], styles).width }"),
+            "#{ measure([Hi]).width }"
+        );
+    }
+
+    #[test]
+    fn flags_style_without_fix() {
+        let source = Source::detached("#style(styles => [Hi])");
+        let deprecations = deprecations(&source);
+        assert_eq!(deprecations.len(), 1);
+        assert_eq!(deprecations[0].fix, None);
+    }
+}
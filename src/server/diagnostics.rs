@@ -1,22 +1,216 @@
 use std::collections::HashMap;
 
 use futures::future::join_all;
-use tower_lsp::lsp_types::{Diagnostic, Url};
+use serde::Serialize;
+use tower_lsp::lsp_types::request::WorkspaceDiagnosticRefresh;
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentDiagnosticReport, DocumentDiagnosticReportKind,
+    FullDocumentDiagnosticReport, RelatedFullDocumentDiagnosticReport, Url,
+    WorkspaceDiagnosticReport, WorkspaceDocumentDiagnosticReport,
+    WorkspaceFullDocumentDiagnosticReport,
+};
 use tower_lsp::Client;
+use tracing::{trace, warn};
+
+use crate::config::DiagnosticsMode;
 
 use super::TypstServer;
 
 pub type DiagnosticsMap = HashMap<Url, Vec<Diagnostic>>;
 
+/// A reference whose target couldn't be found, along with where the reference is
+#[derive(Debug, Serialize)]
+pub struct UnresolvedReference {
+    pub uri: Url,
+    pub diagnostic: Diagnostic,
+}
+
 impl TypstServer {
     pub async fn update_all_diagnostics(&self, diagnostics: DiagnosticsMap) {
+        let const_config = self.const_config();
+        if const_config.supports_pull_diagnostics {
+            // Pulls are answered with these rather than compiling again
+            self.diagnostics.lock().await.latest = Some(diagnostics);
+
+            // The client asks for diagnostics itself, but only knows to ask again for the changed
+            // document, while the compilation may have changed diagnostics in other files as well
+            if !const_config.supports_diagnostic_refresh {
+                return;
+            }
+            if let Err(err) = self
+                .client
+                .send_request::<WorkspaceDiagnosticRefresh>(())
+                .await
+            {
+                warn!(%err, "could not ask client to refresh diagnostics");
+            }
+            return;
+        }
+
         self.diagnostics.lock().await.publish(diagnostics).await;
     }
+
+    /// Withdraws the reported diagnostics when the `diagnostics` setting turned them off, and
+    /// reports those of the open files right away when it turned them on. Going to `onSave`, the
+    /// diagnostics reported so far stay until the next save.
+    pub async fn diagnostics_mode_changed(&self, old: DiagnosticsMode) {
+        let mode = self.config.read().await.diagnostics;
+        if mode == old {
+            return;
+        }
+
+        match mode {
+            DiagnosticsMode::Off => self.update_all_diagnostics(DiagnosticsMap::new()).await,
+            DiagnosticsMode::OnSave => {}
+            DiagnosticsMode::On => {
+                let open_uris = self.workspace().read().await.open_uris();
+                let mut roots = Vec::new();
+                for uri in open_uris {
+                    let root = self.compilation_root(&uri).await;
+                    if !roots.contains(&root) {
+                        roots.push(root);
+                    }
+                }
+
+                let mut diagnostics = DiagnosticsMap::new();
+                for root in roots {
+                    match self.compile_source(&root).await {
+                        Ok((_, compiled)) => diagnostics.extend(compiled),
+                        Err(err) => warn!(%err, %root, "could not compile for diagnostics"),
+                    }
+                }
+                self.update_all_diagnostics(diagnostics).await;
+            }
+        }
+    }
+
+    /// Reports the document's diagnostics from the last compilation reporting them, which with
+    /// `onSave` is the one of the last save, or compiles the main file including the document, or
+    /// the document itself, if nothing was compiled yet. Diagnostics in other files are reported as
+    /// related documents.
+    pub async fn pull_diagnostics(&self, uri: &Url) -> anyhow::Result<DocumentDiagnosticReport> {
+        if self.config.read().await.diagnostics == DiagnosticsMode::Off {
+            return Ok(DocumentDiagnosticReport::Full(
+                RelatedFullDocumentDiagnosticReport {
+                    related_documents: None,
+                    full_document_diagnostic_report: full_report(Vec::new()),
+                },
+            ));
+        }
+
+        let main = self.compilation_root(uri).await;
+        let mut diagnostics = self.latest_diagnostics(&main).await?;
+
+        let items = diagnostics.remove(uri).unwrap_or_default();
+        let related_documents = diagnostics
+            .into_iter()
+            .map(|(uri, items)| (uri, DocumentDiagnosticReportKind::Full(full_report(items))))
+            .collect();
+
+        Ok(DocumentDiagnosticReport::Full(
+            RelatedFullDocumentDiagnosticReport {
+                related_documents: Some(related_documents),
+                full_document_diagnostic_report: full_report(items),
+            },
+        ))
+    }
+
+    /// Reports the diagnostics of the last compilation, or of compiling the pinned main file if
+    /// nothing was compiled yet. Without a pinned main file
+    /// there's no single document to compile, so the report stays empty and clients rely on
+    /// per-document pulls instead.
+    pub async fn pull_workspace_diagnostics(&self) -> anyhow::Result<WorkspaceDiagnosticReport> {
+        if self.config.read().await.diagnostics == DiagnosticsMode::Off {
+            return Ok(WorkspaceDiagnosticReport { items: Vec::new() });
+        }
+        let Some(main) = self.main_url().await else {
+            trace!("no main file pinned, skipping workspace diagnostics");
+            return Ok(WorkspaceDiagnosticReport { items: Vec::new() });
+        };
+        let diagnostics = self.latest_diagnostics(&main).await?;
+
+        let items = diagnostics
+            .into_iter()
+            .map(|(uri, items)| {
+                WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                    uri,
+                    version: None,
+                    full_document_diagnostic_report: full_report(items),
+                })
+            })
+            .collect();
+
+        Ok(WorkspaceDiagnosticReport { items })
+    }
+
+    /// The diagnostics reported last, or those of compiling the main file when there are none yet.
+    /// With `onSave`, diagnostics are only reported on save, so there are none until the first.
+    async fn latest_diagnostics(&self, main: &Url) -> anyhow::Result<DiagnosticsMap> {
+        if let Some(latest) = &self.diagnostics.lock().await.latest {
+            return Ok(latest.clone());
+        }
+        if self.config.read().await.diagnostics == DiagnosticsMode::OnSave {
+            return Ok(DiagnosticsMap::new());
+        }
+
+        let (_, diagnostics) = self.compile_source(main).await?;
+        self.diagnostics.lock().await.latest = Some(diagnostics.clone());
+        Ok(diagnostics)
+    }
+
+    /// Compiles the main file, or the document itself if no main file is pinned, and picks out the
+    /// errors of references to labels or bibliography entries which don't exist
+    pub async fn unresolved_references(
+        &self,
+        uri: &Url,
+    ) -> anyhow::Result<Vec<UnresolvedReference>> {
+        let main = self.main_url().await.unwrap_or_else(|| uri.clone());
+        let (_, diagnostics) = self.compile_source(&main).await?;
+
+        let mut unresolved: Vec<_> = diagnostics
+            .into_iter()
+            .flat_map(|(uri, diagnostics)| {
+                diagnostics
+                    .into_iter()
+                    .filter(is_unresolved_reference)
+                    .map(move |diagnostic| UnresolvedReference {
+                        uri: uri.clone(),
+                        diagnostic,
+                    })
+            })
+            .collect();
+        unresolved.sort_by(|a, b| {
+            (&a.uri, a.diagnostic.range.start).cmp(&(&b.uri, b.diagnostic.range.start))
+        });
+
+        Ok(unresolved)
+    }
+}
+
+/// Typst reports references to missing labels as "label `<name>` does not exist in the document"
+/// and citations of missing entries as "key `name` does not exist in the bibliography"
+fn is_unresolved_reference(diagnostic: &Diagnostic) -> bool {
+    diagnostic.severity == Some(DiagnosticSeverity::ERROR)
+        && (diagnostic
+            .message
+            .contains("does not exist in the document")
+            || diagnostic
+                .message
+                .contains("does not exist in the bibliography"))
+}
+
+fn full_report(items: Vec<Diagnostic>) -> FullDocumentDiagnosticReport {
+    FullDocumentDiagnosticReport {
+        result_id: None,
+        items,
+    }
 }
 
 pub struct DiagnosticsManager {
     client: Client,
     last_published_for: Vec<Url>,
+    /// What the last compilation reported, for clients pulling diagnostics
+    latest: Option<DiagnosticsMap>,
 }
 
 impl DiagnosticsManager {
@@ -24,6 +218,7 @@ impl DiagnosticsManager {
         Self {
             client,
             last_published_for: Vec::new(),
+            latest: None,
         }
     }
 
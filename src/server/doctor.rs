@@ -0,0 +1,204 @@
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+use crate::workspace::font_manager::FontManager;
+use crate::TYPST_VERSION;
+
+use super::TypstServer;
+
+/// File written to the package cache to check that it's writable
+const PROBE_FILE_NAME: &str = ".typst-lsp-doctor";
+
+/// Options for the `doctor` command
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DoctorOptions {
+    /// Skip checking whether the package repository can be reached
+    pub offline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but possibly not as the user expects
+    Warn,
+    Fail,
+}
+
+/// The outcome of one check of the environment, with a message for the user
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl TypstServer {
+    /// Checks the environment the server runs in, for troubleshooting when nothing seems to work
+    pub async fn doctor(&self, options: DoctorOptions) -> Vec<Check> {
+        let mut checks = vec![Check::new(
+            "version",
+            CheckStatus::Pass,
+            format!(
+                "typst-lsp {}, Typst {TYPST_VERSION}",
+                env!("CARGO_PKG_VERSION")
+            ),
+        )];
+
+        // The fonts may still be searched for in the background
+        let file_fonts = tokio::task::spawn_blocking(|| FontManager::shared().file_font_count())
+            .await
+            .unwrap_or_default();
+        checks.push(match file_fonts {
+            0 => Check::new(
+                "fonts",
+                CheckStatus::Warn,
+                "No system fonts were found, only the fonts embedded in the server are available",
+            ),
+            count => Check::new(
+                "fonts",
+                CheckStatus::Pass,
+                format!("Found {count} fonts on the system and in the font paths"),
+            ),
+        });
+
+        let (cache_dir, roots) = {
+            let workspace = self.read_workspace().await;
+            let packages = workspace.package_manager();
+            let roots: Vec<Url> = packages
+                .current()
+                .map(|package| package.root().clone())
+                .collect();
+            (packages.cache_dir().map(Path::to_owned), roots)
+        };
+
+        checks.push(match cache_dir {
+            Some(cache_dir) => tokio::task::spawn_blocking(move || check_writable(&cache_dir))
+                .await
+                .unwrap_or_else(|err| {
+                    Check::new("packageCache", CheckStatus::Fail, err.to_string())
+                }),
+            None => Check::new(
+                "packageCache",
+                CheckStatus::Fail,
+                "No directory to download packages to could be determined",
+            ),
+        });
+
+        checks.push(check_network(options.offline).await);
+
+        let main_file = self.main_url().await;
+        checks.push(match (roots.as_slice(), main_file) {
+            ([], None) => Check::new(
+                "workspaceRoot",
+                CheckStatus::Warn,
+                "No workspace folder is open, so files are compiled with their own directory as \
+                 the root and can't import files above it",
+            ),
+            (roots, main_file) => {
+                let mut message = format!("Roots: {}", roots.iter().map(Url::as_str).join(", "));
+                if let Some(main_file) = main_file {
+                    message.push_str(&format!("; pinned main file: {main_file}"));
+                }
+                Check::new("workspaceRoot", CheckStatus::Pass, message)
+            }
+        });
+
+        checks
+    }
+}
+
+#[cfg(feature = "remote-packages")]
+async fn check_network(offline: bool) -> Check {
+    use crate::workspace::package::external::remote_repo::RemoteRepoProvider;
+
+    if offline {
+        return Check::new("network", CheckStatus::Warn, "Skipped while offline");
+    }
+    let checked = match RemoteRepoProvider::new() {
+        Ok(repo) => repo.check_connection().await.map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    match checked {
+        Ok(()) => Check::new(
+            "network",
+            CheckStatus::Pass,
+            "The package repository can be reached",
+        ),
+        Err(err) => Check::new(
+            "network",
+            CheckStatus::Fail,
+            format!("The package repository can't be reached: {err}"),
+        ),
+    }
+}
+
+#[cfg(not(feature = "remote-packages"))]
+async fn check_network(_offline: bool) -> Check {
+    Check::new(
+        "network",
+        CheckStatus::Warn,
+        "The server was built without package downloads",
+    )
+}
+
+/// Whether packages can be downloaded to the directory, which doesn't have to exist yet
+fn check_writable(dir: &Path) -> Check {
+    if !dir.exists() {
+        return Check::new(
+            "packageCache",
+            CheckStatus::Warn,
+            format!(
+                "{} doesn't exist yet, it is created when the first package is downloaded",
+                dir.display()
+            ),
+        );
+    }
+
+    let probe = dir.join(PROBE_FILE_NAME);
+    match fs::write(&probe, []).and_then(|()| fs::remove_file(&probe)) {
+        Ok(()) => Check::new(
+            "packageCache",
+            CheckStatus::Pass,
+            format!("{} is writable", dir.display()),
+        ),
+        Err(err) => Check::new(
+            "packageCache",
+            CheckStatus::Fail,
+            format!("{} is not writable: {err}", dir.display()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn checks_cache_dir() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(check_writable(dir.path()).status, CheckStatus::Pass);
+        assert!(!dir.path().join(PROBE_FILE_NAME).exists());
+
+        let missing = check_writable(&dir.path().join("packages"));
+        assert_eq!(missing.status, CheckStatus::Warn);
+    }
+}
@@ -1,37 +1,111 @@
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
 use anyhow::bail;
+use serde::Deserialize;
 use tower_lsp::lsp_types::{Range, Url};
+use tracing::error;
 
-use crate::config::ExportPdfMode;
+use crate::config::{DiagnosticsMode, ExportPdfMode};
 
 use super::TypstServer;
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActiveEditorParams {
+    pub uri: Option<Url>,
+}
+
 impl TypstServer {
-    pub async fn on_source_changed(
+    /// Compiles the root of the changed file in the background once changes settle, see
+    /// [`TypstServer::schedule_compile`]
+    pub async fn on_source_changed(&self, uri: &Url, first_change_range: Option<Range>) {
+        let root = self.compilation_root(uri).await;
+        self.schedule_compile(root, first_change_range).await;
+    }
+
+    /// Compiles the changed root, reporting diagnostics and exporting as configured
+    pub(super) async fn compile_changes(
         &self,
-        uri: &Url,
+        root: &Url,
         first_change_range: Option<Range>,
     ) -> anyhow::Result<()> {
+        let started = Instant::now();
+
         let config = self.config.read().await;
-        match config.export_pdf {
+        let publish_diagnostics = config.diagnostics == DiagnosticsMode::On;
+        let compiled = match config.export_pdf {
             ExportPdfMode::OnType => {
-                self.run_diagnostics_and_export(uri, first_change_range)
-                    .await?
+                self.run_diagnostics_and_export(root, first_change_range, publish_diagnostics)
+                    .await
             }
             ExportPdfMode::OnPinnedMainType => {
                 if let Some(main_uri) = self.main_url().await {
-                    self.run_diagnostics_and_export(&main_uri, first_change_range)
-                        .await?
+                    self.run_diagnostics_and_export(
+                        &main_uri,
+                        first_change_range,
+                        publish_diagnostics,
+                    )
+                    .await
+                } else if publish_diagnostics {
+                    self.run_diagnostics(root).await
                 } else {
-                    self.run_diagnostics(uri).await?
+                    Ok(())
                 }
             }
-            _ => {
-                self.run_diagnostics(self.main_url().await.as_ref().unwrap_or(uri))
-                    .await?
+            // Nothing to export, so there's no need to compile without diagnostics to report
+            _ if publish_diagnostics => self.run_diagnostics(root).await,
+            _ => Ok(()),
+        };
+        drop(config);
+        self.compile_throttle.lock().finished(started.elapsed());
+        compiled?;
+
+        self.run_watched_exports().await;
+
+        Ok(())
+    }
+
+    /// Passes the configured `inputs` and those of the profile to the workspace, compiling the open
+    /// files again if that changed the inputs
+    pub async fn update_inputs(&self) {
+        let inputs = self.config.read().await.profile_inputs();
+        let open_uris = {
+            let mut workspace = self.workspace().write().await;
+            if !workspace.set_inputs(&inputs) {
+                return;
             }
+            workspace.open_uris()
+        };
+
+        for uri in open_uris {
+            self.on_source_changed(&uri, None).await;
         }
+    }
 
-        Ok(())
+    /// Handles the `typst-lsp/activeEditor` notification, which editors send when the user
+    /// switches to another file, with `null` when no file is shown
+    pub async fn active_editor_changed(&self, params: ActiveEditorParams) {
+        *self.active_editor.lock() = params.uri;
+    }
+
+    /// With `openPreviewOnStart`, shows the main file in the preview, or the given file if no main
+    /// file is pinned, unless the preview already shows a document. Until the first Typst file is
+    /// opened or a main file is pinned there's nothing to show, so this is called again then.
+    pub async fn open_preview_on_start(&self, uri: &Url) {
+        if !self.config.read().await.open_preview_on_start
+            || self.preview_shown.load(Ordering::Relaxed)
+        {
+            return;
+        }
+
+        let uri = self.main_url().await.unwrap_or_else(|| uri.clone());
+        if !uri.path().ends_with(".typ") {
+            return;
+        }
+
+        if let Err(err) = self.run_export(&uri).await {
+            error!(%err, %uri, "could not open preview on start");
+        }
     }
 
     pub async fn run_export(&self, uri: &Url) -> anyhow::Result<()> {
@@ -48,10 +122,13 @@ impl TypstServer {
         &self,
         uri: &Url,
         first_change_range: Option<Range>,
+        publish_diagnostics: bool,
     ) -> anyhow::Result<()> {
         let (document, diagnostics) = self.compile_source(uri).await?;
 
-        self.update_all_diagnostics(diagnostics).await;
+        if publish_diagnostics {
+            self.update_all_diagnostics(diagnostics).await;
+        }
         if let Some(document) = document {
             self.export_pdf(uri, document, first_change_range).await?;
         } else {
@@ -0,0 +1,223 @@
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind, Position, Url};
+use typst::syntax::{ast, LinkedNode, Source, SyntaxKind};
+
+use crate::lsp_typst_boundary::{lsp_to_typst, typst_to_lsp};
+
+use super::lint::walk;
+use super::TypstServer;
+
+/// What the cursor is on, to find other occurrences of
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Variable(String),
+    Label(String),
+}
+
+impl TypstServer {
+    /// Highlights the occurrences in the document of the variable or label under the cursor. Goes
+    /// by name on the syntax tree alone, so shadowed variables of the same name are highlighted as
+    /// well.
+    pub async fn document_highlights(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> anyhow::Result<Option<Vec<DocumentHighlight>>> {
+        let position_encoding = self.const_config().position_encoding;
+
+        let highlights = self.scope_with_source(uri).await?.run(|source, _| {
+            let offset = lsp_to_typst::position_to_offset(position, position_encoding, source);
+            let occurrences = occurrences(source, offset)?;
+            Some(
+                occurrences
+                    .into_iter()
+                    .map(|(range, kind)| DocumentHighlight {
+                        range: typst_to_lsp::range(range, source, position_encoding).raw_range,
+                        kind: Some(kind),
+                    })
+                    .collect(),
+            )
+        });
+
+        Ok(highlights)
+    }
+}
+
+/// The occurrences of what's at the offset, with whether each defines or uses it
+fn occurrences(
+    source: &Source,
+    offset: usize,
+) -> Option<Vec<(Range<usize>, DocumentHighlightKind)>> {
+    let root = LinkedNode::new(source.root());
+    // The cursor may be right after the name
+    let target = [offset, offset.saturating_sub(1)]
+        .into_iter()
+        .find_map(|offset| target(&root.leaf_at(offset)?))?;
+
+    let mut occurrences = Vec::new();
+    walk(&root, &mut |node| {
+        if let Some(occurrence) = occurrence(node, &target) {
+            occurrences.push(occurrence);
+        }
+    });
+    Some(occurrences)
+}
+
+fn target(leaf: &LinkedNode) -> Option<Target> {
+    match leaf.kind() {
+        SyntaxKind::Ident | SyntaxKind::MathIdent => {
+            Some(Target::Variable(leaf.text().to_string()))
+        }
+        SyntaxKind::Label => Some(Target::Label(leaf.cast::<ast::Label>()?.get().to_owned())),
+        SyntaxKind::RefMarker => {
+            let reference = leaf.parent()?.cast::<ast::Ref>()?;
+            Some(Target::Label(reference.target().to_owned()))
+        }
+        _ => None,
+    }
+}
+
+fn occurrence(node: &LinkedNode, target: &Target) -> Option<(Range<usize>, DocumentHighlightKind)> {
+    match target {
+        Target::Variable(name) => {
+            if !matches!(node.kind(), SyntaxKind::Ident | SyntaxKind::MathIdent)
+                || node.text().as_str() != name
+            {
+                return None;
+            }
+            let kind = if is_binding(node) {
+                DocumentHighlightKind::WRITE
+            } else {
+                DocumentHighlightKind::READ
+            };
+            Some((node.range(), kind))
+        }
+        Target::Label(name) => {
+            if let Some(label) = node.cast::<ast::Label>() {
+                if label.get() != name.as_str() {
+                    return None;
+                }
+                // Labels in markup attach to elements, while labels in code refer to them
+                let kind = if node.parent_kind() == Some(SyntaxKind::Markup) {
+                    DocumentHighlightKind::WRITE
+                } else {
+                    DocumentHighlightKind::READ
+                };
+                return Some((node.range(), kind));
+            }
+
+            if node.kind() == SyntaxKind::RefMarker {
+                let reference = node.parent()?.cast::<ast::Ref>()?;
+                return (reference.target() == name.as_str())
+                    .then(|| (node.range(), DocumentHighlightKind::READ));
+            }
+
+            None
+        }
+    }
+}
+
+/// Whether the identifier is where a variable is bound, rather than used. Approximate, going by
+/// the identifier's place in its parent.
+fn is_binding(ident: &LinkedNode) -> bool {
+    let Some(parent) = ident.parent() else {
+        return false;
+    };
+
+    match parent.kind() {
+        // Before the `=` in `let x = ..`, `let (a, b) = ..` or `x += ..`
+        SyntaxKind::LetBinding | SyntaxKind::DestructAssignment => {
+            comes_before(ident, parent, |kind| kind == SyntaxKind::Eq)
+        }
+        SyntaxKind::Binary => parent
+            .children()
+            .find(|child| is_assignment(child.kind()))
+            .is_some_and(|op| ident.offset() < op.offset()),
+        // The name in `let f(..) = ..`
+        SyntaxKind::Closure => comes_before(ident, parent, |kind| kind == SyntaxKind::Params),
+        // The pattern in `for x in ..`
+        SyntaxKind::ForLoop => comes_before(ident, parent, |kind| kind == SyntaxKind::In),
+        SyntaxKind::Params | SyntaxKind::Destructuring | SyntaxKind::ImportItems => true,
+        // The new name in `import "..": a as b`
+        SyntaxKind::RenamedImportItem => parent
+            .children()
+            .last()
+            .is_some_and(|last| last.range() == ident.range()),
+        // A parameter with a default, like `x` in `(x: 1) => ..`
+        SyntaxKind::Named | SyntaxKind::Spread => {
+            parent.parent_kind() == Some(SyntaxKind::Params)
+                && comes_before(ident, parent, |kind| kind == SyntaxKind::Colon)
+        }
+        _ => false,
+    }
+}
+
+fn is_assignment(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::Eq
+            | SyntaxKind::PlusEq
+            | SyntaxKind::HyphEq
+            | SyntaxKind::StarEq
+            | SyntaxKind::SlashEq
+    )
+}
+
+/// Whether the node comes before the first child of the parent whose kind matches, or there is
+/// no such child
+fn comes_before(
+    node: &LinkedNode,
+    parent: &LinkedNode,
+    is_kind: impl Fn(SyntaxKind) -> bool,
+) -> bool {
+    parent
+        .children()
+        .find(|child| is_kind(child.kind()))
+        .is_none_or(|child| node.offset() < child.offset())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn highlights(text: &str, at: &str) -> Vec<(String, DocumentHighlightKind)> {
+        let source = Source::detached(text);
+        let offset = text.find(at).unwrap() + 1;
+        occurrences(&source, offset)
+            .unwrap()
+            .into_iter()
+            .map(|(range, kind)| (text[range].to_owned(), kind))
+            .collect()
+    }
+
+    #[test]
+    fn highlights_variable_definitions_and_uses() {
+        let text = "#let x = 1\n#let f(y) = x + y\n#f(x)";
+        let kinds: Vec<_> = highlights(text, "x +")
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                DocumentHighlightKind::WRITE,
+                DocumentHighlightKind::READ,
+                DocumentHighlightKind::READ,
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_labels_and_references() {
+        let text = "= Intro <intro>\nSee @intro and #ref(<intro>).";
+        assert_eq!(
+            highlights(text, "@intro"),
+            [
+                ("<intro>".to_owned(), DocumentHighlightKind::WRITE),
+                ("@intro".to_owned(), DocumentHighlightKind::READ),
+                ("<intro>".to_owned(), DocumentHighlightKind::READ),
+            ]
+        );
+    }
+}
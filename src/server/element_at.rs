@@ -0,0 +1,204 @@
+use serde::Serialize;
+use tower_lsp::lsp_types::{Location, Url};
+use typst::layout::{Abs, Frame, FrameItem, Point, Transform};
+use typst::model::Document;
+use typst::syntax::Span;
+
+use crate::lsp_typst_boundary::typst_to_lsp;
+
+use super::TypstServer;
+
+/// The element drawn at a point of a page, as returned by the `elementAt` command
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElementAt {
+    pub kind: ElementKind,
+    /// In points from the top left of the page
+    pub bbox: BoundingBox,
+    /// Where the element comes from, if it has a source
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ElementKind {
+    Text,
+    Shape,
+    Image,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BoundingBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl TypstServer {
+    /// Finds the topmost text, shape or image at the point of the page, in points from its top
+    /// left. Returns `None` if the point hits nothing or the page doesn't exist.
+    pub async fn element_at(
+        &self,
+        uri: &Url,
+        document: &Document,
+        page_index: usize,
+        point: Point,
+    ) -> anyhow::Result<Option<ElementAt>> {
+        let Some(page) = document.pages.get(page_index) else {
+            return Ok(None);
+        };
+        let Some(hit) = hit_test(&page.frame, point) else {
+            return Ok(None);
+        };
+
+        let bbox = BoundingBox {
+            x: hit.min.x.to_pt(),
+            y: hit.min.y.to_pt(),
+            width: (hit.max.x - hit.min.x).to_pt(),
+            height: (hit.max.y - hit.min.y).to_pt(),
+        };
+        let location = match hit.span.id() {
+            Some(id) => {
+                let (project, _) = self.project_and_full_id(uri).await?;
+                let uri = project.full_id_to_uri(project.fill_id(id)).await?;
+                let source = project.read_source_by_uri(&uri)?;
+                source.range(hit.span).map(|range| Location {
+                    uri,
+                    range: typst_to_lsp::range(
+                        range,
+                        &source,
+                        self.const_config().position_encoding,
+                    )
+                    .raw_range,
+                })
+            }
+            None => None,
+        };
+
+        Ok(Some(ElementAt {
+            kind: hit.kind,
+            bbox,
+            location,
+        }))
+    }
+}
+
+/// An element found by [`hit_test`], with its bounds on the page
+#[derive(Debug, Clone, PartialEq)]
+struct Hit {
+    kind: ElementKind,
+    min: Point,
+    max: Point,
+    span: Span,
+}
+
+/// The topmost element of the frame at the point, which is relative to the frame
+fn hit_test(frame: &Frame, point: Point) -> Option<Hit> {
+    let mut hit = None;
+    hit_in(frame, Transform::identity(), point, &mut hit);
+    hit
+}
+
+/// Items are drawn in order, so later hits replace earlier ones
+fn hit_in(frame: &Frame, ts: Transform, point: Point, hit: &mut Option<Hit>) {
+    for (pos, item) in frame.items() {
+        let ts = ts.pre_concat(Transform::translate(pos.x, pos.y));
+        let local = match ts.invert() {
+            Some(inverse) => point.transform(inverse),
+            None => continue,
+        };
+
+        let (kind, min, max, span) = match item {
+            FrameItem::Group(group) => {
+                hit_in(&group.frame, ts.pre_concat(group.transform), point, hit);
+                continue;
+            }
+            FrameItem::Text(text) => {
+                let metrics = text.font.metrics();
+                let min = Point::new(Abs::zero(), -metrics.ascender.at(text.size));
+                let max = Point::new(text.width(), -metrics.descender.at(text.size));
+
+                // The glyph under the point, for its span
+                let mut x = Abs::zero();
+                let glyph = text.glyphs.iter().find(|glyph| {
+                    let advance = glyph.x_advance.at(text.size);
+                    x += advance;
+                    local.x < x
+                });
+                let span = glyph.or(text.glyphs.last()).map(|glyph| glyph.span.0);
+                (
+                    ElementKind::Text,
+                    min,
+                    max,
+                    span.unwrap_or_else(Span::detached),
+                )
+            }
+            FrameItem::Shape(shape, span) => {
+                let size = shape.geometry.bbox_size();
+                (ElementKind::Shape, Point::zero(), size.to_point(), *span)
+            }
+            FrameItem::Image(_, size, span) => {
+                (ElementKind::Image, Point::zero(), size.to_point(), *span)
+            }
+            FrameItem::Meta(..) => continue,
+        };
+
+        if !contains(min, max, local) {
+            continue;
+        }
+        let (min, max) = page_bounds(min, max, ts);
+        *hit = Some(Hit {
+            kind,
+            min,
+            max,
+            span,
+        });
+    }
+}
+
+fn contains(min: Point, max: Point, point: Point) -> bool {
+    (min.x..=max.x).contains(&point.x) && (min.y..=max.y).contains(&point.y)
+}
+
+/// The axis-aligned bounds on the page of a box in an item's coordinates
+fn page_bounds(min: Point, max: Point, ts: Transform) -> (Point, Point) {
+    let corners = [min, Point::new(max.x, min.y), Point::new(min.x, max.y), max]
+        .map(|corner| corner.transform(ts));
+
+    corners
+        .iter()
+        .fold((corners[0], corners[0]), |(min, max), corner| {
+            (
+                Point::new(min.x.min(corner.x), min.y.min(corner.y)),
+                Point::new(max.x.max(corner.x), max.y.max(corner.y)),
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use typst::layout::Size;
+    use typst::visualize::{Color, Geometry};
+
+    use super::*;
+
+    #[test]
+    fn finds_topmost_shape_in_groups() {
+        let square = Geometry::Rect(Size::splat(Abs::pt(10.0))).filled(Color::BLACK.into());
+        let mut inner = Frame::soft(Size::splat(Abs::pt(20.0)));
+        inner.push(
+            Point::splat(Abs::pt(5.0)),
+            FrameItem::Shape(square.clone(), Span::detached()),
+        );
+
+        let mut page = Frame::soft(Size::splat(Abs::pt(100.0)));
+        page.push(Point::zero(), FrameItem::Shape(square, Span::detached()));
+        page.push_frame(Point::splat(Abs::pt(40.0)), inner);
+
+        let hit = hit_test(&page, Point::splat(Abs::pt(50.0))).unwrap();
+        assert_eq!(hit.min, Point::splat(Abs::pt(45.0)));
+        assert_eq!(hit.max, Point::splat(Abs::pt(55.0)));
+        assert_eq!(hit_test(&page, Point::splat(Abs::pt(30.0))), None);
+    }
+}
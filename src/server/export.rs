@@ -1,30 +1,793 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-use tower_lsp::lsp_types::{Range, Url};
+use anyhow::{bail, Context};
+use indexmap::IndexSet;
+use itertools::Itertools;
+use lopdf::{dictionary, Object, Stream};
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Position, Range, Url};
 use tracing::info;
+use typst::foundations::{Bytes, Smart};
+use typst::layout::{Frame, Size};
 use typst::model::Document;
+use typst::visualize::Color;
+use typst::World;
 
+use crate::config::{PdfStandard, PreviewFollow};
+use crate::workspace::package::lock::{PackageLock, LOCK_FILE_NAME};
+use crate::TYPST_VERSION;
+
+use super::typst_compiler::compile;
 use super::ui;
 use super::TypstServer;
 
 impl TypstServer {
+    /// "Exports" the document by handing it to the preview UI, which renders pages lazily as they
+    /// come into view.
+    ///
+    /// Despite the name, no PDF is serialized here, so there are no per-page PDF content streams to
+    /// reuse between exports. Unchanged pages are cheap on this path already, as rendering only
+    /// happens on demand in the UI. The pages which changed since the last document handed over
+    /// are found here though, so the UI only renders those again.
     #[tracing::instrument(skip(self))]
     pub async fn export_pdf(
         &self,
         source_uri: &Url,
         document: Arc<Document>,
         first_change_range: Option<Range>,
+    ) -> anyhow::Result<()> {
+        let first_change_range = if self.preview_follows(source_uri).await {
+            first_change_range
+        } else {
+            None
+        };
+        self.send_to_preview(source_uri, document, first_change_range, None)
+            .await
+    }
+
+    /// Whether the preview of the document compiled from `source_uri` should scroll to edits.
+    /// With `previewFollow` set to `mainOnly` it only does while the editor shows that file, if the
+    /// editor tells which file it shows.
+    async fn preview_follows(&self, source_uri: &Url) -> bool {
+        match self.config.read().await.preview_follow {
+            PreviewFollow::Always => true,
+            PreviewFollow::MainOnly => self
+                .active_editor
+                .lock()
+                .as_ref()
+                .is_none_or(|active| active == source_uri),
+        }
+    }
+
+    /// Compiles the file's document and shows it in the preview, scrolled to where the cursor in
+    /// the file ends up, or to the top without a cursor
+    #[tracing::instrument(skip(self))]
+    pub async fn open_preview(&self, uri: &Url, cursor: Option<Position>) -> anyhow::Result<()> {
+        let root = self.compilation_root(uri).await;
+        let (document, _) = self.compile_source(&root).await?;
+        let document = document.context("failed to generate document after compilation")?;
+
+        let scroll_to = match cursor {
+            Some(cursor) => ui::ScrollTo::Cursor(uri.clone(), cursor),
+            None => ui::ScrollTo::Top,
+        };
+        self.send_to_preview(&root, document, None, Some(scroll_to))
+            .await
+    }
+
+    async fn send_to_preview(
+        &self,
+        source_uri: &Url,
+        document: Arc<Document>,
+        first_change_range: Option<Range>,
+        scroll_to: Option<ui::ScrollTo>,
     ) -> anyhow::Result<()> {
         info!("updating UI");
+        self.preview_shown.store(true, Ordering::Relaxed);
+
+        let page_hashes = ui::page_hashes(&document);
+        let changed_pages = {
+            let mut preview_pages = self.preview_pages.lock();
+            let changed_pages = ui::changed_pages(&preview_pages, &page_hashes);
+            *preview_pages = page_hashes;
+            changed_pages
+        };
 
         self.to_ui_tx
             .send(ui::NewDocumentMessage {
                 document,
                 source_uri: source_uri.clone(),
                 first_change_range,
+                changed_pages: Some(changed_pages),
+                scroll_to,
             })
             .await?;
 
         Ok(())
     }
+
+    /// Compiles the document and collects the files of the current package that Typst read, along
+    /// with a lockfile of the external packages used, to be attached to an exported PDF.
+    pub async fn source_attachments(&self, uri: &Url) -> anyhow::Result<Vec<Attachment>> {
+        let attachments = self
+            .thread_with_world(uri)
+            .await?
+            .run(|world| {
+                compile(&world);
+
+                let ids: IndexSet<_> = iter::once(world.main().id())
+                    .chain(world.accessed_files())
+                    .collect();
+
+                let lock = PackageLock::new(&world.accessed_packages());
+
+                let mut attachments = Vec::with_capacity(ids.len() + 1);
+                for id in ids.into_iter().filter(|id| id.package().is_none()) {
+                    if let Ok(data) = world.file(id) {
+                        let name = id.vpath().as_rootless_path().display().to_string();
+                        attachments.push(Attachment { name, data });
+                    }
+                }
+
+                attachments.push(Attachment {
+                    name: LOCK_FILE_NAME.to_owned(),
+                    data: Bytes::from(lock.to_json().into_bytes()),
+                });
+                attachments
+            })
+            .await?;
+
+        Ok(attachments)
+    }
+}
+
+/// Renders the page at `page_index` to an SVG string, or `None` if the document has no such page.
+pub fn page_svg(document: &Document, page_index: usize) -> Option<String> {
+    let page = document.pages.get(page_index)?;
+    Some(typst_svg::svg(&page.frame))
+}
+
+/// Gap between the pages of a merged SVG, in points
+const MERGED_SVG_GAP_PT: f64 = 10.0;
+
+/// Renders all pages into a single SVG, for embedding the document on a web page as one file.
+/// The pages are stacked vertically and centered, and each has a view, so `document.svg#page-2`
+/// shows only the second page. Glyphs are embedded as outlines and images as data URLs, so the
+/// SVG looks the same on machines without the document's fonts, but its text can't be selected.
+/// Each page's ids are prefixed with the page, so its glyphs, clip paths and gradients don't clash
+/// with those of other pages.
+pub fn merged_svg(document: &Document) -> String {
+    let width = document
+        .pages
+        .iter()
+        .map(|page| page.frame.width().to_pt())
+        .fold(0.0, f64::max);
+
+    let mut views = String::new();
+    let mut pages = String::new();
+    let mut y = 0.0;
+    for (index, page) in document.pages.iter().enumerate() {
+        if index > 0 {
+            y += MERGED_SVG_GAP_PT;
+        }
+        let (page_width, page_height) = (page.frame.width().to_pt(), page.frame.height().to_pt());
+        let x = (width - page_width) / 2.0;
+
+        let _ = write!(
+            views,
+            r#"<view id="page-{}" viewBox="{x} {y} {page_width} {page_height}"/>"#,
+            index + 1
+        );
+        let svg = with_id_prefix(
+            &typst_svg::svg(&page.frame),
+            &format!("page-{}-", index + 1),
+        );
+        pages.push_str(&placed_svg(&svg, x, y, page_width, page_height));
+        y += page_height;
+    }
+
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 {width} {y}" width="{width}pt" height="{y}pt">{views}{pages}</svg>"#
+    )
+}
+
+/// The SVG of a page as an element of the merged SVG, moved to `x`, `y` with the size given in
+/// the merged SVG's units instead of its own `width` and `height`
+fn placed_svg(svg: &str, x: f64, y: f64, width: f64, height: f64) -> String {
+    let start = svg.find("<svg").unwrap_or_default();
+    let end = svg[start..].find('>').map_or(svg.len(), |end| start + end);
+    let tag = without_attribute(&without_attribute(&svg[start..end], "width"), "height");
+    format!(
+        r#"{tag} x="{x}" y="{y}" width="{width}" height="{height}"{}"#,
+        &svg[end..]
+    )
+}
+
+/// Prefixes the ids an SVG defines and its references to them. Typst derives the ids of glyphs,
+/// clip paths and gradients from what they draw, so pages drawing the same glyph define the same
+/// id.
+fn with_id_prefix(svg: &str, prefix: &str) -> String {
+    svg.replace(r#" id=""#, &format!(r#" id="{prefix}"#))
+        .replace(r##"href="#"##, &format!(r##"href="#{prefix}"##))
+        .replace("url(#", &format!("url(#{prefix}"))
+}
+
+fn without_attribute(tag: &str, name: &str) -> String {
+    let prefix = format!(" {name}=\"");
+    let Some(start) = tag.find(&prefix) else {
+        return tag.to_owned();
+    };
+    let value_start = start + prefix.len();
+    match tag[value_start..].find('"') {
+        Some(value_end) => format!("{}{}", &tag[..start], &tag[value_start + value_end + 1..]),
+        None => tag.to_owned(),
+    }
+}
+
+/// The longest side of page images rendered for the client, in pixels
+const MAX_PAGE_PNG_PIXELS: f32 = 2048.0;
+
+/// Renders a single page to a PNG at the given pixels per point, scaled down to fit within
+/// [`MAX_PAGE_PNG_PIXELS`] on each side. Returns `None` if the page doesn't exist.
+pub fn page_png(
+    document: &Document,
+    page_index: usize,
+    pixel_per_pt: f32,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let Some(page) = document.pages.get(page_index) else {
+        return Ok(None);
+    };
+
+    let size = page.frame.size();
+    let longest_side = size.x.max(size.y).to_pt() as f32;
+    let pixel_per_pt = pixel_per_pt.min(MAX_PAGE_PNG_PIXELS / longest_side.max(1.0));
+
+    let png = typst_render::render(&page.frame, pixel_per_pt, Color::WHITE).encode_png()?;
+    Ok(Some(png))
+}
+
+/// Where the PDF for the source at `source_uri` is written: next to the source, with the same name.
+/// Only local sources have a PDF path.
+pub fn pdf_path(source_uri: &Url) -> Option<PathBuf> {
+    let source_path = source_uri.to_file_path().ok()?;
+    Some(source_path.with_extension("pdf"))
+}
+
+/// What to export a document to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    #[default]
+    Pdf,
+    /// One PNG per page
+    Png,
+    /// One SVG per page
+    Svg,
+    /// A single SVG with all pages, see [`merged_svg`]
+    SvgMerged,
+}
+
+/// Pixels per point of exported PNGs, about 144 DPI
+pub const PNG_PIXEL_PER_PT: f32 = 2.0;
+
+/// Exports the document next to the source at `source_path`, with the same name. Pages of
+/// multi-page documents exported as images get their page number appended, like
+/// `figure-1.png`. PNGs are rendered at `pixel_per_pt`. Returns the paths of all written files.
+pub fn export_document(
+    document: &Document,
+    source_path: &Path,
+    format: ExportFormat,
+    pixel_per_pt: f32,
+    pdf_options: &PdfOptions,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let extension = match format {
+        ExportFormat::Pdf => {
+            let path = source_path.with_extension("pdf");
+            write_pdf(document, &path, pdf_options)?;
+            return Ok(vec![path]);
+        }
+        ExportFormat::SvgMerged => {
+            let path = source_path.with_extension("svg");
+            fs::write(&path, merged_svg(document))
+                .with_context(|| format!("could not write {}", path.display()))?;
+            return Ok(vec![path]);
+        }
+        ExportFormat::Png => "png",
+        ExportFormat::Svg => "svg",
+    };
+
+    let stem = source_path
+        .file_stem()
+        .context("source path has no file name")?
+        .to_string_lossy();
+    let digits = document.pages.len().to_string().len();
+
+    let mut written = Vec::with_capacity(document.pages.len());
+    for (index, page) in document.pages.iter().enumerate() {
+        let name = if document.pages.len() == 1 {
+            format!("{stem}.{extension}")
+        } else {
+            format!("{stem}-{:0digits$}.{extension}", index + 1)
+        };
+        let path = source_path.with_file_name(name);
+
+        let data = match format {
+            ExportFormat::Png => {
+                typst_render::render(&page.frame, pixel_per_pt, Color::WHITE).encode_png()?
+            }
+            _ => typst_svg::svg(&page.frame).into_bytes(),
+        };
+        fs::write(&path, data).with_context(|| format!("could not write {}", path.display()))?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Options for writing PDFs, taken from the config.
+#[derive(Debug, Clone, Default)]
+pub struct PdfOptions {
+    pub standard: PdfStandard,
+    /// Files to embed in the PDF
+    pub attachments: Vec<Attachment>,
+}
+
+/// A file embedded in an exported PDF.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub name: String,
+    pub data: Bytes,
+}
+
+impl PdfOptions {
+    /// Checks that the linked version of Typst can export PDFs with these options.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self.standard {
+            PdfStandard::V1_7 => Ok(()),
+            standard => bail!(
+                "{standard} export is not supported by Typst {TYPST_VERSION}, which only exports \
+                 PDF 1.7. Set `pdfStandard` to \"1.7\"."
+            ),
+        }
+    }
+}
+
+/// Serializes the document to a PDF file at `path`.
+pub fn write_pdf(document: &Document, path: &Path, options: &PdfOptions) -> anyhow::Result<()> {
+    options.validate()?;
+
+    let mut pdf = typst_pdf::pdf(document, Smart::Auto, None);
+    if !options.attachments.is_empty() {
+        pdf = embed_attachments(&pdf, &options.attachments)?;
+    }
+    fs::write(path, pdf).with_context(|| format!("could not write PDF to {}", path.display()))
+}
+
+/// Adds the attachments to the PDF as embedded files. Typst 0.11 can't write attachments itself, so
+/// this rewrites the finished PDF.
+fn embed_attachments(pdf: &[u8], attachments: &[Attachment]) -> anyhow::Result<Vec<u8>> {
+    let mut pdf = lopdf::Document::load_mem(pdf).context("could not read exported PDF")?;
+
+    // The name tree must be sorted by name
+    let mut names = Vec::with_capacity(attachments.len() * 2);
+    let mut file_specs = Vec::with_capacity(attachments.len());
+    for attachment in attachments.iter().sorted_by(|a, b| a.name.cmp(&b.name)) {
+        let file = pdf.add_object(Stream::new(
+            dictionary! { "Type" => "EmbeddedFile" },
+            attachment.data.to_vec(),
+        ));
+        let file_spec = pdf.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal(attachment.name.as_str()),
+            "UF" => Object::string_literal(attachment.name.as_str()),
+            "AFRelationship" => "Source",
+            "EF" => dictionary! { "F" => file },
+        });
+
+        names.push(Object::string_literal(attachment.name.as_str()));
+        names.push(file_spec.into());
+        file_specs.push(file_spec.into());
+    }
+    let embedded_files = dictionary! { "Names" => names };
+
+    let catalog = pdf.catalog_mut()?;
+    let has_names = matches!(catalog.get(b"Names"), Ok(Object::Dictionary(_)));
+    if has_names {
+        catalog
+            .get_mut(b"Names")?
+            .as_dict_mut()?
+            .set("EmbeddedFiles", embedded_files);
+    } else {
+        catalog.set("Names", dictionary! { "EmbeddedFiles" => embedded_files });
+    }
+    catalog.set("AF", file_specs);
+
+    let mut out = Vec::new();
+    pdf.save_to(&mut out)?;
+    Ok(out)
+}
+
+/// Options for [`export_slides`]. Pages are scaled to fit within `width` × `height` pixels while
+/// keeping their aspect ratio, so slides are never cropped.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SlidesOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Also write the whole deck as a single PDF with one page per slide
+    pub pdf: bool,
+}
+
+impl Default for SlidesOptions {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            pdf: false,
+        }
+    }
+}
+
+/// Renders each page of the document to a PNG sized for projector output, writing them to a
+/// `<name>-slides` directory next to the source. Returns the paths of all written files.
+pub fn export_slides(
+    document: &Document,
+    source_path: &Path,
+    options: SlidesOptions,
+    pdf_options: &PdfOptions,
+) -> anyhow::Result<Vec<PathBuf>> {
+    if options.width == 0 || options.height == 0 {
+        bail!("slide resolution must not be zero");
+    }
+
+    let stem = source_path
+        .file_stem()
+        .context("source path has no file name")?
+        .to_string_lossy();
+    let dir = source_path.with_file_name(format!("{stem}-slides"));
+    fs::create_dir_all(&dir)?;
+
+    let digits = document.pages.len().to_string().len();
+    let mut written = Vec::with_capacity(document.pages.len() + 1);
+
+    for (index, page) in document.pages.iter().enumerate() {
+        let size = page.frame.size();
+        let pixel_per_pt = f64::min(
+            options.width as f64 / size.x.to_pt(),
+            options.height as f64 / size.y.to_pt(),
+        );
+
+        let pixmap = typst_render::render(&page.frame, pixel_per_pt as f32, Color::WHITE);
+        let png = pixmap.encode_png()?;
+
+        let path = dir.join(format!("{stem}-{:0digits$}.png", index + 1));
+        fs::write(&path, png)?;
+        written.push(path);
+    }
+
+    if options.pdf {
+        let path = dir.join(format!("{stem}.pdf"));
+        write_pdf(document, &path, pdf_options)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// How [`pixel_png`] scales a page into dimensions with a different aspect ratio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PixelFit {
+    /// Show the whole page, centered on a white background
+    #[default]
+    Fit,
+    /// Cover the whole image, cropping the page's edges
+    Fill,
+}
+
+/// Options for exporting a page as a PNG of exact dimensions, e.g. for an icon or a thumbnail
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PixelOptions {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub fit: PixelFit,
+    /// The page to export, required if the document has more than one
+    #[serde(default)]
+    pub page: Option<usize>,
+}
+
+/// Renders the frame of a page to a PNG of exactly `width` × `height` pixels, scaled according
+/// to `fit` while keeping its aspect ratio.
+pub fn pixel_png(frame: &Frame, width: u32, height: u32, fit: PixelFit) -> anyhow::Result<Vec<u8>> {
+    let size = frame.size();
+    if size.x.to_pt() <= 0.0 || size.y.to_pt() <= 0.0 {
+        bail!("the page is empty");
+    }
+
+    let scale_x = width as f64 / size.x.to_pt();
+    let scale_y = height as f64 / size.y.to_pt();
+    let pixel_per_pt = match fit {
+        PixelFit::Fit => scale_x.min(scale_y),
+        PixelFit::Fill => scale_x.max(scale_y),
+    };
+    let page = typst_render::render(frame, pixel_per_pt as f32, Color::WHITE);
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).context("invalid image dimensions")?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    pixmap.draw_pixmap(
+        (width as i32 - page.width() as i32) / 2,
+        (height as i32 - page.height() as i32) / 2,
+        page.as_ref(),
+        &tiny_skia::PixmapPaint::default(),
+        tiny_skia::Transform::identity(),
+        None,
+    );
+
+    Ok(pixmap.encode_png()?)
+}
+
+/// Where [`pixel_png`] output for the source is written: next to it, named after the dimensions
+pub fn pixel_png_path(source_path: &Path, options: &PixelOptions) -> anyhow::Result<PathBuf> {
+    let stem = source_path
+        .file_stem()
+        .context("source path has no file name")?
+        .to_string_lossy();
+    let name = match options.page {
+        Some(index) => format!(
+            "{stem}-{}-{}x{}.png",
+            index + 1,
+            options.width,
+            options.height
+        ),
+        None => format!("{stem}-{}x{}.png", options.width, options.height),
+    };
+    Ok(source_path.with_file_name(name))
+}
+
+/// Options for rendering a rectangle of a page, like a figure, in points from the page's top left
+/// corner
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegionOptions {
+    /// Starting at 0
+    pub page: usize,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Defaults to the file's `dpi` directive, else 2
+    #[serde(default)]
+    pub pixel_per_pt: Option<f32>,
+    /// Write the image next to the source instead of returning it
+    #[serde(default)]
+    pub write: bool,
+}
+
+impl RegionOptions {
+    /// Fails with a message for the user if the rectangle isn't within a page of the size
+    pub fn validate(&self, page: Size) -> Result<(), String> {
+        let values = [self.x, self.y, self.width, self.height];
+        if values.iter().any(|value| !value.is_finite()) {
+            return Err("The region's coordinates must be finite numbers".to_owned());
+        }
+        if self.width <= 0.0 || self.height <= 0.0 {
+            return Err("The region must have a positive width and height".to_owned());
+        }
+
+        // Allow for rounding in coordinates computed by the client
+        let tolerance = 1e-3;
+        let (page_width, page_height) = (page.x.to_pt(), page.y.to_pt());
+        if self.x < -tolerance
+            || self.y < -tolerance
+            || self.x + self.width > page_width + tolerance
+            || self.y + self.height > page_height + tolerance
+        {
+            return Err(format!(
+                "The region must be within the page, which is {page_width:.2}pt × \
+                 {page_height:.2}pt"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Renders the region of the page to a PNG. `typst_render` can only render whole pages, so this
+/// renders the page and crops it.
+pub fn region_png(
+    frame: &Frame,
+    options: &RegionOptions,
+    pixel_per_pt: f32,
+) -> anyhow::Result<Vec<u8>> {
+    let page = typst_render::render(frame, pixel_per_pt, Color::WHITE);
+
+    let scale = f64::from(pixel_per_pt);
+    let left = (options.x * scale).floor().max(0.0) as u32;
+    let top = (options.y * scale).floor().max(0.0) as u32;
+    let right = ((options.x + options.width) * scale).ceil() as u32;
+    let bottom = ((options.y + options.height) * scale).ceil() as u32;
+    let (right, bottom) = (right.min(page.width()), bottom.min(page.height()));
+
+    let rect = tiny_skia::IntRect::from_ltrb(left as i32, top as i32, right as i32, bottom as i32)
+        .context("the region is smaller than a pixel")?;
+    let region = page
+        .clone_rect(rect)
+        .context("the region is outside of the page")?;
+    Ok(region.encode_png()?)
+}
+
+/// Where [`region_png`] output for the source is written: next to it, named after the page
+pub fn region_png_path(source_path: &Path, page_index: usize) -> anyhow::Result<PathBuf> {
+    let stem = source_path
+        .file_stem()
+        .context("source path has no file name")?
+        .to_string_lossy();
+    Ok(source_path.with_file_name(format!("{stem}-{}-region.png", page_index + 1)))
+}
+
+/// Options for exporting the document with other `sys.inputs`, e.g. a draft and a final version
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InputsOptions {
+    /// Set over the configured inputs
+    pub inputs: BTreeMap<String, String>,
+    /// Appended to the name of the exported file, defaults to the inputs
+    pub suffix: Option<String>,
+    pub format: Option<ExportFormat>,
+}
+
+impl InputsOptions {
+    /// A suffix for the file name which tells exports with different inputs apart
+    pub fn suffix(&self) -> String {
+        let suffix = match &self.suffix {
+            Some(suffix) => suffix.clone(),
+            None => self
+                .inputs
+                .iter()
+                .map(|(key, value)| format!("{key}-{value}"))
+                .join("-"),
+        };
+        file_name_suffix(&suffix)
+    }
+}
+
+/// The text with the characters which don't belong in file names replaced
+pub fn file_name_suffix(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use typst::layout::{Abs, Em, FrameItem, Page, Point};
+    use typst::syntax::Span;
+    use typst::text::{Font, Glyph, Lang, TextItem};
+
+    use super::*;
+
+    #[test]
+    fn renders_exact_dimensions() {
+        let frame = Frame::soft(Size::new(Abs::pt(100.0), Abs::pt(50.0)));
+
+        for fit in [PixelFit::Fit, PixelFit::Fill] {
+            let png = pixel_png(&frame, 64, 64, fit).unwrap();
+            let pixmap = tiny_skia::Pixmap::decode_png(&png).unwrap();
+            assert_eq!((pixmap.width(), pixmap.height()), (64, 64));
+        }
+    }
+
+    #[test]
+    fn validates_regions() {
+        let page = Size::new(Abs::pt(100.0), Abs::pt(50.0));
+        let region = |x, y, width, height| RegionOptions {
+            page: 0,
+            x,
+            y,
+            width,
+            height,
+            pixel_per_pt: None,
+            write: false,
+        };
+
+        assert!(region(0.0, 0.0, 100.0, 50.0).validate(page).is_ok());
+        assert!(region(10.0, 10.0, 0.0, 5.0).validate(page).is_err());
+        assert!(region(90.0, 0.0, 20.0, 5.0).validate(page).is_err());
+        assert!(region(0.0, f64::NAN, 5.0, 5.0).validate(page).is_err());
+
+        let frame = Frame::soft(page);
+        let png = region_png(&frame, &region(10.0, 5.0, 20.5, 10.0), 2.0).unwrap();
+        let pixmap = tiny_skia::Pixmap::decode_png(&png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (41, 20));
+    }
+
+    #[test]
+    fn places_page_svgs() {
+        let svg = r#"<svg class="typst-doc" viewBox="0 0 20 10" width="20pt" height="10pt" xmlns="http://www.w3.org/2000/svg"><path d="M 0 0"/></svg>"#;
+        assert_eq!(
+            placed_svg(svg, 5.0, 30.0, 20.0, 10.0),
+            r#"<svg class="typst-doc" viewBox="0 0 20 10" xmlns="http://www.w3.org/2000/svg" x="5" y="30" width="20" height="10"><path d="M 0 0"/></svg>"#
+        );
+    }
+
+    #[test]
+    fn merged_svg_ids_are_unique() {
+        let font = Font::new(
+            Bytes::from_static(include_bytes!("../../assets/fonts/LinLibertine_R.ttf")),
+            0,
+        )
+        .unwrap();
+        let page = || {
+            let glyph = Glyph {
+                id: font.ttf().glyph_index('A').unwrap().0,
+                x_advance: Em::one(),
+                x_offset: Em::zero(),
+                range: 0..1,
+                span: (Span::detached(), 0),
+            };
+            let text = TextItem {
+                font: font.clone(),
+                size: Abs::pt(10.0),
+                fill: Color::BLACK.into(),
+                stroke: None,
+                lang: Lang::ENGLISH,
+                text: "A".into(),
+                glyphs: vec![glyph],
+            };
+            let mut frame = Frame::soft(Size::splat(Abs::pt(20.0)));
+            frame.push(Point::with_y(Abs::pt(10.0)), FrameItem::Text(text));
+            Page {
+                frame,
+                numbering: None,
+                number: 1,
+            }
+        };
+        let document = Document {
+            pages: vec![page(), page()],
+            ..Default::default()
+        };
+
+        let svg = merged_svg(&document);
+        let ids: Vec<_> = svg
+            .split(r#" id=""#)
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect();
+        assert!(ids.iter().all_unique(), "duplicate ids in {ids:?}");
+
+        // Every reference resolves, and each page uses the glyph it defines itself
+        let references: Vec<_> = svg
+            .split(r##"href="#"##)
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect();
+        assert!(references.iter().all(|reference| ids.contains(reference)));
+        for page in ["page-1-", "page-2-"] {
+            assert!(references
+                .iter()
+                .any(|reference| reference.starts_with(page)));
+        }
+    }
+
+    #[test]
+    fn suffix_names_inputs() {
+        let options = InputsOptions {
+            inputs: BTreeMap::from([
+                ("mode".to_owned(), "draft".to_owned()),
+                ("lang".to_owned(), "de/AT".to_owned()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(options.suffix(), "lang-de_AT-mode-draft");
+    }
 }
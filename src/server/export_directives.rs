@@ -0,0 +1,141 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::Url;
+use tracing::warn;
+
+use super::export::{ExportFormat, PNG_PIXEL_PER_PT};
+use super::TypstServer;
+
+const DIRECTIVE_PREFIX: &str = "typst-lsp:";
+/// How many lines at the start of a file are searched for directives
+const DIRECTIVE_LINES: usize = 5;
+const POINTS_PER_INCH: f32 = 72.0;
+
+/// Export settings a file declares for itself in a leading comment like
+/// `// typst-lsp: export=svg, dpi=200`. They take precedence over the config, but not over
+/// arguments passed explicitly to a command.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ExportDirectives {
+    pub format: Option<ExportFormat>,
+    pub dpi: Option<f32>,
+}
+
+impl ExportDirectives {
+    /// Parses the directives from the first lines of the file, skipping invalid ones with a warning
+    pub fn parse(text: &str) -> Self {
+        let mut directives = Self::default();
+
+        for line in text.lines().take(DIRECTIVE_LINES) {
+            let Some(comment) = line.trim_start().strip_prefix("//") else {
+                continue;
+            };
+            let Some(items) = comment.trim_start().strip_prefix(DIRECTIVE_PREFIX) else {
+                continue;
+            };
+
+            for item in items
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+            {
+                let Some((key, value)) = item.split_once('=') else {
+                    warn!(
+                        directive = item,
+                        "ignoring export directive without a value"
+                    );
+                    continue;
+                };
+                let value = value.trim();
+
+                match key.trim() {
+                    "export" => match serde_json::from_value(Value::String(value.to_owned())) {
+                        Ok(format) => directives.format = Some(format),
+                        Err(err) => warn!(%err, value, "ignoring invalid export format"),
+                    },
+                    "dpi" => match value.parse::<f32>() {
+                        Ok(dpi) if dpi > 0.0 => directives.dpi = Some(dpi),
+                        _ => warn!(value, "ignoring invalid dpi, expected a positive number"),
+                    },
+                    key => warn!(key, "ignoring unknown export directive"),
+                }
+            }
+        }
+
+        directives
+    }
+
+    pub fn format_or(&self, format: Option<ExportFormat>) -> ExportFormat {
+        format.or(self.format).unwrap_or_default()
+    }
+
+    /// The requested format, else the directive's, else the profile's, else PDF
+    pub fn format_over_profile(
+        &self,
+        format: Option<ExportFormat>,
+        profile_format: Option<ExportFormat>,
+    ) -> ExportFormat {
+        format
+            .or(self.format)
+            .or(profile_format)
+            .unwrap_or_default()
+    }
+
+    /// Pixels per point for PNGs, from the `dpi` directive if there is one
+    pub fn pixel_per_pt(&self) -> f32 {
+        self.dpi
+            .map_or(PNG_PIXEL_PER_PT, |dpi| dpi / POINTS_PER_INCH)
+    }
+}
+
+impl TypstServer {
+    /// The export directives of the file, or none if it can't be read
+    pub async fn export_directives(&self, uri: &Url) -> ExportDirectives {
+        match self.scope_with_source(uri).await {
+            Ok(scope) => scope.run(|source, _| ExportDirectives::parse(source.text())),
+            Err(err) => {
+                warn!(%err, %uri, "could not read file for export directives");
+                ExportDirectives::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_leading_directives() {
+        let text = "// typst-lsp: export=svg, dpi=200, colour=red\n= Figure";
+        assert_eq!(
+            ExportDirectives::parse(text),
+            ExportDirectives {
+                format: Some(ExportFormat::Svg),
+                dpi: Some(200.0),
+            }
+        );
+    }
+
+    #[test]
+    fn directive_format_wins_over_the_profile() {
+        let directives = ExportDirectives::parse("// typst-lsp: export=svg");
+        let profile_format = Some(ExportFormat::Png);
+        assert_eq!(
+            directives.format_over_profile(None, profile_format),
+            ExportFormat::Svg
+        );
+        assert_eq!(
+            directives.format_over_profile(Some(ExportFormat::Pdf), profile_format),
+            ExportFormat::Pdf
+        );
+        assert_eq!(
+            ExportDirectives::default().format_over_profile(None, profile_format),
+            ExportFormat::Png
+        );
+    }
+
+    #[test]
+    fn ignores_invalid_and_late_directives() {
+        let text = "// typst-lsp: export=docx, dpi=-1\n\n\n\n\n// typst-lsp: export=png";
+        assert_eq!(ExportDirectives::parse(text), ExportDirectives::default());
+    }
+}
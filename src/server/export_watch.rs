@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use siphasher::sip128::{Hasher128, SipHasher13};
+use tower_lsp::lsp_types::Url;
+use tracing::{info, warn};
+use typst::model::Document;
+
+use super::export::{export_document, ExportFormat, PdfOptions};
+use super::TypstServer;
+
+/// Files which are exported again whenever their output changes, independently of `exportPdf`
+#[derive(Debug, Default)]
+pub struct ExportWatches {
+    files: HashMap<Url, ExportWatch>,
+}
+
+#[derive(Debug)]
+struct ExportWatch {
+    /// The format asked for, else the one from the file's export directives
+    format: Option<ExportFormat>,
+    /// Hash of the document last exported, to skip exports when an edit doesn't change the output
+    last_document: Option<u128>,
+}
+
+impl TypstServer {
+    /// Exports the file now, and again after changes until [`Self::unwatch_export`]. Watching an
+    /// already watched file switches it to the new format. Without a format, the one from the
+    /// file's export directives is used, or PDF. Returns the paths written now.
+    pub async fn watch_export(
+        &self,
+        uri: &Url,
+        format: Option<ExportFormat>,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        if uri.to_file_path().is_err() {
+            anyhow::bail!("only local files can be exported");
+        }
+
+        self.export_watches.lock().files.insert(
+            uri.clone(),
+            ExportWatch {
+                format,
+                last_document: None,
+            },
+        );
+        self.export_watched(uri).await
+    }
+
+    /// Stops exporting the file after changes. Returns whether it was watched.
+    pub fn unwatch_export(&self, uri: &Url) -> bool {
+        self.export_watches.lock().files.remove(uri).is_some()
+    }
+
+    /// Compiles each watched file and exports those whose document changed. Compiling unchanged
+    /// files again is cheap, since Typst caches everything the edit didn't affect.
+    pub async fn run_watched_exports(&self) {
+        let uris: Vec<_> = self.export_watches.lock().files.keys().cloned().collect();
+
+        for uri in uris {
+            if let Err(err) = self.export_watched(&uri).await {
+                warn!(%err, %uri, "could not export watched file");
+            }
+        }
+    }
+
+    async fn export_watched(&self, uri: &Url) -> anyhow::Result<Vec<PathBuf>> {
+        let source_path = uri
+            .to_file_path()
+            .map_err(|()| anyhow!("only local files can be exported"))?;
+
+        let (document, _) = self.compile_source(uri).await?;
+        // Keep the last export around while the document has errors
+        let Some(document) = document else {
+            return Ok(Vec::new());
+        };
+        let hash = hash_document(&document);
+
+        let format = {
+            let watches = self.export_watches.lock();
+            match watches.files.get(uri) {
+                Some(watch) if watch.last_document != Some(hash) => watch.format,
+                // Unwatched in the meantime, or unchanged
+                _ => return Ok(Vec::new()),
+            }
+        };
+
+        // Read the directives for every export, so editing them takes effect
+        let directives = self.export_directives(uri).await;
+        let format = directives.format_or(format);
+        let profile_pixel_per_pt = self.config.read().await.export_pixel_per_pt();
+        let pdf_options = match format {
+            ExportFormat::Pdf => self.pdf_options(uri).await?,
+            _ => PdfOptions::default(),
+        };
+        let written = tokio::task::spawn_blocking(move || {
+            let pixel_per_pt = profile_pixel_per_pt.unwrap_or_else(|| directives.pixel_per_pt());
+            export_document(&document, &source_path, format, pixel_per_pt, &pdf_options)
+        })
+        .await??;
+        info!(%uri, ?format, files = written.len(), "exported watched file");
+
+        if let Some(watch) = self.export_watches.lock().files.get_mut(uri) {
+            watch.last_document = Some(hash);
+        }
+        Ok(written)
+    }
+}
+
+/// Hashes what ends up in exports, the pages and the metadata written to PDFs
+fn hash_document(document: &Document) -> u128 {
+    let mut hasher = SipHasher13::new();
+    for page in &document.pages {
+        page.frame.hash(&mut hasher);
+    }
+    document.title.hash(&mut hasher);
+    document.author.hash(&mut hasher);
+    document.keywords.hash(&mut hasher);
+    document.date.hash(&mut hasher);
+    hasher.finish128().as_u128()
+}
@@ -1,10 +1,13 @@
+use std::sync::Arc;
+
 use anyhow::Context;
-use tower_lsp::lsp_types::{Hover, Url};
+use tower_lsp::lsp_types::{Hover, HoverContents, MarkedString, Url};
 use typst::syntax::LinkedNode;
 use typst::World;
 
 use crate::lsp_typst_boundary::{lsp_to_typst, typst_to_lsp, LspPosition};
 
+use super::math_hover::math_hover;
 use super::TypstServer;
 
 impl TypstServer {
@@ -18,6 +21,7 @@ impl TypstServer {
         let doc = self.document.lock().await.clone();
 
         let fid = self.workspace().read().await.full_id(uri)?;
+        let math_hover_cache = Arc::clone(&self.math_hover_cache);
         let result = self
             .thread_with_world(self.main_url().await.as_ref().unwrap_or(uri))
             .await?
@@ -27,30 +31,47 @@ impl TypstServer {
                 let typst_offset =
                     lsp_to_typst::position_to_offset(position, position_encoding, &source);
 
-                let typst_tooltip = typst_ide::tooltip(&world, Some(&doc), &source, typst_offset)?;
+                let typst_tooltip = typst_ide::tooltip(&world, Some(&doc), &source, typst_offset);
+                let math = math_hover(&world, &math_hover_cache, &source, typst_offset);
 
-                Some((typst_offset, typst_tooltip))
+                if typst_tooltip.is_none() && math.is_none() {
+                    return None;
+                }
+                Some((typst_offset, typst_tooltip, math))
             })
-            .await;
-        let Some((typst_offset, typst_tooltip)) = result else {
+            .await?;
+        let Some((typst_offset, typst_tooltip, math)) = result else {
             return Ok(None);
         };
 
-        let lsp_tooltip = typst_to_lsp::tooltip(&typst_tooltip);
+        let lsp_tooltip = typst_tooltip.as_ref().map(typst_to_lsp::tooltip);
+        let contents = match (lsp_tooltip, &math) {
+            (Some(HoverContents::Scalar(tooltip)), Some((image, _))) => {
+                HoverContents::Array(vec![tooltip, MarkedString::String(image.clone())])
+            }
+            (Some(tooltip), _) => tooltip,
+            (None, Some((image, _))) => HoverContents::Scalar(MarkedString::String(image.clone())),
+            (None, None) => return Ok(None),
+        };
 
         let lsp_hovered_range = self.scope_with_source(uri).await?.run(|source, _| {
-            let typst_hovered_node = LinkedNode::new(source.root())
-                .leaf_at(typst_offset)
-                .context("")?;
+            // Highlight the whole equation when previewing it
+            let typst_hovered_range = match math {
+                Some((_, range)) => range,
+                None => LinkedNode::new(source.root())
+                    .leaf_at(typst_offset)
+                    .context("")?
+                    .range(),
+            };
             anyhow::Ok(typst_to_lsp::range(
-                typst_hovered_node.range(),
+                typst_hovered_range,
                 source,
                 self.const_config().position_encoding,
             ))
         })?;
 
         Ok(Some(Hover {
-            contents: lsp_tooltip,
+            contents,
             range: Some(lsp_hovered_range.raw_range),
         }))
     }
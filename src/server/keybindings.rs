@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use slint::platform::Key;
+use tracing::warn;
+
+/// What a key in the preview can do, by the name it's configured with in `previewKeybindings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ToggleInvert,
+    ToggleLayout,
+    ToggleOutline,
+    CopyPage,
+    /// Copies `file:line:col` of what's in the middle of the preview
+    CopySourceLocation,
+    Present,
+    /// While presenting
+    NextPage,
+    /// While presenting
+    PreviousPage,
+    /// While presenting
+    ExitPresentation,
+}
+
+impl Action {
+    const ALL: [Self; 12] = [
+        Self::ZoomIn,
+        Self::ZoomOut,
+        Self::ZoomReset,
+        Self::ToggleInvert,
+        Self::ToggleLayout,
+        Self::ToggleOutline,
+        Self::CopyPage,
+        Self::CopySourceLocation,
+        Self::Present,
+        Self::NextPage,
+        Self::PreviousPage,
+        Self::ExitPresentation,
+    ];
+
+    /// The name of the action, which the preview window dispatches on
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ZoomIn => "zoom-in",
+            Self::ZoomOut => "zoom-out",
+            Self::ZoomReset => "zoom-reset",
+            Self::ToggleInvert => "toggle-invert",
+            Self::ToggleLayout => "toggle-layout",
+            Self::ToggleOutline => "toggle-outline",
+            Self::CopyPage => "copy-page",
+            Self::CopySourceLocation => "copy-source-location",
+            Self::Present => "present",
+            Self::NextPage => "next-page",
+            Self::PreviousPage => "previous-page",
+            Self::ExitPresentation => "exit-presentation",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_combos(self) -> &'static [&'static str] {
+        match self {
+            Self::ZoomIn => &["Ctrl+="],
+            Self::ZoomOut => &["Ctrl+-"],
+            Self::ZoomReset => &["Ctrl+0"],
+            Self::ToggleInvert => &["Ctrl+I"],
+            Self::ToggleLayout => &["Ctrl+B"],
+            Self::ToggleOutline => &["Ctrl+O"],
+            Self::CopyPage => &["Ctrl+Shift+C"],
+            Self::CopySourceLocation => &["Ctrl+Shift+L"],
+            Self::Present => &["F5"],
+            Self::NextPage => &["Right", "Down", "PageDown", "Space"],
+            Self::PreviousPage => &["Left", "Up", "PageUp"],
+            Self::ExitPresentation => &["Escape"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub control: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+/// A key with the modifiers held down, like `Ctrl+Shift+C`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    modifiers: Modifiers,
+    /// The text of the key press, lowercase for letters so combos with Shift match either way
+    key: String,
+}
+
+impl KeyCombo {
+    pub fn new(text: &str, modifiers: Modifiers) -> Self {
+        Self {
+            modifiers,
+            key: text.to_lowercase(),
+        }
+    }
+}
+
+/// Keys without text of their own, by the names they're configured with
+const NAMED_KEYS: &[(&str, Key)] = &[
+    ("Escape", Key::Escape),
+    ("Tab", Key::Tab),
+    ("Return", Key::Return),
+    ("Backspace", Key::Backspace),
+    ("Delete", Key::Delete),
+    ("Left", Key::LeftArrow),
+    ("Right", Key::RightArrow),
+    ("Up", Key::UpArrow),
+    ("Down", Key::DownArrow),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("F1", Key::F1),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+];
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(combo: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = combo.split('+').map(str::trim).collect();
+        // `Ctrl++` binds the plus key
+        if combo.trim_end().ends_with("++") {
+            parts.pop();
+            parts.pop();
+            parts.push("+");
+        }
+        let Some((key, modifier_names)) = parts.split_last() else {
+            return Err(format!("empty key combo {combo:?}"));
+        };
+
+        let mut modifiers = Modifiers::default();
+        for modifier in modifier_names {
+            let held = match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => &mut modifiers.control,
+                "shift" => &mut modifiers.shift,
+                "alt" | "option" => &mut modifiers.alt,
+                "meta" | "cmd" | "super" => &mut modifiers.meta,
+                _ => return Err(format!("unknown modifier {modifier:?} in {combo:?}")),
+            };
+            *held = true;
+        }
+
+        let key = match NAMED_KEYS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        {
+            Some((_, key)) => char::from(*key).to_string(),
+            None if key.eq_ignore_ascii_case("space") => " ".to_owned(),
+            None if key.chars().count() == 1 => key.to_string(),
+            None => return Err(format!("unknown key {key:?} in {combo:?}")),
+        };
+
+        Ok(Self::new(&key, modifiers))
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Modifiers {
+            control,
+            shift,
+            alt,
+            meta,
+        } = self.modifiers;
+        for (held, name) in [
+            (control, "Ctrl"),
+            (shift, "Shift"),
+            (alt, "Alt"),
+            (meta, "Meta"),
+        ] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        let name = NAMED_KEYS
+            .iter()
+            .find(|(_, key)| char::from(*key).to_string() == self.key)
+            .map(|(name, _)| name.to_string());
+        let name = match self.key.as_str() {
+            " " => "Space".to_owned(),
+            key => name.unwrap_or_else(|| key.to_uppercase()),
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Which action each key combo in the preview triggers, see [`Action`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keybindings {
+    actions: HashMap<KeyCombo, Action>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self::new(&BTreeMap::new())
+    }
+}
+
+impl Keybindings {
+    /// The default bindings, with those of the actions in the config replaced by the configured
+    /// combos. Unknown actions and invalid combos are skipped with a warning. A combo bound to
+    /// several actions keeps the first configured one, or else the configured one over the
+    /// default.
+    pub fn new(config: &BTreeMap<String, Vec<String>>) -> Self {
+        let mut configured: Vec<(Action, Vec<KeyCombo>)> = Vec::new();
+        for (name, combos) in config {
+            let Some(action) = Action::from_name(name) else {
+                warn!(
+                    action = name,
+                    "skipping keybinding for unknown preview action"
+                );
+                continue;
+            };
+            let combos = combos
+                .iter()
+                .filter_map(|combo| {
+                    combo
+                        .parse()
+                        .inspect_err(
+                            |err| warn!(%err, action = name, "skipping invalid keybinding"),
+                        )
+                        .ok()
+                })
+                .collect();
+            configured.push((action, combos));
+        }
+
+        let defaults: Vec<_> = Action::ALL
+            .into_iter()
+            .filter(|action| {
+                !configured
+                    .iter()
+                    .any(|(configured, _)| configured == action)
+            })
+            .map(|action| {
+                let combos = action
+                    .default_combos()
+                    .iter()
+                    .map(|combo| combo.parse().expect("default keybinding should be valid"))
+                    .collect();
+                (action, combos)
+            })
+            .collect();
+
+        let mut actions = HashMap::new();
+        for (action, combos) in configured.into_iter().chain(defaults) {
+            for combo in combos {
+                match actions.get(&combo) {
+                    Some(bound) => warn!(
+                        %combo,
+                        bound = Action::name(*bound),
+                        action = action.name(),
+                        "key is bound to several preview actions, keeping the first"
+                    ),
+                    None => {
+                        actions.insert(combo, action);
+                    }
+                }
+            }
+        }
+
+        Self { actions }
+    }
+
+    /// The action bound to the key press, by the text of the key
+    pub fn action(&self, text: &str, modifiers: Modifiers) -> Option<Action> {
+        self.actions.get(&KeyCombo::new(text, modifiers)).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn configured_combos_replace_defaults() {
+        let ctrl = Modifiers {
+            control: true,
+            ..Default::default()
+        };
+        let config = BTreeMap::from([
+            ("zoom-in".to_owned(), vec!["Ctrl+K".to_owned()]),
+            ("toggle-layout".to_owned(), vec!["Ctrl+Shift+L".to_owned()]),
+            ("present".to_owned(), vec!["Ctrl+".to_owned()]),
+        ]);
+        let keybindings = Keybindings::new(&config);
+
+        assert_eq!(keybindings.action("k", ctrl), Some(Action::ZoomIn));
+        assert_eq!(keybindings.action("=", ctrl), None);
+        assert_eq!(keybindings.action("-", ctrl), Some(Action::ZoomOut));
+        let ctrl_shift = Modifiers {
+            shift: true,
+            ..ctrl
+        };
+        assert_eq!(
+            keybindings.action("L", ctrl_shift),
+            Some(Action::ToggleLayout)
+        );
+        // The invalid combo leaves the action without keys
+        assert_eq!(
+            keybindings.action(&char::from(Key::F5).to_string(), Modifiers::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_combos() {
+        let combo: KeyCombo = "ctrl+shift+c".parse().unwrap();
+        assert_eq!(combo.to_string(), "Ctrl+Shift+C");
+        assert_eq!("Cmd++".parse::<KeyCombo>().unwrap().to_string(), "Meta++");
+        assert_eq!(
+            "PageDown".parse::<KeyCombo>().unwrap().to_string(),
+            "PageDown"
+        );
+        assert!("Hyper+X".parse::<KeyCombo>().is_err());
+        assert!("Ctrl+Enter2".parse::<KeyCombo>().is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+use tower_lsp::lsp_types::{Location, Url};
+use tracing::warn;
+use typst::syntax::{LinkedNode, Source};
+
+use crate::config::PositionEncoding;
+use crate::lsp_typst_boundary::typst_to_lsp;
+
+use super::lint::{label_use, walk, LabelUse};
+use super::TypstServer;
+
+/// A label attached to an element in markup, like `= Intro <intro>`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelDefinition {
+    pub name: String,
+    pub location: Location,
+    /// How often the label is referenced, by `@name`, `<name>` in code or `label("name")`
+    pub references: usize,
+    /// Whether another file or element in the workspace has a label with the same name, which
+    /// Typst errors on when both end up in one document and the label is referenced
+    pub duplicate: bool,
+}
+
+impl TypstServer {
+    /// Every label defined in the Typst files of the workspace, sorted by name
+    pub async fn list_labels(&self) -> Vec<LabelDefinition> {
+        let position_encoding = self.const_config().position_encoding;
+        let workspace = self.read_workspace().await;
+
+        let sources: Vec<_> = workspace
+            .known_uris()
+            .into_iter()
+            .filter(|uri| uri.path().ends_with(".typ"))
+            .filter_map(|uri| match workspace.read_source(&uri) {
+                Ok(source) => Some((uri, source)),
+                Err(err) => {
+                    warn!(%err, %uri, "could not read source to list labels");
+                    None
+                }
+            })
+            .collect();
+
+        label_definitions(&sources, position_encoding)
+    }
+}
+
+/// The labels defined in the sources, with the references to them from all sources
+fn label_definitions(
+    sources: &[(Url, Source)],
+    position_encoding: PositionEncoding,
+) -> Vec<LabelDefinition> {
+    let mut defined = Vec::new();
+    let mut references: HashMap<String, usize> = HashMap::new();
+
+    for (uri, source) in sources {
+        walk(
+            &LinkedNode::new(source.root()),
+            &mut |node| match label_use(node) {
+                Some(LabelUse::Definition(name)) => {
+                    let range = typst_to_lsp::range(node.range(), source, position_encoding);
+                    defined.push((name, Location::new(uri.clone(), range.raw_range)));
+                }
+                Some(LabelUse::Reference(name)) => *references.entry(name).or_default() += 1,
+                None => {}
+            },
+        );
+    }
+
+    let counts = defined.iter().counts_by(|(name, _)| name.clone());
+    defined
+        .into_iter()
+        .sorted_by(|(a, a_location), (b, b_location)| {
+            (a, a_location.uri.as_str(), a_location.range.start).cmp(&(
+                b,
+                b_location.uri.as_str(),
+                b_location.range.start,
+            ))
+        })
+        .map(|(name, location)| LabelDefinition {
+            references: references.get(&name).copied().unwrap_or_default(),
+            duplicate: counts[&name] > 1,
+            name,
+            location,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_references_and_flags_duplicates() {
+        let main = Url::parse("file:///project/main.typ").unwrap();
+        let chapter = Url::parse("file:///project/chapter.typ").unwrap();
+        let sources = [
+            (
+                main.clone(),
+                Source::detached("= Intro <intro>\nSee @intro and @fig.\n#figure[] <fig>"),
+            ),
+            (
+                chapter.clone(),
+                Source::detached("#figure[] <fig>\n#query(<intro>)"),
+            ),
+        ];
+
+        let labels: Vec<_> = label_definitions(&sources, PositionEncoding::Utf16)
+            .into_iter()
+            .map(|label| {
+                (
+                    label.name,
+                    label.location.uri,
+                    label.references,
+                    label.duplicate,
+                )
+            })
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                ("fig".to_owned(), chapter, 1, true),
+                ("fig".to_owned(), main.clone(), 1, true),
+                ("intro".to_owned(), main, 2, false),
+            ]
+        );
+    }
+}
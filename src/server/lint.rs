@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, DiagnosticTag};
+use typst::syntax::ast::{self, AstNode};
+use typst::syntax::{FileId, LinkedNode, Source, SyntaxKind};
+use typst::World;
+
+use crate::config::ConstConfig;
+use crate::lsp_typst_boundary::typst_to_lsp;
+use crate::workspace::project::Project;
+use crate::workspace::world::ProjectWorld;
+
+use super::diagnostics::DiagnosticsMap;
+
+/// A label or import which is never used
+#[derive(Debug, Clone)]
+pub struct Unused {
+    pub id: FileId,
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+/// Finds labels which no source references and imported names which the importing source doesn't
+/// use. Both checks are approximate: they go by name, so shadowing or building labels dynamically
+/// can hide unused ones, but used ones are never flagged.
+pub fn unused(sources: &[Source]) -> Vec<Unused> {
+    let mut defined_labels = Vec::new();
+    let mut used_labels = HashSet::new();
+    let mut unused = Vec::new();
+
+    for source in sources {
+        let root = LinkedNode::new(source.root());
+
+        walk(&root, &mut |node| match label_use(node) {
+            Some(LabelUse::Definition(name)) => {
+                defined_labels.push((source.id(), node.range(), name));
+            }
+            Some(LabelUse::Reference(name)) => {
+                used_labels.insert(name);
+            }
+            None => {}
+        });
+
+        unused.extend(unused_imports(source, &root));
+    }
+
+    unused.extend(
+        defined_labels
+            .into_iter()
+            .filter(|(_, _, name)| !used_labels.contains(name))
+            .map(|(id, range, name)| Unused {
+                id,
+                range,
+                message: format!("label <{name}> is never referenced"),
+            }),
+    );
+    unused
+}
+
+/// The Typst sources of the current package which the world read, starting with the main source
+pub fn project_sources(world: &ProjectWorld) -> Vec<Source> {
+    let main = world.main();
+    let accessed = world.accessed_files().into_iter().filter(|&id| {
+        id != main.id()
+            && id.package().is_none()
+            && id.vpath().as_rootless_path().extension() == Some(OsStr::new("typ"))
+    });
+
+    std::iter::once(main.clone())
+        .chain(accessed.filter_map(|id| world.source(id).ok()))
+        .collect()
+}
+
+/// Calls `f` on the node and then on each of its descendants, depth first
+pub fn walk(node: &LinkedNode, f: &mut impl FnMut(&LinkedNode)) {
+    f(node);
+    for child in node.children() {
+        walk(&child, f);
+    }
+}
+
+/// Converts the findings to hint diagnostics which editors show as faded out
+pub async fn unused_diagnostics(
+    project: &Project,
+    sources: &[Source],
+    unused: Vec<Unused>,
+    const_config: &ConstConfig,
+) -> DiagnosticsMap {
+    let mut diagnostics = DiagnosticsMap::new();
+
+    for unused in unused {
+        let Some(source) = sources.iter().find(|source| source.id() == unused.id) else {
+            continue;
+        };
+        let Ok(uri) = project.full_id_to_uri(project.fill_id(unused.id)).await else {
+            continue;
+        };
+
+        let range = typst_to_lsp::range(unused.range, source, const_config.position_encoding);
+        diagnostics.entry(uri).or_default().push(Diagnostic {
+            range: range.raw_range,
+            severity: Some(DiagnosticSeverity::HINT),
+            source: Some("typst-lsp".to_owned()),
+            message: unused.message,
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            ..Default::default()
+        });
+    }
+
+    diagnostics
+}
+
+/// Finds names imported into the source which are never mentioned after being imported
+fn unused_imports(source: &Source, root: &LinkedNode) -> Vec<Unused> {
+    let mut bindings = Vec::new();
+    walk(root, &mut |node| {
+        let Some(ast::Imports::Items(items)) = node
+            .cast::<ast::ModuleImport>()
+            .and_then(|import| import.imports())
+        else {
+            return;
+        };
+
+        for item in items.iter() {
+            let ident = match item {
+                ast::ImportItem::Simple(ident) => ident,
+                ast::ImportItem::Renamed(renamed) => renamed.new_name(),
+            };
+            if let Some(binding) = node.find(ident.span()) {
+                bindings.push((binding.range(), ident.get().to_owned()));
+            }
+        }
+    });
+
+    let binding_ranges: HashSet<_> = bindings.iter().map(|(range, _)| range.clone()).collect();
+    let mut used = HashSet::new();
+    walk(root, &mut |node| {
+        if !matches!(node.kind(), SyntaxKind::Ident | SyntaxKind::MathIdent)
+            || binding_ranges.contains(&node.range())
+        {
+            return;
+        }
+        used.insert(node.text().to_owned());
+    });
+
+    bindings
+        .into_iter()
+        .filter(|(_, name)| !used.contains(name))
+        .map(|(range, name)| Unused {
+            id: source.id(),
+            range,
+            message: format!("`{name}` is imported but never used"),
+        })
+        .collect()
+}
+
+/// How a node mentions a label
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelUse {
+    /// A label in markup, attaching it to an element
+    Definition(String),
+    /// `@name`, a label in code or `label("name")`
+    Reference(String),
+}
+
+/// Whether the node defines or references a label, and its name
+pub fn label_use(node: &LinkedNode) -> Option<LabelUse> {
+    if let Some(label) = node.cast::<ast::Label>() {
+        // Labels in markup attach to elements, while labels in code refer to them, e.g. in
+        // `query(<label>)`
+        let name = label.get().to_owned();
+        if node.parent_kind() == Some(SyntaxKind::Markup) {
+            Some(LabelUse::Definition(name))
+        } else {
+            Some(LabelUse::Reference(name))
+        }
+    } else if let Some(reference) = node.cast::<ast::Ref>() {
+        Some(LabelUse::Reference(reference.target().to_owned()))
+    } else {
+        label_call(node).map(LabelUse::Reference)
+    }
+}
+
+/// The label named in a call like `label("name")`
+pub fn label_call(node: &LinkedNode) -> Option<String> {
+    let call = node.cast::<ast::FuncCall>()?;
+    let ast::Expr::Ident(callee) = call.callee() else {
+        return None;
+    };
+    if callee.as_str() != "label" {
+        return None;
+    }
+
+    call.args().items().find_map(|arg| match arg {
+        ast::Arg::Pos(ast::Expr::Str(name)) => Some(name.get().to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn messages(text: &str) -> Vec<String> {
+        let mut messages: Vec<_> = unused(&[Source::detached(text)])
+            .into_iter()
+            .map(|unused| unused.message)
+            .collect();
+        messages.sort();
+        messages
+    }
+
+    #[test]
+    fn flags_unreferenced_labels() {
+        let text = "= Intro <intro>\n= Outro <outro>\nSee @intro.";
+        assert_eq!(messages(text), ["label <outro> is never referenced"]);
+    }
+
+    #[test]
+    fn flags_unused_imports() {
+        let text = "#import \"lib.typ\": used, unused, other as renamed\n#used()";
+        assert_eq!(
+            messages(text),
+            [
+                "`renamed` is imported but never used",
+                "`unused` is imported but never used",
+            ]
+        );
+    }
+}
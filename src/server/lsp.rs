@@ -8,12 +8,12 @@ use serde_json::Value as JsonValue;
 use tokio::sync::RwLock;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{jsonrpc, LanguageServer};
-use tracing::{error, info, trace, warn};
+use tracing::{error, info, trace};
 use typst::World;
 
 use crate::config::{
-    get_config_registration, Config, ConstConfig, ExperimentalFormatterMode, ExportPdfMode,
-    SemanticTokensMode,
+    get_config_registration, Config, ConstConfig, DiagnosticsMode, ExperimentalFormatterMode,
+    ExportPdfMode, SemanticTokensMode,
 };
 use crate::ext::InitializeParamsExt;
 use crate::lsp_typst_boundary::typst_to_lsp::offset_to_position;
@@ -21,15 +21,18 @@ use crate::lsp_typst_boundary::{lsp_to_typst, typst_to_lsp, LspRawRange};
 use crate::server::formatting::{get_formatting_registration, get_formatting_unregistration};
 use crate::workspace::Workspace;
 
+use super::code_lens::code_lenses;
 use super::command::LspCommand;
+use super::on_type_formatting::get_on_type_formatting_options;
 use super::semantic_tokens::{
     get_semantic_tokens_options, get_semantic_tokens_registration,
     get_semantic_tokens_unregistration,
 };
-use super::TypstServer;
+use super::snippets;
+use super::SharedServer;
 
 #[async_trait]
-impl LanguageServer for TypstServer {
+impl LanguageServer for SharedServer {
     #[tracing::instrument(skip(self))]
     async fn initialize(&self, params: InitializeParams) -> jsonrpc::Result<InitializeResult> {
         self.tracing_init();
@@ -42,6 +45,9 @@ impl LanguageServer for TypstServer {
         self.const_config
             .set(ConstConfig::from(&params))
             .expect("const config should not yet be initialized");
+        self.const_config()
+            .validate_features()
+            .map_err(|err| jsonrpc::Error::invalid_params(err.to_string()))?;
 
         if let Some(init) = &params.initialization_options {
             let mut config = self.config.write().await;
@@ -52,6 +58,7 @@ impl LanguageServer for TypstServer {
                 .map_err(ToString::to_string)
                 .map_err(jsonrpc::Error::invalid_params)?;
         }
+        self.update_inputs().await;
 
         if let Err(err) = self.register_workspace_files().await {
             error!(%err, "could not register workspace files on init");
@@ -69,6 +76,15 @@ impl LanguageServer for TypstServer {
             _ => None,
         };
 
+        let diagnostic_provider = params.supports_pull_diagnostics().then(|| {
+            DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                identifier: Some("typst".to_owned()),
+                inter_file_dependencies: true,
+                workspace_diagnostics: true,
+                ..Default::default()
+            })
+        });
+
         let document_formatting_provider = match config.formatter {
             ExperimentalFormatterMode::On
                 if !params.supports_document_formatting_dynamic_registration() =>
@@ -122,6 +138,14 @@ impl LanguageServer for TypstServer {
                     ..Default::default()
                 }),
                 document_formatting_provider,
+                document_on_type_formatting_provider: Some(get_on_type_formatting_options()),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(true),
+                }),
+                diagnostic_provider,
                 ..Default::default()
             },
             ..Default::default()
@@ -212,6 +236,9 @@ impl LanguageServer for TypstServer {
             }));
         }
 
+        let preload_packages = config.preload_packages.clone();
+        drop(config);
+
         if const_config.supports_config_change_registration {
             trace!("setting up to request config change notifications");
 
@@ -236,6 +263,12 @@ impl LanguageServer for TypstServer {
         }
 
         info!("server initialized");
+
+        if let Some(main_uri) = self.main_url().await {
+            self.open_preview_on_start(&main_uri).await;
+        }
+
+        self.preload_packages(&preload_packages);
     }
 
     #[tracing::instrument(skip_all)]
@@ -257,9 +290,9 @@ impl LanguageServer for TypstServer {
 
         drop(workspace);
 
-        if let Err(err) = self.on_source_changed(&uri, None).await {
-            error!(%err, %uri, "could not handle source change");
-        };
+        self.on_source_changed(&uri, None).await;
+
+        self.open_preview_on_start(&uri).await;
     }
 
     #[tracing::instrument(skip_all, fields(uri = %params.text_document.uri))]
@@ -285,27 +318,43 @@ impl LanguageServer for TypstServer {
 
         drop(workspace);
 
-        if let Err(err) = self.on_source_changed(&uri, first_change_range).await {
-            error!(%err, %uri, "could not handle source change");
-        };
+        self.update_symbol_index(&uri).await;
+
+        self.on_source_changed(&uri, first_change_range).await;
     }
 
     #[tracing::instrument(skip_all, fields(uri = %params.text_document.uri))]
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
         let uri = params.text_document.uri;
 
+        self.update_symbol_index(&uri).await;
+
         let config = self.config.read().await;
+        let diagnostics = config.diagnostics;
 
-        let uri = match config.export_pdf {
-            ExportPdfMode::OnPinnedMainSave => Some(self.main_url().await.unwrap_or(uri)),
-            ExportPdfMode::OnSave => Some(uri),
+        let export_uri = match config.export_pdf {
+            ExportPdfMode::OnPinnedMainSave => Some(self.main_url().await.unwrap_or(uri.clone())),
+            ExportPdfMode::OnSave => Some(uri.clone()),
             _ => None,
         };
-        let Some(uri) = uri else {
-            return;
-        };
 
-        if let Err(err) = self.run_diagnostics_and_export(&uri, None).await {
+        let saved = match export_uri {
+            Some(export_uri) => {
+                self.run_diagnostics_and_export(
+                    &export_uri,
+                    None,
+                    diagnostics != DiagnosticsMode::Off,
+                )
+                .await
+            }
+            // With diagnostics on, they were already reported as the document changed
+            None if diagnostics == DiagnosticsMode::OnSave => {
+                let root = self.compilation_root(&uri).await;
+                self.run_diagnostics(&root).await
+            }
+            None => return,
+        };
+        if let Err(err) = saved {
             error!(%err, %uri, "could not handle source save");
         };
     }
@@ -316,9 +365,21 @@ impl LanguageServer for TypstServer {
 
         let mut workspace = self.workspace().write().await;
 
+        let uris = changes
+            .iter()
+            .map(|change| change.uri.clone())
+            .collect_vec();
         for change in changes {
             self.handle_file_change_event(&mut workspace, change);
         }
+
+        drop(workspace);
+
+        for uri in &uris {
+            self.update_symbol_index(uri).await;
+        }
+
+        self.assets_changed(&uris).await;
     }
 
     #[tracing::instrument(skip(self))]
@@ -345,22 +406,95 @@ impl LanguageServer for TypstServer {
             arguments,
             work_done_progress_params: _,
         } = params;
-        match LspCommand::parse(&command) {
+        let response = match LspCommand::parse(&command) {
             Some(LspCommand::ExportPdf) => {
                 self.command_export_pdf(arguments).await?;
+                None
             }
             Some(LspCommand::ClearCache) => {
                 self.command_clear_cache(arguments).await?;
+                None
             }
             Some(LspCommand::PinMain) => {
                 self.command_pin_main(arguments).await?;
+                None
+            }
+            Some(LspCommand::ExportPageSvg) => Some(self.command_export_page_svg(arguments).await?),
+            Some(LspCommand::UsedFonts) => Some(self.command_used_fonts(arguments).await?),
+            Some(LspCommand::SourceTree) => Some(self.command_source_tree(arguments).await?),
+            Some(LspCommand::DebugWorld) => Some(self.command_debug_world(arguments).await?),
+            Some(LspCommand::ExportSlides) => Some(self.command_export_slides(arguments).await?),
+            Some(LspCommand::LockPackages) => Some(self.command_lock_packages(arguments).await?),
+            Some(LspCommand::CreateFile) => {
+                self.command_create_file(arguments).await?;
+                None
+            }
+            Some(LspCommand::DownloadPackage) => {
+                self.command_download_package(arguments).await?;
+                None
+            }
+            Some(LspCommand::OpenPdf) => {
+                self.command_open_pdf(arguments).await?;
+                None
+            }
+            Some(LspCommand::CheckReferences) => {
+                Some(self.command_check_references(arguments).await?)
+            }
+            Some(LspCommand::WatchExport) => Some(self.command_watch_export(arguments).await?),
+            Some(LspCommand::UnwatchExport) => {
+                self.command_unwatch_export(arguments).await?;
+                None
+            }
+            Some(LspCommand::ShowConfig) => Some(self.command_show_config().await?),
+            Some(LspCommand::ExportAll) => Some(self.command_export_all().await?),
+            Some(LspCommand::RenderPreviewImage) => {
+                Some(self.command_render_preview_image(arguments).await?)
+            }
+            Some(LspCommand::ExportOutline) => Some(self.command_export_outline(arguments).await?),
+            Some(LspCommand::ElementAt) => Some(self.command_element_at(arguments).await?),
+            Some(LspCommand::ExportSelection) => {
+                Some(self.command_export_selection(arguments).await?)
+            }
+            Some(LspCommand::ExportPageSize) => {
+                Some(self.command_export_page_size(arguments).await?)
             }
+            Some(LspCommand::MissingFiles) => Some(self.command_missing_files(arguments).await?),
+            Some(LspCommand::ParseTree) => Some(self.command_parse_tree(arguments).await?),
+            Some(LspCommand::OpenDeepLink) => Some(self.command_open_deep_link(arguments).await?),
+            Some(LspCommand::Benchmark) => Some(self.command_benchmark(arguments).await?),
+            Some(LspCommand::OpenPreview) => {
+                self.command_open_preview(arguments).await?;
+                None
+            }
+            Some(LspCommand::ExportPixels) => Some(self.command_export_pixels(arguments).await?),
+            Some(LspCommand::Query) => Some(self.command_query(arguments).await?),
+            Some(LspCommand::DiffExport) => Some(self.command_diff_export(arguments).await?),
+            Some(LspCommand::RevealPdf) => {
+                self.command_reveal_pdf(arguments).await?;
+                None
+            }
+            Some(LspCommand::ExportWithInputs) => {
+                Some(self.command_export_with_inputs(arguments).await?)
+            }
+            Some(LspCommand::Doctor) => Some(self.command_doctor(arguments).await?),
+            Some(LspCommand::ExportDiagnostics) => {
+                Some(self.command_export_diagnostics(arguments).await?)
+            }
+            Some(LspCommand::RenderRegion) => Some(self.command_render_region(arguments).await?),
+            Some(LspCommand::UseProfile) => Some(self.command_use_profile(arguments).await?),
+            Some(LspCommand::ListLabels) => Some(self.command_list_labels().await?),
+            Some(LspCommand::TogglePreview) => Some(self.command_toggle_preview(arguments).await?),
+            Some(LspCommand::Compare) => Some(self.command_compare(arguments).await?),
+            Some(LspCommand::CheckBibliography) => {
+                Some(self.command_check_bibliography(arguments).await?)
+            }
+            Some(LspCommand::WatchAssets) => Some(self.command_watch_assets(arguments).await?),
             None => {
                 error!("asked to execute unknown command");
                 return Err(jsonrpc::Error::method_not_found());
             }
         };
-        Ok(None)
+        Ok(response)
     }
 
     #[tracing::instrument(
@@ -409,11 +543,12 @@ impl LanguageServer for TypstServer {
 
         let position_encoding = self.const_config().position_encoding;
         let doc = { self.document.lock().await.clone() };
+        let snippets_file = self.config.read().await.snippets_file.clone();
         let fid = self.workspace().read().await.full_id(&uri).map_err(|err| {
             error!(%err, %uri, "error getting completion");
             jsonrpc::Error::internal_error()
         })?;
-        let completions = self
+        let (completions, snippet_context) = self
             .thread_with_world(self.main_url().await.as_ref().unwrap_or(&uri))
             .await
             .map_err(|err| {
@@ -421,23 +556,45 @@ impl LanguageServer for TypstServer {
                 jsonrpc::Error::internal_error()
             })?
             .run(move |world| {
-                let source = world.source(fid.into()).ok()?;
+                let Ok(source) = world.source(fid.into()) else {
+                    return (None, None);
+                };
 
                 let typst_offset =
                     lsp_to_typst::position_to_offset(position, position_encoding, &source);
-                let (typst_start_offset, completions) =
-                    typst_ide::autocomplete(&world, Some(&doc), &source, typst_offset, explicit)?;
-                let lsp_start_position =
-                    offset_to_position(typst_start_offset, position_encoding, &source);
-
-                Some((lsp_start_position, completions))
+                let completions =
+                    typst_ide::autocomplete(&world, Some(&doc), &source, typst_offset, explicit)
+                        .map(|(typst_start_offset, completions)| {
+                            let lsp_start_position =
+                                offset_to_position(typst_start_offset, position_encoding, &source);
+                            (lsp_start_position, completions)
+                        });
+                let snippet_context =
+                    snippets::snippet_context(&source, typst_offset).map(|(mode, start)| {
+                        (mode, offset_to_position(start, position_encoding, &source))
+                    });
+
+                (completions, snippet_context)
             })
             .await
+            .map_err(|err| {
+                error!(%err, %uri, "error getting completion");
+                jsonrpc::Error::internal_error()
+            })?;
+
+        let mut items = completions
             .map(|(start_position, completions)| {
                 let replace_range = LspRawRange::new(start_position, position);
-                typst_to_lsp::completions(&completions, replace_range).into()
-            });
-        Ok(completions)
+                typst_to_lsp::completions(&completions, replace_range)
+            })
+            .unwrap_or_default();
+        if let Some((mode, start_position)) = snippet_context {
+            let snippets = snippets::snippets(snippets_file.as_deref());
+            let replace_range = LspRawRange::new(start_position, position);
+            items.extend(snippets::completions(&snippets, mode, replace_range));
+        }
+
+        Ok((!items.is_empty()).then(|| items.into()))
     }
 
     #[tracing::instrument(
@@ -490,38 +647,11 @@ impl LanguageServer for TypstServer {
         &self,
         params: WorkspaceSymbolParams,
     ) -> jsonrpc::Result<Option<Vec<SymbolInformation>>> {
-        let handle_read_err = |err| warn!(%err, "could not read source");
-        let handle_symbol_err = |err| {
-            error!(%err, "failed to get document symbols");
-            jsonrpc::Error::internal_error()
-        };
-
-        let query = (!params.query.is_empty()).then_some(params.query.as_str());
-
-        let workspace = self.read_workspace().await;
-
-        let uris = workspace.known_uris();
-
-        trace!(?uris, "getting sources for these URIs");
-
-        let uris_sources = uris
-            .into_iter()
-            .map(|uri| workspace.read_source(&uri).map(|source| (uri, source)))
-            .map(|result| result.map_err(handle_read_err))
-            .filter_map(Result::ok)
-            .collect_vec();
-
-        trace!(?uris_sources, "getting symbols for these sources");
-
-        let symbols = uris_sources
-            .iter()
-            .flat_map(|(uri, source)| self.document_symbols(source, uri, query))
-            .try_collect()
-            .map_err(handle_symbol_err);
+        let symbols = self.workspace_symbols(&params.query).await;
 
         trace!(?symbols, "got symbols");
 
-        Some(symbols).transpose()
+        Ok(Some(symbols))
     }
 
     #[tracing::instrument(skip_all, fields(uri = %params.text_document.uri))]
@@ -596,6 +726,7 @@ impl LanguageServer for TypstServer {
                 .map(Config::values_to_map),
         };
 
+        let old_diagnostics = self.config.read().await.diagnostics;
         let result = match values {
             Ok(values) => {
                 let mut config = self.config.write().await;
@@ -607,6 +738,8 @@ impl LanguageServer for TypstServer {
         match result {
             Ok(()) => {
                 info!("new settings applied");
+                self.update_inputs().await;
+                self.diagnostics_mode_changed(old_diagnostics).await;
             }
             Err(err) => {
                 error!(%err, "error applying new settings");
@@ -634,6 +767,129 @@ impl LanguageServer for TypstServer {
         Ok(selection_range)
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> jsonrpc::Result<Option<Vec<DocumentHighlight>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        self.document_highlights(&uri, position)
+            .await
+            .map_err(|err| {
+                error!(%err, %uri, "error getting document highlights");
+                jsonrpc::Error::internal_error()
+            })
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        self.0
+            .prepare_call_hierarchy(&uri, position)
+            .await
+            .map_err(|err| {
+                error!(%err, %uri, "error preparing call hierarchy");
+                jsonrpc::Error::internal_error()
+            })
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        self.0.incoming_calls(&params.item).await.map_err(|err| {
+            error!(%err, "error getting incoming calls");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        self.0.outgoing_calls(&params.item).await.map_err(|err| {
+            error!(%err, "error getting outgoing calls");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> jsonrpc::Result<Option<Vec<CodeLens>>> {
+        Ok(Some(code_lenses(&params.text_document.uri)))
+    }
+
+    async fn code_lens_resolve(&self, params: CodeLens) -> jsonrpc::Result<CodeLens> {
+        self.resolve_code_lens(params).await.map_err(|err| {
+            error!(%err, "error resolving code lens");
+            jsonrpc::Error::internal_error()
+        })
+    }
+
+    async fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> jsonrpc::Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+
+        let actions = self.code_actions(&uri, params.range).await.map_err(|err| {
+            error!(%err, %uri, "error getting code actions");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        Ok(Some(actions))
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        fields(uri = %params.text_document_position.text_document.uri, ch = params.ch)
+    )]
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        self.format_on_type(&uri, position, &params.ch)
+            .await
+            .map_err(|err| {
+                error!(%err, %uri, "error formatting on type");
+                jsonrpc::Error::internal_error()
+            })
+    }
+
+    #[tracing::instrument(skip_all, fields(uri = %params.text_document.uri))]
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> jsonrpc::Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+
+        let report = self.pull_diagnostics(&uri).await.map_err(|err| {
+            error!(%err, %uri, "error getting diagnostics");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        Ok(DocumentDiagnosticReportResult::Report(report))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn workspace_diagnostic(
+        &self,
+        _params: WorkspaceDiagnosticParams,
+    ) -> jsonrpc::Result<WorkspaceDiagnosticReportResult> {
+        let report = self.pull_workspace_diagnostics().await.map_err(|err| {
+            error!(%err, "error getting workspace diagnostics");
+            jsonrpc::Error::internal_error()
+        })?;
+
+        Ok(WorkspaceDiagnosticReportResult::Report(report))
+    }
+
     async fn formatting(
         &self,
         params: DocumentFormattingParams,
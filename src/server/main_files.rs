@@ -0,0 +1,170 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+use tower_lsp::lsp_types::Url;
+use tracing::{info, warn};
+use typst::syntax::FileId;
+
+use crate::workspace::project::Project;
+
+use super::export::{export_document, ExportFormat, PdfOptions};
+use super::TypstServer;
+
+/// The files each main file included in its last compilation, to find the main file to compile
+/// when one of them is edited, and the documents to compile when a watched asset changes
+#[derive(Debug, Default)]
+pub struct IncludedFiles {
+    files: HashMap<Url, HashSet<Url>>,
+}
+
+impl IncludedFiles {
+    fn main_including(&self, uri: &Url) -> Option<Url> {
+        self.files
+            .iter()
+            .filter(|(_, included)| included.contains(uri))
+            .map(|(main, _)| main.clone())
+            .min()
+    }
+
+    /// Every compiled document which read the file, in a stable order
+    pub(super) fn mains_reading(&self, uri: &Url) -> Vec<Url> {
+        let mut mains: Vec<_> = self
+            .files
+            .iter()
+            .filter(|(_, included)| included.contains(uri))
+            .map(|(main, _)| main.clone())
+            .collect();
+        mains.sort();
+        mains
+    }
+
+    /// The files the document read when it was last compiled
+    pub(super) fn read_by(&self, main: &Url) -> Vec<Url> {
+        self.files
+            .get(main)
+            .map(|included| included.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl TypstServer {
+    /// The files in the workspace matching the `mainFiles` globs, in a stable order
+    pub async fn main_files(&self) -> Vec<Url> {
+        let Some(globs) = self.config.read().await.main_files_globs() else {
+            return Vec::new();
+        };
+
+        let workspace = self.read_workspace().await;
+        let mut main_files: Vec<_> = workspace
+            .known_uris()
+            .into_iter()
+            .filter(|uri| {
+                workspace.full_id(uri).is_ok_and(|full_id| {
+                    full_id.package().spec().is_none()
+                        && globs.is_match(full_id.vpath().as_rootless_path())
+                })
+            })
+            .collect();
+        main_files.sort();
+
+        main_files
+    }
+
+    /// The file to compile for changes in the given one: the pinned main file, else the main file
+    /// from `mainFiles` which includes it, else the file itself. Main files which were never
+    /// compiled are compiled here to learn which files they include.
+    pub async fn compilation_root(&self, uri: &Url) -> Url {
+        if let Some(main) = self.main_url().await {
+            return main;
+        }
+
+        let main_files = self.main_files().await;
+        if main_files.is_empty() || main_files.contains(uri) {
+            return uri.clone();
+        }
+
+        if let Some(main) = self.included_files.lock().main_including(uri) {
+            return main;
+        }
+
+        for main in main_files {
+            if self.included_files.lock().files.contains_key(&main) {
+                continue;
+            }
+            if let Err(err) = self.compile_source(&main).await {
+                warn!(%err, %main, "could not compile main file to find included files");
+            }
+            if let Some(main) = self.included_files.lock().main_including(uri) {
+                return main;
+            }
+        }
+
+        uri.clone()
+    }
+
+    pub(super) async fn record_included_files(
+        &self,
+        main: &Url,
+        project: &Project,
+        accessed: Vec<FileId>,
+    ) {
+        let mut included = HashSet::with_capacity(accessed.len());
+        for id in accessed {
+            if let Ok(uri) = project.full_id_to_uri(project.fill_id(id)).await {
+                included.insert(uri);
+            }
+        }
+
+        self.included_files
+            .lock()
+            .files
+            .insert(main.clone(), included);
+    }
+
+    /// Exports every main file from `mainFiles` as a PDF, or in the format of the profile or from
+    /// its export directives, or the pinned main file if there is one. Returns the written files.
+    pub async fn export_all(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let main_files = match self.main_url().await {
+            Some(main) => vec![main],
+            None => self.main_files().await,
+        };
+        if main_files.is_empty() {
+            bail!("no main file is pinned and no file matches `mainFiles`");
+        }
+
+        let mut written = Vec::new();
+        for uri in main_files {
+            let source_path = uri
+                .to_file_path()
+                .map_err(|()| anyhow!("only local files can be exported"))?;
+
+            let (document, _) = self.compile_source(&uri).await?;
+            let Some(document) = document else {
+                warn!(%uri, "skipping main file which failed to compile");
+                continue;
+            };
+
+            let directives = self.export_directives(&uri).await;
+            let (profile_format, profile_pixel_per_pt) = {
+                let config = self.config.read().await;
+                (config.export_format(), config.export_pixel_per_pt())
+            };
+            let format = directives.format_over_profile(None, profile_format);
+            let pdf_options = match format {
+                ExportFormat::Pdf => self.pdf_options(&uri).await?,
+                _ => PdfOptions::default(),
+            };
+            let files = tokio::task::spawn_blocking(move || {
+                let pixel_per_pt =
+                    profile_pixel_per_pt.unwrap_or_else(|| directives.pixel_per_pt());
+                export_document(&document, &source_path, format, pixel_per_pt, &pdf_options)
+            })
+            .await??;
+            written.extend(files);
+        }
+        info!(files = written.len(), "exported all main files");
+
+        Ok(written)
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use comemo::Prehashed;
+use siphasher::sip128::{Hasher128, SipHasher13};
+use typst::diag::{EcoString, FileResult};
+use typst::eval::Tracer;
+use typst::foundations::{Bytes, Datetime};
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{ast, FileId, LinkedNode, Source, SyntaxKind};
+use typst::text::{Font, FontBook};
+use typst::visualize::Color;
+use typst::{Library, World};
+
+use crate::workspace::world::ProjectWorld;
+
+/// Rendered equations are small, but don't keep an unbounded number of them around
+const CACHE_LIMIT: usize = 128;
+
+/// Pixels per point when rendering an equation. Hovers are small, so this only needs to be sharp,
+/// not large.
+const PIXEL_PER_PT: f32 = 2.0;
+
+/// Styles the snippet so the page hugs the equation
+const PREAMBLE: &str = "#set page(width: auto, height: auto, margin: 0.25em)\n";
+
+/// Equation previews by hash of the equation's source text. Equations which failed to render are
+/// cached as `None`, so they aren't retried on every hover.
+#[derive(Debug, Default)]
+pub struct MathHoverCache {
+    images: parking_lot::Mutex<HashMap<u128, Option<String>>>,
+}
+
+impl MathHoverCache {
+    fn get_or_render(&self, text: &str, render: impl FnOnce() -> Option<String>) -> Option<String> {
+        let hash = hash(text);
+        if let Some(image) = self.images.lock().get(&hash) {
+            return image.clone();
+        }
+
+        let image = render();
+
+        let mut images = self.images.lock();
+        if images.len() >= CACHE_LIMIT {
+            images.clear();
+        }
+        images.insert(hash, image.clone());
+        image
+    }
+}
+
+/// An equation preview for the hover at the given offset, along with the range of the equation.
+/// Works when hovering the equation itself or a label or reference naming it.
+pub fn math_hover(
+    world: &ProjectWorld,
+    cache: &MathHoverCache,
+    source: &Source,
+    offset: usize,
+) -> Option<(String, Range<usize>)> {
+    let root = LinkedNode::new(source.root());
+    let leaf = root.leaf_at(offset)?;
+
+    let equation = equation_at(&leaf).or_else(|| labelled_equation(&root, &leaf))?;
+    let text = equation.get().clone().into_text();
+
+    let image = cache.get_or_render(&text, || render_equation(world, source.id(), &text))?;
+    Some((format!("![equation]({image})"), equation.range()))
+}
+
+fn equation_at<'a>(leaf: &LinkedNode<'a>) -> Option<LinkedNode<'a>> {
+    let mut node = Some(leaf.clone());
+    while let Some(current) = node {
+        if current.is::<ast::Equation>() {
+            return Some(current);
+        }
+        node = current.parent().cloned();
+    }
+    None
+}
+
+/// Finds the equation labelled with the hovered label or referenced by the hovered reference
+fn labelled_equation<'a>(root: &LinkedNode<'a>, leaf: &LinkedNode) -> Option<LinkedNode<'a>> {
+    let name = match leaf.kind() {
+        SyntaxKind::Label => leaf.cast::<ast::Label>()?.get().to_owned(),
+        SyntaxKind::RefMarker => leaf
+            .parent()
+            .and_then(|parent| parent.cast::<ast::Ref>())?
+            .target()
+            .to_owned(),
+        _ => return None,
+    };
+
+    find_labelled_equation(root, &name)
+}
+
+fn find_labelled_equation<'a>(node: &LinkedNode<'a>, name: &str) -> Option<LinkedNode<'a>> {
+    if node
+        .cast::<ast::Label>()
+        .is_some_and(|label| label.get() == name)
+    {
+        // The label applies to the preceding element, skipping whitespace
+        let mut prev = node.prev_sibling();
+        while let Some(sibling) = prev {
+            if sibling.is::<ast::Equation>() {
+                return Some(sibling);
+            }
+            if sibling.kind() != SyntaxKind::Space {
+                return None;
+            }
+            prev = sibling.prev_sibling();
+        }
+        return None;
+    }
+
+    node.children()
+        .find_map(|child| find_labelled_equation(&child, name))
+}
+
+/// Compiles the equation on its own and renders it to a PNG data URI. Definitions from the rest of
+/// the document aren't available, but imports relative to the source still resolve.
+fn render_equation(world: &ProjectWorld, id: FileId, text: &str) -> Option<String> {
+    let snippet = SnippetWorld {
+        world,
+        main: Source::new(id, format!("{PREAMBLE}{text}")),
+    };
+
+    let mut tracer = Tracer::default();
+    let document = typst::compile(&snippet, &mut tracer).ok()?;
+    let frame = &document.pages.first()?.frame;
+
+    let png = typst_render::render(frame, PIXEL_PER_PT, Color::WHITE)
+        .encode_png()
+        .ok()?;
+    Some(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+fn hash(text: &str) -> u128 {
+    let mut hasher = SipHasher13::new();
+    text.hash(&mut hasher);
+    hasher.finish128().as_u128()
+}
+
+/// A world for compiling a snippet in place of one of the project's sources
+struct SnippetWorld<'a> {
+    world: &'a ProjectWorld,
+    main: Source,
+}
+
+impl World for SnippetWorld<'_> {
+    fn library(&self) -> &Prehashed<Library> {
+        self.world.library()
+    }
+
+    fn book(&self) -> &Prehashed<FontBook> {
+        self.world.book()
+    }
+
+    fn main(&self) -> Source {
+        self.main.clone()
+    }
+
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        if id == self.main.id() {
+            Ok(self.main.clone())
+        } else {
+            self.world.source(id)
+        }
+    }
+
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        self.world.file(id)
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        self.world.font(index)
+    }
+
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        self.world.today(offset)
+    }
+
+    fn packages(&self) -> &[(PackageSpec, Option<EcoString>)] {
+        self.world.packages()
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::str::FromStr;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{Location, Url};
+use typst::diag::FileError;
+use typst::syntax::ast::{self, AstNode};
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{FileId, LinkedNode, Source};
+
+use crate::lsp_typst_boundary::typst_to_lsp;
+
+use super::lint::{self, walk};
+use super::typst_compiler::compile;
+use super::TypstServer;
+
+/// Functions which read the file at the path passed as their first argument
+const FILE_FUNCTIONS: &[&str] = &[
+    "image",
+    "read",
+    "json",
+    "csv",
+    "yaml",
+    "toml",
+    "xml",
+    "cbor",
+    "bibliography",
+];
+
+/// A path in an import, include or call like `image(..)` which could not be resolved
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingFile {
+    pub path: String,
+    pub message: String,
+    pub location: Location,
+}
+
+impl TypstServer {
+    /// Compiles the file's document and collects every path in its sources which Typst failed to
+    /// read, not just the first error that stopped compilation.
+    pub async fn missing_files(&self, uri: &Url) -> anyhow::Result<Vec<MissingFile>> {
+        let root = self.compilation_root(uri).await;
+        let (project, _) = self.project_and_full_id(&root).await?;
+
+        let unresolved = self
+            .thread_with_world(&root)
+            .await?
+            .run(|world| {
+                let _ = compile(&world);
+                let failed: HashMap<_, _> = world.failed_files().into_iter().collect();
+
+                lint::project_sources(&world)
+                    .into_iter()
+                    .map(|source| {
+                        let unresolved = unresolved_paths(&source, &failed);
+                        (source, unresolved)
+                    })
+                    .filter(|(_, unresolved)| !unresolved.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .await?;
+
+        let position_encoding = self.const_config().position_encoding;
+        let mut missing = Vec::new();
+        for (source, unresolved) in unresolved {
+            let uri = project.full_id_to_uri(project.fill_id(source.id())).await?;
+            for (range, path, message) in unresolved {
+                missing.push(MissingFile {
+                    path,
+                    message,
+                    location: Location {
+                        uri: uri.clone(),
+                        range: typst_to_lsp::range(range, &source, position_encoding).raw_range,
+                    },
+                });
+            }
+        }
+
+        Ok(missing)
+    }
+}
+
+/// The paths in the source which refer to files that failed, with their range and the error
+fn unresolved_paths(
+    source: &Source,
+    failed: &HashMap<FileId, FileError>,
+) -> Vec<(Range<usize>, String, String)> {
+    let mut unresolved = Vec::new();
+    walk(&LinkedNode::new(source.root()), &mut |node| {
+        let Some((path, range)) = path_argument(node) else {
+            return;
+        };
+
+        let error = if path.starts_with('@') {
+            let Ok(spec) = PackageSpec::from_str(&path) else {
+                return;
+            };
+            failed
+                .iter()
+                .find(|(id, _)| id.package() == Some(&spec))
+                .map(|(_, err)| err)
+        } else {
+            failed.get(&source.id().join(&path))
+        };
+
+        if let Some(error) = error {
+            unresolved.push((range, path, error.to_string()));
+        }
+    });
+    unresolved
+}
+
+/// The path string in an import, include or call of a function reading a file, with its range
+fn path_argument(node: &LinkedNode) -> Option<(String, Range<usize>)> {
+    let expr = if let Some(import) = node.cast::<ast::ModuleImport>() {
+        import.source()
+    } else if let Some(include) = node.cast::<ast::ModuleInclude>() {
+        include.source()
+    } else {
+        let call = node.cast::<ast::FuncCall>()?;
+        let ast::Expr::Ident(callee) = call.callee() else {
+            return None;
+        };
+        if !FILE_FUNCTIONS.contains(&callee.as_str()) {
+            return None;
+        }
+        call.args().items().find_map(|arg| match arg {
+            ast::Arg::Pos(expr) => Some(expr),
+            _ => None,
+        })?
+    };
+
+    let ast::Expr::Str(path) = expr else {
+        return None;
+    };
+    let range = node.find(path.span())?.range();
+    Some((path.get().to_string(), range))
+}
+
+#[cfg(test)]
+mod test {
+    use typst::syntax::VirtualPath;
+
+    use super::*;
+
+    #[test]
+    fn finds_paths_of_failed_files() {
+        let text = "#import \"missing.typ\": a\n#include \"chapter.typ\"\n#image(\"fig.png\")";
+        let id = FileId::new(None, VirtualPath::new("main.typ"));
+        let source = Source::new(id, text.to_owned());
+        let failed = HashMap::from([
+            (
+                id.join("missing.typ"),
+                FileError::NotFound("missing.typ".into()),
+            ),
+            (id.join("fig.png"), FileError::NotFound("fig.png".into())),
+        ]);
+
+        let paths: Vec<_> = unresolved_paths(&source, &failed)
+            .into_iter()
+            .map(|(range, path, _)| (text[range].to_owned(), path))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("\"missing.typ\"".to_owned(), "missing.typ".to_owned()),
+                ("\"fig.png\"".to_owned(), "fig.png".to_owned()),
+            ]
+        );
+    }
+}
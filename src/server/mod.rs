@@ -1,9 +1,13 @@
-use std::sync::Arc;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Weak};
 
 use once_cell::sync::OnceCell;
 use tokio::runtime;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::{Mutex, OwnedRwLockReadGuard, RwLock, RwLockReadGuard};
+use tokio::task::JoinHandle;
 use tower_lsp::lsp_types::Url;
 use tower_lsp::Client;
 use tracing_subscriber::{reload, Registry};
@@ -15,61 +19,176 @@ use crate::server::semantic_tokens::SemanticTokenCache;
 use crate::workspace::fs::FsResult;
 use crate::workspace::package::FullFileId;
 use crate::workspace::project::Project;
-use crate::workspace::world::typst_thread::TypstThread;
+use crate::workspace::world::typst_thread::{TypstPanic, TypstThread};
 use crate::workspace::world::ProjectWorld;
 use crate::workspace::{Workspace, TYPST_STDLIB};
 
+use self::asset_watch::AssetWatches;
+use self::compile_throttle::CompileThrottle;
 use self::diagnostics::DiagnosticsManager;
+use self::export_watch::ExportWatches;
 use self::log::LspLayer;
+use self::main_files::IncludedFiles;
+use self::math_hover::MathHoverCache;
+use self::symbol_index::SymbolIndex;
 
+pub mod asset_watch;
+pub mod benchmark;
+pub mod bibliography;
+pub mod call_hierarchy;
+pub mod code_action;
+pub mod code_lens;
 pub mod command;
+pub mod compile_throttle;
+pub mod debug_world;
+pub mod deep_link;
+pub mod deprecations;
 pub mod diagnostics;
+pub mod doctor;
 pub mod document;
+pub mod document_highlight;
+pub mod element_at;
 pub mod export;
+pub mod export_directives;
+pub mod export_watch;
 pub mod formatting;
 pub mod hover;
+pub mod keybindings;
+pub mod labels;
+pub mod lint;
 pub mod log;
 pub mod lsp;
+pub mod main_files;
+pub mod math_hover;
+pub mod missing_files;
+pub mod on_type_formatting;
+pub mod outline;
+pub mod package_lock;
+pub mod page_size;
+pub mod parse_tree;
+pub mod preload;
+pub mod query;
+pub mod reveal;
+pub mod sarif;
+pub mod selection_export;
 pub mod selection_range;
 pub mod semantic_tokens;
 pub mod signature;
+pub mod snippets;
+pub mod source_tree;
+pub mod symbol_index;
 pub mod symbols;
 pub mod typst_compiler;
 pub mod ui;
+pub mod used_fonts;
+pub mod visual_diff;
 pub mod watch;
 
 pub struct TypstServer {
+    /// The server itself, for tasks outliving the request or notification spawning them
+    this: Weak<TypstServer>,
     to_ui_tx: Sender<ui::NewDocumentMessage>,
     client: Client,
     document: Mutex<Arc<Document>>,
     typst_thread: TypstThread,
     workspace: Arc<OnceCell<Arc<RwLock<Workspace>>>>,
     config: Arc<RwLock<Config>>,
+    /// Number of compilations currently running, shared with the preview
+    compiling: Arc<tokio::sync::watch::Sender<usize>>,
+    /// Whether the preview window is shown, shared with the preview
+    preview_visible: Arc<tokio::sync::watch::Sender<bool>>,
+    /// The document the preview shows next to its own, see the `compare` command
+    compare: tokio::sync::watch::Sender<Option<ui::CompareDocument>>,
+    /// Whether any document was handed to the preview yet
+    preview_shown: AtomicBool,
+    /// Hashes of the pages of the document last handed to the preview, see [`ui::page_hashes`]
+    preview_pages: parking_lot::Mutex<Vec<u128>>,
+    /// The file the editor shows, if it tells us, see [`TypstServer::active_editor_changed`]
+    active_editor: parking_lot::Mutex<Option<Url>>,
     const_config: OnceCell<ConstConfig>,
     semantic_tokens_delta_cache: Arc<parking_lot::RwLock<SemanticTokenCache>>,
+    math_hover_cache: Arc<MathHoverCache>,
+    symbol_index: parking_lot::Mutex<SymbolIndex>,
+    export_watches: parking_lot::Mutex<ExportWatches>,
+    asset_watches: parking_lot::Mutex<AssetWatches>,
+    included_files: parking_lot::Mutex<IncludedFiles>,
+    compile_throttle: parking_lot::Mutex<CompileThrottle>,
     diagnostics: Mutex<DiagnosticsManager>,
     lsp_tracing_layer_handle: reload::Handle<Option<LspLayer>, Registry>,
 }
 
+/// The [`TypstServer`] as tower-lsp serves it. The server is shared with the tasks it spawns, see
+/// [`TypstServer::spawn`].
+pub struct SharedServer(Arc<TypstServer>);
+
+impl SharedServer {
+    pub fn new(server: Arc<TypstServer>) -> Self {
+        Self(server)
+    }
+
+    pub async fn active_editor_changed(&self, params: document::ActiveEditorParams) {
+        self.0.active_editor_changed(params).await
+    }
+}
+
+impl Deref for SharedServer {
+    type Target = TypstServer;
+
+    fn deref(&self) -> &TypstServer {
+        &self.0
+    }
+}
+
 impl TypstServer {
     pub fn new(
         client: Client,
         lsp_tracing_layer_handle: reload::Handle<Option<LspLayer>, Registry>,
         to_ui_tx: Sender<ui::NewDocumentMessage>,
         workspace: Arc<OnceCell<Arc<RwLock<Workspace>>>>,
-    ) -> Self {
-        Self {
+        config: Arc<RwLock<Config>>,
+        compiling: Arc<tokio::sync::watch::Sender<usize>>,
+        preview_visible: Arc<tokio::sync::watch::Sender<bool>>,
+        compare: tokio::sync::watch::Sender<Option<ui::CompareDocument>>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
             to_ui_tx,
             typst_thread: Default::default(),
             workspace,
-            config: Default::default(),
+            config,
+            compiling,
+            preview_visible,
+            compare,
+            preview_shown: Default::default(),
+            preview_pages: Default::default(),
+            active_editor: Default::default(),
             const_config: Default::default(),
             semantic_tokens_delta_cache: Default::default(),
+            math_hover_cache: Default::default(),
+            symbol_index: Default::default(),
+            export_watches: Default::default(),
+            asset_watches: Default::default(),
+            included_files: Default::default(),
+            compile_throttle: Default::default(),
             diagnostics: Mutex::new(DiagnosticsManager::new(client.clone())),
             lsp_tracing_layer_handle,
             client,
             document: Default::default(),
-        }
+        })
+    }
+
+    /// Runs the future on its own task, so the request or notification spawning it can be
+    /// answered without waiting for it
+    pub fn spawn<F, Fut>(&self, f: F) -> JoinHandle<()>
+    where
+        F: FnOnce(Arc<TypstServer>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let this = self
+            .this
+            .upgrade()
+            .expect("server should be alive while handling messages");
+        tokio::spawn(f(this))
     }
 
     pub fn const_config(&self) -> &ConstConfig {
@@ -136,7 +255,7 @@ impl TypstServer {
     pub async fn typst<T: Send + 'static>(
         &self,
         f: impl FnOnce(runtime::Handle) -> T + Send + 'static,
-    ) -> T {
+    ) -> Result<T, TypstPanic> {
         self.typst_thread.run(f).await
     }
 }
@@ -166,7 +285,7 @@ impl<'a> WorldThread<'a> {
     pub async fn run<T: Send + 'static>(
         self,
         f: impl FnOnce(ProjectWorld) -> T + Send + 'static,
-    ) -> T {
+    ) -> Result<T, TypstPanic> {
         self.typst_thread
             .run_with_world(self.main_project, self.main, f)
             .await
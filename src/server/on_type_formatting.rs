@@ -0,0 +1,73 @@
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{DocumentOnTypeFormattingOptions, Position, TextEdit, Url};
+use typst::syntax::Source;
+
+use crate::lsp_typst_boundary::{lsp_to_typst, typst_to_lsp};
+
+use super::TypstServer;
+
+/// Delimiters which editors commonly close automatically
+const CLOSING_DELIMITERS: &[&str] = &["]", "$", ")", "}"];
+
+pub fn get_on_type_formatting_options() -> DocumentOnTypeFormattingOptions {
+    DocumentOnTypeFormattingOptions {
+        first_trigger_character: CLOSING_DELIMITERS[0].to_owned(),
+        more_trigger_character: Some(
+            CLOSING_DELIMITERS[1..]
+                .iter()
+                .map(|delimiter| delimiter.to_string())
+                .collect(),
+        ),
+    }
+}
+
+impl TypstServer {
+    /// After typing a closing delimiter right in front of an identical one, removes the one in
+    /// front if it's left over from the editor closing the delimiter automatically.
+    pub async fn format_on_type(
+        &self,
+        uri: &Url,
+        position: Position,
+        typed: &str,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let position_encoding = self.const_config().position_encoding;
+
+        let edits = self.scope_with_source(uri).await?.run(|source, _| {
+            let offset = lsp_to_typst::position_to_offset(position, position_encoding, source);
+            let duplicate = duplicate_delimiter(source, offset, typed)?;
+            Some(vec![TextEdit {
+                range: typst_to_lsp::range(duplicate, source, position_encoding).raw_range,
+                new_text: String::new(),
+            }])
+        });
+
+        Ok(edits)
+    }
+}
+
+/// The range of the delimiter after the offset, if it repeats the one typed before the offset and
+/// only makes the document invalid. Deciding by the syntax errors keeps delimiters which close an
+/// enclosing block, like the second `]` in `#box[#emph[a]|]`.
+fn duplicate_delimiter(source: &Source, offset: usize, typed: &str) -> Option<Range<usize>> {
+    if !CLOSING_DELIMITERS.contains(&typed) {
+        return None;
+    }
+
+    let text = source.text();
+    if !text.get(..offset)?.ends_with(typed) || !text.get(offset..)?.starts_with(typed) {
+        return None;
+    }
+
+    let root = source.root();
+    if !root.erroneous() {
+        return None;
+    }
+
+    let range = offset..offset + typed.len();
+    let mut without = text.to_owned();
+    without.replace_range(range.clone(), "");
+
+    let errors_without = typst::syntax::parse(&without).errors().len();
+    (errors_without < root.errors().len()).then_some(range)
+}
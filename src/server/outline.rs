@@ -0,0 +1,109 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use typst::foundations::{NativeElement, StyleChain};
+use typst::layout::Position;
+use typst::model::{Document, HeadingElem};
+
+/// A heading of the compiled document which is included in outlines
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub title: String,
+    /// Starting at 1 for top-level headings
+    pub level: usize,
+    pub position: Position,
+}
+
+/// The document's headings which are included in outlines, in document order
+pub fn headings(document: &Document) -> Vec<Heading> {
+    let styles = StyleChain::default();
+    let headings = document.introspector.query(&HeadingElem::elem().select());
+
+    headings
+        .iter()
+        .filter_map(|content| {
+            let heading = content.to_packed::<HeadingElem>()?;
+            if !heading.outlined(styles) {
+                return None;
+            }
+            Some(Heading {
+                title: heading.body().plain_text().to_string(),
+                level: heading.resolve_level(styles).get(),
+                position: document.introspector.position(heading.location()?),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutlineFormat {
+    /// A nested list
+    #[default]
+    Markdown,
+    /// Indented lines
+    Text,
+}
+
+/// Options for the `exportOutline` command
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OutlineOptions {
+    pub format: OutlineFormat,
+    /// A file to also write the table of contents to, relative to the document's directory
+    pub output: Option<PathBuf>,
+}
+
+/// A table of contents of the headings, indented by level, with the page each heading is on
+pub fn table_of_contents(headings: &[Heading], format: OutlineFormat) -> String {
+    let mut toc = String::new();
+    for heading in headings {
+        let indent = "  ".repeat(heading.level.saturating_sub(1));
+        let page = heading.position.page;
+        let title = heading.title.trim();
+        let _ = match format {
+            OutlineFormat::Markdown => writeln!(toc, "{indent}- {title} (p. {page})"),
+            OutlineFormat::Text => writeln!(toc, "{indent}{title} .... {page}"),
+        };
+    }
+    toc
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+
+    use typst::layout::{Abs, Point};
+
+    use super::*;
+
+    fn heading(title: &str, level: usize, page: usize) -> Heading {
+        Heading {
+            title: title.to_owned(),
+            level,
+            position: Position {
+                page: NonZeroUsize::new(page).unwrap(),
+                point: Point::splat(Abs::zero()),
+            },
+        }
+    }
+
+    #[test]
+    fn nests_headings_by_level() {
+        let headings = [
+            heading("Introduction", 1, 1),
+            heading("Motivation", 2, 2),
+            heading("Results", 1, 4),
+        ];
+
+        assert_eq!(
+            table_of_contents(&headings, OutlineFormat::Markdown),
+            "- Introduction (p. 1)\n  - Motivation (p. 2)\n- Results (p. 4)\n"
+        );
+        assert_eq!(
+            table_of_contents(&headings, OutlineFormat::Text),
+            "Introduction .... 1\n  Motivation .... 2\nResults .... 4\n"
+        );
+    }
+}
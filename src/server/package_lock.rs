@@ -0,0 +1,39 @@
+use std::fs;
+
+use anyhow::Context;
+use tower_lsp::lsp_types::Url;
+use typst::syntax::{FileId, VirtualPath};
+
+use crate::workspace::package::lock::{PackageLock, LOCK_FILE_NAME};
+
+use super::typst_compiler::compile;
+use super::TypstServer;
+
+impl TypstServer {
+    /// Compiles the document and writes the exact versions of the packages it uses to `typst.lock`
+    /// in the project root, replacing any existing lockfile. Returns the URI of the lockfile.
+    pub async fn lock_packages(&self, uri: &Url) -> anyhow::Result<Url> {
+        let packages = self
+            .thread_with_world(uri)
+            .await?
+            .run(|world| {
+                compile(&world);
+                world.accessed_packages()
+            })
+            .await?;
+        let lock = PackageLock::new(&packages);
+
+        let (project, _) = self.project_and_full_id(uri).await?;
+
+        let lock_id = FileId::new(None, VirtualPath::new(LOCK_FILE_NAME));
+        let lock_uri = project.full_id_to_uri(project.fill_id(lock_id)).await?;
+        let lock_path = lock_uri
+            .to_file_path()
+            .map_err(|()| anyhow::anyhow!("project root is not a local directory"))?;
+
+        fs::write(&lock_path, lock.to_json())
+            .with_context(|| format!("could not write {}", lock_path.display()))?;
+
+        Ok(lock_uri)
+    }
+}
@@ -0,0 +1,121 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use typst::layout::Paper;
+use typst::syntax::{ast, LinkedNode, Source, SyntaxKind};
+
+/// A page size to export a document at instead of its own, either the name of a paper size
+/// Typst knows, like `"a4"` or `"us-letter"`, or custom dimensions like `"210mm"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PageSize {
+    Paper(String),
+    Custom { width: String, height: String },
+}
+
+impl PageSize {
+    /// The set rule which applies the size, failing with a message for the user if it's invalid
+    pub fn set_rule(&self) -> Result<String, String> {
+        match self {
+            Self::Paper(name) => {
+                Paper::from_str(name).map_err(|_| format!("Unknown paper size {name:?}"))?;
+                Ok(format!("#set page(paper: {name:?})"))
+            }
+            Self::Custom { width, height } => Ok(format!(
+                "#set page(width: {}, height: {})",
+                length(width)?,
+                length(height)?
+            )),
+        }
+    }
+
+    /// A short name for the size, to tell files exported at different sizes apart
+    pub fn suffix(&self) -> String {
+        match self {
+            Self::Paper(name) => name.to_lowercase(),
+            Self::Custom { width, height } => format!("{}x{}", width.trim(), height.trim()),
+        }
+    }
+}
+
+/// Validates a length like `8.5in`, with a unit of `pt`, `mm`, `cm` or `in`
+fn length(length: &str) -> Result<&str, String> {
+    let length = length.trim();
+    let number = ["pt", "mm", "cm", "in"]
+        .iter()
+        .find_map(|unit| length.strip_suffix(unit));
+    match number.and_then(|number| number.parse::<f64>().ok()) {
+        Some(number) if number.is_finite() && number > 0.0 => Ok(length),
+        _ => Err(format!(
+            "Invalid page dimension {length:?}, expected a positive length in pt, mm, cm or in"
+        )),
+    }
+}
+
+/// The text of the source with the set rule overriding the page size: at the start, and again
+/// after each top-level `set page(..)` and `show: ..`, since templates applied by the latter
+/// usually set the page themselves. Rules in nested blocks or other files still win.
+pub fn with_page_size(source: &Source, set_rule: &str) -> String {
+    let root = LinkedNode::new(source.root());
+    let mut text = String::with_capacity(source.text().len() + set_rule.len());
+    text.push_str(set_rule);
+    text.push('\n');
+
+    let mut copied = 0;
+    for child in root.children() {
+        let sets_page = match child.kind() {
+            SyntaxKind::SetRule => child.cast::<ast::SetRule>().is_some_and(
+                |rule| matches!(rule.target(), ast::Expr::Ident(ident) if ident.as_str() == "page"),
+            ),
+            SyntaxKind::ShowRule => child
+                .cast::<ast::ShowRule>()
+                .is_some_and(|rule| rule.selector().is_none()),
+            _ => false,
+        };
+        if sets_page {
+            let end = child.range().end;
+            text.push_str(&source.text()[copied..end]);
+            text.push_str(set_rule);
+            copied = end;
+        }
+    }
+    text.push_str(&source.text()[copied..]);
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overrides_page_rules_and_templates() {
+        let source =
+            Source::detached("#set page(flipped: true)\n#show: doc.with(title: [A])\nBody");
+        let rule = PageSize::Paper("a5".to_owned()).set_rule().unwrap();
+
+        assert_eq!(
+            with_page_size(&source, &rule),
+            format!(
+                "{rule}\n#set page(flipped: true){rule}\n#show: doc.with(title: [A]){rule}\nBody"
+            )
+        );
+    }
+
+    #[test]
+    fn validates_sizes() {
+        assert!(PageSize::Paper("a4".to_owned()).set_rule().is_ok());
+        assert!(PageSize::Paper("a42".to_owned()).set_rule().is_err());
+
+        let custom = |width: &str| PageSize::Custom {
+            width: width.to_owned(),
+            height: "11in".to_owned(),
+        };
+        assert_eq!(
+            custom("8.5in").set_rule().unwrap(),
+            "#set page(width: 8.5in, height: 11in)"
+        );
+        assert!(custom("-3cm").set_rule().is_err());
+        assert!(custom("12px").set_rule().is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{Range as LspRange, Url};
+use typst::syntax::{LinkedNode, Source};
+
+use crate::config::PositionEncoding;
+use crate::lsp_typst_boundary::typst_to_lsp;
+
+use super::TypstServer;
+
+/// A node of the syntax tree, as returned by the `parseTree` command
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseTreeNode {
+    /// The name of the node's `SyntaxKind`, like `"Heading"`
+    pub kind: String,
+    /// Byte offsets into the source
+    pub bytes: Range<usize>,
+    pub range: LspRange,
+    /// The text of leaves, which inner nodes leave to their children
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// The messages of error leaves
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ParseTreeNode>,
+}
+
+impl TypstServer {
+    /// The syntax tree of the file, as the parser produced it
+    pub async fn parse_tree(&self, uri: &Url) -> anyhow::Result<ParseTreeNode> {
+        let position_encoding = self.const_config().position_encoding;
+        let tree = self
+            .scope_with_source(uri)
+            .await?
+            .run(|source, _| parse_tree(source, position_encoding));
+        Ok(tree)
+    }
+}
+
+fn parse_tree(source: &Source, position_encoding: PositionEncoding) -> ParseTreeNode {
+    tree_node(&LinkedNode::new(source.root()), source, position_encoding)
+}
+
+fn tree_node(
+    node: &LinkedNode,
+    source: &Source,
+    position_encoding: PositionEncoding,
+) -> ParseTreeNode {
+    let children: Vec<_> = node
+        .children()
+        .map(|child| tree_node(&child, source, position_encoding))
+        .collect();
+
+    // The errors of inner nodes are those of the leaves below them
+    let leaf = children.is_empty();
+    let errors = if leaf { node.errors() } else { Vec::new() };
+
+    ParseTreeNode {
+        kind: format!("{:?}", node.kind()),
+        bytes: node.range(),
+        range: typst_to_lsp::range(node.range(), source, position_encoding).raw_range,
+        text: leaf.then(|| node.text().to_string()),
+        errors: errors
+            .into_iter()
+            .map(|error| error.message.to_string())
+            .collect(),
+        children,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn maps_nodes_to_ranges() {
+        let source = Source::detached("= Hi\n*bold*");
+        let tree = parse_tree(&source, PositionEncoding::Utf16);
+
+        assert_eq!(tree.kind, "Markup");
+        let strong = tree
+            .children
+            .iter()
+            .find(|child| child.kind == "Strong")
+            .unwrap();
+        assert_eq!(strong.bytes, 5..11);
+        assert_eq!(strong.range.start.line, 1);
+        assert_eq!(strong.range.start.character, 0);
+        assert_eq!(strong.children[1].children[0].text.as_deref(), Some("bold"));
+    }
+}
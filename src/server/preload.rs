@@ -0,0 +1,134 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::bail;
+use itertools::Itertools;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressCreateParams, WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+use tracing::{info, warn};
+use typst::syntax::package::PackageSpec;
+
+use super::TypstServer;
+
+/// Distinguishes the progress tokens of concurrent downloads
+static DOWNLOAD_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl TypstServer {
+    /// Downloads the given packages ahead of time, so the first compilation doesn't have to wait
+    /// for them. Failures are logged, but don't stop the remaining packages from loading. The
+    /// downloads run in the background, so they don't hold up other requests.
+    #[tracing::instrument(skip(self))]
+    pub fn preload_packages(&self, specs: &[String]) {
+        let specs: Vec<PackageSpec> = specs
+            .iter()
+            .filter_map(|spec| {
+                PackageSpec::from_str(spec)
+                    .map_err(|err| warn!(%err, spec, "invalid package to preload"))
+                    .ok()
+            })
+            .collect();
+
+        if specs.is_empty() {
+            return;
+        }
+
+        self.spawn(move |server| async move {
+            if let Err(err) = server
+                .download_packages("Preloading Typst packages", &specs)
+                .await
+            {
+                warn!(%err, "could not preload all packages");
+            }
+        });
+    }
+
+    /// Downloads the packages which aren't available locally yet, reporting progress to the client.
+    /// Fails if any package could not be downloaded, after trying all of them.
+    #[tracing::instrument(skip(self))]
+    pub async fn download_packages(
+        &self,
+        title: &str,
+        specs: &[PackageSpec],
+    ) -> anyhow::Result<()> {
+        if specs.is_empty() {
+            return Ok(());
+        }
+
+        let count = DOWNLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let token = NumberOrString::String(format!("typst-lsp/downloadPackages/{count}"));
+        let progress = self
+            .client
+            .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .map_err(|err| warn!(%err, "could not create progress for downloading packages"))
+            .is_ok();
+        let progress_token = progress.then_some(&token);
+
+        self.report_progress(
+            progress_token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.to_owned(),
+                percentage: Some(0),
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        // Downloads take a while, so they mustn't keep the workspace from being edited meanwhile
+        let external = self.read_workspace().await.package_manager().external();
+
+        let mut failed = Vec::new();
+        for (index, spec) in specs.iter().enumerate() {
+            self.report_progress(
+                progress_token,
+                WorkDoneProgress::Report(WorkDoneProgressReport {
+                    message: Some(spec.to_string()),
+                    percentage: Some((index * 100 / specs.len()) as u32),
+                    ..Default::default()
+                }),
+            )
+            .await;
+
+            match external.package(spec).await {
+                Ok(_) => info!(%spec, "downloaded package"),
+                Err(err) => {
+                    warn!(%err, %spec, "could not download package");
+                    failed.push(spec);
+                }
+            }
+        }
+
+        self.report_progress(
+            progress_token,
+            WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+        )
+        .await;
+
+        if !failed.is_empty() {
+            bail!("could not download {}", failed.iter().join(", "));
+        }
+        Ok(())
+    }
+
+    /// Sends progress to the client, unless it couldn't create the progress `token`.
+    pub(super) async fn report_progress(
+        &self,
+        token: Option<&NumberOrString>,
+        value: WorkDoneProgress,
+    ) {
+        if let Some(token) = token {
+            self.client
+                .send_notification::<Progress>(ProgressParams {
+                    token: token.clone(),
+                    value: ProgressParamsValue::WorkDone(value),
+                })
+                .await;
+        }
+    }
+}
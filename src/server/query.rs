@@ -0,0 +1,108 @@
+use comemo::Track;
+use itertools::Itertools;
+use serde::Deserialize;
+use typst::eval::{eval_string, EvalMode};
+use typst::foundations::{Content, IntoValue, LocatableSelector, Scope};
+use typst::model::Document;
+use typst::syntax::Span;
+use typst::World;
+
+/// Options for the `query` command, like those of `typst query`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryOptions {
+    /// Evaluated as Typst code, like `heading` or `figure.where(kind: image)`
+    pub selector: String,
+    /// Only return this field of each element
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Return the single matching element instead of an array, failing if there isn't exactly one
+    #[serde(default)]
+    pub one: bool,
+}
+
+/// The elements of the document matching the selector, as JSON. Fails with a message for the
+/// user if the selector is invalid or `one` is set but not exactly one element matches.
+pub fn query(
+    world: &dyn World,
+    document: &Document,
+    options: &QueryOptions,
+) -> Result<serde_json::Value, String> {
+    let selector = eval_string(
+        world.track(),
+        &options.selector,
+        Span::detached(),
+        EvalMode::Code,
+        Scope::default(),
+    )
+    .map_err(|errors| {
+        let message = errors.iter().map(|error| &error.message).join(", ");
+        format!("Invalid selector: {message}")
+    })?
+    .cast::<LocatableSelector>()
+    .map_err(|err| format!("Invalid selector: {err}"))?;
+
+    let elements = document
+        .introspector
+        .query(&selector.0)
+        .into_iter()
+        .collect();
+    select(elements, options)
+}
+
+/// Maps the elements to JSON as requested by the options
+fn select(elements: Vec<Content>, options: &QueryOptions) -> Result<serde_json::Value, String> {
+    if options.one && elements.len() != 1 {
+        return Err(format!(
+            "Expected exactly one element, found {}",
+            elements.len()
+        ));
+    }
+
+    let values: Vec<_> = elements
+        .into_iter()
+        .filter_map(|element| match &options.field {
+            Some(field) => element.get_by_name(field),
+            None => Some(element.into_value()),
+        })
+        .collect();
+
+    let json = if options.one {
+        values.first().map(serde_json::to_value)
+    } else {
+        Some(serde_json::to_value(&values))
+    };
+    json.unwrap_or(Ok(serde_json::Value::Null))
+        .map_err(|err| format!("The elements can't be represented as JSON: {err}"))
+}
+
+#[cfg(test)]
+mod test {
+    use typst::foundations::NativeElement;
+    use typst::model::HeadingElem;
+    use typst::text::TextElem;
+
+    use super::*;
+
+    #[test]
+    fn selects_fields_of_one_element() {
+        let heading = HeadingElem::new(TextElem::packed("Intro")).pack();
+        let options = |field: Option<&str>, one| QueryOptions {
+            selector: "heading".to_owned(),
+            field: field.map(str::to_owned),
+            one,
+        };
+
+        let all = select(
+            vec![heading.clone(), heading.clone()],
+            &options(None, false),
+        )
+        .unwrap();
+        assert_eq!(all.as_array().map(Vec::len), Some(2));
+
+        let body = select(vec![heading.clone()], &options(Some("body"), true)).unwrap();
+        assert_eq!(body["text"], "Intro");
+
+        assert!(select(vec![heading.clone(), heading], &options(None, true)).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use tower_lsp::lsp_types::Url;
+use tracing::{info, warn};
+
+/// Opens the system's file manager with the file selected: with Explorer on Windows, Finder on
+/// macOS, and elsewhere with the file manager registered on D-Bus. File managers which can't
+/// select files are opened on the file's directory instead.
+pub fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    if cfg!(target_os = "windows") {
+        // Explorer exits with an error code even when it succeeds
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        Command::new("explorer").arg(select).spawn()?;
+        return Ok(());
+    }
+    if cfg!(target_os = "macos") {
+        Command::new("open").arg("-R").arg(path).spawn()?;
+        return Ok(());
+    }
+
+    match show_items(path) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            info!(%err, "file manager can't select files, opening the directory instead");
+            let dir = path.parent().unwrap_or(path);
+            Command::new("xdg-open")
+                .arg(dir)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+            Ok(())
+        }
+    }
+}
+
+/// Asks the file manager to show the file with the freedesktop `FileManager1` D-Bus interface
+fn show_items(path: &Path) -> io::Result<()> {
+    let uri = Url::from_file_path(path)
+        .map_err(|()| io::Error::new(io::ErrorKind::InvalidInput, "path is not absolute"))?;
+    let status = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ])
+        .arg(format!("array:string:{uri}"))
+        .arg("string:")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        warn!(%status, "FileManager1.ShowItems failed");
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "no file manager implements FileManager1",
+        ))
+    }
+}
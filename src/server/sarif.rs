@@ -0,0 +1,201 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Url};
+
+use crate::config::PositionEncoding;
+
+use super::diagnostics::DiagnosticsMap;
+use super::TypstServer;
+
+/// Name of the report written to the workspace root when no path is given
+pub const DEFAULT_REPORT_NAME: &str = "typst-diagnostics.sarif";
+
+/// What the `exportDiagnostics` command found, for CI to fail on errors
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsSummary {
+    pub report: Url,
+    pub errors: usize,
+    pub warnings: usize,
+    /// 1 if there are errors, else 0, like the exit code of a compiler
+    pub exit_code: i32,
+}
+
+impl TypstServer {
+    /// Compiles every main file, or the pinned main file, and writes the diagnostics of all of
+    /// them to a SARIF report at `path`, or in the workspace root
+    pub async fn export_diagnostics(
+        &self,
+        path: Option<PathBuf>,
+    ) -> anyhow::Result<DiagnosticsSummary> {
+        let main_files = match self.main_url().await {
+            Some(main) => vec![main],
+            None => self.main_files().await,
+        };
+        if main_files.is_empty() {
+            bail!("no main file is pinned and no file matches `mainFiles`");
+        }
+
+        let mut diagnostics = DiagnosticsMap::new();
+        for uri in &main_files {
+            let (_, compiled) = self.compile_source(uri).await?;
+            for (uri, items) in compiled {
+                let known = diagnostics.entry(uri).or_default();
+                for item in items {
+                    // Files included by several main files report the same diagnostics
+                    if !known.contains(&item) {
+                        known.push(item);
+                    }
+                }
+            }
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let workspace = self.read_workspace().await;
+                let root = workspace
+                    .package_manager()
+                    .current()
+                    .map(|package| package.root().clone())
+                    .min()
+                    .or_else(|| main_files.first().and_then(|main| main.join(".").ok()))
+                    .context("no workspace root to write the report to")?;
+                root.to_file_path()
+                    .map_err(|()| anyhow::anyhow!("the workspace root is not a local directory"))?
+                    .join(DEFAULT_REPORT_NAME)
+            }
+        };
+
+        let diagnostics: Vec<_> = diagnostics
+            .into_iter()
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .collect();
+        let count = |severity| {
+            diagnostics
+                .iter()
+                .flat_map(|(_, items)| items)
+                .filter(|item| item.severity == Some(severity))
+                .count()
+        };
+        let (errors, warnings) = (
+            count(DiagnosticSeverity::ERROR),
+            count(DiagnosticSeverity::WARNING),
+        );
+
+        let report = sarif_report(&diagnostics, self.const_config().position_encoding);
+        let json = serde_json::to_vec_pretty(&report)?;
+        tokio::fs::write(&path, json)
+            .await
+            .with_context(|| format!("could not write {}", path.display()))?;
+
+        Ok(DiagnosticsSummary {
+            report: Url::from_file_path(&path)
+                .map_err(|()| anyhow::anyhow!("report path is not absolute"))?,
+            errors,
+            warnings,
+            exit_code: i32::from(errors > 0),
+        })
+    }
+}
+
+/// A SARIF 2.1.0 log with a result for each diagnostic, located in its file
+fn sarif_report(diagnostics: &[(Url, Vec<Diagnostic>)], encoding: PositionEncoding) -> Value {
+    let results: Vec<_> = diagnostics
+        .iter()
+        .flat_map(|(uri, items)| items.iter().map(move |item| sarif_result(uri, item)))
+        .collect();
+
+    // LSP positions count UTF-8 bytes with that encoding, which SARIF can't describe, so those
+    // columns are only right for ASCII lines
+    let column_kind = match encoding {
+        PositionEncoding::Utf16 => "utf16CodeUnits",
+        PositionEncoding::Utf8 => "unicodeCodePoints",
+    };
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "typst-lsp",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/nvarner/typst-lsp",
+                }
+            },
+            "columnKind": column_kind,
+            "results": results,
+        }]
+    })
+}
+
+fn sarif_result(uri: &Url, diagnostic: &Diagnostic) -> Value {
+    let level = match diagnostic.severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        _ => "note",
+    };
+    let rule_id = match &diagnostic.code {
+        Some(NumberOrString::String(code)) => code.clone(),
+        Some(NumberOrString::Number(code)) => code.to_string(),
+        None => diagnostic
+            .source
+            .clone()
+            .unwrap_or_else(|| "typst".to_owned()),
+    };
+    let range = diagnostic.range;
+
+    // SARIF lines and columns start at 1
+    json!({
+        "ruleId": rule_id,
+        "level": level,
+        "message": { "text": diagnostic.message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": uri },
+                "region": {
+                    "startLine": range.start.line + 1,
+                    "startColumn": range.start.character + 1,
+                    "endLine": range.end.line + 1,
+                    "endColumn": range.end.character + 1,
+                }
+            }
+        }]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::*;
+
+    #[test]
+    fn reports_diagnostics_as_results() {
+        let uri = Url::parse("file:///project/main.typ").unwrap();
+        let diagnostic = Diagnostic {
+            range: Range::new(Position::new(2, 4), Position::new(2, 9)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("typst".to_owned()),
+            message: "unknown variable: foo".to_owned(),
+            ..Default::default()
+        };
+
+        let report = sarif_report(&[(uri, vec![diagnostic])], PositionEncoding::Utf16);
+        let result = &report["runs"][0]["results"][0];
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["ruleId"], "typst");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"]["uri"],
+            "file:///project/main.typ"
+        );
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(location["region"]["startColumn"], 5);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use typst::syntax::{ast, LinkedNode, Source, SyntaxKind};
+
+use super::lint::walk;
+
+/// Margin around a snippet, which is otherwise sized to fit its content
+const SNIPPET_PAGE: &str = "#set page(width: auto, height: auto, margin: 5pt)";
+
+/// Builds a standalone document from the selected markup, for exporting it on its own. It is
+/// preceded by the imports, bindings and set and show rules at the top level of the file before
+/// the selection, so it looks and evaluates like it does in place. Fails with a message for the
+/// user if the selection can't stand alone.
+pub fn snippet_text(source: &Source, range: Range<usize>) -> Result<String, String> {
+    let selected = source
+        .text()
+        .get(range.clone())
+        .filter(|selected| !selected.trim().is_empty())
+        .ok_or("The selection is empty")?;
+
+    let root = LinkedNode::new(source.root());
+    check_references(&root, &range)?;
+
+    let mut text = String::new();
+    for child in root.children() {
+        let is_preamble = matches!(
+            child.kind(),
+            SyntaxKind::ModuleImport
+                | SyntaxKind::LetBinding
+                | SyntaxKind::SetRule
+                | SyntaxKind::ShowRule
+        );
+        if is_preamble && child.range().end <= range.start {
+            text.push('#');
+            text.push_str(&source.text()[child.range()]);
+            text.push('\n');
+        }
+    }
+    text.push_str(SNIPPET_PAGE);
+    text.push('\n');
+    text.push_str(selected);
+
+    Ok(text)
+}
+
+/// Fails if the selection references a label which isn't attached to anything in it
+fn check_references(root: &LinkedNode, range: &Range<usize>) -> Result<(), String> {
+    let mut defined = HashSet::new();
+    let mut referenced = Vec::new();
+    walk(root, &mut |node| {
+        if node.range().start < range.start || node.range().end > range.end {
+            return;
+        }
+        if let Some(label) = node.cast::<ast::Label>() {
+            if node.parent_kind() == Some(SyntaxKind::Markup) {
+                defined.insert(label.get().to_owned());
+            }
+        } else if let Some(reference) = node.cast::<ast::Ref>() {
+            referenced.push(reference.target().to_owned());
+        }
+    });
+
+    match referenced.iter().find(|name| !defined.contains(*name)) {
+        Some(name) => Err(format!(
+            "The selection references <{name}>, which is defined outside of it, so it can't be \
+             exported on its own"
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_preamble_before_selection() {
+        let text =
+            "#import \"lib.typ\": fig\n#set text(red)\nIntro\n#figure[Table]\n#set text(blue)";
+        let source = Source::detached(text);
+        let start = text.find("#figure").unwrap();
+        let end = text.find("\n#set text(blue)").unwrap();
+
+        assert_eq!(
+            snippet_text(&source, start..end).unwrap(),
+            format!("#import \"lib.typ\": fig\n#set text(red)\n{SNIPPET_PAGE}\n#figure[Table]")
+        );
+    }
+
+    #[test]
+    fn rejects_references_outside_selection() {
+        let text = "= Intro <intro>\nAs in @intro.";
+        let source = Source::detached(text);
+        let start = text.find("As").unwrap();
+
+        let err = snippet_text(&source, start..text.len()).unwrap_err();
+        assert!(err.contains("<intro>"), "{err}");
+    }
+}
@@ -1,25 +1,12 @@
+use std::ops::Range;
+
 use tower_lsp::lsp_types::SelectionRange;
-use typst::syntax::{LinkedNode, Source};
+use typst::syntax::{LinkedNode, Source, SyntaxKind};
 
-use crate::config::PositionEncoding;
 use crate::lsp_typst_boundary::{lsp_to_typst, typst_to_lsp, LspPosition};
 
 use super::TypstServer;
 
-fn range_for_node(
-    source: &Source,
-    position_encoding: PositionEncoding,
-    node: &LinkedNode,
-) -> SelectionRange {
-    let range = typst_to_lsp::range(node.range(), source, position_encoding);
-    SelectionRange {
-        range: range.raw_range,
-        parent: node
-            .parent()
-            .map(|node| Box::new(range_for_node(source, position_encoding, node))),
-    }
-}
-
 impl TypstServer {
     pub fn get_selection_range(
         &self,
@@ -33,8 +20,103 @@ impl TypstServer {
                 lsp_to_typst::position_to_offset(position, position_encoding, source);
             let tree = LinkedNode::new(source.root());
             let leaf = tree.leaf_at(typst_offset)?;
-            ranges.push(range_for_node(source, position_encoding, &leaf));
+
+            let selection = expanding_ranges(&leaf).into_iter().rev().fold(
+                None,
+                |parent: Option<SelectionRange>, range| {
+                    Some(SelectionRange {
+                        range: typst_to_lsp::range(range, source, position_encoding).raw_range,
+                        parent: parent.map(Box::new),
+                    })
+                },
+            )?;
+            ranges.push(selection);
         }
         Some(ranges)
     }
 }
+
+/// The ranges to select when expanding the selection from the leaf, innermost first: the leaf's
+/// ancestors, with the paragraph around nodes in markup before the whole markup. Ancestors which
+/// span the same range as their child are skipped, so each expansion grows the selection.
+fn expanding_ranges(leaf: &LinkedNode) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut node = leaf.clone();
+    loop {
+        push_growing(&mut ranges, node.range());
+
+        let Some(parent) = node.parent().cloned() else {
+            break;
+        };
+        if parent.kind() == SyntaxKind::Markup {
+            if let Some(paragraph) = paragraph(&parent, node.index()) {
+                push_growing(&mut ranges, paragraph);
+            }
+        }
+        node = parent;
+    }
+    ranges
+}
+
+fn push_growing(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    let grows = ranges
+        .last()
+        .is_none_or(|last| range.start <= last.start && range.end >= last.end && range != *last);
+    if grows {
+        ranges.push(range);
+    }
+}
+
+/// The range of the paragraph around the markup's child at `index`, without surrounding spaces
+fn paragraph(markup: &LinkedNode, index: usize) -> Option<Range<usize>> {
+    let children: Vec<_> = markup.children().collect();
+    let is_parbreak = |child: &LinkedNode| child.kind() == SyntaxKind::Parbreak;
+
+    let start = children[..index]
+        .iter()
+        .rposition(is_parbreak)
+        .map_or(0, |parbreak| parbreak + 1);
+    let end = children[index..]
+        .iter()
+        .position(is_parbreak)
+        .map_or(children.len(), |parbreak| index + parbreak);
+
+    let mut content = children
+        .get(start..end)?
+        .iter()
+        .filter(|child| child.kind() != SyntaxKind::Space);
+    let first = content.next()?;
+    let last = content.next_back().unwrap_or(first);
+    Some(first.offset()..last.range().end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expands_through_paragraph() {
+        let source = Source::detached("First *para*.\n\nSecond #box[in *content*] here.");
+        let offset = source.text().find("content").unwrap();
+        let root = LinkedNode::new(source.root());
+        let leaf = root.leaf_at(offset + 1).unwrap();
+
+        let texts: Vec<_> = expanding_ranges(&leaf)
+            .into_iter()
+            .map(|range| &source.text()[range])
+            .collect();
+
+        assert_eq!(
+            texts,
+            [
+                "content",
+                "*content*",
+                "in *content*",
+                "[in *content*]",
+                "box[in *content*]",
+                "Second #box[in *content*] here.",
+                source.text(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, InsertTextFormat, Range, TextEdit,
+};
+use tracing::warn;
+use typst::syntax::{LinkedNode, Source, SyntaxKind};
+
+/// A snippet written as markup. Snippets starting with `#` are also offered in code mode right
+/// after a `#`, without it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Snippet {
+    pub label: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+    /// The inserted text, in the LSP snippet syntax with tab stops like `$1` and `${2:default}`
+    pub body: String,
+}
+
+/// Where snippets can be inserted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnippetMode {
+    /// At the start of a line in markup
+    Markup,
+    /// Right after a `#` in markup
+    Code,
+}
+
+const BUILTIN: &[(&str, &str, &str)] = &[
+    (
+        "figure",
+        "Figure with an image and a caption",
+        "#figure(\n  image(\"${1:path}\"),\n  caption: [${2:Caption}],\n) <${3:label}>",
+    ),
+    (
+        "table",
+        "Table with a header row",
+        "#table(\n  columns: ${1:2},\n  table.header[${2:Header}][${3:Header}],\n  [$4], [$5],\n)",
+    ),
+    (
+        "code block",
+        "Raw block with syntax highlighting",
+        "```${1:lang}\n$0\n```",
+    ),
+    (
+        "bibliography",
+        "Bibliography from a file",
+        "#bibliography(\"${1:references.bib}\")",
+    ),
+    (
+        "theorem",
+        "Theorem environment",
+        "#let theorem = figure.with(kind: \"theorem\", supplement: [Theorem])\n#theorem[$0] <${1:label}>",
+    ),
+    ("equation", "Block equation", "$ $0 $"),
+];
+
+/// The built-in snippets followed by the ones in the user's snippets file, if any
+pub fn snippets(file: Option<&Path>) -> Vec<Snippet> {
+    let builtin = BUILTIN.iter().map(|&(label, detail, body)| Snippet {
+        label: label.to_owned(),
+        detail: Some(detail.to_owned()),
+        body: body.to_owned(),
+    });
+
+    let custom = file.map(read_snippets).unwrap_or_default();
+
+    builtin.chain(custom).collect()
+}
+
+fn read_snippets(path: &Path) -> Vec<Snippet> {
+    let snippets = fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str(&json)?));
+
+    snippets.unwrap_or_else(|err| {
+        warn!(%err, path = %path.display(), "could not read snippets file");
+        Vec::new()
+    })
+}
+
+/// Finds whether snippets can be inserted at the offset, and the offset of the word they would
+/// replace
+pub fn snippet_context(source: &Source, offset: usize) -> Option<(SnippetMode, usize)> {
+    let text = source.text().get(..offset)?;
+    let word = text
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .map(char::len_utf8)
+        .sum::<usize>();
+    let start = offset - word;
+    let before = &text[..start];
+
+    let root = LinkedNode::new(source.root());
+
+    if before.ends_with('#') {
+        // The leaf ending at the word is the `#`, unless it's escaped or in a string or raw text
+        let hash = root.leaf_at(start)?;
+        return (hash.kind() == SyntaxKind::Hash).then_some((SnippetMode::Code, start));
+    }
+
+    let leaf = root.leaf_at(offset)?;
+    let in_markup = leaf.parent_kind() == Some(SyntaxKind::Markup);
+
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (in_markup && before[line_start..].trim().is_empty()).then_some((SnippetMode::Markup, start))
+}
+
+pub fn completions(snippets: &[Snippet], mode: SnippetMode, range: Range) -> Vec<CompletionItem> {
+    snippets
+        .iter()
+        .filter_map(|snippet| {
+            let body = match mode {
+                SnippetMode::Markup => snippet.body.as_str(),
+                SnippetMode::Code => snippet.body.strip_prefix('#')?,
+            };
+
+            Some(CompletionItem {
+                label: snippet.label.clone(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: snippet.detail.clone(),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text: body.to_owned(),
+                })),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::Url;
+use tracing::warn;
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{ast, FileId, LinkedNode, Source};
+use typst::World;
+
+use crate::workspace::project::Project;
+
+use super::typst_compiler::compile;
+use super::TypstServer;
+
+/// The `#include` and `#import` relationships between the files of a compiled document.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceTree {
+    pub main: Url,
+    /// Every source reached while compiling, starting with the main source
+    pub files: Vec<SourceTreeFile>,
+    /// Typst files in the workspace which were not reached from the main source
+    pub orphans: Vec<Url>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceTreeFile {
+    pub uri: Url,
+    pub edges: Vec<SourceTreeEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceTreeEdge {
+    pub kind: EdgeKind,
+    /// The URI of the included or imported file, or the spec of an imported package
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EdgeKind {
+    Include,
+    Import,
+}
+
+enum EdgeTarget {
+    File(FileId),
+    Package(PackageSpec),
+}
+
+impl TypstServer {
+    /// Compiles the document and collects the sources Typst requested along the way, together with
+    /// the `#include` and `#import` edges between them.
+    pub async fn source_tree(&self, uri: &Url) -> anyhow::Result<SourceTree> {
+        let (project, files) = self
+            .scope_with_source(uri)
+            .await?
+            .run2(|source, project| async move {
+                let files = self
+                    .thread_with_world((source, project.clone()))
+                    .await?
+                    .run(|world| {
+                        compile(&world);
+
+                        let main = world.main();
+                        let accessed = world.accessed_files();
+                        let sources = std::iter::once(main.id())
+                            .chain(accessed.into_iter().filter(|&id| id != main.id()))
+                            .filter_map(|id| world.source(id).ok())
+                            .map(|source| (source.id(), source_edges(&source)))
+                            .collect::<Vec<_>>();
+
+                        (main.id(), sources)
+                    })
+                    .await?;
+
+                anyhow::Ok((project, files))
+            })
+            .await?;
+
+        let (main_id, sources) = files;
+        let main = id_to_uri(&project, main_id).await?;
+
+        let mut files = Vec::with_capacity(sources.len());
+        for (id, edges) in sources {
+            let Ok(uri) = id_to_uri(&project, id).await else {
+                continue;
+            };
+
+            let mut resolved = Vec::with_capacity(edges.len());
+            for (kind, target) in edges {
+                let target = match target {
+                    EdgeTarget::File(id) => match id_to_uri(&project, id).await {
+                        Ok(uri) => uri.to_string(),
+                        Err(err) => {
+                            warn!(%err, ?id, "could not resolve source tree edge");
+                            continue;
+                        }
+                    },
+                    EdgeTarget::Package(spec) => spec.to_string(),
+                };
+                resolved.push(SourceTreeEdge { kind, target });
+            }
+
+            files.push(SourceTreeFile {
+                uri,
+                edges: resolved,
+            });
+        }
+
+        // The project holds a read lock on the workspace, which must be released before locking it
+        // again, or a waiting writer could deadlock us
+        drop(project);
+
+        let reached: HashSet<&Url> = files.iter().map(|file| &file.uri).collect();
+        let mut orphans: Vec<Url> = self
+            .read_workspace()
+            .await
+            .known_uris()
+            .into_iter()
+            .filter(|uri| !reached.contains(uri))
+            .collect();
+        orphans.sort();
+
+        Ok(SourceTree {
+            main,
+            files,
+            orphans,
+        })
+    }
+}
+
+async fn id_to_uri(project: &Project, id: FileId) -> anyhow::Result<Url> {
+    Ok(project.full_id_to_uri(project.fill_id(id)).await?)
+}
+
+/// Finds the `#include` and `#import` expressions in the source whose path is a string literal.
+/// Dynamic paths can't be resolved without evaluating, so they are skipped.
+fn source_edges(source: &Source) -> Vec<(EdgeKind, EdgeTarget)> {
+    let mut edges = Vec::new();
+    collect_edges(source, &LinkedNode::new(source.root()), &mut edges);
+    edges
+}
+
+fn collect_edges(source: &Source, node: &LinkedNode, edges: &mut Vec<(EdgeKind, EdgeTarget)>) {
+    let edge = if let Some(include) = node.cast::<ast::ModuleInclude>() {
+        Some((EdgeKind::Include, include.source()))
+    } else {
+        node.cast::<ast::ModuleImport>()
+            .map(|import| (EdgeKind::Import, import.source()))
+    };
+
+    if let Some((kind, ast::Expr::Str(path))) = edge {
+        let path = path.get();
+        let target = if path.starts_with('@') {
+            PackageSpec::from_str(&path).ok().map(EdgeTarget::Package)
+        } else {
+            Some(EdgeTarget::File(source.id().join(&path)))
+        };
+        edges.extend(target.map(|target| (kind, target)));
+    }
+
+    for child in node.children() {
+        collect_edges(source, &child, edges);
+    }
+}
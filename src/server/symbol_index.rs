@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{SymbolInformation, Url};
+use tracing::{trace, warn};
+use typst::syntax::{LinkedNode, Source, SyntaxKind};
+
+use crate::config::PositionEncoding;
+
+use super::symbols::get_ident;
+use super::TypstServer;
+
+/// Symbols of every Typst file in the workspace, for `workspace/symbol`. Built on the first query,
+/// then kept up to date as files change.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    files: Option<HashMap<Url, Vec<SymbolInformation>>>,
+}
+
+impl TypstServer {
+    /// Searches the labels, headings and top-level definitions of all files in the workspace
+    pub async fn workspace_symbols(&self, query: &str) -> Vec<SymbolInformation> {
+        if self.symbol_index.lock().files.is_none() {
+            let files = self.index_workspace().await;
+            self.symbol_index.lock().files.get_or_insert(files);
+        }
+
+        let index = self.symbol_index.lock();
+        index
+            .files
+            .iter()
+            .flat_map(HashMap::values)
+            .flatten()
+            .filter(|symbol| fuzzy_match(query, &symbol.name))
+            .cloned()
+            .collect()
+    }
+
+    /// Reindexes the file after it changed, if the index was built already. Files which can't be
+    /// read anymore are dropped from the index.
+    pub async fn update_symbol_index(&self, uri: &Url) {
+        if self.symbol_index.lock().files.is_none() {
+            return;
+        }
+
+        let source = self.read_workspace().await.read_source(uri);
+        let position_encoding = self.const_config().position_encoding;
+
+        let mut index = self.symbol_index.lock();
+        let Some(files) = &mut index.files else {
+            return;
+        };
+        match source {
+            Ok(source) => {
+                files.insert(uri.clone(), index_source(&source, uri, position_encoding));
+            }
+            Err(_) => {
+                files.remove(uri);
+            }
+        }
+    }
+
+    async fn index_workspace(&self) -> HashMap<Url, Vec<SymbolInformation>> {
+        let position_encoding = self.const_config().position_encoding;
+        let workspace = self.read_workspace().await;
+
+        let uris = workspace.known_uris();
+        trace!(count = uris.len(), "indexing workspace symbols");
+
+        uris.into_iter()
+            .filter_map(|uri| match workspace.read_source(&uri) {
+                Ok(source) => Some((uri, source)),
+                Err(err) => {
+                    warn!(%err, %uri, "could not read source to index");
+                    None
+                }
+            })
+            .map(|(uri, source)| {
+                let symbols = index_source(&source, &uri, position_encoding);
+                (uri, symbols)
+            })
+            .collect()
+    }
+}
+
+fn index_source(
+    source: &Source,
+    uri: &Url,
+    position_encoding: PositionEncoding,
+) -> Vec<SymbolInformation> {
+    let mut symbols = Vec::new();
+    collect_symbols(
+        &LinkedNode::new(source.root()),
+        source,
+        uri,
+        position_encoding,
+        false,
+        &mut symbols,
+    );
+    symbols
+}
+
+/// Collects labels and headings anywhere, but definitions only outside of blocks, since local
+/// variables aren't useful to jump to from across the workspace
+fn collect_symbols(
+    node: &LinkedNode,
+    source: &Source,
+    uri: &Url,
+    position_encoding: PositionEncoding,
+    nested: bool,
+    symbols: &mut Vec<SymbolInformation>,
+) {
+    if !(nested && node.kind() == SyntaxKind::Ident) {
+        match get_ident(node, source, uri, None, position_encoding) {
+            Ok(symbol) => symbols.extend(symbol),
+            Err(err) => warn!(%err, %uri, "could not index symbol"),
+        }
+    }
+
+    let nested = nested
+        || matches!(
+            node.kind(),
+            SyntaxKind::CodeBlock | SyntaxKind::ContentBlock
+        );
+    for child in node.children() {
+        collect_symbols(&child, source, uri, position_encoding, nested, symbols);
+    }
+}
+
+/// Whether the characters of the query appear in order in the name, ignoring case
+fn fuzzy_match(query: &str, name: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|query_char| name.any(|name_char| name_char == query_char))
+}
@@ -23,7 +23,7 @@ pub fn get_symbols<'a>(
 
 /// Get symbol for a leaf node of a valid type, or `None` if the node is an invalid type.
 #[allow(deprecated)]
-fn get_ident(
+pub fn get_ident(
     node: &LinkedNode,
     source: &Source,
     uri: &Url,
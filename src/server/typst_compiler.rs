@@ -1,16 +1,27 @@
 use std::sync::Arc;
 
 use comemo::Track;
-use tower_lsp::lsp_types::Url;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tower_lsp::lsp_types::notification::Notification;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, MessageType, Range, Url};
+use tower_lsp::Client;
+use typst::diag::SourceDiagnostic;
 use typst::engine::Route;
 use typst::eval::Tracer;
 use typst::foundations::Module;
 use typst::model::Document;
 use typst::World;
 
+use crate::config::{LintDeprecatedMode, LintUnusedMode};
 use crate::lsp_typst_boundary::typst_to_lsp;
+use crate::workspace::world::typst_thread::TypstPanic;
+use crate::workspace::world::ProjectWorld;
+use crate::TYPST_VERSION;
 
+use super::deprecations;
 use super::diagnostics::DiagnosticsMap;
+use super::lint;
 use super::TypstServer;
 
 impl TypstServer {
@@ -19,33 +30,83 @@ impl TypstServer {
         &self,
         uri: &Url,
     ) -> anyhow::Result<(Option<Arc<Document>>, DiagnosticsMap)> {
+        let _compiling = CompilingGuard::begin(&self.client, &self.compiling, uri).await;
+        let (lint_unused, lint_deprecated, track_included) = {
+            let config = self.config.read().await;
+            (
+                config.lint_unused == LintUnusedMode::Enable,
+                config.lint_deprecated == LintDeprecatedMode::Enable,
+                // The files read are also what tells which documents watched assets affect
+                !config.main_files.is_empty() || self.asset_watches.lock().is_watching(),
+            )
+        };
+
         let doc = self
             .scope_with_source(uri)
             .await?
             .run2(|source, project| async move {
-                let (document, diagnostics) = self
+                let compiled = self
                     .thread_with_world((source, project.clone()))
                     .await?
-                    .run(|world| {
-                        comemo::evict(30);
-
-                        let mut tracer = Tracer::default();
-                        let result = typst::compile(&world, &mut tracer);
-
-                        let mut diagnostics = tracer.warnings();
-                        match result {
-                            Ok(document) => (Some(Arc::new(document)), diagnostics),
-                            Err(errors) => {
-                                diagnostics.extend_from_slice(&errors);
-                                (None, diagnostics)
-                            }
-                        }
+                    .run(move |world| {
+                        let compiled = compile(&world);
+                        let lints = (lint_unused || lint_deprecated).then(|| {
+                            let sources = lint::project_sources(&world);
+                            let unused = if lint_unused {
+                                lint::unused(&sources)
+                            } else {
+                                Vec::new()
+                            };
+                            (sources, unused)
+                        });
+                        let accessed = track_included.then(|| world.accessed_files());
+                        (compiled, lints, accessed)
                     })
                     .await;
+                let ((document, diagnostics), lints, accessed) = match compiled {
+                    Ok(compiled) => compiled,
+                    Err(panicked) => {
+                        self.client
+                            .show_message(
+                                MessageType::ERROR,
+                                format!("{panicked}. This is a bug in Typst, please report it."),
+                            )
+                            .await;
+                        // Otherwise the document just stops updating, with nothing pointing to why
+                        let diagnostics = DiagnosticsMap::from([(
+                            uri.clone(),
+                            vec![panic_diagnostic(&panicked)],
+                        )]);
+                        return Ok((None, diagnostics));
+                    }
+                };
 
-                let diagnostics =
-                    typst_to_lsp::diagnostics(&project, diagnostics.as_ref(), self.const_config())
+                let mut diagnostics =
+                    typst_to_lsp::diagnostics(&project, &diagnostics, self.const_config()).await;
+                if let Some((sources, unused)) = lints {
+                    let lints =
+                        lint::unused_diagnostics(&project, &sources, unused, self.const_config())
+                            .await;
+                    for (uri, lints) in lints {
+                        diagnostics.entry(uri).or_default().extend(lints);
+                    }
+
+                    if lint_deprecated {
+                        let deprecated = deprecations::deprecation_diagnostics(
+                            &project,
+                            &sources,
+                            self.const_config(),
+                        )
                         .await;
+                        for (uri, deprecated) in deprecated {
+                            diagnostics.entry(uri).or_default().extend(deprecated);
+                        }
+                    }
+                }
+
+                if let Some(accessed) = accessed {
+                    self.record_included_files(uri, &project, accessed).await;
+                }
 
                 let res: anyhow::Result<(Option<Arc<Document>>, DiagnosticsMap)> =
                     Ok((document, diagnostics));
@@ -75,7 +136,7 @@ impl TypstServer {
                     &world.main(),
                 )
             })
-            .await;
+            .await?;
 
         let (module, errors) = match result {
             Ok(module) => (Some(module), Default::default()),
@@ -89,3 +150,120 @@ impl TypstServer {
         Ok((module, diagnostics))
     }
 }
+
+/// Compiles the main source of the world. Returns the document, if compilation succeeded, along
+/// with all warnings and errors.
+pub fn compile(world: &ProjectWorld) -> (Option<Arc<Document>>, Vec<SourceDiagnostic>) {
+    comemo::evict(30);
+
+    let mut tracer = Tracer::default();
+    let result = typst::compile(world, &mut tracer);
+
+    let mut diagnostics: Vec<_> = tracer.warnings().into_iter().collect();
+    match result {
+        Ok(document) => (Some(Arc::new(document)), diagnostics),
+        Err(errors) => {
+            diagnostics.extend(errors);
+            (None, diagnostics)
+        }
+    }
+}
+
+/// A diagnostic at the start of the document for a panic while compiling it, with what to include
+/// when reporting it
+pub fn panic_diagnostic(panicked: &TypstPanic) -> Diagnostic {
+    Diagnostic {
+        range: Range::default(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("typst-lsp".to_owned()),
+        message: format!(
+            "Typst crashed while compiling this document: {}\n\nThis is a bug in Typst \
+             {TYPST_VERSION}. Please report it at https://github.com/typst/typst/issues with this \
+             message and the smallest document which still crashes.",
+            panicked.message
+        ),
+        ..Default::default()
+    }
+}
+
+/// Notification sent when compiling a file begins and ends, so editors can show that the server is
+/// busy
+pub enum Compiling {}
+
+impl Notification for Compiling {
+    type Params = CompilingParams;
+    const METHOD: &'static str = "typst-lsp/compiling";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompilingParams {
+    pub uri: Url,
+    pub compiling: bool,
+}
+
+/// Reports a compilation as running until dropped, both to the client and to the preview through
+/// the count of running compilations. Ending on drop keeps begin and end balanced even when the
+/// compile fails or its request is cancelled.
+struct CompilingGuard {
+    client: Client,
+    compiling: Arc<watch::Sender<usize>>,
+    uri: Url,
+}
+
+impl CompilingGuard {
+    async fn begin(client: &Client, compiling: &Arc<watch::Sender<usize>>, uri: &Url) -> Self {
+        compiling.send_modify(|count| *count += 1);
+        client
+            .send_notification::<Compiling>(CompilingParams {
+                uri: uri.clone(),
+                compiling: true,
+            })
+            .await;
+
+        Self {
+            client: client.clone(),
+            compiling: Arc::clone(compiling),
+            uri: uri.clone(),
+        }
+    }
+}
+
+impl Drop for CompilingGuard {
+    fn drop(&mut self) {
+        self.compiling
+            .send_modify(|count| *count = count.saturating_sub(1));
+
+        let client = self.client.clone();
+        let params = CompilingParams {
+            uri: self.uri.clone(),
+            compiling: false,
+        };
+        tokio::spawn(async move { client.send_notification::<Compiling>(params).await });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::workspace::world::typst_thread::TypstThread;
+
+    use super::*;
+
+    /// Stands in for a construct Typst panics on, since a real one is fixed once it's found
+    #[tokio::test]
+    async fn reports_panics_as_diagnostic() {
+        let thread = TypstThread::default();
+        let panicked = thread
+            .run(|_| -> usize { panic!("index out of bounds: the len is 0 but the index is 0") })
+            .await
+            .unwrap_err();
+
+        let diagnostic = panic_diagnostic(&panicked);
+        assert_eq!(diagnostic.range, Range::default());
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert!(diagnostic
+            .message
+            .contains("the len is 0 but the index is 0"));
+        assert!(diagnostic.message.contains(TYPST_VERSION));
+    }
+}
@@ -1,24 +1,40 @@
 use once_cell::sync::OnceCell;
 use send_wrapper::SendWrapper;
+use serde::{Deserialize, Serialize};
+use siphasher::sip128::{Hasher128, SipHasher13};
 use slint::{Model, ModelNotify, ModelTracker};
-use std::sync::mpsc::Receiver as StdReceiver;
-use std::sync::mpsc::Sender as StdSender;
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 use std::{cell::RefCell, sync::Mutex};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio::sync::oneshot;
+use tokio::sync::watch;
 use tokio::sync::RwLock;
+use tower_lsp::lsp_types::notification::Notification;
 use tower_lsp::lsp_types::Position as LspPosition;
-use tower_lsp::lsp_types::{Range, ShowDocumentParams, Url};
+use tower_lsp::lsp_types::{Range, ShowDocumentParams, TextEdit, Url, WorkspaceEdit};
 use tower_lsp::Client;
-use typst::layout::Position as TypstPosition;
+use typst::layout::{Frame, Position as TypstPosition};
 use typst::model::Document;
+use typst::syntax::ast::{self, AstNode};
+use typst::syntax::{FileId, LinkedNode, Source, SyntaxKind};
 use typst_ide::Jump;
 
-use crate::server::WorldThread;
+use crate::config::{
+    Config, JumpScrollMargin, PreviewLayout, PreviewPageStyle, PreviewQuality,
+    DEFAULT_PREVIEW_CACHED_PAGES, DEFAULT_PREVIEW_CACHE_MB,
+};
+use crate::server::deep_link::DeepLink;
+use crate::server::keybindings::{Keybindings, Modifiers};
+use crate::server::outline::headings;
+use crate::server::{WorldBuilder, WorldThread};
+use crate::workspace::package::external::manager::watch_downloads;
 use crate::workspace::package::PackageId;
-use crate::workspace::project::Project;
 use crate::workspace::world::typst_thread::TypstThread;
 use crate::workspace::Workspace;
 
@@ -28,30 +44,276 @@ use crate::workspace::Workspace;
 
 // Model that lazily converts pages of a typst `Document` to a `slint::image` when they are scrolled into view.
 // The usefulness of this comes from slint's `ListView` only instantiating elements that are visible.
+// `row_data` is only ever called by the `ListView`, so pages are rendered once they come into view.
+// Only the first `previewPrerenderPages` pages are rendered ahead of time, into the render cache.
+// `row_data` doesn't wait for the render, so the pages in view render in parallel: until a
+// render finishes, the row shows a blank image of the page's size, which the finished render then
+// replaces.
+//
+// At most `cache_limit.pages` rendered pages taking up at most `cache_limit.bytes` are kept. Beyond
+// that, the least recently viewed pages are dropped and rendered again once they are scrolled back
+// into view.
 pub struct LazyImagesModel {
     images: RefCell<Vec<Option<slint::Image>>>,
+    /// The size of each page in pixels once rendered, so rows keep their size while rendering
+    page_sizes: RefCell<Vec<(f32, f32)>>,
+    /// The rows being rendered, with the number of their render request. Results of requests
+    /// which aren't in here any more are outdated and dropped.
+    rendering: RefCell<HashMap<usize, u64>>,
+    requests: Cell<u64>,
+    // Rows that currently hold an image, least recently viewed first
+    recently_viewed: RefCell<VecDeque<usize>>,
+    cache_limit: Cell<CacheLimit>,
     notify: ModelNotify,
+    /// How the pages are grouped into rows for the list, see [`PageRowsModel`]
+    layout: Cell<PreviewLayout>,
+    rows_notify: ModelNotify,
+    /// Which list of the window shows the pages, to render them from its document
+    pane: Pane,
     ui_request_tx: Sender<UiRequest>,
-    pixelbuffer_rx: StdReceiver<slint::SharedPixelBuffer<slint::Rgba8Pixel>>,
 }
 
 impl LazyImagesModel {
-    pub fn new(
-        ui_request_tx: Sender<UiRequest>,
-        pixelbuffer_rx: StdReceiver<slint::SharedPixelBuffer<slint::Rgba8Pixel>>,
-    ) -> Self {
+    pub fn new(pane: Pane, ui_request_tx: Sender<UiRequest>) -> Self {
         LazyImagesModel {
+            pane,
             images: RefCell::new(Vec::new()),
+            page_sizes: Default::default(),
+            rendering: Default::default(),
+            requests: Default::default(),
+            recently_viewed: Default::default(),
+            cache_limit: Default::default(),
             notify: Default::default(),
+            layout: Default::default(),
+            rows_notify: Default::default(),
             ui_request_tx,
-            pixelbuffer_rx,
         }
     }
 
-    pub fn reset_all(&self, new_len: usize) {
+    /// Replaces all pages with ones of the given sizes, see [`page_pixel_sizes`]
+    pub fn reset_all(&self, page_sizes: Vec<(f32, f32)>) {
+        let new_len = page_sizes.len();
         *self.images.borrow_mut() = std::iter::repeat_with(|| None).take(new_len).collect();
+        *self.page_sizes.borrow_mut() = page_sizes;
+        self.rendering.borrow_mut().clear();
+        self.recently_viewed.borrow_mut().clear();
         self.notify.reset();
+        self.rows_notify.reset();
     }
+
+    /// Drops the images of the rows and tells the list, so visible rows are rendered again right
+    /// away and the others once they are scrolled into view. The pages may have changed size.
+    pub fn invalidate_rows(&self, rows: &[usize], page_sizes: Vec<(f32, f32)>) {
+        *self.page_sizes.borrow_mut() = page_sizes;
+        let list_rows = self.rows();
+        for &row in rows {
+            self.forget_row(row);
+            self.notify.row_changed(row);
+            if let Some(list_row) = list_rows.iter().position(|pages| pages.contains(&row)) {
+                self.rows_notify.row_changed(list_row);
+            }
+        }
+    }
+
+    /// Regroups the pages into rows. Rendered pages are kept, since only their arrangement changes.
+    pub fn set_layout(&self, layout: PreviewLayout) {
+        if self.layout.replace(layout) != layout {
+            self.rows_notify.reset();
+        }
+    }
+
+    fn rows(&self) -> Vec<std::ops::Range<usize>> {
+        layout_rows(self.layout.get(), self.images.borrow().len())
+    }
+
+    /// Drops the image of the row without telling the list, so it's rendered again the next time
+    /// the row is scrolled into view
+    pub fn forget_row(&self, row: usize) {
+        if let Some(image) = self.images.borrow_mut().get_mut(row) {
+            *image = None;
+        }
+        self.rendering.borrow_mut().remove(&row);
+        self.recently_viewed
+            .borrow_mut()
+            .retain(|&viewed| viewed != row);
+    }
+
+    /// Shows the page rendered for the request, unless the row was reset or invalidated since
+    pub fn finish_render(
+        &self,
+        row: usize,
+        request: u64,
+        pixel_buffer: slint::SharedPixelBuffer<slint::Rgba8Pixel>,
+    ) {
+        if self.rendering.borrow().get(&row) != Some(&request) {
+            return;
+        }
+        self.rendering.borrow_mut().remove(&row);
+        self.set_row_data(row, slint::Image::from_rgba8_premultiplied(pixel_buffer));
+    }
+
+    /// Forgets the request, which was dropped since the row was scrolled out of view. If the list
+    /// still shows the row, telling it about the change makes it ask for the page again.
+    pub fn cancel_render(&self, row: usize, request: u64) {
+        if self.rendering.borrow().get(&row) != Some(&request) {
+            return;
+        }
+        self.rendering.borrow_mut().remove(&row);
+        self.notify_row_changed(row);
+    }
+
+    fn notify_row_changed(&self, row: usize) {
+        self.notify.row_changed(row);
+        if let Some(list_row) = self.rows().iter().position(|pages| pages.contains(&row)) {
+            self.rows_notify.row_changed(list_row);
+        }
+    }
+
+    /// The size of the page once rendered, in pixels
+    fn page_size(&self, row: usize) -> (f32, f32) {
+        if let Some(Some(image)) = self.images.borrow().get(row) {
+            let size = image.size();
+            return (size.width as f32, size.height as f32);
+        }
+        self.page_sizes
+            .borrow()
+            .get(row)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_cache_limit(&self, cache_limit: CacheLimit) {
+        self.cache_limit.set(cache_limit);
+        self.evict();
+    }
+
+    fn mark_viewed(&self, row: usize) {
+        let mut recently_viewed = self.recently_viewed.borrow_mut();
+        if let Some(position) = recently_viewed.iter().position(|&viewed| viewed == row) {
+            recently_viewed.remove(position);
+        }
+        recently_viewed.push_back(row);
+    }
+
+    // Drop the least recently viewed images until we are within the cache limit. Rows which are
+    // still on screen keep showing their image since the `ListView` holds its own reference.
+    fn evict(&self) {
+        let cache_limit = self.cache_limit.get();
+
+        let mut recently_viewed = self.recently_viewed.borrow_mut();
+        let mut images = self.images.borrow_mut();
+
+        let mut cached_bytes: usize = recently_viewed
+            .iter()
+            .filter_map(|&row| images.get(row)?.as_ref())
+            .map(image_bytes)
+            .sum();
+        let exceeds_limit = |pages: usize, bytes: usize| {
+            cache_limit.pages.is_some_and(|limit| pages > limit)
+                || cache_limit.bytes.is_some_and(|limit| bytes > limit)
+        };
+
+        // Always keep the page that was just rendered
+        while recently_viewed.len() > 1 && exceeds_limit(recently_viewed.len(), cached_bytes) {
+            let Some(row) = recently_viewed.pop_front() else {
+                break;
+            };
+            if let Some(image) = images.get_mut(row).and_then(Option::take) {
+                cached_bytes -= image_bytes(&image);
+            }
+        }
+    }
+}
+
+/// Bounds on what the [`LazyImagesModel`] keeps cached. `None` means unbounded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheLimit {
+    pub pages: Option<usize>,
+    pub bytes: Option<usize>,
+}
+
+// Rendered pages are RGBA8 pixel buffers
+fn image_bytes(image: &slint::Image) -> usize {
+    let size = image.size();
+    size.width as usize * size.height as usize * 4
+}
+
+/// Rendered pages by hash of their frame and how they were rendered. Unlike the images of the
+/// [`LazyImagesModel`], these survive recompiles, so pages which didn't change after an edit
+/// elsewhere are shown without rendering them again. The buffers are shared with the images shown,
+/// so pages in both caches only take up memory once. Since pages of earlier revisions pile up
+/// here, the cache stays bounded by the default limits, [`DEFAULT_PREVIEW_CACHED_PAGES`] and
+/// [`DEFAULT_PREVIEW_CACHE_MB`], where the configuration leaves them unset.
+#[derive(Default)]
+pub struct RenderCache {
+    pages: HashMap<RenderKey, slint::SharedPixelBuffer<slint::Rgba8Pixel>>,
+    // Least recently used first
+    recently_used: VecDeque<RenderKey>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RenderKey {
+    frame: u128,
+    pixel_per_pt: u32,
+    invert: bool,
+}
+
+impl RenderKey {
+    fn new(frame: &Frame, pixel_per_pt: f32, invert: bool) -> Self {
+        let mut hasher = SipHasher13::new();
+        frame.hash(&mut hasher);
+        Self {
+            frame: hasher.finish128().as_u128(),
+            pixel_per_pt: pixel_per_pt.to_bits(),
+            invert,
+        }
+    }
+}
+
+impl RenderCache {
+    fn get(&mut self, key: &RenderKey) -> Option<slint::SharedPixelBuffer<slint::Rgba8Pixel>> {
+        let pixel_buffer = self.pages.get(key)?.clone();
+        self.mark_used(*key);
+        Some(pixel_buffer)
+    }
+
+    fn insert(
+        &mut self,
+        key: RenderKey,
+        pixel_buffer: slint::SharedPixelBuffer<slint::Rgba8Pixel>,
+        cache_limit: CacheLimit,
+    ) {
+        self.pages.insert(key, pixel_buffer);
+        self.mark_used(key);
+
+        let mut cached_bytes: usize = self.pages.values().map(pixel_buffer_bytes).sum();
+        let page_limit = cache_limit.pages.unwrap_or(DEFAULT_PREVIEW_CACHED_PAGES);
+        let byte_limit = cache_limit
+            .bytes
+            .unwrap_or(DEFAULT_PREVIEW_CACHE_MB * 1024 * 1024);
+        let exceeds_limit = |pages: usize, bytes: usize| pages > page_limit || bytes > byte_limit;
+
+        // Always keep the page that was just rendered
+        while self.recently_used.len() > 1 && exceeds_limit(self.pages.len(), cached_bytes) {
+            let Some(key) = self.recently_used.pop_front() else {
+                break;
+            };
+            if let Some(pixel_buffer) = self.pages.remove(&key) {
+                cached_bytes -= pixel_buffer_bytes(&pixel_buffer);
+            }
+        }
+    }
+
+    fn mark_used(&mut self, key: RenderKey) {
+        if let Some(position) = self.recently_used.iter().position(|&used| used == key) {
+            self.recently_used.remove(position);
+        }
+        self.recently_used.push_back(key);
+    }
+}
+
+fn pixel_buffer_bytes(pixel_buffer: &slint::SharedPixelBuffer<slint::Rgba8Pixel>) -> usize {
+    pixel_buffer.as_bytes().len()
 }
 
 impl Model for LazyImagesModel {
@@ -64,27 +326,32 @@ impl Model for LazyImagesModel {
     fn row_data(&self, row: usize) -> Option<Self::Data> {
         tracing::error!("getting page {} of doc", row);
 
-        let data = self
-            .images
-            .borrow_mut()
-            .get_mut(row)?
-            .get_or_insert_with(|| {
+        let image = self.images.borrow().get(row)?.clone();
+        let Some(image) = image else {
+            if !self.rendering.borrow().contains_key(&row) {
+                let request = self.requests.get() + 1;
+                self.requests.set(request);
+                self.rendering.borrow_mut().insert(row, request);
                 self.ui_request_tx
-                    .blocking_send(UiRequest::Render(row))
+                    .blocking_send(UiRequest::Render(self.pane, row, request))
                     .expect("requesting render failed");
+            }
+            // The list lays the row out by `page_size` until the render arrives
+            return Some(slint::Image::default());
+        };
 
-                let pixel_buffer = self.pixelbuffer_rx.recv().expect("receiving pixbuf failed");
-                slint::Image::from_rgba8_premultiplied(pixel_buffer)
-            })
-            .clone();
+        self.mark_viewed(row);
+        self.evict();
 
-        Some(data)
+        Some(image)
     }
 
     fn set_row_data(&self, row: usize, data: Self::Data) {
         if row < self.row_count() {
             self.images.borrow_mut()[row] = Some(data);
-            self.notify.row_changed(row);
+            self.mark_viewed(row);
+            self.evict();
+            self.notify_row_changed(row);
         }
     }
 
@@ -97,50 +364,329 @@ impl Model for LazyImagesModel {
     }
 }
 
+/// The pages of a [`LazyImagesModel`] grouped into the rows of the list by its layout. The list
+/// shows this, while presenting shows the pages one by one.
+pub struct PageRowsModel(std::rc::Rc<LazyImagesModel>);
+
+impl Model for PageRowsModel {
+    type Data = PageRow;
+
+    fn row_count(&self) -> usize {
+        self.0.rows().len()
+    }
+
+    fn row_data(&self, row: usize) -> Option<Self::Data> {
+        let mut pages = self.0.rows().get(row)?.clone();
+        let left_page = pages.start;
+        let left = self.0.row_data(left_page)?;
+        let (left_width, left_height) = self.0.page_size(left_page);
+        let right_page = pages.nth(1);
+        let right = right_page.and_then(|page| self.0.row_data(page));
+        let (right_width, right_height) =
+            right_page.map_or((0.0, 0.0), |page| self.0.page_size(page));
+
+        Some(PageRow {
+            left,
+            left_width,
+            left_height,
+            has_right: right.is_some(),
+            right: right.unwrap_or_default(),
+            right_width,
+            right_height,
+        })
+    }
+
+    fn model_tracker(&self) -> &dyn ModelTracker {
+        &self.0.rows_notify
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
 pub struct Ui {
     document: Mutex<Arc<Document>>,
     source_uri: Mutex<Option<Url>>,
     zoom: Mutex<f32>,
     workspace: Arc<OnceCell<Arc<RwLock<Workspace>>>>,
+    config: Arc<RwLock<Config>>,
     // TODO: Share a typst thread with the `TypstServer`? Like we share a `Workspace`?
     typst_thread: TypstThread,
     client: Client,
     main_window: slint::Weak<MainWindow>,
     images_model: Arc<SendWrapper<std::rc::Rc<LazyImagesModel>>>,
+    /// The document shown next to the preview, and the pages of it in the list
+    compare_document: Mutex<Option<Arc<Document>>>,
+    compare_model: Arc<SendWrapper<std::rc::Rc<LazyImagesModel>>>,
+    /// Kept alive once used, since on some platforms the copied image is only available as long
+    /// as the clipboard is
+    clipboard: Mutex<Option<arboard::Clipboard>>,
+    /// Where the headings listed in the outline panel are, by their index in the panel
+    outline_positions: Mutex<Vec<TypstPosition>>,
+    /// Whether the preview was toggled away from the configured `preview_invert`
+    invert_toggled: Mutex<bool>,
+    /// The layout switched to in the preview, overriding the configured `preview_layout`
+    layout_toggled: Mutex<Option<PreviewLayout>>,
+    render_cache: Arc<Mutex<RenderCache>>,
+    /// Pixels per point at 100% zoom of the pages currently in the list
+    render_scale: Mutex<f32>,
+    /// How the pages of the last document shown were rendered
+    shown_render: Mutex<Option<RenderLook>>,
+    drafts: Arc<Mutex<Drafts>>,
+    render_queue: Arc<RenderQueue>,
+    /// Where the list is scrolled to, kept up to date by the UI thread
+    viewport: Arc<Mutex<Viewport>>,
+    /// Incremented for every document, so overviews of replaced documents aren't shown
+    overview_generation: Arc<AtomicU64>,
+    /// Counts the documents pre-rendered, so pages of outdated documents are skipped
+    prerender_generation: Arc<AtomicU64>,
+    /// Shared with the key handler of the window, along with the config they were built from
+    keybindings: Arc<Mutex<Keybindings>>,
+    keybindings_config: Mutex<Option<BTreeMap<String, Vec<String>>>>,
+    /// The gap between pages in the list in logical pixels, as last set on the window, so
+    /// positions in the list are computed like the window lays it out
+    page_gap: Mutex<f32>,
+    ui_request_tx: Sender<UiRequest>,
+}
+
+/// Tracks pages rendered as drafts during fast scrolling or editing, to render them properly once
+/// there was no activity for the configured `previewRefineDelay`
+#[derive(Default)]
+struct Drafts {
+    /// Incremented whenever the list is reset, so drafts of replaced pages aren't refined
+    generation: u64,
+    pages: HashSet<usize>,
+    last_reset: Option<Instant>,
+    last_render: Option<Instant>,
+    /// Whether the list was reset twice within the refine delay, so pages are drafted until the
+    /// next refinement
+    editing: bool,
+    refine_delay: Duration,
+    /// Sends [`UiRequest::RefineDrafts`] once the refine delay passes, restarted by every draft
+    refine_timer: Option<tokio::task::AbortHandle>,
+}
+
+/// Render requests this close to each other come from scrolling
+const SCROLLING_INTERVAL: Duration = Duration::from_millis(150);
+
+impl Drafts {
+    /// Records a reset of the list, which outdates drafts being refined
+    fn reset(&mut self) {
+        let now = Instant::now();
+        self.editing = self
+            .last_reset
+            .is_some_and(|last_reset| now - last_reset < self.refine_delay);
+        self.generation += 1;
+        self.last_reset = Some(now);
+    }
+
+    /// Starts the timer for refining drafts over, cancelling the running one
+    fn restart_refine_timer(&mut self, refine_tx: Sender<UiRequest>) {
+        if let Some(timer) = self.refine_timer.take() {
+            timer.abort();
+        }
+        let delay = self.refine_delay;
+        let timer = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = refine_tx.send(UiRequest::RefineDrafts).await;
+        });
+        self.refine_timer = Some(timer.abort_handle());
+    }
+
+    /// Records the render request and decides whether it's from fast scrolling. Pages requested
+    /// right after the list was reset are the ones already in view, so they aren't drafted.
+    fn scrolling(&mut self) -> bool {
+        let now = Instant::now();
+        let since_reset = self.last_reset.map(|last_reset| now - last_reset);
+        let since_render = self.last_render.replace(now).map(|last| now - last);
+
+        since_reset.is_none_or(|since_reset| since_reset > SCROLLING_INTERVAL)
+            && since_render.is_some_and(|since_render| since_render < SCROLLING_INTERVAL)
+    }
 }
 
 pub struct NewDocumentMessage {
     pub document: Arc<Document>,
     pub source_uri: Url,
     pub first_change_range: Option<Range>,
+    /// The pages whose frames differ from the document shown before, so only those are rendered
+    /// again. `None` if unknown, which replaces all pages.
+    pub changed_pages: Option<Vec<usize>>,
+    /// Where to scroll to, or `None` to scroll to the first change, if there is one
+    pub scroll_to: Option<ScrollTo>,
+}
+
+/// Where to scroll the preview to when showing a document
+#[derive(Debug, Clone)]
+pub enum ScrollTo {
+    /// Where the editor's cursor in the file ends up in the document
+    Cursor(Url, LspPosition),
+    Top,
+}
+
+impl NewDocumentMessage {
+    /// Takes over the changes of the message skipped for this one, so pages changed by it are
+    /// still rendered again
+    fn merge_skipped(&mut self, skipped: NewDocumentMessage) {
+        self.first_change_range = self.first_change_range.or(skipped.first_change_range);
+        self.scroll_to = self.scroll_to.take().or(skipped.scroll_to);
+        self.changed_pages = match (self.changed_pages.take(), skipped.changed_pages) {
+            (Some(mut changed), Some(skipped)) => {
+                changed.extend(skipped);
+                changed.sort_unstable();
+                changed.dedup();
+                Some(changed)
+            }
+            _ => None,
+        };
+    }
+}
+
+/// Hashes of the frames of the document's pages, to tell which pages changed between documents
+pub fn page_hashes(document: &Document) -> Vec<u128> {
+    document
+        .pages
+        .iter()
+        .map(|page| {
+            let mut hasher = SipHasher13::new();
+            page.frame.hash(&mut hasher);
+            hasher.finish128().as_u128()
+        })
+        .collect()
+}
+
+/// The pages whose hashes differ, including pages only one of the documents has
+pub fn changed_pages(old: &[u128], new: &[u128]) -> Vec<usize> {
+    (0..old.len().max(new.len()))
+        .filter(|&page| old.get(page) != new.get(page))
+        .collect()
+}
+
+/// Notification sent after showing a file which shouldn't be edited, like one in the package cache,
+/// so editors can mark its buffer read-only
+pub enum ReadOnlyFile {}
+
+impl Notification for ReadOnlyFile {
+    type Params = ReadOnlyFileParams;
+    const METHOD: &'static str = "typst-lsp/readOnlyFile";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnlyFileParams {
+    pub uri: Url,
+}
+
+/// A list of pages in the window: the preview, or the document it's compared with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Main,
+    Compare,
+}
+
+/// A document to show next to the preview, see the `compare` command
+#[derive(Clone)]
+pub struct CompareDocument {
+    pub document: Arc<Document>,
+    /// Left and right of each other, rather than above and below
+    pub side_by_side: bool,
+    /// Scroll both lists together
+    pub sync_scroll: bool,
+}
+
+/// Options for the `compare` command
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompareOptions {
+    /// Show the file at this git revision, to compare it against the current version
+    pub revision: Option<String>,
+    pub side_by_side: bool,
+    pub sync_scroll: bool,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            revision: None,
+            side_by_side: true,
+            sync_scroll: true,
+        }
+    }
 }
 
 pub enum UiRequest {
-    Render(usize),
+    /// Render the page of the pane for the numbered request of its model
+    Render(Pane, usize, u64),
+    /// Render the drafted pages properly, once scrolling and editing stopped
+    RefineDrafts,
     JumpFromClick(ListViewClick),
     Zoom(f32),
+    CopyPage {
+        y: f32,
+        image_scale: f32,
+    },
+    JumpToOutline(usize),
+    CopyLabel(ListViewClick),
+    CopyDeepLink(ListViewClick),
+    /// Copy `file:line:col` of the source at the click, which is the middle of the viewport
+    CopySourceLocation(ListViewClick),
+    ToggleInvert,
+    ToggleLayout,
 }
 
 impl Ui {
     pub async fn run(
         workspace: Arc<OnceCell<Arc<RwLock<Workspace>>>>,
+        config: Arc<RwLock<Config>>,
         client: Client,
         mut to_ui_rx: Receiver<NewDocumentMessage>,
+        mut compiling_rx: watch::Receiver<usize>,
+        preview_visible: Arc<watch::Sender<bool>>,
+        mut compare_rx: watch::Receiver<Option<CompareDocument>>,
     ) {
         let (ui_request_tx, mut ui_request_rx) = channel(10);
-        let (pixelbuffer_tx, pixelbuffer_rx) = std::sync::mpsc::channel();
 
         let (tx_window_and_model, rx_window_and_model) = tokio::sync::oneshot::channel();
 
         // The UI / slint event loop thread
         let jump_click_tx = ui_request_tx.clone();
         let zoom_tx = ui_request_tx.clone();
+        let copy_page_tx = ui_request_tx.clone();
+        let outline_tx = ui_request_tx.clone();
+        let context_click_tx = ui_request_tx.clone();
+        let link_click_tx = ui_request_tx.clone();
+        let source_location_tx = ui_request_tx.clone();
+        let invert_tx = ui_request_tx.clone();
+        let layout_tx = ui_request_tx.clone();
+        let drafts_tx = ui_request_tx.clone();
+        let compare_tx = ui_request_tx.clone();
+        let viewport: Arc<Mutex<Viewport>> = Default::default();
+        let ui_viewport = Arc::clone(&viewport);
+        let keybindings: Arc<Mutex<Keybindings>> = Default::default();
+        let ui_keybindings = Arc::clone(&keybindings);
+        let close_visible_tx = Arc::clone(&preview_visible);
         thread::spawn(|| {
-            let images_model =
-                std::rc::Rc::new(LazyImagesModel::new(ui_request_tx, pixelbuffer_rx));
+            let images_model = std::rc::Rc::new(LazyImagesModel::new(Pane::Main, ui_request_tx));
+            let compare_model = std::rc::Rc::new(LazyImagesModel::new(Pane::Compare, compare_tx));
 
             let main_window = MainWindow::new().unwrap();
             main_window.set_image_sources(slint::ModelRc::from(images_model.clone()));
+            let rows_model = std::rc::Rc::new(PageRowsModel(images_model.clone()));
+            main_window.set_page_rows(slint::ModelRc::from(rows_model));
+            let compare_rows_model = std::rc::Rc::new(PageRowsModel(compare_model.clone()));
+            main_window.set_compare_rows(slint::ModelRc::from(compare_rows_model));
+
+            main_window.on_key_action(move |text, control, shift, alt, meta| {
+                let modifiers = Modifiers {
+                    control,
+                    shift,
+                    alt,
+                    meta,
+                };
+                let action = ui_keybindings.lock().unwrap().action(&text, modifiers);
+                action.map_or_else(Default::default, |action| action.name().into())
+            });
 
             main_window.on_zoom_changed(move |zoom| {
                 zoom_tx
@@ -148,19 +694,109 @@ impl Ui {
                     .expect("could not send zoom request");
             });
 
+            main_window.on_invert_toggled(move || {
+                invert_tx
+                    .blocking_send(UiRequest::ToggleInvert)
+                    .expect("could not send invert request");
+            });
+
+            main_window.on_layout_toggled(move || {
+                layout_tx
+                    .blocking_send(UiRequest::ToggleLayout)
+                    .expect("could not send layout request");
+            });
+
+            main_window.on_copy_page(move |y, image_scale| {
+                copy_page_tx
+                    .blocking_send(UiRequest::CopyPage { y, image_scale })
+                    .expect("could not send copy page request");
+            });
+
+            main_window.on_outline_clicked(move |index| {
+                outline_tx
+                    .blocking_send(UiRequest::JumpToOutline(index as usize))
+                    .expect("could not send outline jump request");
+            });
+
+            main_window.on_context_clicked(move |click: ListViewClick| {
+                context_click_tx
+                    .blocking_send(UiRequest::CopyLabel(click))
+                    .expect("could not send copy label request");
+            });
+
+            main_window.on_link_clicked(move |click: ListViewClick| {
+                link_click_tx
+                    .blocking_send(UiRequest::CopyDeepLink(click))
+                    .expect("could not send copy link request");
+            });
+
+            main_window.on_copy_source_location(move |click: ListViewClick| {
+                source_location_tx
+                    .blocking_send(UiRequest::CopySourceLocation(click))
+                    .expect("could not send copy source location request");
+            });
+
+            let presenting_window = main_window.as_weak();
+            main_window.on_presenting_changed(move |presenting| {
+                presenting_window
+                    .unwrap()
+                    .window()
+                    .set_fullscreen(presenting);
+            });
+
             main_window.on_clicked(move |click: ListViewClick| {
                 jump_click_tx
                     .blocking_send(UiRequest::JumpFromClick(click))
                     .expect("could not send jump click request");
             });
 
-            let _ =
-                tx_window_and_model.send((main_window.as_weak(), SendWrapper::new(images_model)));
+            // Polled rather than reported by the list, which has no callback for scrolling
+            let viewport_timer = slint::Timer::default();
+            let viewport_window = main_window.as_weak();
+            // Where both lists were scrolled to when last synchronized, to tell which of them was
+            // scrolled since
+            let synced_viewport_y = Cell::new(0.0);
+            viewport_timer.start(slint::TimerMode::Repeated, VIEWPORT_INTERVAL, move || {
+                if let Some(main_window) = viewport_window.upgrade() {
+                    *ui_viewport.lock().unwrap() = Viewport {
+                        top: main_window.get_list_viewport_y().abs(),
+                        height: main_window.get_list_visible_height(),
+                        pixel_scale: 1.6666666 / main_window.window().scale_factor(),
+                    };
+
+                    if main_window.get_comparing() && main_window.get_compare_sync() {
+                        let main_y = main_window.get_list_viewport_y();
+                        let compare_y = main_window.get_compare_viewport_y();
+                        if main_y != synced_viewport_y.get() {
+                            main_window.set_compare_viewport_y(main_y);
+                            synced_viewport_y.set(main_y);
+                        } else if compare_y != synced_viewport_y.get() {
+                            main_window.set_list_viewport_y(compare_y);
+                            synced_viewport_y.set(compare_y);
+                        }
+                    }
+                }
+            });
+
+            // Closing only hides the window, so `togglePreview` can bring it back as it was
+            main_window.window().on_close_requested(move || {
+                close_visible_tx.send_replace(false);
+                slint::CloseRequestResponse::HideWindow
+            });
+
+            let _ = tx_window_and_model.send((
+                main_window.as_weak(),
+                SendWrapper::new(images_model),
+                SendWrapper::new(compare_model),
+            ));
 
-            main_window.run().unwrap();
+            // Keeps running while the window is hidden, so documents still come in and it's
+            // current when shown again
+            main_window.show().unwrap();
+            slint::run_event_loop_until_quit().unwrap();
         });
 
-        let (main_window, images_model) = rx_window_and_model.await.unwrap();
+        let (main_window, images_model, compare_model) = rx_window_and_model.await.unwrap();
 
         let ui = Self {
             document: Default::default(),
@@ -168,9 +804,28 @@ impl Ui {
             zoom: Mutex::new(1.0),
             typst_thread: Default::default(),
             workspace,
+            config,
             client,
             main_window,
             images_model: Arc::new(images_model),
+            compare_document: Default::default(),
+            compare_model: Arc::new(compare_model),
+            clipboard: Default::default(),
+            outline_positions: Default::default(),
+            invert_toggled: Mutex::new(false),
+            layout_toggled: Default::default(),
+            render_cache: Default::default(),
+            render_scale: Mutex::new(PreviewQuality::default().render_scale()),
+            shown_render: Default::default(),
+            drafts: Default::default(),
+            render_queue: Default::default(),
+            viewport,
+            overview_generation: Default::default(),
+            prerender_generation: Default::default(),
+            keybindings,
+            keybindings_config: Default::default(),
+            page_gap: Mutex::new(PreviewPageStyle::default().gap),
+            ui_request_tx: drafts_tx,
         };
 
         // Wait for documents to come in from LSP
@@ -179,28 +834,37 @@ impl Ui {
                 tracing::error!("ok, got document!");
                 let mut msg = msg;
                 // Don't waste time rendering old versions.
-                while let Ok(next_msg) = to_ui_rx.try_recv() {
+                while let Ok(mut next_msg) = to_ui_rx.try_recv() {
                     tracing::error!("actually: skipping ahead, got more document!");
+                    next_msg.merge_skipped(msg);
                     msg = next_msg;
                 }
 
-                ui.show_document(msg.document, msg.source_uri, msg.first_change_range)
-                    .await;
+                ui.show_document(msg).await;
             }
         };
         // Wait for render requests to come in from slint UI
         let fut2 = async {
             while let Some(ui_request) = ui_request_rx.recv().await {
                 match ui_request {
-                    UiRequest::Render(page_index) => {
+                    UiRequest::Render(Pane::Main, page_index, request) => {
                         tracing::error!("got render request for pgae {}", page_index);
 
                         // Don't hold the lock the whole time, just clone the `Arc` (`to_owned()`)
                         let document = ui.document.lock().unwrap().to_owned();
 
-                        let zoom = ui.zoom.lock().unwrap().clone();
+                        let render = ui.render_request().await;
+                        let draft = render.scale > PreviewQuality::Draft.render_scale() && {
+                            let mut drafts = ui.drafts.lock().unwrap();
+                            drafts.scrolling() || drafts.editing
+                        };
+                        if draft {
+                            let mut drafts = ui.drafts.lock().unwrap();
+                            drafts.pages.insert(page_index);
+                            drafts.restart_refine_timer(ui.ui_request_tx.clone());
+                        }
 
-                        // Rendering can take a while. So spawn in separate task.
+                        // Rendering can take a while. So hand it to the render pool.
                         // This allows everything else here to proceed.
                         // Importantly, receiving documents can proceed!
                         // So if rendering does take long and lots of new documents come
@@ -208,30 +872,168 @@ impl Ui {
                         // received and will as the next step render the newest version (not all
                         // the already outdated intermediate versions that haven't been received
                         // yet).
-                        let response_tx = pixelbuffer_tx.clone();
-                        tokio::spawn(async move {
-                            Self::render_page(document, zoom, page_index, response_tx).await
+                        // The list shows this page, so it goes ahead of refining drafts. If it was
+                        // scrolled away by the time it's rendered, the render is dropped.
+                        let layout = ui.layout().await;
+                        let gap = *ui.page_gap.lock().unwrap();
+                        let viewport = Arc::clone(&ui.viewport);
+                        let model = Arc::clone(&ui.images_model);
+                        ui.render_queue.spawn(RenderPriority::Visible, move || {
+                            if !viewport.lock().unwrap().near(
+                                &document,
+                                layout,
+                                gap,
+                                page_index,
+                                render.zoom,
+                            ) {
+                                slint::invoke_from_event_loop(move || {
+                                    model.cancel_render(page_index, request)
+                                })
+                                .unwrap();
+                                return;
+                            }
+                            Self::render_page(document, render, page_index, draft, model, request)
                         });
                     }
+                    UiRequest::Render(Pane::Compare, page_index, request) => {
+                        let document = ui.compare_document.lock().unwrap().to_owned();
+                        let render = ui.render_request().await;
+                        let model = Arc::clone(&ui.compare_model);
+                        ui.render_queue.spawn(RenderPriority::Visible, move || {
+                            // Closed in the meantime
+                            if let Some(document) =
+                                document.filter(|document| page_index < document.pages.len())
+                            {
+                                Self::render_page(
+                                    document, render, page_index, false, model, request,
+                                )
+                            }
+                        });
+                    }
+                    UiRequest::RefineDrafts => {
+                        ui.refine_drafts().await;
+                    }
                     UiRequest::JumpFromClick(click) => {
                         tracing::error!("got ui click! {:?}", click);
                         ui.jump_from_click(click).await;
                     }
                     UiRequest::Zoom(zoom) => {
                         tracing::error!("got zoom request {}", zoom);
-                        *ui.zoom.lock().unwrap() = zoom.abs().max(0.3).min(3.0);
-                        let number_pages = ui.document.lock().unwrap().pages.len();
-
-                        let model = Arc::clone(&ui.images_model);
-                        slint::invoke_from_event_loop(move || {
-                            model.reset_all(number_pages);
-                        })
-                        .unwrap();
+                        let zoom = zoom.abs().clamp(0.3, 3.0);
+                        *ui.zoom.lock().unwrap() = zoom;
+                        ui.reset_pages();
+
+                        let zoom_label = format!("{:.0}%", zoom * 100.0);
+                        ui.main_window
+                            .upgrade_in_event_loop(move |main_window| {
+                                main_window.set_zoom_label(zoom_label.into());
+                            })
+                            .unwrap();
+                    }
+                    UiRequest::ToggleInvert => {
+                        tracing::error!("got invert request");
+                        {
+                            let mut invert_toggled = ui.invert_toggled.lock().unwrap();
+                            *invert_toggled = !*invert_toggled;
+                        }
+                        // Cached pages were rendered the other way
+                        ui.reset_pages();
+                    }
+                    UiRequest::ToggleLayout => {
+                        tracing::error!("got layout request");
+                        let layout = ui.layout().await.next();
+                        *ui.layout_toggled.lock().unwrap() = Some(layout);
+                        ui.apply_layout(layout);
+                    }
+                    UiRequest::CopyPage { y, image_scale } => {
+                        tracing::error!("got copy page request at y = {}", y);
+                        ui.copy_page(y, image_scale).await;
+                    }
+                    UiRequest::CopyLabel(click) => {
+                        tracing::error!("got ui context click! {:?}", click);
+                        ui.copy_label(click).await;
+                    }
+                    UiRequest::CopyDeepLink(click) => {
+                        ui.copy_deep_link(click).await;
+                    }
+                    UiRequest::CopySourceLocation(click) => {
+                        ui.copy_source_location(click).await;
+                    }
+                    UiRequest::JumpToOutline(index) => {
+                        let position = ui.outline_positions.lock().unwrap().get(index).cloned();
+                        if let Some(position) = position {
+                            let document = ui.document.lock().unwrap().to_owned();
+                            let zoom = *ui.zoom.lock().unwrap();
+                            ui.scroll(&document, zoom, &position).await;
+                        }
+                    }
+                }
+            }
+        };
+        // Show a spinner while the server is compiling
+        let fut3 = async {
+            while compiling_rx.changed().await.is_ok() {
+                let compiling = *compiling_rx.borrow_and_update() > 0;
+                ui.main_window
+                    .upgrade_in_event_loop(move |main_window| {
+                        main_window.set_compiling(compiling);
+                    })
+                    .unwrap();
+            }
+        };
+        // Show which packages are being downloaded, since compiling waits for them
+        let fut4 = async {
+            let mut downloads = watch_downloads();
+            let mut showing = false;
+            while downloads.changed().await.is_ok() {
+                let text = match downloads.borrow_and_update().as_slice() {
+                    [] => None,
+                    [spec] => Some(format!("Downloading {spec}...")),
+                    [spec, others @ ..] => Some(format!(
+                        "Downloading {spec} and {} more packages...",
+                        others.len()
+                    )),
+                };
+                match text {
+                    Some(text) => {
+                        ui.set_status(text.into(), HighlightMode::Normal);
+                        showing = true;
+                    }
+                    None if showing => {
+                        ui.set_status("".into(), HighlightMode::Normal);
+                        showing = false;
                     }
+                    None => {}
                 }
             }
         };
-        futures::join!(fut1, fut2);
+        // Hide and show the window for `togglePreview`, keeping the pages and the scroll position
+        let fut5 = async {
+            let mut visible_rx = preview_visible.subscribe();
+            while visible_rx.changed().await.is_ok() {
+                let visible = *visible_rx.borrow_and_update();
+                ui.main_window
+                    .upgrade_in_event_loop(move |main_window| {
+                        let shown = if visible {
+                            main_window.show()
+                        } else {
+                            main_window.hide()
+                        };
+                        if let Err(err) = shown {
+                            tracing::error!(%err, visible, "could not show or hide the preview");
+                        }
+                    })
+                    .unwrap();
+            }
+        };
+        // Show the document to compare with next to the preview, or close it
+        let fut6 = async {
+            while compare_rx.changed().await.is_ok() {
+                let compare = compare_rx.borrow_and_update().clone();
+                ui.show_compare(compare);
+            }
+        };
+        futures::join!(fut1, fut2, fut3, fut4, fut5, fut6);
     }
 
     fn workspace(&self) -> &Arc<RwLock<Workspace>> {
@@ -240,16 +1042,19 @@ impl Ui {
             .expect("workspace should be initialized")
     }
 
+    /// A world for the previewed document. It reads files through the same workspace as the
+    /// server, so open documents are read with their unsaved changes, like in the editor.
     async fn thread_with_world(&self) -> WorldThread {
-        let (main, main_project) = {
-            let uri = self.source_uri.lock().unwrap();
-            let uri = uri.as_ref().expect("Do not have a source uri");
-            let workspace = Arc::clone(self.workspace()).read_owned().await;
-            let full_id = workspace.full_id(&uri).unwrap();
-            let source = workspace.read_source(&uri).unwrap();
-            let project = Project::new(full_id.package(), workspace);
-            (source, project)
-        };
+        let uri = self
+            .source_uri
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("Do not have a source uri");
+        let (main, main_project) = WorldBuilder::MainUri(&uri)
+            .main_project(self.workspace())
+            .await
+            .unwrap();
 
         WorldThread {
             main,
@@ -258,116 +1063,106 @@ impl Ui {
         }
     }
 
-    async fn jump_from_click(&self, click: ListViewClick) {
+    /// Finds what's at the clicked position in the document. Shows why in the status bar if there's
+    /// nothing, but stays quiet about clicks between pages.
+    async fn jump_at_click(&self, document: &Arc<Document>, click: &ListViewClick) -> Option<Jump> {
         // Find the page from which the click came.
-        let document = self.document.lock().unwrap();
-        let document = document.to_owned();
-
-        let (page_index, page_x, page_y) = {
-            let mut page_y = click.listview_y;
-            let mut page_x = click.listview_x;
-            let mut found_page_index = None;
-            let mut ypos = 5.0;
-            for (page_index, page) in document.pages.iter().enumerate() {
-                page_y = click.listview_y - ypos;
-                ypos += (page.frame.height().to_pt() as f32) * click.image_scale;
-                tracing::error!(
-                    "checking -> checking if in page ending at {} (rel y = {})",
-                    ypos,
-                    page_y
-                );
-                if ypos > click.listview_y {
-                    let page_width = (page.frame.width().to_pt() as f32) * click.image_scale;
-                    let page_position_x = (click.viewport_visible_width - page_width) / 2.0;
-                    let page_position_x = page_position_x.max(0.0);
-                    page_x = click.listview_x - page_position_x;
-                    found_page_index = Some(page_index);
-                    break;
-                }
-                ypos += 10.0;
-            }
-            let Some(found_page_index) = found_page_index else {
-                return;
-            };
-            (found_page_index, page_x, page_y)
-        };
+        let layout = self.layout().await;
+        let gap = *self.page_gap.lock().unwrap();
+        let (pages, page_y) = row_at_y(document, layout, click.listview_y, click.image_scale, gap)?;
+        let (page_index, page_x) = page_at_x(
+            document,
+            pages,
+            click.listview_x,
+            click.image_scale,
+            click.viewport_visible_width,
+            gap,
+        );
         tracing::error!("-> click relative to page y = {}, x = {}", page_y, page_x);
 
         // Find jump location from position in that page
-        let (tx, rx) = oneshot::channel();
-        let document_for_typst = document.clone(); // Keep `document` alive for later
-        self.thread_with_world()
+        let document_for_typst = document.clone();
+        let image_scale = click.image_scale;
+        let jump = self
+            .thread_with_world()
             .await
             .run(move |world| {
                 // `image_scale` takes into account zoom level etc.
                 let point = typst::layout::Point {
-                    x: typst::layout::Abs::pt((page_x / click.image_scale).into()),
-                    y: typst::layout::Abs::pt((page_y / click.image_scale).into()),
+                    x: typst::layout::Abs::pt((page_x / image_scale).into()),
+                    y: typst::layout::Abs::pt((page_y / image_scale).into()),
                 };
-                let jump = typst_ide::jump_from_click(
+                typst_ide::jump_from_click(
                     &world,
                     &document_for_typst,
                     &document_for_typst.pages[page_index].frame,
                     point,
-                );
-                tx.send(jump).expect("couldn't send jump");
+                )
             })
             .await;
 
-        let jump = rx.await.expect("couldn't recv jump");
+        let Ok(jump) = jump else {
+            self.show_status(
+                "Typst failed to find the clicked location".into(),
+                HighlightMode::Warning,
+            );
+            return None;
+        };
         tracing::error!("-> got jump {:?}", jump);
 
-        let Some(jump) = jump else {
+        if jump.is_none() {
             self.position_highlight(click.x, click.y, HighlightMode::Warning);
             self.show_status("Nothing to click here...".into(), HighlightMode::Warning);
+        }
+        jump
+    }
+
+    /// The URL and source of a file the document was compiled from
+    async fn file_source(&self, file_id: FileId) -> (Url, Source) {
+        let workspace = Arc::clone(self.workspace()).read_owned().await;
+        let package_id = if let Some(package_spec) = file_id.package() {
+            // TODO: Is there a way to avoid the clone?
+            PackageId::new_external(package_spec.clone())
+        } else {
+            workspace
+                .full_id(
+                    self.source_uri
+                        .lock()
+                        .unwrap()
+                        .as_ref()
+                        .expect("Do not have a source uri?"),
+                )
+                .unwrap()
+                .package()
+        };
+
+        let package = workspace
+            .package_manager()
+            .package(package_id)
+            .await
+            .expect("package not found?");
+        let uri = package.vpath_to_uri(file_id.vpath()).unwrap();
+        let source = workspace.read_source(&uri).unwrap();
+
+        (uri, source)
+    }
+
+    async fn jump_from_click(&self, click: ListViewClick) {
+        let document = self.document.lock().unwrap().to_owned();
+        let Some(jump) = self.jump_at_click(&document, &click).await else {
             return;
         };
 
         // Do the jump
         match jump {
             Jump::Source(file_id, position) => {
-                let (uri, source) = {
-                    let workspace = Arc::clone(self.workspace()).read_owned().await;
-                    let package_id = if let Some(package_spec) = file_id.package() {
-                        // TODO: Is there a way to avoid the clone?
-                        PackageId::new_external(package_spec.clone())
-                    } else {
-                        workspace
-                            .full_id(
-                                self.source_uri
-                                    .lock()
-                                    .unwrap()
-                                    .as_ref()
-                                    .expect("Do not have a source uri?"),
-                            )
-                            .unwrap()
-                            .package()
-                    };
-
-                    let package = workspace
-                        .package_manager()
-                        .package(package_id)
-                        .await
-                        .expect("package not found?");
-                    let uri = package.vpath_to_uri(file_id.vpath()).unwrap();
-                    let source = workspace.read_source(&uri).unwrap();
-
-                    (uri, source)
-                };
-
-                let position = LspPosition {
-                    line: source
-                        .byte_to_line(position)
-                        .expect("couldn't map start line") as u32,
-                    character: source
-                        .byte_to_column(position)
-                        .expect("couldn't map start column") as u32,
-                };
+                let (uri, source) = self.file_source(file_id).await;
+                let position = lsp_position(&source, position);
 
                 tracing::error!("-> jump Source =  {:?}", uri);
 
                 let params = ShowDocumentParams {
-                    uri,
+                    uri: uri.clone(),
                     external: Some(false),
                     take_focus: Some(true),
                     // TODO: does this work with non-ascii?
@@ -382,10 +1177,19 @@ impl Ui {
                     .show_document(params)
                     .await
                     .expect("could not show document?");
+
+                // Files of external packages live in the package cache, where edits would be lost
+                // or, worse, change the package for every project using it
+                if file_id.package().is_some() {
+                    self.client
+                        .send_notification::<ReadOnlyFile>(ReadOnlyFileParams { uri })
+                        .await;
+                }
             }
             Jump::Position(position) => {
                 self.position_highlight(click.x, click.y, HighlightMode::Normal);
-                self.scroll(&document, self.zoom.lock().unwrap().clone(), &position);
+                let zoom = *self.zoom.lock().unwrap();
+                self.scroll(&document, zoom, &position).await;
             }
             Jump::Url(url) => {
                 let params = if let Ok(url) = Url::parse(url.as_str()) {
@@ -434,52 +1238,386 @@ impl Ui {
         };
     }
 
-    async fn show_document(
-        &self,
-        new_doc: Arc<Document>,
-        new_source_uri: Url,
-        first_change_range: Option<Range>,
-    ) {
-        let new_len = new_doc.pages.len();
-
-        *self.document.lock().unwrap() = new_doc;
-        *self.source_uri.lock().unwrap() = Some(new_source_uri);
+    /// Copies a reference to the label nearest to the clicked position. A heading without a label
+    /// gets one inserted into the source first.
+    async fn copy_label(&self, click: ListViewClick) {
+        let document = self.document.lock().unwrap().to_owned();
+        let Some(jump) = self.jump_at_click(&document, &click).await else {
+            return;
+        };
+        let Jump::Source(file_id, offset) = jump else {
+            self.show_status("No label here".into(), HighlightMode::Warning);
+            return;
+        };
 
-        let model = Arc::clone(&self.images_model);
-        slint::invoke_from_event_loop(move || {
-            model.reset_all(new_len);
-        })
-        .unwrap();
+        let (uri, source) = self.file_source(file_id).await;
+        let name = match label_target(&source, offset) {
+            Some(LabelTarget::Existing(name)) => name,
+            Some(LabelTarget::Heading { end, name }) => {
+                let position = lsp_position(&source, end);
+                let edit = TextEdit {
+                    range: Range {
+                        start: position,
+                        end: position,
+                    },
+                    new_text: format!(" <{name}>"),
+                };
+                let edit = WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri, vec![edit])])),
+                    ..Default::default()
+                };
+                let applied = self.client.apply_edit(edit).await;
+                if !applied.is_ok_and(|response| response.applied) {
+                    self.show_status(
+                        format!("Could not add label <{name}> to heading").into(),
+                        HighlightMode::Warning,
+                    );
+                    return;
+                }
+                name
+            }
+            None => {
+                self.position_highlight(click.x, click.y, HighlightMode::Warning);
+                self.show_status("No label or heading here".into(), HighlightMode::Warning);
+                return;
+            }
+        };
 
-        if let Some(range) = first_change_range {
-            self.jump_to_first_change(range).await;
+        let reference = format!("@{name}");
+        match self.with_clipboard(|clipboard| clipboard.set_text(&reference)) {
+            Ok(()) => {
+                self.position_highlight(click.x, click.y, HighlightMode::Normal);
+                self.show_status(format!("Copied {reference}").into(), HighlightMode::Normal);
+            }
+            Err(err) => {
+                tracing::error!(%err, "could not copy reference to clipboard");
+                self.show_status(
+                    format!("Could not copy {reference}: {err}").into(),
+                    HighlightMode::Warning,
+                );
+            }
         }
     }
 
-    async fn jump_to_first_change(&self, range: Range) {
-        // Don't hold the lock the whole time, just clone the `Arc` (`to_owned()`)
+    /// Copies a deep link to the source of the clicked position, see [`DeepLink`]
+    async fn copy_deep_link(&self, click: ListViewClick) {
         let document = self.document.lock().unwrap().to_owned();
-        let zoom = self.zoom.lock().unwrap().clone();
+        let Some(jump) = self.jump_at_click(&document, &click).await else {
+            return;
+        };
+        let Jump::Source(file_id, offset) = jump else {
+            self.show_status("No source to link to here".into(), HighlightMode::Warning);
+            return;
+        };
+
+        let (_, source) = self.file_source(file_id).await;
+        let Some(link) = DeepLink::new(file_id, &source, offset) else {
+            return;
+        };
+        let link = link.to_string();
+        match self.with_clipboard(|clipboard| clipboard.set_text(&link)) {
+            Ok(()) => {
+                self.position_highlight(click.x, click.y, HighlightMode::Normal);
+                self.show_status("Copied link to source".into(), HighlightMode::Normal);
+            }
+            Err(err) => {
+                tracing::error!(%err, "could not copy link to clipboard");
+                self.show_status(
+                    format!("Could not copy link: {err}").into(),
+                    HighlightMode::Warning,
+                );
+            }
+        }
+    }
+
+    /// Copies `file:line:col` of the source at the clicked position, for citing where something
+    /// in the preview is written
+    async fn copy_source_location(&self, click: ListViewClick) {
+        let document = self.document.lock().unwrap().to_owned();
+        let Some(jump) = self.jump_at_click(&document, &click).await else {
+            return;
+        };
+        let Jump::Source(file_id, offset) = jump else {
+            self.show_status("No source here".into(), HighlightMode::Warning);
+            return;
+        };
+
+        let (uri, source) = self.file_source(file_id).await;
+        let Some(location) = source_location(&uri, &source, offset) else {
+            return;
+        };
+        match self.with_clipboard(|clipboard| clipboard.set_text(&location)) {
+            Ok(()) => {
+                self.position_highlight(click.x, click.y, HighlightMode::Normal);
+                self.show_status(format!("Copied {location}").into(), HighlightMode::Normal);
+            }
+            Err(err) => {
+                tracing::error!(%err, "could not copy source location to clipboard");
+                self.show_status(
+                    format!("Could not copy {location}: {err}").into(),
+                    HighlightMode::Warning,
+                );
+            }
+        }
+    }
+
+    async fn show_document(&self, msg: NewDocumentMessage) {
+        let NewDocumentMessage {
+            document: new_doc,
+            source_uri: new_source_uri,
+            first_change_range,
+            changed_pages,
+            scroll_to,
+        } = msg;
+        let new_len = new_doc.pages.len();
+        let old_len = self.document.lock().unwrap().pages.len();
+        let (outline_items, outline_positions): (Vec<_>, _) = outline(&new_doc).into_iter().unzip();
+        *self.outline_positions.lock().unwrap() = outline_positions;
+
+        *self.document.lock().unwrap() = new_doc;
+        *self.source_uri.lock().unwrap() = Some(new_source_uri);
+
+        let (cache_limit, render_scale, bleed_pt, show_overview, page_style, profile, prerender) = {
+            let config = self.config.read().await;
+            self.update_keybindings(&config.preview_keybindings);
+            self.drafts.lock().unwrap().refine_delay = config.preview_refine_delay();
+            (
+                cache_limit(&config),
+                config.preview_quality().render_scale(),
+                config.preview_bleed_pt().unwrap_or(0.0) as f32,
+                config.preview_overview,
+                config.preview_page_style.clone(),
+                config.profile().and(config.active_profile.clone()),
+                config.preview_prerender_pages,
+            )
+        };
+        *self.page_gap.lock().unwrap() = page_style.gap;
+        *self.render_scale.lock().unwrap() = render_scale;
+        let render_look = self.render_request().await.look();
+        let old_render_look = self.shown_render.lock().unwrap().replace(render_look);
+
+        let model = Arc::clone(&self.images_model);
+        let compare_model = Arc::clone(&self.compare_model);
+        slint::invoke_from_event_loop(move || {
+            model.set_cache_limit(cache_limit);
+            compare_model.set_cache_limit(cache_limit);
+        })
+        .unwrap();
+        self.apply_layout(self.layout().await);
+        match changed_pages {
+            // Same pages rendered the same way, so only the changed ones need to be rendered again
+            Some(changed_pages) if new_len == old_len && old_render_look == Some(render_look) => {
+                self.invalidate_pages(changed_pages)
+            }
+            _ => self.reset_pages(),
+        }
+
+        self.main_window
+            .upgrade_in_event_loop(move |main_window| {
+                main_window.set_outline(slint::ModelRc::new(slint::VecModel::from(outline_items)));
+                main_window.set_bleed_pt(bleed_pt);
+                main_window.set_page_gap(page_style.gap);
+                main_window.set_page_gap_color(match page_style.gap_rgb() {
+                    Some([r, g, b]) => slint::Color::from_rgb_u8(r, g, b),
+                    None => slint::Color::from_argb_u8(0, 0, 0, 0),
+                });
+                main_window.set_page_shadow(page_style.shadow);
+                main_window.set_page_corner_radius(page_style.corner_radius);
+                main_window.set_profile_label(profile.unwrap_or_default().into());
+            })
+            .unwrap();
+
+        self.prerender_pages(prerender).await;
+        self.render_overview(show_overview).await;
+
+        match scroll_to {
+            Some(ScrollTo::Cursor(uri, position)) => self.jump_to_cursor(&uri, position).await,
+            Some(ScrollTo::Top) => self.scroll_to_top(),
+            None => {
+                if let Some(range) = first_change_range {
+                    self.jump_to_first_change(range).await;
+                }
+            }
+        }
+    }
+
+    /// Shows the document in a second list next to the preview's, or hides that list
+    fn show_compare(&self, compare: Option<CompareDocument>) {
+        let (page_sizes, side_by_side, sync_scroll) = match &compare {
+            Some(compare) => (
+                self.page_sizes(&compare.document),
+                compare.side_by_side,
+                compare.sync_scroll,
+            ),
+            None => (Vec::new(), true, false),
+        };
+        let comparing = compare.is_some();
+        *self.compare_document.lock().unwrap() = compare.map(|compare| compare.document);
+
+        let model = Arc::clone(&self.compare_model);
+        self.main_window
+            .upgrade_in_event_loop(move |main_window| {
+                main_window.set_comparing(comparing);
+                main_window.set_compare_side_by_side(side_by_side);
+                main_window.set_compare_sync(sync_scroll);
+                if sync_scroll {
+                    main_window.set_compare_viewport_y(main_window.get_list_viewport_y());
+                }
+                model.reset_all(page_sizes);
+            })
+            .unwrap();
+    }
+
+    /// Rebuilds the keybindings if their config changed. The UI starts before the config arrives from
+    /// the editor, so this happens with the documents rather than at startup.
+    fn update_keybindings(&self, config: &BTreeMap<String, Vec<String>>) {
+        let mut keybindings_config = self.keybindings_config.lock().unwrap();
+        if keybindings_config.as_ref() != Some(config) {
+            *self.keybindings.lock().unwrap() = Keybindings::new(config);
+            *keybindings_config = Some(config.clone());
+        }
+    }
+
+    /// Renders the first pages of the document in the background, into the render cache the list
+    /// takes pages from, so they show right away instead of being rendered once they're in view.
+    /// Pages the list waits for and drafts being refined go first.
+    async fn prerender_pages(&self, count: usize) {
+        let generation = self.prerender_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let document = self.document.lock().unwrap().to_owned();
+        let render = self.render_request().await;
+
+        for page_index in 0..count.min(document.pages.len()) {
+            let document = Arc::clone(&document);
+            let render = render.clone();
+            let prerender_generation = Arc::clone(&self.prerender_generation);
+            self.render_queue.spawn(RenderPriority::Prerender, move || {
+                if prerender_generation.load(Ordering::Relaxed) != generation {
+                    return;
+                }
+                // Unchanged pages are still in the cache, so this only renders the changed ones
+                render_pixel_buffer(&document, &render, page_index, false);
+            });
+        }
+    }
+
+    /// Renders all pages of the document at a tiny scale for the overview strip, in one batch in
+    /// the background, or clears the strip if it's hidden
+    async fn render_overview(&self, show_overview: bool) {
+        let generation = self.overview_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        if !show_overview {
+            self.main_window
+                .upgrade_in_event_loop(|main_window| {
+                    main_window.set_overview_pages(slint::ModelRc::default())
+                })
+                .unwrap();
+            return;
+        }
+
+        let document = self.document.lock().unwrap().to_owned();
+        let invert = self.render_request().await.invert;
+        let overview_generation = Arc::clone(&self.overview_generation);
+        let main_window = self.main_window.clone();
+        self.render_queue.spawn(RenderPriority::Overview, move || {
+            let is_current = || overview_generation.load(Ordering::Relaxed) == generation;
+            let mut pixel_buffers = Vec::with_capacity(document.pages.len());
+            for page in &document.pages {
+                if !is_current() {
+                    return;
+                }
+                let mut pixmap = typst_render::render(
+                    &page.frame,
+                    OVERVIEW_PIXEL_PER_PT,
+                    typst::visualize::Color::WHITE,
+                );
+                if invert {
+                    invert_lightness(pixmap.data_mut());
+                }
+                let (width, height) = (pixmap.width(), pixmap.height());
+                pixel_buffers.push(
+                    slint::SharedPixelBuffer::<slint::Rgba8Pixel>::clone_from_slice(
+                        &pixmap.take(),
+                        width,
+                        height,
+                    ),
+                );
+            }
+
+            let overview_generation = Arc::clone(&overview_generation);
+            main_window
+                .upgrade_in_event_loop(move |main_window| {
+                    if overview_generation.load(Ordering::Relaxed) != generation {
+                        return;
+                    }
+                    let images: Vec<_> = pixel_buffers
+                        .into_iter()
+                        .map(slint::Image::from_rgba8_premultiplied)
+                        .collect();
+                    main_window
+                        .set_overview_pages(slint::ModelRc::new(slint::VecModel::from(images)));
+                })
+                .unwrap();
+        });
+    }
+
+    async fn jump_to_first_change(&self, range: Range) {
+        let main_uri = self
+            .source_uri
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("Do not have a source uri");
+        self.jump_to_cursor(&main_uri, range.start).await;
+    }
+
+    /// Scrolls to where the position in the file ends up in the document
+    async fn jump_to_cursor(&self, uri: &Url, position: LspPosition) {
+        // Don't hold the lock the whole time, just clone the `Arc` (`to_owned()`)
+        let document = self.document.lock().unwrap().to_owned();
+        let zoom = self.zoom.lock().unwrap().clone();
+        let layout = self.layout().await;
+        let gap = *self.page_gap.lock().unwrap();
+        let margin = self.config.read().await.jump_scroll_margin;
 
         let source = {
-            let main_uri = self.source_uri.lock().unwrap();
-            let main_uri = main_uri.as_ref().expect("Do not have a source uri");
             let workspace = Arc::clone(self.workspace()).read_owned().await;
-            workspace.read_source(&main_uri).unwrap()
+            match workspace.read_source(uri) {
+                Ok(source) => source,
+                Err(err) => {
+                    tracing::error!(%err, %uri, "could not read source to scroll to");
+                    return;
+                }
+            }
         };
 
         // Spawn this since this can wait. Make room for new documents to come in as quickly as possible.
         let main_window = self.main_window.clone();
         tokio::spawn(async move {
             let cursor = source
-                .line_column_to_byte(range.start.line as usize, range.start.character as usize)
-                .unwrap_or_else(|| source.len_bytes() - 1);
+                .line_column_to_byte(position.line as usize, position.character as usize)
+                .unwrap_or_else(|| source.len_bytes().saturating_sub(1));
             if let Some(position) = typst_ide::jump_from_cursor(&document, &source, cursor + 1) {
-                Self::scroll_in_window(main_window, &document, zoom, &position);
+                Self::scroll_in_window(
+                    main_window,
+                    &document,
+                    layout,
+                    gap,
+                    zoom,
+                    margin,
+                    &position,
+                );
             }
         });
     }
 
+    fn scroll_to_top(&self) {
+        self.main_window
+            .upgrade_in_event_loop(|main_window| {
+                main_window.set_list_viewport_x(0.0);
+                main_window.set_list_viewport_y(0.0);
+            })
+            .unwrap();
+    }
+
     fn position_highlight(&self, x: f32, y: f32, mode: HighlightMode) {
         self.main_window
             .upgrade_in_event_loop(move |main_window| {
@@ -498,22 +1636,36 @@ impl Ui {
             .unwrap();
     }
 
-    fn scroll(&self, document: &Arc<Document>, zoom: f32, position: &TypstPosition) {
-        Self::scroll_in_window(self.main_window.clone(), document, zoom, position);
+    async fn scroll(&self, document: &Arc<Document>, zoom: f32, position: &TypstPosition) {
+        let layout = self.layout().await;
+        let gap = *self.page_gap.lock().unwrap();
+        let margin = self.config.read().await.jump_scroll_margin;
+        Self::scroll_in_window(
+            self.main_window.clone(),
+            document,
+            layout,
+            gap,
+            zoom,
+            margin,
+            position,
+        );
     }
 
     fn scroll_in_window(
         main_window: slint::Weak<MainWindow>,
         document: &Arc<Document>,
+        layout: PreviewLayout,
+        gap: f32,
         zoom: f32,
+        margin: JumpScrollMargin,
         position: &TypstPosition,
     ) {
         tracing::error!("-> got position to scroll to! {:?}", position);
         // TODO: sometimes this scrolls to the "correct" location only on the 2nd try/change.
         //       see https://github.com/slint-ui/slint/issues/4463
         let page_index = position.page.get() - 1;
-        let page_size = document.pages[page_index].frame.size().to_point().y.to_pt() as f32;
         let ypos = position.point.y;
+        let document = Arc::clone(document);
 
         main_window
             .upgrade_in_event_loop(move |main_window| {
@@ -522,10 +1674,8 @@ impl Ui {
                 let image_scale = zoom * (1.6666666 / main_window.window().scale_factor());
 
                 // add page offset, take into account zoom
-                // TODO: this assumes all pages have same height.
                 let ypos = (ypos.to_pt() as f32) * image_scale
-                    + 5.0
-                    + (page_index as f32) * (page_size * image_scale + 10.0);
+                    + row_top(&document, layout, page_index, image_scale, gap);
 
                 tracing::error!("scrolling to {:?} on page {:?}", ypos, page_index);
                 let current_ypos = main_window.get_list_viewport_y().abs();
@@ -533,38 +1683,234 @@ impl Ui {
 
                 // Only scroll if `ypos` not not already visible
                 if ypos < current_ypos || ypos > current_ypos + current_visible_height {
-                    // Don't put the last change at the very top of the viewport.
-                    // Want to see some stuff above last change as well.
-                    let ypos = ypos - current_visible_height * 0.3;
+                    // Don't put the last change at the very top of the viewport, unless configured
+                    // so. Want to see some stuff above last change as well.
+                    let ypos = ypos - margin.pixels(current_visible_height);
                     main_window.set_list_viewport_y(-ypos);
                 }
             })
             .unwrap();
     }
 
-    async fn render_page(
+    /// How pages are arranged in the list right now
+    async fn layout(&self) -> PreviewLayout {
+        let layout_toggled = *self.layout_toggled.lock().unwrap();
+        match layout_toggled {
+            Some(layout) => layout,
+            None => self.config.read().await.preview_layout,
+        }
+    }
+
+    fn apply_layout(&self, layout: PreviewLayout) {
+        let model = Arc::clone(&self.images_model);
+        let compare_model = Arc::clone(&self.compare_model);
+        slint::invoke_from_event_loop(move || {
+            model.set_layout(layout);
+            compare_model.set_layout(layout);
+        })
+        .unwrap();
+    }
+
+    /// How pages should be rendered right now
+    async fn render_request(&self) -> RenderRequest {
+        let zoom = *self.zoom.lock().unwrap();
+        let scale = *self.render_scale.lock().unwrap();
+        let config = self.config.read().await;
+        RenderRequest {
+            zoom,
+            scale,
+            invert: config.preview_invert != *self.invert_toggled.lock().unwrap(),
+            render_cache: Arc::clone(&self.render_cache),
+            cache_limit: cache_limit(&config),
+        }
+    }
+
+    /// Replaces all pages in the list, to be rendered again as they come into view
+    fn reset_pages(&self) {
+        let render_scale = *self.render_scale.lock().unwrap();
+        let page_sizes = self.page_sizes(&self.document.lock().unwrap());
+
+        // Hold the lock while queueing the reset, so drafts being refined are either shown before
+        // the reset or not at all
+        let mut drafts = self.drafts.lock().unwrap();
+        drafts.reset();
+        drafts.pages.clear();
+
+        // Rendered with the same zoom and scale, so they're replaced along with the preview's
+        let compare_page_sizes = self
+            .compare_document
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or_else(Vec::new, |document| self.page_sizes(document));
+
+        let model = Arc::clone(&self.images_model);
+        let compare_model = Arc::clone(&self.compare_model);
+        let main_window = self.main_window.clone();
+        slint::invoke_from_event_loop(move || {
+            if let Some(main_window) = main_window.upgrade() {
+                main_window.set_render_scale(render_scale);
+            }
+            model.reset_all(page_sizes);
+            compare_model.reset_all(compare_page_sizes);
+        })
+        .unwrap();
+    }
+
+    /// Renders the given pages again as they come into view, keeping all others
+    fn invalidate_pages(&self, pages: Vec<usize>) {
+        if pages.is_empty() {
+            return;
+        }
+
+        // Like a reset, but drafts of unchanged pages are still refined later on
+        let mut drafts = self.drafts.lock().unwrap();
+        drafts.reset();
+        drafts.pages.retain(|page| !pages.contains(page));
+
+        let page_sizes = self.page_sizes(&self.document.lock().unwrap());
+        let model = Arc::clone(&self.images_model);
+        slint::invoke_from_event_loop(move || model.invalidate_rows(&pages, page_sizes)).unwrap();
+    }
+
+    /// The sizes of the document's pages in pixels, rendered at the current zoom and scale
+    fn page_sizes(&self, document: &Document) -> Vec<(f32, f32)> {
+        let zoom = *self.zoom.lock().unwrap();
+        let render_scale = *self.render_scale.lock().unwrap();
+        page_pixel_sizes(document, zoom * render_scale)
+    }
+
+    /// Renders the drafted pages still in view properly, once the refine timer fired without
+    /// being restarted by more drafts
+    async fn refine_drafts(&self) {
+        let (generation, pages) = {
+            let mut drafts = self.drafts.lock().unwrap();
+            drafts.refine_timer = None;
+            drafts.editing = false;
+            (drafts.generation, std::mem::take(&mut drafts.pages))
+        };
+        if pages.is_empty() {
+            return;
+        }
+        tracing::error!("refining {} drafted pages", pages.len());
+
+        let document = self.document.lock().unwrap().to_owned();
+        let render = self.render_request().await;
+        let layout = self.layout().await;
+        let gap = *self.page_gap.lock().unwrap();
+        for page_index in pages {
+            let document = Arc::clone(&document);
+            let render = render.clone();
+            let drafts = Arc::clone(&self.drafts);
+            let viewport = Arc::clone(&self.viewport);
+            let model = Arc::clone(&self.images_model);
+            self.render_queue.spawn(RenderPriority::Refine, move || {
+                if drafts.lock().unwrap().generation != generation {
+                    return;
+                }
+                // Scrolled away since. Forget the draft instead, so the page is rendered properly
+                // once it's scrolled back into view.
+                if !viewport
+                    .lock()
+                    .unwrap()
+                    .shows(&document, layout, gap, page_index, render.zoom)
+                {
+                    slint::invoke_from_event_loop(move || model.forget_row(page_index)).unwrap();
+                    return;
+                }
+                let pixel_buffer = render_pixel_buffer(&document, &render, page_index, false);
+
+                let drafts = drafts.lock().unwrap();
+                if drafts.generation != generation {
+                    return;
+                }
+                slint::invoke_from_event_loop(move || {
+                    let image = slint::Image::from_rgba8_premultiplied(pixel_buffer);
+                    model.set_row_data(page_index, image);
+                })
+                .unwrap();
+            });
+        }
+    }
+
+    /// Renders the page and hands it to the model, which shows it if the request is still current
+    fn render_page(
         document: Arc<Document>,
-        zoom: f32,
+        render: RenderRequest,
         page_index: usize,
-        pixelbuffer_tx: StdSender<slint::SharedPixelBuffer<slint::Rgba8Pixel>>,
+        draft: bool,
+        model: Arc<SendWrapper<std::rc::Rc<LazyImagesModel>>>,
+        request: u64,
     ) {
         tracing::error!("-> rendering page {} of doc", page_index);
-        let frame = &document.pages.get(page_index).unwrap().frame;
-
-        tracing::error!("-> starting typst_render");
-        let pixmap = typst_render::render(frame, zoom * 3.0, typst::visualize::Color::WHITE);
-        tracing::error!("-> ... done");
-        let width = pixmap.width();
-        let height = pixmap.height();
-        let pixel_buffer = slint::SharedPixelBuffer::<slint::Rgba8Pixel>::clone_from_slice(
-            &pixmap.take(),
-            width,
-            height,
-        );
+        let start = Instant::now();
+        let pixel_buffer = render_pixel_buffer(&document, &render, page_index, draft);
+        tracing::error!("-> rendered page {} in {:?}", page_index, start.elapsed());
+
+        slint::invoke_from_event_loop(move || {
+            model.finish_render(page_index, request, pixel_buffer)
+        })
+        .unwrap();
+    }
+
+    /// Copies the page at the given y position in the list to the clipboard as an image
+    async fn copy_page(&self, y: f32, image_scale: f32) {
+        let document = self.document.lock().unwrap().to_owned();
+        // The left page of a spread, since the position comes from the keyboard rather than a click
+        let layout = self.layout().await;
+        let gap = *self.page_gap.lock().unwrap();
+        let Some((pages, _)) = row_at_y(&document, layout, y, image_scale, gap) else {
+            self.show_status("No page to copy".into(), HighlightMode::Warning);
+            return;
+        };
+        let page_index = pages.start;
+
+        let pixmap = tokio::task::spawn_blocking(move || {
+            let frame = &document.pages[page_index].frame;
+            typst_render::render(frame, COPY_PIXEL_PER_PT, typst::visualize::Color::WHITE)
+        })
+        .await
+        .expect("rendering page for clipboard panicked");
+
+        let image = arboard::ImageData {
+            width: pixmap.width() as usize,
+            height: pixmap.height() as usize,
+            bytes: pixmap.take().into(),
+        };
+
+        match self.with_clipboard(|clipboard| clipboard.set_image(image)) {
+            Ok(()) => self.show_status(
+                format!("Copied page {} to clipboard", page_index + 1).into(),
+                HighlightMode::Normal,
+            ),
+            Err(err) => {
+                tracing::error!(%err, "could not copy page to clipboard");
+                self.show_status(
+                    format!("Could not copy page: {err}").into(),
+                    HighlightMode::Warning,
+                );
+            }
+        }
+    }
+
+    fn with_clipboard(
+        &self,
+        f: impl FnOnce(&mut arboard::Clipboard) -> Result<(), arboard::Error>,
+    ) -> Result<(), arboard::Error> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+        if clipboard.is_none() {
+            *clipboard = Some(arboard::Clipboard::new()?);
+        }
+        f(clipboard.as_mut().unwrap())
+    }
 
-        pixelbuffer_tx
-            .send(pixel_buffer)
-            .expect("sending pixbuf failed");
+    /// Shows the status until it's replaced, unlike [`Self::show_status`]
+    fn set_status(&self, text: slint::SharedString, mode: HighlightMode) {
+        self.main_window
+            .upgrade_in_event_loop(move |main_window| {
+                main_window.set_status(Status { text, mode });
+            })
+            .unwrap();
     }
 
     fn show_status(&self, text: slint::SharedString, mode: HighlightMode) {
@@ -584,6 +1930,492 @@ impl Ui {
     }
 }
 
+/// The items for the preview's outline panel, each with the position in the document clicking it
+/// scrolls to
+fn outline(document: &Document) -> Vec<(OutlineItem, TypstPosition)> {
+    headings(document)
+        .into_iter()
+        .map(|heading| {
+            let item = OutlineItem {
+                title: heading.title.as_str().into(),
+                level: heading.level as i32,
+            };
+            (item, heading.position)
+        })
+        .collect()
+}
+
+/// Converts a byte offset to an LSP position the same way jumps to the source do
+fn lsp_position(source: &Source, offset: usize) -> LspPosition {
+    LspPosition {
+        line: source
+            .byte_to_line(offset)
+            .expect("couldn't map start line") as u32,
+        character: source
+            .byte_to_column(offset)
+            .expect("couldn't map start column") as u32,
+    }
+}
+
+/// `file:line:col` of the offset, with the path of local files and the URL of others, counting
+/// from 1 like compilers do
+fn source_location(uri: &Url, source: &Source, offset: usize) -> Option<String> {
+    let file = match uri.to_file_path() {
+        Ok(path) => path.display().to_string(),
+        Err(()) => uri.to_string(),
+    };
+    let line = source.byte_to_line(offset)? + 1;
+    let column = source.byte_to_column(offset)? + 1;
+    Some(format!("{file}:{line}:{column}"))
+}
+
+enum LabelTarget {
+    Existing(String),
+    /// A heading without a label, which could get one with the name at the end of the heading
+    Heading {
+        end: usize,
+        name: String,
+    },
+}
+
+/// Finds the label attached to the innermost element around the offset which has one, or else the
+/// heading around the offset
+fn label_target(source: &Source, offset: usize) -> Option<LabelTarget> {
+    let root = LinkedNode::new(source.root());
+    let leaf = root.leaf_at(offset)?;
+
+    let mut heading = None;
+    let mut node = Some(leaf);
+    while let Some(current) = node {
+        if let Some(label) = attached_label(&current) {
+            return Some(LabelTarget::Existing(label));
+        }
+        if current.kind() == SyntaxKind::Heading && heading.is_none() {
+            heading = Some(current.clone());
+        }
+        node = current.parent().cloned();
+    }
+
+    let heading = heading?;
+    let text = heading
+        .cast::<ast::Heading>()?
+        .body()
+        .to_untyped()
+        .clone()
+        .into_text();
+    let name = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    (!name.is_empty()).then(|| LabelTarget::Heading {
+        end: heading.range().end,
+        name,
+    })
+}
+
+/// The label after the node in markup, or at the end of the node if it's a heading
+fn attached_label(node: &LinkedNode) -> Option<String> {
+    let label_of = |node: &LinkedNode| {
+        node.cast::<ast::Label>()
+            .map(|label| label.get().to_owned())
+    };
+
+    if let Some(body) = node.cast::<ast::Heading>().map(|heading| heading.body()) {
+        let body = node.find(body.span())?;
+        if let Some(label) = body.children().rev().find_map(|child| label_of(&child)) {
+            return Some(label);
+        }
+    }
+
+    if node.parent_kind() != Some(SyntaxKind::Markup) {
+        return None;
+    }
+    // Skips the space in between
+    label_of(&node.next_sibling()?)
+}
+
+/// Inverts the lightness of premultiplied RGBA pixels while keeping their hue and chroma, so dark
+/// text on a light page turns light on a dark page, but red stays red instead of turning cyan
+fn invert_lightness(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]].map(i32::from);
+        // Shifting all channels by the same amount keeps hue and chroma, and this amount mirrors
+        // the lightness `(max + min) / 2` while staying within `0..=a`
+        let shift = a - r.max(g).max(b) - r.min(g).min(b);
+        for channel in &mut pixel[..3] {
+            *channel = (i32::from(*channel) + shift) as u8;
+        }
+    }
+}
+
+/// How urgently a page is needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RenderPriority {
+    /// The overview strip, which is only a help for seeking
+    Overview,
+    /// The first pages of a new document, which are likely shown soon
+    Prerender,
+    /// Refining a draft, which may have been scrolled out of view by now
+    Refine,
+    /// A page the list is waiting for to show it
+    Visible,
+}
+
+struct QueuedRender {
+    priority: RenderPriority,
+    /// Earlier renders of the same priority go first
+    order: Reverse<u64>,
+    job: Box<dyn FnOnce() + Send>,
+}
+
+impl PartialEq for QueuedRender {
+    fn eq(&self, other: &Self) -> bool {
+        (self.priority, self.order) == (other.priority, other.order)
+    }
+}
+
+impl Eq for QueuedRender {}
+
+impl PartialOrd for QueuedRender {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedRender {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.priority, self.order).cmp(&(other.priority, other.order))
+    }
+}
+
+/// Renders pages off the async runtime on a thread per CPU, since rendering is CPU-bound. Renders
+/// wait in a priority queue rather than the pool's own, so a page the list waits for doesn't sit
+/// behind refining drafts queued before it.
+struct RenderQueue {
+    pool: rayon::ThreadPool,
+    queued: Mutex<(BinaryHeap<QueuedRender>, u64)>,
+}
+
+impl Default for RenderQueue {
+    fn default() -> Self {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .thread_name(|index| format!("preview-render-{index}"))
+            .build()
+            .expect("could not start render threads");
+
+        Self {
+            pool,
+            queued: Default::default(),
+        }
+    }
+}
+
+impl RenderQueue {
+    fn spawn(self: &Arc<Self>, priority: RenderPriority, job: impl FnOnce() + Send + 'static) {
+        {
+            let mut queued = self.queued.lock().unwrap();
+            let (renders, next) = &mut *queued;
+            renders.push(QueuedRender {
+                priority,
+                order: Reverse(*next),
+                job: Box::new(job),
+            });
+            *next += 1;
+        }
+
+        // Each spawned task runs whichever render is most urgent by the time a thread is free
+        let queue = Arc::clone(self);
+        self.pool.spawn(move || {
+            let render = queue.queued.lock().unwrap().0.pop();
+            if let Some(render) = render {
+                (render.job)();
+            }
+        });
+    }
+}
+
+/// The part of the list in view, in logical pixels
+#[derive(Debug, Clone, Copy, Default)]
+struct Viewport {
+    top: f32,
+    height: f32,
+    /// The size of a point in logical pixels at 100% zoom
+    pixel_scale: f32,
+}
+
+/// How often the UI thread records where the list is scrolled to
+const VIEWPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+impl Viewport {
+    /// Whether the page is in view or within a screen of it at the given zoom, or where the list
+    /// is scrolled to isn't known yet. Scrolling is only recorded every [`VIEWPORT_INTERVAL`], so
+    /// pages just scrolled into view count as well.
+    fn near(
+        &self,
+        document: &Document,
+        layout: PreviewLayout,
+        gap: f32,
+        page_index: usize,
+        zoom: f32,
+    ) -> bool {
+        let around = Viewport {
+            top: self.top - self.height,
+            height: 3.0 * self.height,
+            ..*self
+        };
+        self.height <= 0.0 || around.shows(document, layout, gap, page_index, zoom)
+    }
+
+    /// Whether any part of the page is in view at the given zoom
+    fn shows(
+        &self,
+        document: &Document,
+        layout: PreviewLayout,
+        gap: f32,
+        page_index: usize,
+        zoom: f32,
+    ) -> bool {
+        let image_scale = zoom * self.pixel_scale;
+        let page_top = row_top(document, layout, page_index, image_scale, gap);
+        let page_bottom =
+            page_top + (document.pages[page_index].frame.height().to_pt() as f32) * image_scale;
+
+        page_top < self.top + self.height && page_bottom > self.top
+    }
+}
+
+/// How a page should be rendered for the preview
+#[derive(Clone)]
+struct RenderRequest {
+    zoom: f32,
+    /// Pixels per point at 100% zoom
+    scale: f32,
+    invert: bool,
+    render_cache: Arc<Mutex<RenderCache>>,
+    cache_limit: CacheLimit,
+}
+
+/// The parts of a [`RenderRequest`] which change how pages look
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RenderLook {
+    zoom: f32,
+    scale: f32,
+    invert: bool,
+}
+
+impl RenderRequest {
+    fn look(&self) -> RenderLook {
+        RenderLook {
+            zoom: self.zoom,
+            scale: self.scale,
+            invert: self.invert,
+        }
+    }
+}
+
+/// Renders a page for the preview. Drafts are rendered at the resolution of
+/// [`PreviewQuality::Draft`] and scaled up to the size of a proper render, so they take the same
+/// space in the list. Only proper renders are cached.
+///
+/// The pixels are premultiplied like tiny-skia renders them, as the images are created with
+/// `from_rgba8_premultiplied`. Transparent elements are composited onto the white page once,
+/// while rendering, which leaves every pixel opaque.
+fn render_pixel_buffer(
+    document: &Document,
+    render: &RenderRequest,
+    page_index: usize,
+    draft: bool,
+) -> slint::SharedPixelBuffer<slint::Rgba8Pixel> {
+    let frame = &document.pages.get(page_index).unwrap().frame;
+    let pixel_per_pt = render.zoom * render.scale;
+
+    let key = (!draft).then(|| RenderKey::new(frame, pixel_per_pt, render.invert));
+    if let Some(key) = &key {
+        if let Some(pixel_buffer) = render.render_cache.lock().unwrap().get(key) {
+            tracing::error!("-> unchanged since last render");
+            return pixel_buffer;
+        }
+    }
+
+    tracing::error!("-> starting typst_render (draft: {})", draft);
+    let mut pixmap = if draft {
+        let draft_pixel_per_pt = render.zoom * PreviewQuality::Draft.render_scale();
+        let pixmap =
+            typst_render::render(frame, draft_pixel_per_pt, typst::visualize::Color::WHITE);
+        let size = frame.size();
+        let pixels = |pt: f64| render_pixels(pt, pixel_per_pt);
+        scale_pixmap(pixmap, pixels(size.x.to_pt()), pixels(size.y.to_pt()))
+    } else {
+        typst_render::render(frame, pixel_per_pt, typst::visualize::Color::WHITE)
+    };
+    tracing::error!("-> ... done");
+    if render.invert {
+        invert_lightness(pixmap.data_mut());
+    }
+    let width = pixmap.width();
+    let height = pixmap.height();
+    let pixel_buffer = slint::SharedPixelBuffer::<slint::Rgba8Pixel>::clone_from_slice(
+        &pixmap.take(),
+        width,
+        height,
+    );
+
+    if let Some(key) = key {
+        render
+            .render_cache
+            .lock()
+            .unwrap()
+            .insert(key, pixel_buffer.clone(), render.cache_limit);
+    }
+    pixel_buffer
+}
+
+fn scale_pixmap(pixmap: tiny_skia::Pixmap, width: u32, height: u32) -> tiny_skia::Pixmap {
+    let Some(mut scaled) = tiny_skia::Pixmap::new(width, height) else {
+        return pixmap;
+    };
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / pixmap.width() as f32,
+        height as f32 / pixmap.height() as f32,
+    );
+    let paint = tiny_skia::PixmapPaint {
+        quality: tiny_skia::FilterQuality::Bilinear,
+        ..Default::default()
+    };
+    scaled.draw_pixmap(0, 0, pixmap.as_ref(), &paint, transform, None);
+    scaled
+}
+
+/// How many pixels a length in points takes up rendered at `pixel_per_pt`, rounded like
+/// `typst_render` does
+fn render_pixels(pt: f64, pixel_per_pt: f32) -> u32 {
+    (pt as f32 * pixel_per_pt).round().max(1.0) as u32
+}
+
+/// The sizes of the document's pages in pixels once rendered at `pixel_per_pt`
+fn page_pixel_sizes(document: &Document, pixel_per_pt: f32) -> Vec<(f32, f32)> {
+    document
+        .pages
+        .iter()
+        .map(|page| {
+            let size = page.frame.size();
+            let pixels = |pt: f64| render_pixels(pt, pixel_per_pt) as f32;
+            (pixels(size.x.to_pt()), pixels(size.y.to_pt()))
+        })
+        .collect()
+}
+
+fn cache_limit(config: &Config) -> CacheLimit {
+    CacheLimit {
+        pages: config.preview_cached_pages,
+        bytes: config.preview_cache_mb.map(|mb| mb * 1024 * 1024),
+    }
+}
+
+/// Pixels per point when copying a page, about 144 DPI
+const COPY_PIXEL_PER_PT: f32 = 2.0;
+
+/// Pixels per point of the pages in the overview strip, enough to recognize a page's layout
+const OVERVIEW_PIXEL_PER_PT: f32 = 0.1;
+
+/// The pages in each row of the list
+fn layout_rows(layout: PreviewLayout, page_count: usize) -> Vec<std::ops::Range<usize>> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    if layout == PreviewLayout::SpreadWithCover && page_count > 0 {
+        rows.push(0..1);
+        start = 1;
+    }
+
+    let per_row = match layout {
+        PreviewLayout::Single => 1,
+        PreviewLayout::Spread | PreviewLayout::SpreadWithCover => 2,
+    };
+    while start < page_count {
+        let end = (start + per_row).min(page_count);
+        rows.push(start..end);
+        start = end;
+    }
+    rows
+}
+
+/// Rows are as high as their tallest page
+fn row_height(document: &Document, pages: std::ops::Range<usize>, image_scale: f32) -> f32 {
+    document.pages[pages]
+        .iter()
+        .map(|page| (page.frame.height().to_pt() as f32) * image_scale)
+        .fold(0.0, f32::max)
+}
+
+/// Finds the row of pages at the given y position in the list of page images, along with the y
+/// position relative to the top of that row. Rows are laid out with half the gap above the first
+/// row and the gap between rows, with their pages at the top, like `page_gap` in `MainWindow`.
+fn row_at_y(
+    document: &Document,
+    layout: PreviewLayout,
+    y: f32,
+    image_scale: f32,
+    gap: f32,
+) -> Option<(std::ops::Range<usize>, f32)> {
+    let mut ypos = gap / 2.0;
+    for pages in layout_rows(layout, document.pages.len()) {
+        let row_y = y - ypos;
+        ypos += row_height(document, pages.clone(), image_scale);
+        if ypos > y {
+            return Some((pages, row_y));
+        }
+        ypos += gap;
+    }
+    None
+}
+
+/// The y position in the list of the top of the page's row
+fn row_top(
+    document: &Document,
+    layout: PreviewLayout,
+    page_index: usize,
+    image_scale: f32,
+    gap: f32,
+) -> f32 {
+    let mut ypos = gap / 2.0;
+    for pages in layout_rows(layout, document.pages.len()) {
+        if pages.contains(&page_index) {
+            break;
+        }
+        ypos += row_height(document, pages, image_scale) + gap;
+    }
+    ypos
+}
+
+/// Finds the page of the row at the given x position in the list, along with the x position
+/// relative to the left of that page. The pages of a row are centered with the gap between them.
+/// Positions between pages belong to the page on the left.
+fn page_at_x(
+    document: &Document,
+    pages: std::ops::Range<usize>,
+    x: f32,
+    image_scale: f32,
+    visible_width: f32,
+    gap: f32,
+) -> (usize, f32) {
+    let width =
+        |page_index: usize| (document.pages[page_index].frame.width().to_pt() as f32) * image_scale;
+    let row_width = pages.clone().map(width).sum::<f32>() + gap * (pages.len() - 1) as f32;
+
+    let mut page_position_x = ((visible_width - row_width) / 2.0).max(0.0);
+    let last = pages.end - 1;
+    for page_index in pages.start..last {
+        let next_position_x = page_position_x + width(page_index) + gap;
+        if x < next_position_x {
+            return (page_index, x - page_position_x);
+        }
+        page_position_x = next_position_x;
+    }
+    (last, x - page_position_x)
+}
+
 slint::slint! {
     import { ListView } from "std-widgets.slint";
 
@@ -603,63 +2435,383 @@ slint::slint! {
         viewport_visible_width: length,
     }
 
+    // The sizes are those of the rendered pages in pixels, known before the images are rendered
+    export struct PageRow {
+        left: image,
+        left_width: float,
+        left_height: float,
+        right: image,
+        right_width: float,
+        right_height: float,
+        has_right: bool,
+    }
+
+    export struct OutlineItem {
+        title: string,
+        level: int,
+    }
+
     export struct Status {
         text: string,
         mode: HighlightMode,
     }
 
+    // Outlines the trim box of a page and shades the bleed around it
+    component BleedOverlay inherits Rectangle {
+        in property <length> bleed;
+        property <color> bleed-color: #ff00ff30;
+        Rectangle { x: 0px; y: 0px; width: parent.width; height: bleed; background: bleed-color; }
+        Rectangle { x: 0px; y: parent.height - bleed; width: parent.width; height: bleed; background: bleed-color; }
+        Rectangle { x: 0px; y: bleed; width: bleed; height: parent.height - 2 * bleed; background: bleed-color; }
+        Rectangle { x: parent.width - bleed; y: bleed; width: bleed; height: parent.height - 2 * bleed; background: bleed-color; }
+        Rectangle {
+            x: bleed;
+            y: bleed;
+            width: parent.width - 2 * bleed;
+            height: parent.height - 2 * bleed;
+            border-width: 1px;
+            border-color: #ff00ff;
+        }
+    }
+
+    // A page of the list, optionally with rounded corners and a shadow like in a PDF viewer
+    component PageFrame inherits Rectangle {
+        in property <image> source;
+        in property <bool> shadow;
+        in property <length> corner-radius;
+        border-radius: corner-radius;
+        clip: corner-radius > 0;
+        background: shadow ? white : transparent;
+        drop-shadow-blur: shadow ? 6px : 0px;
+        drop-shadow-offset-y: shadow ? 2px : 0px;
+        drop-shadow-color: rgba(0, 0, 0, 0.4);
+        Image {
+            width: parent.width;
+            height: parent.height;
+            source: source;
+        }
+    }
+
+    // A row of the list with one page, or two side by side
+    component PageRowItem inherits Rectangle {
+        in property <PageRow> page_row;
+        in property <float> render_scale;
+        in property <float> zoom;
+        in property <float> bleed_pt;
+        in property <length> page_gap;
+        in property <bool> page_shadow;
+        in property <length> page_corner_radius;
+        // Pages are rendered at `render_scale` pixels per point
+        property <length> left-width: (page_row.left_width/render_scale) * 1px * (1.6666666 * 1phx/1px);
+        property <length> right-width: page_row.has_right ? (page_row.right_width/render_scale) * 1px * (1.6666666 * 1phx/1px) : 0px;
+        property <length> left-height: (page_row.left_height/render_scale) * 1px * (1.6666666 * 1phx/1px);
+        property <length> right-height: page_row.has_right ? (page_row.right_height/render_scale) * 1px * (1.6666666 * 1phx/1px) : 0px;
+        property <length> bleed: bleed_pt * 1px * (1.6666666 * 1phx/1px) * zoom;
+        width: page_row.has_right ? self.left-width + page_gap + self.right-width : self.left-width;
+        height: (max(page_row.left_height, page_row.has_right ? page_row.right_height : 0)/render_scale) * 1px * (1.6666666 * 1phx/1px) + page_gap; // + gap for spacing
+        PageFrame {
+            x: 0px;
+            y: page_gap / 2;
+            width: parent.left-width;
+            height: parent.left-height;
+            source: page_row.left;
+            shadow: page_shadow;
+            corner-radius: page_corner_radius;
+        }
+        PageFrame {
+            x: parent.left-width + page_gap;
+            y: page_gap / 2;
+            width: parent.right-width;
+            height: parent.right-height;
+            visible: page_row.has_right;
+            source: page_row.right;
+            shadow: page_shadow;
+            corner-radius: page_corner_radius;
+        }
+        if bleed_pt > 0 : BleedOverlay {
+            x: 0px;
+            y: page_gap / 2;
+            width: parent.left-width;
+            height: parent.left-height;
+            bleed: parent.bleed;
+        }
+        if bleed_pt > 0 && page_row.has_right : BleedOverlay {
+            x: parent.left-width + page_gap;
+            y: page_gap / 2;
+            width: parent.right-width;
+            height: parent.right-height;
+            bleed: parent.bleed;
+        }
+    }
+
     export component MainWindow inherits Window {
         in property <[image]> image_sources;
+        // The pages grouped into rows by the layout
+        in property <[PageRow]> page_rows;
+        in-out property <length> list_viewport_x <=> mylist.viewport-x;
         in-out property <length> list_viewport_y <=> mylist.viewport-y;
         out property <length> list_visible_height <=> mylist.visible-height;
+        // A second document shown next to the preview, see `show_compare`
+        in property <[PageRow]> compare_rows;
+        in property <bool> comparing: false;
+        in property <bool> compare_side_by_side: true;
+        // Scrolled along with the preview, synchronized from Rust
+        in property <bool> compare_sync: false;
+        in-out property <length> compare_viewport_y <=> comparelist.viewport-y;
 
         property<float> zoom: 1.0;
+        in property <float> render_scale: 3.0;
+        // How far the bleed extends past the trim box of pages in points, shown if it's positive
+        in property <float> bleed_pt: 0;
+        // Between pages, with half of it above the first row. Positions in the list are computed
+        // in Rust from the same gap, see `row_at_y`.
+        in property <length> page_gap: 10px;
+        in property <color> page_gap_color: transparent;
+        in property <bool> page_shadow: false;
+        in property <length> page_corner_radius: 0px;
         callback zoom_changed(float);
+        callback invert_toggled();
+        callback layout_toggled();
+        // The y position in the list of the page to copy, and the scale of the page images
+        callback copy_page(length, float);
+
+        // Shows one page at a time, fit to the screen, like slides
+        property <bool> presenting: false;
+        property <int> presentation_page: 0;
+        callback presenting_changed(bool);
+
+        // The name of the action bound to the key press with the modifiers control, shift, alt and
+        // meta, or "" if there is none. Keys are configurable, so Rust dispatches them.
+        callback key_action(string, bool, bool, bool, bool) -> string;
+        function run-action(action: string) {
+            if (action == "present") {
+                presenting = !presenting;
+                presentation_page = 0;
+                presenting-changed(presenting);
+                return;
+            }
+            if (presenting) {
+                if (action == "exit-presentation") {
+                    presenting = false;
+                    presenting-changed(presenting);
+                }
+                if (action == "next-page") {
+                    presentation_page = min(presentation_page + 1, image_sources.length - 1);
+                }
+                if (action == "previous-page") {
+                    presentation_page = max(presentation_page - 1, 0);
+                }
+                return;
+            }
+            if (action == "zoom-in") {
+                zoom = min(zoom + 0.1, 3.0);
+                zoom-changed(zoom);
+            }
+            if (action == "zoom-out") {
+                zoom = max(zoom - 0.1, 0.3);
+                zoom-changed(zoom);
+            }
+            if (action == "zoom-reset") {
+                zoom = 1.0;
+                zoom-changed(zoom);
+            }
+            if (action == "toggle-invert") {
+                invert-toggled();
+            }
+            if (action == "toggle-layout") {
+                layout-toggled();
+            }
+            if (action == "toggle-outline") {
+                outline_visible = !outline_visible;
+            }
+            if (action == "copy-page") {
+                copy-page(
+                    - mylist.viewport-y + mylist.visible-height / 2,
+                    (1.6666666 * 1phx/1px) * zoom,
+                );
+            }
+            if (action == "copy-source-location") {
+                copy-source-location(click_at(mylist.visible-width / 2, mylist.visible-height / 2));
+            }
+        }
 
         forward-focus: my-key-handler;
         my-key-handler := FocusScope {
             key-pressed(event) => {
-                if (event.modifiers.control) {
-                    if (event.text == "=") {
-                        zoom = min(zoom + 0.1, 3.0);
-                        zoom-changed(zoom);
-                    }
-                    if (event.text == "-") {
-                        zoom = max(zoom - 0.1, 0.3);
-                        zoom-changed(zoom);
-                    }
-                }
+                run-action(key-action(event.text, event.modifiers.control, event.modifiers.shift, event.modifiers.alt, event.modifiers.meta));
                 accept
             }
         }
 
         callback clicked(ListViewClick);
+        // Right click, to copy a reference to what's there
+        callback context_clicked(ListViewClick);
+        // Middle click, to copy a deep link to the source of what's there
+        callback link_clicked(ListViewClick);
+        // Shortcut, to copy `file:line:col` of what's in the middle of the viewport
+        callback copy_source_location(ListViewClick);
+        function click_at(x: length, y: length) -> ListViewClick {
+            return {
+                x: x,
+                y: y,
+                // note: viewport offset is negative
+                listview_x: - mylist.viewport-x + x,
+                listview_y: - mylist.viewport-y + y,
+                image_scale: (1.6666666 * 1phx/1px)*zoom,
+                viewport_visible_width: mylist.visible-width,
+            };
+        }
+        // Dragging pans the pages once the pointer moved further than this, shorter drags are
+        // still clicks
+        property <length> pan-threshold: 4px;
+        property <bool> panning: false;
+        // The viewport when the drag started
+        property <length> pan-start-x;
+        property <length> pan-start-y;
+        // Behind the list, so it shows between the pages
+        Rectangle {
+            width: mylist.width;
+            height: mylist.height;
+            background: page_gap_color;
+        }
         my-touch-area := TouchArea {
             width: mylist.width;
             height: mylist.height;
+            mouse-cursor: panning ? MouseCursor.grabbing : MouseCursor.default;
             clicked => {
-                clicked({
-                    x: my-touch-area.pressed-x,
-                    y: my-touch-area.pressed-y,
-                    // note: viewport offset is negative
-                    listview_x: - mylist.viewport-x + my-touch-area.pressed-x,
-                    listview_y: - mylist.viewport-y + my-touch-area.pressed-y,
-                    image_scale: (1.6666666 * 1phx/1px)*zoom,
-                    viewport_visible_width: mylist.visible-width,
-               });
+                if (!panning) {
+                    clicked(click_at(my-touch-area.pressed-x, my-touch-area.pressed-y));
+                }
+            }
+            pointer-event(event) => {
+                if (event.button == PointerEventButton.left && event.kind == PointerEventKind.down) {
+                    panning = false;
+                    pan-start-x = list_viewport_x;
+                    pan-start-y = list_viewport_y;
+                }
+                if (event.button == PointerEventButton.right && event.kind == PointerEventKind.down) {
+                    context-clicked(click_at(my-touch-area.mouse-x, my-touch-area.mouse-y));
+                }
+                if (event.button == PointerEventButton.middle && event.kind == PointerEventKind.down) {
+                    link-clicked(click_at(my-touch-area.mouse-x, my-touch-area.mouse-y));
+                }
+            }
+            moved => {
+                if (abs(self.mouse-x - self.pressed-x) > pan-threshold || abs(self.mouse-y - self.pressed-y) > pan-threshold) {
+                    panning = true;
+                }
+                if (panning) {
+                    // Viewport offsets are negative, down to the part of the content not shown
+                    list_viewport_x = max(min(pan-start-x + self.mouse-x - self.pressed-x, 0px), min(mylist.visible-width - mylist.viewport-width, 0px));
+                    list_viewport_y = max(min(pan-start-y + self.mouse-y - self.pressed-y, 0px), min(mylist.visible-height - mylist.viewport-height, 0px));
+                }
             }
         }
 
         mylist := ListView {
-            for image_source in image_sources : Rectangle {
-                // 1/3 for resolution
-                width: (image_source.width/3) * 1px * (1.6666666 * 1phx/1px);
-                height: (image_source.height/3) * 1px * (1.6666666 * 1phx/1px) + 10px; // +10px for spacing
+            width: comparing && !presenting && compare_side_by_side ? root.width / 2 : root.width;
+            height: comparing && !presenting && !compare_side_by_side ? root.height / 2 : root.height;
+            for page_row in page_rows : PageRowItem {
+                page_row: page_row;
+                render_scale: root.render_scale;
+                zoom: root.zoom;
+                bleed_pt: root.bleed_pt;
+                page_gap: root.page_gap;
+                page_shadow: root.page_shadow;
+                page_corner_radius: root.page_corner_radius;
+                x: max(0px, (parent.width - self.width) / 2);
+            }
+        }
+
+        // The document the preview is compared with, right of or below the preview
+        Rectangle {
+            x: comparelist.x;
+            y: comparelist.y;
+            width: comparelist.width;
+            height: comparelist.height;
+            visible: comparelist.visible;
+            background: page_gap_color;
+        }
+        comparelist := ListView {
+            x: compare_side_by_side ? root.width / 2 : 0px;
+            y: compare_side_by_side ? 0px : root.height / 2;
+            width: compare_side_by_side ? root.width / 2 : root.width;
+            height: compare_side_by_side ? root.height : root.height / 2;
+            visible: comparing && !presenting;
+            for page_row in compare_rows : PageRowItem {
+                page_row: page_row;
+                render_scale: root.render_scale;
+                zoom: root.zoom;
+                bleed_pt: root.bleed_pt;
+                page_gap: root.page_gap;
+                page_shadow: root.page_shadow;
+                page_corner_radius: root.page_corner_radius;
                 x: max(0px, (parent.width - self.width) / 2);
-                Image {
-                    width: parent.width;
-                    source: image_source;
+            }
+        }
+        // Between the two lists
+        Rectangle {
+            x: comparelist.x;
+            y: comparelist.y;
+            width: compare_side_by_side ? 1px : comparelist.width;
+            height: compare_side_by_side ? comparelist.height : 1px;
+            visible: comparelist.visible;
+            background: #808080;
+        }
+
+        // All pages at a tiny scale along the right edge of the list. Dragging along it seeks
+        // through the document, showing the page under the pointer larger next to the strip.
+        in property <[image]> overview_pages;
+        property <int> overview-target: -1;
+        overview := Rectangle {
+            x: mylist.visible-width - self.width;
+            y: 0px;
+            width: 40px;
+            height: mylist.visible-height;
+            visible: overview_pages.length > 0 && !presenting;
+            background: rgba(34, 34, 34, 0.75);
+            property <length> slot: self.height / max(overview_pages.length, 1);
+            for page[index] in overview_pages : Image {
+                x: 2px;
+                y: index * parent.slot;
+                width: parent.width - 4px;
+                height: max(parent.slot - 1px, 1px);
+                image-fit: contain;
+                source: page;
+            }
+            overview-touch-area := TouchArea {
+                function seek(y: length) {
+                    // How far down the strip the pointer is, from 0 to 1
+                    overview-target = min(floor(max(0, min(1, y / self.height)) * overview_pages.length), overview_pages.length - 1);
+                    list_viewport_y = -max(0, min(1, y / self.height)) * max(mylist.viewport-height - mylist.visible-height, 0px);
+                }
+                pointer-event(event) => {
+                    if (event.button == PointerEventButton.left && event.kind == PointerEventKind.down) {
+                        seek(self.mouse-y);
+                    }
+                    if (event.kind == PointerEventKind.up) {
+                        overview-target = -1;
+                    }
                 }
+                moved => {
+                    seek(self.mouse-y);
+                }
+            }
+        }
+        Rectangle {
+            x: overview.x - self.width - 8px;
+            y: max(0px, min(overview.height - self.height, overview-touch-area.mouse-y - self.height / 2));
+            width: 160px;
+            height: 220px;
+            visible: overview.visible && overview-target >= 0;
+            background: rgb(68, 68, 68);
+            drop-shadow-blur: 8px;
+            drop-shadow-color: rgba(0, 0, 0, 0.5);
+            Image {
+                width: parent.width - 8px;
+                height: parent.height - 8px;
+                image-fit: contain;
+                source: overview_pages[max(overview-target, 0)];
             }
         }
 
@@ -679,6 +2831,124 @@ slint::slint! {
             }
         }
 
+        in property <string> zoom_label: "100%";
+        Rectangle {
+            x: parent.width - self.width - 8px;
+            // Above the status bar
+            y: parent.height - self.height - 24px;
+            width: zoom-label-text.preferred-width + 8px;
+            height: 16px;
+            border-radius: 4px;
+            background: rgba(68, 68, 68, 0.8);
+            zoom-label-text := Text {
+                color: rgb(254, 254, 254);
+                font-size: 10px;
+                text: zoom_label;
+            }
+        }
+
+        // The profile in use, if any
+        in property <string> profile_label: "";
+        Rectangle {
+            x: 8px;
+            // Above the status bar
+            y: parent.height - self.height - 24px;
+            width: profile-label-text.preferred-width + 8px;
+            height: 16px;
+            border-radius: 4px;
+            background: rgba(68, 68, 68, 0.8);
+            visible: profile_label != "";
+            profile-label-text := Text {
+                color: rgb(254, 254, 254);
+                font-size: 10px;
+                text: profile_label;
+            }
+        }
+
+        in property <bool> compiling: false;
+        Rectangle {
+            x: parent.width - self.width - 8px;
+            y: 8px;
+            width: 24px;
+            height: 24px;
+            visible: compiling;
+            border-radius: self.width / 2;
+            background: rgb(68, 68, 68);
+            Rectangle {
+                width: 12px;
+                height: 12px;
+                border-radius: self.width / 2;
+                // Pulses while the compilation is running
+                background: rgba(254, 254, 254, 0.3 + 0.7 * mod(animation-tick(), 0.8s) / 0.8s);
+            }
+        }
+
+        in property <[OutlineItem]> outline;
+        property <bool> outline_visible: false;
+        callback outline_clicked(int);
+        Rectangle {
+            x: 8px;
+            y: 8px;
+            width: 24px;
+            height: 24px;
+            visible: outline.length > 0;
+            border-radius: 4px;
+            background: rgb(68, 68, 68);
+            Text {
+                color: rgb(254, 254, 254);
+                text: "≡";
+            }
+            TouchArea {
+                clicked => {
+                    outline_visible = !outline_visible;
+                }
+            }
+        }
+        Rectangle {
+            x: 0px;
+            y: 40px;
+            width: min(250px, parent.width / 2);
+            height: parent.height - self.y - 20px;
+            visible: outline_visible && outline.length > 0;
+            background: rgba(68, 68, 68, 0.95);
+            ListView {
+                for item[index] in outline : Rectangle {
+                    height: 20px;
+                    Text {
+                        x: 8px + (item.level - 1) * 12px;
+                        width: parent.width - self.x - 8px;
+                        vertical-alignment: center;
+                        overflow: elide;
+                        color: rgb(254, 254, 254);
+                        font-size: 11px;
+                        text: item.title;
+                    }
+                    TouchArea {
+                        clicked => {
+                            outline-clicked(index);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Covers the list, the status bar and the compilation indicator while presenting
+        Rectangle {
+            visible: presenting;
+            background: black;
+            Image {
+                width: parent.width;
+                height: parent.height;
+                image-fit: contain;
+                source: image_sources[presentation_page];
+            }
+            TouchArea {
+                clicked => {
+                    presentation_page = min(presentation_page + 1, image_sources.length - 1);
+                }
+            }
+        }
+
         in property <PositionHighlight> position_highlight;
         in property <bool> position_highlight_visible: false;
         Rectangle {
@@ -700,3 +2970,178 @@ slint::slint! {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use temp_dir::TempDir;
+    use tower_lsp::lsp_types::{InitializeParams, WorkspaceFolder};
+    use typst::syntax::VirtualPath;
+    use typst::World;
+
+    use crate::workspace::fs::local::LocalFs;
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn preview_reads_unsaved_includes() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.child("main.typ"), "#include \"chapter.typ\"").unwrap();
+        fs::write(temp_dir.child("chapter.typ"), "saved").unwrap();
+
+        let root_uri = LocalFs::path_to_uri(temp_dir.path()).unwrap();
+        let main_uri = LocalFs::path_to_uri(temp_dir.child("main.typ")).unwrap();
+        let chapter_uri = LocalFs::path_to_uri(temp_dir.child("chapter.typ")).unwrap();
+
+        let mut workspace = Workspace::new(&InitializeParams {
+            workspace_folders: Some(vec![WorkspaceFolder {
+                uri: root_uri,
+                name: "root".to_owned(),
+            }]),
+            ..Default::default()
+        });
+        workspace.register_files().unwrap();
+        workspace
+            .open_lsp(chapter_uri, "unsaved".to_owned())
+            .unwrap();
+        let workspace = Arc::new(RwLock::new(workspace));
+
+        let (main, project) = WorldBuilder::MainUri(&main_uri)
+            .main_project(&workspace)
+            .await
+            .unwrap();
+        let chapter_id = FileId::new(None, VirtualPath::new("chapter.typ"));
+        let chapter = TypstThread::default()
+            .run_with_world(project, main, move |world| {
+                world
+                    .source(chapter_id)
+                    .map(|source| source.text().to_owned())
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(chapter, "unsaved");
+    }
+
+    #[test]
+    fn spread_rows() {
+        assert_eq!(layout_rows(PreviewLayout::Spread, 3), vec![0..2, 2..3]);
+        assert_eq!(
+            layout_rows(PreviewLayout::SpreadWithCover, 4),
+            vec![0..1, 1..3, 3..4]
+        );
+        assert!(layout_rows(PreviewLayout::SpreadWithCover, 0).is_empty());
+    }
+
+    #[test]
+    fn changed_pages_include_added_and_removed() {
+        assert_eq!(changed_pages(&[1, 2, 3], &[1, 5, 3]), vec![1]);
+        assert_eq!(changed_pages(&[1, 2], &[1, 2, 3]), vec![2]);
+        assert_eq!(changed_pages(&[1, 2, 3], &[4]), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn resets_within_refine_delay_are_editing() {
+        let mut drafts = Drafts {
+            refine_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+        drafts.reset();
+        assert!(!drafts.editing);
+        drafts.reset();
+        assert!(drafts.editing);
+
+        drafts.refine_delay = Duration::ZERO;
+        drafts.reset();
+        assert!(!drafts.editing);
+    }
+
+    #[test]
+    fn render_cache_is_bounded_without_configured_limits() {
+        let mut render_cache = RenderCache::default();
+        for frame in 0..DEFAULT_PREVIEW_CACHED_PAGES as u128 + 10 {
+            let key = RenderKey {
+                frame,
+                pixel_per_pt: 1.0f32.to_bits(),
+                invert: false,
+            };
+            render_cache.insert(key, slint::SharedPixelBuffer::new(1, 1), Default::default());
+        }
+        assert_eq!(render_cache.pages.len(), DEFAULT_PREVIEW_CACHED_PAGES);
+    }
+
+    #[test]
+    fn source_locations_count_from_one() {
+        let id = FileId::new(None, VirtualPath::new("main.typ"));
+        let source = Source::new(id, "= Intro\nSome *text*".to_owned());
+        let uri = Url::parse("file:///project/main.typ").unwrap();
+        assert_eq!(
+            source_location(&uri, &source, 13).as_deref(),
+            Some("/project/main.typ:2:6")
+        );
+
+        let untitled = Url::parse("untitled:Untitled-1").unwrap();
+        assert_eq!(
+            source_location(&untitled, &source, 0).as_deref(),
+            Some("untitled:Untitled-1:1:1")
+        );
+    }
+
+    #[test]
+    fn composites_transparent_overlays_onto_the_page() {
+        use typst::layout::{Abs, FrameItem, Page, Point, Size};
+        use typst::syntax::Span;
+        use typst::visualize::{Color, Geometry};
+
+        // Two overlapping squares of red at half opacity
+        let square = Geometry::Rect(Size::splat(Abs::pt(10.0)))
+            .filled(Color::from_u8(255, 0, 0, 128).into());
+        let mut frame = Frame::soft(Size::splat(Abs::pt(20.0)));
+        frame.push(
+            Point::zero(),
+            FrameItem::Shape(square.clone(), Span::detached()),
+        );
+        frame.push(
+            Point::splat(Abs::pt(5.0)),
+            FrameItem::Shape(square, Span::detached()),
+        );
+        let document = Document {
+            pages: vec![Page {
+                frame,
+                numbering: None,
+                number: 1,
+            }],
+            ..Default::default()
+        };
+        let render = RenderRequest {
+            zoom: 1.0,
+            scale: 1.0,
+            invert: false,
+            render_cache: Default::default(),
+            cache_limit: Default::default(),
+        };
+
+        let pixel_buffer = render_pixel_buffer(&document, &render, 0, false);
+        let pixel = |x: usize, y: usize| {
+            let pixel = pixel_buffer.as_slice()[y * pixel_buffer.width() as usize + x];
+            [pixel.r, pixel.g, pixel.b, pixel.a]
+        };
+        let close = |actual: [u8; 4], expected: [u8; 4]| {
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(&actual, expected)| actual.abs_diff(expected) <= 1)
+        };
+
+        // White shows through by half, and by a quarter where the squares overlap
+        assert!(
+            close(pixel(2, 2), [255, 127, 127, 255]),
+            "{:?}",
+            pixel(2, 2)
+        );
+        assert!(close(pixel(7, 7), [255, 63, 63, 255]), "{:?}", pixel(7, 7));
+        assert_eq!(pixel(17, 17), [255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use serde::Serialize;
+use tower_lsp::lsp_types::Url;
+use tracing::warn;
+use typst::layout::{Frame, FrameItem};
+use typst::model::Document;
+use typst::syntax::{ast, LinkedNode, Source};
+use typst::text::Font;
+
+use super::TypstServer;
+
+/// Families Typst uses when a document doesn't set `text(font: ..)`, for text and math
+/// respectively.
+const DEFAULT_FAMILIES: &[&str] = &["linux libertine", "new computer modern math"];
+
+/// A font which appears in the output of a compiled document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsedFont {
+    pub family: String,
+    /// Whether the font was not requested by the document, so Typst fell back to it
+    pub fallback: bool,
+    /// Number of glyphs set in this font across the document
+    pub glyphs: usize,
+}
+
+impl TypstServer {
+    /// Lists the fonts actually used in the compiled document. Requested families are collected
+    /// from `text(font: ..)` arguments in all workspace sources, since set rules in templates
+    /// affect the document as well.
+    // Fonts hash by their data and index, which never change
+    #[allow(clippy::mutable_key_type)]
+    pub async fn used_fonts(&self, document: &Document) -> Vec<UsedFont> {
+        let workspace = self.read_workspace().await;
+
+        let requested: HashSet<String> = workspace
+            .known_uris()
+            .into_iter()
+            .filter_map(|uri: Url| {
+                workspace
+                    .read_source(&uri)
+                    .map_err(|err| warn!(%err, %uri, "could not read source"))
+                    .ok()
+            })
+            .flat_map(|source| requested_families(&source))
+            .chain(DEFAULT_FAMILIES.iter().map(ToString::to_string))
+            .collect();
+
+        let mut glyphs = HashMap::new();
+        for page in &document.pages {
+            count_glyphs(&page.frame, &mut glyphs);
+        }
+
+        glyphs
+            .into_iter()
+            .map(|(font, glyphs)| {
+                let family = font.info().family.clone();
+                let fallback = !requested.contains(&family.to_lowercase());
+                UsedFont {
+                    family,
+                    fallback,
+                    glyphs,
+                }
+            })
+            .sorted_by(|a, b| {
+                b.glyphs
+                    .cmp(&a.glyphs)
+                    .then_with(|| a.family.cmp(&b.family))
+            })
+            .collect()
+    }
+}
+
+/// Counts the glyphs set in each font in the frame, including nested frames.
+#[allow(clippy::mutable_key_type)]
+fn count_glyphs(frame: &Frame, glyphs: &mut HashMap<Font, usize>) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => count_glyphs(&group.frame, glyphs),
+            FrameItem::Text(text) => {
+                *glyphs.entry(text.font.clone()).or_default() += text.glyphs.len();
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Collects the lowercased families given as string literals to `text(font: ..)` in the source,
+/// in calls and set rules.
+fn requested_families(source: &Source) -> Vec<String> {
+    let mut families = Vec::new();
+    collect_requested_families(&LinkedNode::new(source.root()), &mut families);
+    families
+}
+
+fn collect_requested_families(node: &LinkedNode, families: &mut Vec<String>) {
+    let callee_and_args = match node.cast::<ast::FuncCall>() {
+        Some(call) => Some((call.callee(), call.args())),
+        None => node
+            .cast::<ast::SetRule>()
+            .map(|set| (set.target(), set.args())),
+    };
+    if let Some((ast::Expr::Ident(callee), args)) = callee_and_args {
+        if callee.get() == "text" {
+            let font_args = args.items().filter_map(|arg| match arg {
+                ast::Arg::Named(named) if named.name().get() == "font" => Some(named.expr()),
+                _ => None,
+            });
+
+            for expr in font_args {
+                match expr {
+                    ast::Expr::Str(family) => families.push(family.get().to_lowercase().into()),
+                    ast::Expr::Array(array) => {
+                        families.extend(array.items().filter_map(|item| match item {
+                            ast::ArrayItem::Pos(ast::Expr::Str(family)) => {
+                                Some(family.get().to_lowercase().into())
+                            }
+                            _ => None,
+                        }))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    for child in node.children() {
+        collect_requested_families(&child, families);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requested_families_from_set_rules() {
+        let source = Source::detached(
+            r#"#set text(font: "Inria Serif")
+#text(font: ("IBM Plex Serif", "Noto Sans Arabic"))[Hello]
+#set par(justify: true)"#,
+        );
+
+        let families = requested_families(&source);
+
+        assert_eq!(
+            vec!["inria serif", "ibm plex serif", "noto sans arabic"],
+            families
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context;
+use serde::Deserialize;
+use tiny_skia::Pixmap;
+use typst::model::Document;
+use typst::visualize::Color;
+
+/// Options for the `diffExport` command
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiffOptions {
+    /// The git revision to compare the file against
+    pub revision: String,
+    pub pixel_per_pt: f32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            revision: "HEAD".to_owned(),
+            pixel_per_pt: 2.0,
+        }
+    }
+}
+
+/// A page which looks different in the two versions, with its diff image
+#[derive(Debug, Clone)]
+pub struct PageDiff {
+    /// Starting at 1
+    pub page: usize,
+    pub changed_pixels: usize,
+    pub path: PathBuf,
+}
+
+/// The text of the file at the git revision, failing with a message for the user if git can't
+/// provide it
+pub fn text_at_revision(path: &Path, revision: &str) -> Result<String, String> {
+    let dir = path.parent().ok_or("The file has no directory")?;
+    let name = path
+        .file_name()
+        .ok_or("The file has no name")?
+        .to_string_lossy();
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{revision}:./{name}"))
+        .output()
+        .map_err(|err| format!("Could not run git: {err}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "Could not get the file at {revision}: {}",
+            stderr.trim()
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("The file at {revision} is not UTF-8"))
+}
+
+/// Renders the pages of both documents and writes an image of each page which differs to the
+/// directory, highlighting the changed pixels. Pages only in one of the documents are shown as
+/// changed entirely.
+pub fn export_visual_diff(
+    old: &Document,
+    new: &Document,
+    dir: &Path,
+    pixel_per_pt: f32,
+) -> anyhow::Result<Vec<PageDiff>> {
+    let render = |document: &Document, index: usize| {
+        document
+            .pages
+            .get(index)
+            .map(|page| typst_render::render(&page.frame, pixel_per_pt, Color::WHITE))
+    };
+
+    let pages = old.pages.len().max(new.pages.len());
+    let digits = pages.to_string().len();
+    let mut diffs = Vec::new();
+    for index in 0..pages {
+        let Some((image, changed_pixels)) =
+            diff_pixmaps(render(old, index).as_ref(), render(new, index).as_ref())
+        else {
+            continue;
+        };
+        if changed_pixels == 0 {
+            continue;
+        }
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("page-{:0digits$}.png", index + 1));
+        fs::write(&path, image.encode_png()?)?;
+        diffs.push(PageDiff {
+            page: index + 1,
+            changed_pixels,
+            path,
+        });
+    }
+
+    Ok(diffs)
+}
+
+/// An image of the new page, faded, with the pixels which differ from the old page in red, and
+/// how many pixels differ. Pixels outside of one of the pages count as changed.
+fn diff_pixmaps(old: Option<&Pixmap>, new: Option<&Pixmap>) -> Option<(Pixmap, usize)> {
+    let size = |pixmap: Option<&Pixmap>| pixmap.map_or((0, 0), |p| (p.width(), p.height()));
+    let ((old_width, old_height), (new_width, new_height)) = (size(old), size(new));
+    let (width, height) = (old_width.max(new_width), old_height.max(new_height));
+    let mut diff = Pixmap::new(width, height)?;
+
+    let pixel = |pixmap: Option<&Pixmap>, x, y| pixmap.and_then(|pixmap| pixmap.pixel(x, y));
+    let mut changed_pixels = 0;
+    for y in 0..height {
+        for x in 0..width {
+            let (old, new) = (pixel(old, x, y), pixel(new, x, y));
+            let color = match (old, new) {
+                (Some(old), Some(new)) if old == new => {
+                    // Renders are opaque, so the premultiplied color is the color
+                    let fade = |channel: u8| 255 - (255 - channel) / 4;
+                    [fade(new.red()), fade(new.green()), fade(new.blue())]
+                }
+                _ => {
+                    changed_pixels += 1;
+                    [255, 0, 0]
+                }
+            };
+            let index = (y * width + x) as usize * 4;
+            diff.data_mut()[index..index + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+        }
+    }
+
+    Some((diff, changed_pixels))
+}
+
+/// Where the diff images for the source are written: a `<name>-diff` directory next to it
+pub fn diff_dir(source_path: &Path) -> anyhow::Result<PathBuf> {
+    let stem = source_path
+        .file_stem()
+        .context("source path has no file name")?
+        .to_string_lossy();
+    Ok(source_path.with_file_name(format!("{stem}-diff")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn filled(width: u32, height: u32, color: tiny_skia::Color) -> Pixmap {
+        let mut pixmap = Pixmap::new(width, height).unwrap();
+        pixmap.fill(color);
+        pixmap
+    }
+
+    #[test]
+    fn counts_changed_and_missing_pixels() {
+        let white = filled(2, 2, tiny_skia::Color::WHITE);
+        let mut changed = white.clone();
+        changed.pixels_mut()[0] = tiny_skia::ColorU8::from_rgba(0, 0, 0, 255).premultiply();
+
+        let (_, same) = diff_pixmaps(Some(&white), Some(&white)).unwrap();
+        assert_eq!(same, 0);
+
+        let (diff, one) = diff_pixmaps(Some(&white), Some(&changed)).unwrap();
+        assert_eq!(one, 1);
+        assert_eq!(&diff.data()[..4], &[255, 0, 0, 255]);
+
+        let (diff, taller) =
+            diff_pixmaps(Some(&white), Some(&filled(2, 3, tiny_skia::Color::WHITE))).unwrap();
+        assert_eq!((diff.height(), taller), (3, 2));
+
+        let (_, removed) = diff_pixmaps(Some(&white), None).unwrap();
+        assert_eq!(removed, 4);
+    }
+}
@@ -2,17 +2,26 @@
 
 use core::fmt;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use comemo::Prehashed;
 use fontdb::{Database, Source};
 use once_cell::sync::OnceCell;
-use tracing::error;
+use parking_lot::Mutex;
+use tracing::{error, info};
 use typst::foundations::Bytes;
 use typst::text::{Font, FontBook, FontInfo};
 
 use super::fs::local::LocalFs;
 use super::fs::FsError;
 
+/// Additional directories to search for fonts, as given by `--font-path`.
+static FONT_PATHS: OnceCell<Vec<PathBuf>> = OnceCell::new();
+
+/// The font manager shared by every workspace and project in this process, so the (slow) search
+/// for system fonts only happens once.
+static SHARED: OnceCell<FontManager> = OnceCell::new();
+
 /// Searches for fonts.
 pub struct FontManager {
     book: Prehashed<FontBook>,
@@ -25,13 +34,47 @@ impl FontManager {
         Builder::new()
     }
 
+    /// Set the additional font directories merged into the shared font manager. Must be called
+    /// before the shared font manager is first used to have any effect.
+    pub fn set_font_paths(font_paths: Vec<PathBuf>) {
+        if FONT_PATHS.set(font_paths).is_err() {
+            error!("font paths were already set");
+        }
+    }
+
+    /// The additional font directories given with `set_font_paths`
+    pub fn font_paths() -> &'static [PathBuf] {
+        FONT_PATHS.get().map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Get the process-wide font manager, searching for fonts on first use.
+    pub fn shared() -> &'static FontManager {
+        SHARED.get_or_init(|| {
+            let start = Instant::now();
+            let font_paths = Self::font_paths();
+
+            let manager = Self::builder()
+                .with_paths(font_paths)
+                .with_system()
+                .with_embedded()
+                .build();
+
+            info!(
+                fonts = manager.fonts.len(),
+                elapsed = ?start.elapsed(),
+                "searched for fonts"
+            );
+            manager
+        })
+    }
+
     pub fn book(&self) -> &Prehashed<FontBook> {
         &self.book
     }
 
     pub fn font(&self, id: usize) -> Option<Font> {
         let slot = self.fonts.get(id)?;
-        let font = slot.get_font().cloned();
+        let font = slot.get_font();
         match font {
             Ok(font) => Some(font),
             Err(err) => {
@@ -41,8 +84,17 @@ impl FontManager {
         }
     }
 
-    pub fn clear(&mut self) {
-        self.fonts.iter_mut().for_each(|font| font.invalidate());
+    /// How many fonts were found in font files, from the system and the additional font
+    /// directories, as opposed to those embedded in the binary
+    pub fn file_font_count(&self) -> usize {
+        self.fonts
+            .iter()
+            .filter(|slot| matches!(slot.source, FontSource::Path(_)))
+            .count()
+    }
+
+    pub fn clear(&self) {
+        self.fonts.iter().for_each(FontSlot::invalidate);
     }
 }
 
@@ -58,34 +110,55 @@ impl fmt::Debug for FontManager {
 // TODO: special handling for fonts that are in a project?
 
 /// Holds details about the location of a font and lazily the font itself.
+///
+/// Only the font's metadata goes into the [`FontBook`] up front. The font itself is parsed the
+/// first time Typst asks for it, so fonts a document never uses are never fully parsed.
 #[derive(Debug)]
 struct FontSlot {
-    /// If `None`, the font is embedded
-    path: Option<PathBuf>,
+    source: FontSource,
     index: u32,
-    font: OnceCell<Font>,
+    font: Mutex<Option<Font>>,
+}
+
+/// Where the data for a [`FontSlot`] comes from.
+#[derive(Debug)]
+enum FontSource {
+    /// A font file on the local filesystem, read when the font is first used
+    Path(PathBuf),
+    /// A font embedded in the binary
+    Embedded(Bytes),
 }
 
 impl FontSlot {
-    pub fn get_font(&self) -> FontResult<&Font> {
-        self.font.get_or_try_init(|| self.init())
+    pub fn get_font(&self) -> FontResult<Font> {
+        let mut font = self.font.lock();
+        if let Some(font) = font.as_ref() {
+            return Ok(font.clone());
+        }
+
+        let loaded = self.init()?;
+        *font = Some(loaded.clone());
+        Ok(loaded)
     }
 
     fn init(&self) -> FontResult<Font> {
-        let path = self.path().expect("should not init font without path");
-        let data = LocalFs::read_path_raw(path)?;
+        let data = match &self.source {
+            FontSource::Path(path) => Self::read(path)?,
+            FontSource::Embedded(bytes) => bytes.clone(),
+        };
 
-        Font::new(data.into(), self.index).ok_or(FontError::Parse)
+        Font::new(data, self.index).ok_or(FontError::Parse)
     }
 
-    fn path(&self) -> Option<&Path> {
-        self.path.as_deref()
+    fn read(path: &Path) -> FontResult<Bytes> {
+        let data = LocalFs::read_path_raw(path)?;
+        Ok(data.into())
     }
 
-    pub fn invalidate(&mut self) {
+    pub fn invalidate(&self) {
         // don't invalidate embedded fonts
-        if self.path.is_some() {
-            self.font.take();
+        if let FontSource::Path(_) = self.source {
+            self.font.lock().take();
         }
     }
 }
@@ -124,13 +197,12 @@ impl Builder {
     /// Add fonts that are embedded in the binary.
     pub fn with_embedded(mut self) -> Self {
         let mut add = |bytes: &'static [u8]| {
-            let bytes = Bytes::from_static(bytes);
-            for (i, font) in Font::iter(bytes).enumerate() {
-                self.book.push(font.info().clone());
+            for (i, info) in FontInfo::iter(bytes).enumerate() {
+                self.book.push(info);
                 self.fonts.push(FontSlot {
-                    path: None,
+                    source: FontSource::Embedded(Bytes::from_static(bytes)),
                     index: i as u32,
-                    font: OnceCell::with_value(font),
+                    font: Mutex::new(None),
                 });
             }
         };
@@ -152,6 +224,19 @@ impl Builder {
         self
     }
 
+    /// Include fonts in the given directories.
+    pub fn with_paths(mut self, font_paths: &[PathBuf]) -> Self {
+        let mut db = Database::new();
+
+        // Fonts from the given paths have first priority.
+        for path in font_paths {
+            db.load_fonts_dir(path);
+        }
+
+        self.add_database(&db);
+        self
+    }
+
     /// Include system fonts.
     pub fn with_system(mut self) -> Self {
         self.search_system();
@@ -165,6 +250,11 @@ impl Builder {
         // System fonts have second priority.
         db.load_system_fonts();
 
+        self.add_database(&db);
+    }
+
+    /// Add all font faces found in the database.
+    fn add_database(&mut self, db: &Database) {
         for face in db.faces() {
             let path = match &face.source {
                 Source::File(path) | Source::SharedFile(path, _) => path,
@@ -173,6 +263,7 @@ impl Builder {
                 Source::Binary(_) => continue,
             };
 
+            // Only reads the font's tables needed for metadata; the font is fully parsed lazily
             let info = db
                 .with_face_data(face.id, FontInfo::new)
                 .expect("database must contain this font");
@@ -180,9 +271,9 @@ impl Builder {
             if let Some(info) = info {
                 self.book.push(info);
                 self.fonts.push(FontSlot {
-                    path: Some(path.clone()),
+                    source: FontSource::Path(path.clone()),
                     index: face.index,
-                    font: OnceCell::new(),
+                    font: Mutex::new(None),
                 });
             }
         }
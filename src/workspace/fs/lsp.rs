@@ -65,6 +65,8 @@ impl LspFs {
             .for_each(|change| Self::apply_one_change(source, change, position_encoding));
     }
 
+    /// Applies a change to the source, where `Source::edit` only reparses the parts of the syntax
+    /// tree around the edit, which keeps typing in large files cheap
     fn apply_one_change(
         source: &mut Source,
         change: TextDocumentContentChangeEvent,
@@ -99,3 +101,102 @@ impl LspFs {
             .ok_or_else(|| FsError::NotProvided(anyhow!("URI not found")))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use typst::syntax::Span;
+
+    use crate::lsp_typst_boundary::typst_to_lsp;
+
+    use super::*;
+
+    /// Pieces of markup, code and math to put together into arbitrary, often broken, documents
+    const FRAGMENTS: &[&str] = &[
+        "#",
+        "[",
+        "]",
+        "{",
+        "}",
+        "(",
+        ")",
+        "= ",
+        "*",
+        "_",
+        "$",
+        "\n",
+        "\n\n",
+        "#let x = 1",
+        "ä",
+        "🥺",
+        "// ",
+        "/*",
+        "*/",
+        "\"",
+        "<a>",
+        "@a",
+        " ",
+        "text",
+        "#f(",
+        "\\",
+        "- ",
+        "`",
+    ];
+
+    /// A xorshift generator, so the same edits are tried on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    #[test]
+    fn incremental_edits_match_full_reparse() {
+        let encoding = PositionEncoding::Utf16;
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut expected = "= Intro <intro>\n#let f(x) = [*#x*]\nSome $a^2$ text in @intro.\n\
+                            #f[_nested_ {1 + 2}]\n- item\n- /* note */ item 🥺\n"
+            .repeat(4);
+        let mut source = Source::detached(expected.clone());
+
+        for step in 0..2000 {
+            let text = source.text();
+            let start = floor_char_boundary(text, rng.below(text.len() + 1));
+            let end = floor_char_boundary(text, (start + rng.below(8)).min(text.len()));
+            let replacement: String = (0..rng.below(3))
+                .map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())])
+                .collect();
+
+            let range = typst_to_lsp::range(start..end, &source, encoding).raw_range;
+            let change = TextDocumentContentChangeEvent {
+                range: Some(range),
+                range_length: None,
+                text: replacement.clone(),
+            };
+            LspFs::apply_one_change(&mut source, change, encoding);
+            expected.replace_range(start..end, &replacement);
+            assert_eq!(source.text(), expected, "text differs after step {step}");
+
+            // Spans are handed out differently by incremental and full parses
+            let mut incremental = source.root().clone();
+            let mut full = Source::detached(expected.clone()).root().clone();
+            incremental.synthesize(Span::detached());
+            full.synthesize(Span::detached());
+            assert!(
+                incremental == full,
+                "syntax tree differs after step {step} on {expected:?}"
+            );
+        }
+    }
+}
@@ -62,6 +62,10 @@ impl FsManager {
         self.lsp.open(uri, text, package_manager)
     }
 
+    pub fn open_uris(&self) -> HashSet<Url> {
+        self.lsp.known_uris()
+    }
+
     pub fn close_lsp(&mut self, uri: &Url) {
         self.lsp.close(uri)
     }
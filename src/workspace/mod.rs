@@ -31,16 +31,18 @@
 //! also go backwards, representing a URI specifying a valid file as a `FileId` together with the
 //! context needed to interpret it, which is a project.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
 
 use comemo::Prehashed;
 use itertools::Itertools;
 use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
 use tower_lsp::lsp_types::{
     InitializeParams, TextDocumentContentChangeEvent, Url, WorkspaceFoldersChangeEvent,
 };
-use tracing::trace;
-use typst::foundations::Bytes;
+use tracing::{trace, warn};
+use typst::foundations::{Bytes, Dict, Value};
 use typst::syntax::Source;
 use typst::Library;
 
@@ -49,7 +51,7 @@ use crate::ext::InitializeParamsExt;
 
 use self::font_manager::FontManager;
 use self::fs::manager::FsManager;
-use self::fs::{FsResult, KnownUriProvider, ReadProvider};
+use self::fs::{FsResult, KnownUriProvider, ReadProvider, WriteProvider};
 use self::package::external::manager::ExternalPackageManager;
 use self::package::manager::PackageManager;
 use self::package::{FullFileId, Package};
@@ -60,15 +62,47 @@ pub mod package;
 pub mod project;
 pub mod world;
 
+/// The experimental features of the bundled Typst version which can be requested with the
+/// `features` initialization option. Typst 0.11 has no feature flags yet.
+pub const SUPPORTED_FEATURES: &[&str] = &[];
+
 lazy_static! {
     pub static ref TYPST_STDLIB: Prehashed<Library> = Prehashed::new(Library::default());
 }
 
+static CLI_INPUTS: OnceCell<BTreeMap<String, String>> = OnceCell::new();
+
+/// Sets the `sys.inputs` given on the command line, which the editor's `inputs` are merged over.
+/// Must be called before the workspace is created.
+pub fn set_cli_inputs(inputs: BTreeMap<String, String>) {
+    if CLI_INPUTS.set(inputs).is_err() {
+        warn!("inputs were already set, ignoring new inputs");
+    }
+}
+
+/// The `sys.inputs` given with `set_cli_inputs`
+pub fn cli_inputs() -> &'static BTreeMap<String, String> {
+    static EMPTY: BTreeMap<String, String> = BTreeMap::new();
+    CLI_INPUTS.get().unwrap_or(&EMPTY)
+}
+
+/// Builds a standard library whose `sys.inputs` are the given strings
+pub fn library_with_inputs(inputs: &BTreeMap<String, String>) -> Prehashed<Library> {
+    let inputs: Dict = inputs
+        .iter()
+        .map(|(key, value)| (key.as_str().into(), Value::Str(value.as_str().into())))
+        .collect();
+    Prehashed::new(Library::builder().with_inputs(inputs).build())
+}
+
 #[derive(Debug)]
 pub struct Workspace {
     fs: FsManager,
-    fonts: FontManager,
+    fonts: &'static FontManager,
     packages: PackageManager,
+    /// The `sys.inputs` of the library, from the command line and the editor's settings
+    inputs: BTreeMap<String, String>,
+    library: Arc<Prehashed<Library>>,
 }
 
 impl Workspace {
@@ -77,13 +111,41 @@ impl Workspace {
 
         Self {
             fs: FsManager::default(),
-            fonts: FontManager::builder().with_system().with_embedded().build(),
+            fonts: FontManager::shared(),
             packages: PackageManager::new(root_paths, ExternalPackageManager::new()),
+            inputs: cli_inputs().clone(),
+            library: Arc::new(library_with_inputs(cli_inputs())),
+        }
+    }
+
+    pub fn library(&self) -> &Arc<Prehashed<Library>> {
+        &self.library
+    }
+
+    pub fn inputs(&self) -> &BTreeMap<String, String> {
+        &self.inputs
+    }
+
+    /// Sets the editor's inputs, merged over those from the command line. Returns whether the
+    /// inputs changed, in which case documents need to be compiled again.
+    pub fn set_inputs(&mut self, inputs: &BTreeMap<String, String>) -> bool {
+        let mut merged = cli_inputs().clone();
+        merged.extend(
+            inputs
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        if merged == self.inputs {
+            return false;
         }
+
+        self.library = Arc::new(library_with_inputs(&merged));
+        self.inputs = merged;
+        true
     }
 
     pub fn font_manager(&self) -> &FontManager {
-        &self.fonts
+        self.fonts
     }
 
     pub fn package_manager(&self) -> &PackageManager {
@@ -113,14 +175,30 @@ impl Workspace {
         self.fs.read_bytes(uri, &self.packages)
     }
 
+    /// Sources are parsed once and kept until they change: open documents are reparsed
+    /// incrementally on edits, other files are cached until invalidated. The returned `Source`
+    /// shares its syntax tree with the cached one, so features can call this freely instead of
+    /// caching the parse themselves.
     pub fn read_source(&self, uri: &Url) -> FsResult<Source> {
         self.fs.read_source(uri, &self.packages)
     }
 
+    /// Writes the file on the local filesystem and starts tracking it.
+    pub fn write_local(&mut self, uri: Url, data: &[u8]) -> FsResult<()> {
+        self.fs.write_raw(&uri, data)?;
+        self.fs.new_local(uri);
+        Ok(())
+    }
+
     pub fn known_uris(&self) -> HashSet<Url> {
         self.fs.known_uris()
     }
 
+    /// The files open in the editor
+    pub fn open_uris(&self) -> HashSet<Url> {
+        self.fs.open_uris()
+    }
+
     pub fn open_lsp(&mut self, uri: Url, text: String) -> FsResult<()> {
         self.fs.open_lsp(uri, text, &self.packages)
     }
@@ -50,6 +50,10 @@ impl LocalProvider {
         Self { root: root_dir }
     }
 
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
     fn fs_path(&self, spec: &PackageSpec) -> PathBuf {
         let subdir = format!("{}/{}/{}/", spec.namespace, spec.name, spec.version);
         self.root.join(subdir)
@@ -1,6 +1,9 @@
+use std::path::Path;
+
 use anyhow::anyhow;
+use lazy_static::lazy_static;
 use tokio::io::AsyncReadExt;
-use tokio::sync::OnceCell;
+use tokio::sync::{watch, OnceCell};
 use tower_lsp::lsp_types::Url;
 use tracing::{info, warn};
 use typst::diag::EcoString;
@@ -26,6 +29,39 @@ fn get_default_repo_provider() -> DefaultRepoProvider {
 #[cfg(not(feature = "remote-packages"))]
 fn get_default_repo_provider() -> DefaultRepoProvider {}
 
+lazy_static! {
+    /// Packages being downloaded by any package manager right now
+    static ref DOWNLOADS: watch::Sender<Vec<PackageSpec>> = watch::channel(Vec::new()).0;
+}
+
+/// Watches which packages are being downloaded, to show progress outside of the editor, which
+/// may not know about downloads triggered by compiling.
+pub fn watch_downloads() -> watch::Receiver<Vec<PackageSpec>> {
+    DOWNLOADS.subscribe()
+}
+
+/// Lists the package in [`DOWNLOADS`] while it's alive
+struct Download<'a> {
+    spec: &'a PackageSpec,
+}
+
+impl<'a> Download<'a> {
+    fn begin(spec: &'a PackageSpec) -> Self {
+        DOWNLOADS.send_modify(|downloads| downloads.push(spec.clone()));
+        Self { spec }
+    }
+}
+
+impl Drop for Download<'_> {
+    fn drop(&mut self) {
+        DOWNLOADS.send_modify(|downloads| {
+            if let Some(index) = downloads.iter().position(|spec| spec == self.spec) {
+                downloads.remove(index);
+            }
+        });
+    }
+}
+
 #[derive(Debug)]
 pub struct ExternalPackageManager<
     Dest: RepoRetrievalDest = LocalProvider,
@@ -82,6 +118,11 @@ impl ExternalPackageManager {
             packages: OnceCell::default(),
         }
     }
+
+    /// The directory packages are downloaded to, if one could be determined
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.cache.as_ref().map(LocalProvider::root)
+    }
 }
 
 impl<Dest: RepoRetrievalDest, Repo: RepoProvider> ExternalPackageManager<Dest, Repo> {
@@ -103,9 +144,16 @@ impl<Dest: RepoRetrievalDest, Repo: RepoProvider> ExternalPackageManager<Dest, R
         self.providers().find_map(|provider| provider.full_id(uri))
     }
 
+    /// Whether the package is available without downloading it
+    pub fn is_available(&self, spec: &PackageSpec) -> bool {
+        self.providers()
+            .any(|provider| provider.package(spec).is_some())
+    }
+
     #[tracing::instrument]
     async fn download_to_cache(&self, spec: &PackageSpec) -> ExternalPackageResult<Package> {
         if let Some(cache) = &self.cache {
+            let _download = Download::begin(spec);
             Ok(cache.store_from(&self.repo, spec).await?)
         } else {
             Err(ExternalPackageError::Other(anyhow!(
@@ -77,6 +77,17 @@ impl RemoteRepoProvider {
         Ok(())
     }
 
+    /// Checks that the repository can be reached, without downloading anything
+    pub async fn check_connection(&self) -> RepoResult<()> {
+        self.client
+            .head(self.index_url(PREVIEW_NAMESPACE))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(RepoError::Network)?;
+        Ok(())
+    }
+
     fn url(&self, spec: &PackageSpec) -> Url {
         let path = format!("{}/{}-{}.tar.gz", spec.namespace, spec.name, spec.version);
         self.base_url.join(&path).expect("should be a valid URL")
@@ -0,0 +1,88 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use typst::syntax::package::PackageSpec;
+
+/// The name of the lockfile in the project root
+pub const LOCK_FILE_NAME: &str = "typst.lock";
+
+/// The exact versions of the external packages a document was built with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageLock {
+    /// Package specs like `@preview/example:0.1.0`, sorted and without duplicates
+    pub packages: BTreeSet<String>,
+}
+
+impl PackageLock {
+    pub fn new<'a>(specs: impl IntoIterator<Item = &'a PackageSpec>) -> Self {
+        Self {
+            packages: specs.into_iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    pub fn from_json(json: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("package lock should serialize")
+    }
+
+    /// The locked version of the package, if it is locked at a different version than `spec`
+    /// requests. A lock holding several versions of the package, one of them `spec`'s, doesn't
+    /// conflict.
+    pub fn conflict(&self, spec: &PackageSpec) -> Option<PackageSpec> {
+        if self.specs().any(|locked| &locked == spec) {
+            return None;
+        }
+        self.specs().find(|locked| {
+            locked.namespace == spec.namespace
+                && locked.name == spec.name
+                && locked.version != spec.version
+        })
+    }
+
+    fn specs(&self) -> impl Iterator<Item = PackageSpec> + '_ {
+        self.packages
+            .iter()
+            .filter_map(|spec| PackageSpec::from_str(spec).ok())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spec(spec: &str) -> PackageSpec {
+        PackageSpec::from_str(spec).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let lock = PackageLock::new(&[spec("@preview/b:0.2.0"), spec("@preview/a:0.1.0")]);
+        let parsed = PackageLock::from_json(lock.to_json().as_bytes()).unwrap();
+        assert_eq!(lock, parsed);
+    }
+
+    #[test]
+    fn conflict_only_for_other_versions() {
+        let lock = PackageLock::new(&[spec("@preview/a:0.1.0")]);
+
+        assert_eq!(None, lock.conflict(&spec("@preview/a:0.1.0")));
+        assert_eq!(None, lock.conflict(&spec("@preview/b:0.2.0")));
+        assert_eq!(None, lock.conflict(&spec("@local/a:0.2.0")));
+        assert_eq!(
+            Some(spec("@preview/a:0.1.0")),
+            lock.conflict(&spec("@preview/a:0.2.0"))
+        );
+
+        let lock = PackageLock::new(&[spec("@preview/a:0.1.0"), spec("@preview/a:0.2.0")]);
+        assert_eq!(None, lock.conflict(&spec("@preview/a:0.1.0")));
+        assert_eq!(None, lock.conflict(&spec("@preview/a:0.2.0")));
+        assert_eq!(
+            Some(spec("@preview/a:0.1.0")),
+            lock.conflict(&spec("@preview/a:0.3.0"))
+        );
+    }
+}
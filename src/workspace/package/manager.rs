@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use itertools::Itertools;
@@ -29,7 +31,7 @@ use super::{FullFileId, Package, PackageId, PackageIdInner};
 #[derive(Debug)]
 pub struct PackageManager {
     current: HashMap<Url, Package>,
-    external: ExternalPackageManager,
+    external: Arc<ExternalPackageManager>,
 }
 
 impl PackageManager {
@@ -42,7 +44,10 @@ impl PackageManager {
 
         info!(?current, ?external, "initialized package manager");
 
-        Self { current, external }
+        Self {
+            current,
+            external: Arc::new(external),
+        }
     }
 
     pub async fn package(&self, id: PackageId) -> PackageResult<Package> {
@@ -131,6 +136,22 @@ impl PackageManager {
         info!(current = ?self.current, "updated current packages");
     }
 
+    /// Whether the external package is available without downloading it
+    pub fn is_available(&self, spec: &PackageSpec) -> bool {
+        self.external.is_available(spec)
+    }
+
+    /// The manager of external packages, shared so packages can be downloaded without holding on
+    /// to the workspace
+    pub fn external(&self) -> Arc<ExternalPackageManager> {
+        Arc::clone(&self.external)
+    }
+
+    /// The directory external packages are downloaded to, if one could be determined
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.external.cache_dir()
+    }
+
     pub fn current(&self) -> impl Iterator<Item = &Package> {
         self.current.values()
     }
@@ -9,6 +9,7 @@ use typst::syntax::{FileId, VirtualPath};
 use crate::ext::{UriResult, UrlExt, VirtualPathExt};
 
 pub mod external;
+pub mod lock;
 pub mod manager;
 
 /// Represents a package that is provided. In particular, the `FsManager` should be able to access
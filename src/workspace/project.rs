@@ -1,4 +1,5 @@
 use core::fmt;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use comemo::Prehashed;
@@ -16,12 +17,14 @@ use crate::ext::FileIdExt;
 use super::fs::local::UriToFsPathError;
 use super::fs::FsResult;
 use super::package::{FullFileId, PackageId};
-use super::{Workspace, TYPST_STDLIB};
+use super::{library_with_inputs, Workspace};
 
 #[derive(Clone)]
 pub struct Project {
     current: PackageId,
     workspace: Arc<OwnedRwLockReadGuard<Workspace>>,
+    /// A library with other inputs than the workspace's, see [`Project::with_inputs`]
+    library: Option<Arc<Prehashed<Library>>>,
 }
 
 impl Project {
@@ -29,15 +32,36 @@ impl Project {
         Self {
             current,
             workspace: Arc::new(workspace),
+            library: None,
         }
     }
 
+    /// The project with the inputs set over the workspace's, to compile a variant of the document
+    pub fn with_inputs(mut self, inputs: &BTreeMap<String, String>) -> Self {
+        if !inputs.is_empty() {
+            let mut merged = self.workspace().inputs().clone();
+            merged.extend(
+                inputs
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+            self.library = Some(Arc::new(library_with_inputs(&merged)));
+        }
+        self
+    }
+
     fn workspace(&self) -> &Workspace {
         &self.workspace
     }
 
+    /// The standard library, shared by all projects unless they [override the
+    /// inputs](Project::with_inputs). Requested features are validated against
+    /// [`SUPPORTED_FEATURES`](super::SUPPORTED_FEATURES) on initialization, and would be enabled
+    /// here, but the bundled Typst version has none.
     pub fn typst_stdlib(&self) -> &Prehashed<Library> {
-        &TYPST_STDLIB
+        self.library
+            .as_deref()
+            .unwrap_or_else(|| self.workspace().library().as_ref())
     }
 
     pub fn font_book(&self) -> &Prehashed<FontBook> {
@@ -64,6 +88,35 @@ impl Project {
         self.workspace().read_source(uri)
     }
 
+    pub fn read_bytes_by_uri(&self, uri: &Url) -> FsResult<Bytes> {
+        self.workspace().read_bytes(uri)
+    }
+
+    /// Whether the external package is available without downloading it
+    pub fn package_available(&self, spec: &PackageSpec) -> bool {
+        self.workspace().package_manager().is_available(spec)
+    }
+
+    /// Whether the URI is inside one of the workspace's root directories
+    pub fn in_workspace(&self, uri: &Url) -> bool {
+        self.workspace()
+            .package_manager()
+            .current()
+            .any(|package| package.uri_to_vpath(uri).is_ok())
+    }
+
+    /// The IDs of the files known to be in the current package
+    pub fn file_ids(&self) -> Vec<FileId> {
+        let workspace = self.workspace();
+        workspace
+            .known_uris()
+            .into_iter()
+            .filter_map(|uri| workspace.full_id(&uri).ok())
+            .filter(|full_id| full_id.package() == self.current)
+            .map(FileId::from)
+            .collect()
+    }
+
     pub async fn read_source_by_id(&self, id: FileId) -> FsResult<Source> {
         let full_id = self.fill_id(id);
         let uri = self.full_id_to_uri(full_id).await?;
@@ -84,6 +137,7 @@ impl fmt::Debug for Project {
         f.debug_struct("Project")
             .field("current", &self.current)
             .field("workspace", &"...")
+            .field("library", &self.library.as_ref().map(|_| "..."))
             .finish()
     }
 }
@@ -104,3 +158,28 @@ impl From<UriToFsPathError> for UriToIdError {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::RwLock;
+    use tower_lsp::lsp_types::{InitializeParams, WorkspaceFolder};
+
+    use super::*;
+
+    #[test]
+    fn siblings_sharing_a_prefix_are_outside() {
+        let root = Url::parse("file:///proj").unwrap();
+        let workspace = Workspace::new(&InitializeParams {
+            workspace_folders: Some(vec![WorkspaceFolder {
+                uri: root.clone(),
+                name: "proj".to_owned(),
+            }]),
+            ..Default::default()
+        });
+        let workspace = Arc::new(RwLock::new(workspace)).try_read_owned().unwrap();
+        let project = Project::new(PackageId::new_current(root), workspace);
+
+        assert!(project.in_workspace(&Url::parse("file:///proj/main.typ").unwrap()));
+        assert!(!project.in_workspace(&Url::parse("file:///proj2/main.typ").unwrap()));
+    }
+}
@@ -20,6 +20,11 @@ impl Now {
         chrono_to_typst_datetime_only_date(datetime)
     }
 
+    /// The time used throughout the compilation, capturing it now if it wasn't yet
+    pub fn captured(&self) -> chrono::DateTime<Utc> {
+        *self.chrono_now()
+    }
+
     fn chrono_now(&self) -> &chrono::DateTime<Utc> {
         self.now.get_or_init(Utc::now)
     }
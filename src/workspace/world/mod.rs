@@ -1,14 +1,19 @@
 use comemo::Prehashed;
 use futures::Future;
+use indexmap::{IndexMap, IndexSet};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
 use tokio::runtime;
-use typst::diag::{EcoString, FileResult};
+use tracing::warn;
+use typst::diag::{eco_format, EcoString, FileError, FileResult, PackageError};
 use typst::foundations::{Bytes, Datetime};
 use typst::syntax::package::PackageSpec;
-use typst::syntax::{FileId, Source};
+use typst::syntax::{FileId, Source, VirtualPath};
 use typst::text::{Font, FontBook};
 use typst::{Library, World};
 
 use crate::workspace::fs::FsError;
+use crate::workspace::package::lock::{PackageLock, LOCK_FILE_NAME};
 use crate::workspace::project::Project;
 
 use self::clock::Now;
@@ -26,6 +31,12 @@ pub struct ProjectWorld {
     main: Source,
     /// Current time. Will be cached lazily for consistency throughout a compilation.
     now: Now,
+    /// Files Typst asked for through this world, in the order they were first requested
+    accessed: Mutex<IndexSet<FileId>>,
+    /// The requested files which could not be read, with why
+    failed: Mutex<IndexMap<FileId, FileError>>,
+    /// The project's `typst.lock`, if any. Read lazily on the first access to a package.
+    lock: OnceCell<Option<PackageLock>>,
     handle: runtime::Handle,
 }
 
@@ -35,10 +46,84 @@ impl ProjectWorld {
             project,
             main,
             now: Now::new(),
+            accessed: Mutex::default(),
+            failed: Mutex::default(),
+            lock: OnceCell::new(),
             handle,
         }
     }
 
+    /// The files requested via [`World::source`] or [`World::file`] so far, including ones which
+    /// could not be read. The main source is not included unless it was requested explicitly.
+    pub fn accessed_files(&self) -> Vec<FileId> {
+        self.accessed.lock().iter().copied().collect()
+    }
+
+    /// The files requested so far which could not be read, with the error for each. Typst
+    /// reports only the first of them that stops compilation, while this has all of them.
+    pub fn failed_files(&self) -> Vec<(FileId, FileError)> {
+        self.failed
+            .lock()
+            .iter()
+            .map(|(id, err)| (*id, err.clone()))
+            .collect()
+    }
+
+    /// The external packages requested so far, in the order they were first requested
+    pub fn accessed_packages(&self) -> Vec<PackageSpec> {
+        let accessed = self.accessed.lock();
+        let packages: IndexSet<_> = accessed.iter().filter_map(|id| id.package()).collect();
+        packages.into_iter().cloned().collect()
+    }
+
+    /// The files Typst could access in the current package, without having requested them
+    pub fn file_ids(&self) -> Vec<FileId> {
+        self.project.file_ids()
+    }
+
+    pub fn now(&self) -> &Now {
+        &self.now
+    }
+
+    fn record_access(&self, id: FileId) {
+        self.accessed.lock().insert(id);
+    }
+
+    fn record_result<T>(&self, id: FileId, result: FileResult<T>) -> FileResult<T> {
+        if let Err(err) = &result {
+            self.failed.lock().insert(id, err.clone());
+        }
+        result
+    }
+
+    fn lock(&self) -> Option<&PackageLock> {
+        self.lock
+            .get_or_init(|| {
+                let id = FileId::new(None, VirtualPath::new(LOCK_FILE_NAME));
+                let json = self.block(self.project.read_bytes_by_id(id)).ok()?;
+                PackageLock::from_json(&json)
+                    .map_err(|err| warn!(%err, "could not parse package lock"))
+                    .ok()
+            })
+            .as_ref()
+    }
+
+    /// Fails if the file is in a package which `typst.lock` pins to another version, so documents
+    /// don't silently build against packages other than the locked ones.
+    fn check_lock(&self, id: FileId) -> FileResult<()> {
+        let Some(spec) = id.package() else {
+            return Ok(());
+        };
+        let Some(locked) = self.lock().and_then(|lock| lock.conflict(spec)) else {
+            return Ok(());
+        };
+
+        Err(FileError::Package(PackageError::Other(Some(eco_format!(
+            "{spec} does not match the version locked in {LOCK_FILE_NAME}, {locked}. \
+             Run the \"lock packages\" command to update the lockfile."
+        )))))
+    }
+
     /// Runs a `Future` in a non-async function, blocking until completion
     ///
     /// `comemo` doesn't support async, so Typst can't, so we're stuck with this for now to run
@@ -66,14 +151,22 @@ impl World for ProjectWorld {
 
     #[tracing::instrument]
     fn source(&self, id: FileId) -> FileResult<Source> {
-        self.block(self.project.read_source_by_id(id))
-            .map_err(|err: FsError| err.report_and_convert(id))
+        self.record_access(id);
+        let source = self.check_lock(id).and_then(|()| {
+            self.block(self.project.read_source_by_id(id))
+                .map_err(|err: FsError| err.report_and_convert(id))
+        });
+        self.record_result(id, source)
     }
 
     #[tracing::instrument]
     fn file(&self, id: FileId) -> FileResult<Bytes> {
-        self.block(self.project.read_bytes_by_id(id))
-            .map_err(|err: FsError| err.report_and_convert(id))
+        self.record_access(id);
+        let bytes = self.check_lock(id).and_then(|()| {
+            self.block(self.project.read_bytes_by_id(id))
+                .map_err(|err: FsError| err.report_and_convert(id))
+        });
+        self.record_result(id, bytes)
     }
 
     #[tracing::instrument]
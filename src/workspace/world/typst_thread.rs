@@ -1,9 +1,11 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::mpsc;
 use std::thread;
 
 use tokio::runtime;
 use tokio::sync::oneshot;
-use tracing::{trace, warn};
+use tracing::{error, trace, warn};
 use typst::syntax::Source;
 
 use crate::workspace::project::Project;
@@ -16,18 +18,30 @@ pub struct TypstThread {
     sender: parking_lot::Mutex<mpsc::Sender<Request>>,
 }
 
+/// Typst panicked while running a request on the Typst thread
+#[derive(thiserror::Error, Debug, Clone)]
+#[error("Typst panicked: {message}")]
+pub struct TypstPanic {
+    pub message: String,
+}
+
+impl TypstPanic {
+    fn new(payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_owned());
+        Self { message }
+    }
+}
+
 impl Default for TypstThread {
     fn default() -> Self {
         let handle = runtime::Handle::current();
         let (sender, receiver) = mpsc::channel::<Request>();
 
-        thread::spawn(move || {
-            while let Ok(request) = receiver.recv() {
-                trace!("got new request on Typst thread");
-                request.run(handle.clone());
-                trace!("completed request on Typst thread");
-            }
-        });
+        spawn_worker(handle, receiver);
 
         Self {
             sender: parking_lot::Mutex::new(sender),
@@ -35,6 +49,24 @@ impl Default for TypstThread {
     }
 }
 
+/// Runs requests until the channel closes. After a panic, Typst's caches may be left in an
+/// inconsistent state, so the remaining requests are handed over to a fresh thread.
+fn spawn_worker(handle: runtime::Handle, receiver: mpsc::Receiver<Request>) {
+    thread::spawn(move || {
+        while let Ok(request) = receiver.recv() {
+            trace!("got new request on Typst thread");
+            let result = panic::catch_unwind(AssertUnwindSafe(|| request.run(handle.clone())));
+            if result.is_err() {
+                error!("request panicked on Typst thread, restarting it");
+                comemo::evict(0);
+                spawn_worker(handle, receiver);
+                return;
+            }
+            trace!("completed request on Typst thread");
+        }
+    });
+}
+
 impl TypstThread {
     #[tracing::instrument(skip(self, f))]
     pub async fn run_with_world<Ret: Send + 'static>(
@@ -42,7 +74,7 @@ impl TypstThread {
         world_project: Project,
         world_main: Source,
         f: impl FnOnce(ProjectWorld) -> Ret + Send + 'static,
-    ) -> Ret {
+    ) -> Result<Ret, TypstPanic> {
         let f_prime = move |handle| {
             let world = ProjectWorld::new(world_project, world_main, handle);
             f(world)
@@ -51,24 +83,43 @@ impl TypstThread {
         self.run(f_prime).await
     }
 
+    /// Runs the function on the Typst thread. If it panics, the panic is returned as an error and
+    /// the thread is restarted, so later requests still get an answer.
     #[tracing::instrument(skip_all)]
     pub async fn run<Ret: Send + 'static>(
         &self,
         f: impl FnOnce(runtime::Handle) -> Ret + Send + 'static,
-    ) -> Ret {
+    ) -> Result<Ret, TypstPanic> {
         let (sender, receiver) = oneshot::channel();
         let f_prime = move |handle| {
-            let t = f(handle);
+            // The request was cancelled while it was waiting in the queue, e.g. through
+            // `$/cancelRequest`, which drops the future awaiting the result. Typst can't be
+            // interrupted once it runs, but we can at least avoid starting obsolete work.
+            if sender.is_closed() {
+                trace!("skipping cancelled request on Typst thread");
+                return;
+            }
+
+            let t = panic::catch_unwind(AssertUnwindSafe(|| f(handle)))
+                .map_err(|payload| TypstPanic::new(payload.as_ref()));
+            let panicked = t.is_err();
             if sender.send(t).is_err() {
                 // Receiver was dropped. The main thread may have exited, or the request may have
                 // been cancelled.
                 warn!("could not send back return value from Typst thread");
             }
+
+            // Let the worker restart the thread now that the caller has its answer
+            if panicked {
+                panic::resume_unwind(Box::new("request panicked on Typst thread"));
+            }
         };
 
         self.send_request(Request::new(f_prime));
 
-        receiver.await.unwrap()
+        receiver
+            .await
+            .expect("Typst thread should answer every request it runs")
     }
 
     #[tracing::instrument(skip_all)]
@@ -91,3 +142,21 @@ impl Request {
         (self.task)(handle);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn recovers_from_panic() {
+        let thread = TypstThread::default();
+
+        let panicked = thread
+            .run(|_| -> usize { panic!("boom") })
+            .await
+            .unwrap_err();
+        assert_eq!(panicked.message, "boom");
+
+        assert_eq!(thread.run(|_| 1 + 1).await.unwrap(), 2);
+    }
+}